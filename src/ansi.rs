@@ -0,0 +1,133 @@
+//! Removing terminal escape sequences from captured output (`--strip-ansi`).
+//!
+//! A small state machine after the ECMA-48 / VT500 parser: CSI sequences
+//! end at their final byte, OSC and the other string sequences (DCS, SOS,
+//! PM, APC) end at BEL or ST. The 8-bit C1 forms are handled the same way.
+
+const ESC: char = '\x1b';
+const BEL: char = '\x07';
+/// 8-bit CSI, DCS, SOS, ST, OSC, PM, APC
+const C1_CSI: char = '\u{9b}';
+const C1_DCS: char = '\u{90}';
+const C1_SOS: char = '\u{98}';
+const C1_ST: char = '\u{9c}';
+const C1_OSC: char = '\u{9d}';
+const C1_PM: char = '\u{9e}';
+const C1_APC: char = '\u{9f}';
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Plain text
+    Ground,
+    /// After ESC
+    Escape,
+    /// After ESC and intermediate bytes (e.g. `ESC ( B`)
+    EscapeIntermediate,
+    /// Inside `CSI ... final`
+    Csi,
+    /// Inside OSC, DCS, SOS, PM or APC, until BEL (OSC only) or ST
+    String,
+    /// ESC inside a string; `\` completes the ST
+    StringEscape,
+}
+
+/// `text` without escape sequences. An unterminated sequence at the end is
+/// dropped too.
+pub fn strip(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut state = State::Ground;
+    for c in text.chars() {
+        state = match (state, c) {
+            (State::Ground, ESC) => State::Escape,
+            (State::Ground, C1_CSI) => State::Csi,
+            (State::Ground, C1_OSC | C1_DCS | C1_SOS | C1_PM | C1_APC) => State::String,
+            (State::Ground, _) => {
+                out.push(c);
+                State::Ground
+            }
+
+            (State::Escape, '[') => State::Csi,
+            (State::Escape, ']' | 'P' | 'X' | '^' | '_') => State::String,
+            (State::Escape, ' '..='/') => State::EscapeIntermediate,
+            // Any other byte completes a two-byte sequence (`ESC 7`, `ESC M`, ...)
+            (State::Escape | State::EscapeIntermediate, _) if is_final(c) => State::Ground,
+            (State::EscapeIntermediate, ' '..='/') => State::EscapeIntermediate,
+            // Malformed: give up on the sequence and keep the text
+            (State::Escape | State::EscapeIntermediate, _) => resume(&mut out, c),
+
+            // Parameter and intermediate bytes
+            (State::Csi, '0'..='?' | ' '..='/') => State::Csi,
+            (State::Csi, '@'..='~') => State::Ground,
+            (State::Csi, _) => resume(&mut out, c),
+
+            (State::String, BEL | C1_ST) => State::Ground,
+            (State::String, ESC) => State::StringEscape,
+            (State::String, _) => State::String,
+            (State::StringEscape, '\\') => State::Ground,
+            (State::StringEscape, ESC) => State::StringEscape,
+            (State::StringEscape, _) => State::String,
+        };
+    }
+    out
+}
+
+/// Final byte of an escape sequence
+fn is_final(c: char) -> bool {
+    ('0'..='~').contains(&c)
+}
+
+/// Leave a broken sequence: a new ESC starts another one, anything else is
+/// kept as text
+fn resume(out: &mut String, c: char) -> State {
+    if c == ESC {
+        State::Escape
+    } else {
+        out.push(c);
+        State::Ground
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sgr_colors() {
+        assert_eq!(
+            strip("\x1b[01;34mdir\x1b[0m  file\n\x1b[38;5;208morange\x1b[m"),
+            "dir  file\norange"
+        );
+    }
+
+    #[test]
+    fn test_other_csi_and_escapes() {
+        // Cursor movement, erase line, private modes, charset selection
+        assert_eq!(strip("\x1b[2K\x1b[1Gdone\x1b[?25h"), "done");
+        assert_eq!(strip("\x1b(Bplain\x1b7\x1b8"), "plain");
+        assert_eq!(strip("a\u{9b}31mb"), "ab");
+    }
+
+    #[test]
+    fn test_osc_sequences() {
+        // Hyperlink (ST-terminated) and window title (BEL-terminated)
+        assert_eq!(
+            strip("\x1b]8;;https://example.com\x1b\\link\x1b]8;;\x1b\\"),
+            "link"
+        );
+        assert_eq!(strip("\x1b]0;title\x07text"), "text");
+        assert_eq!(strip("\x1bPq#0;2;0;0;0\x1b\\after"), "after");
+    }
+
+    #[test]
+    fn test_plain_text_and_unicode_untouched() {
+        let text = "tabs\tand \"quotes\" [not csi] ünïcødé ✓\r\n";
+        assert_eq!(strip(text), text);
+    }
+
+    #[test]
+    fn test_broken_and_unterminated_sequences() {
+        assert_eq!(strip("a\x1b[31\nb"), "a\nb");
+        assert_eq!(strip("tail\x1b["), "tail");
+        assert_eq!(strip("tail\x1b]0;never ends"), "tail");
+    }
+}
@@ -0,0 +1,504 @@
+use anyhow::{Context, Result};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use super::{BackendError, ClipboardBackend};
+use crate::debug;
+use crate::selection::Selection;
+
+/// PowerShell script that prints the Windows clipboard as UTF-8 without a trailing newline
+const WSL_PASTE_SCRIPT: &str =
+    "[Console]::OutputEncoding = [Text.Encoding]::UTF8; [Console]::Out.Write((Get-Clipboard -Raw))";
+
+/// Clipboard driven by external programs (e.g. `wl-copy`/`wl-paste`).
+///
+/// Copy commands receive the text on stdin; paste commands print it to stdout.
+#[derive(Debug, Clone)]
+pub struct ExternalBackend {
+    name: &'static str,
+    copy_cmd: &'static [&'static str],
+    paste_cmd: &'static [&'static str],
+    clear_cmd: &'static [&'static str],
+    html_paste_cmd: Option<&'static [&'static str]>,
+    list_types_cmd: Option<&'static [&'static str]>,
+    typed_paste_cmd: Option<&'static [&'static str]>,
+    utf16_input: bool,
+    crlf_output: bool,
+    primary_arg: Option<&'static str>,
+    selection: Selection,
+}
+
+impl ExternalBackend {
+    pub const fn new(
+        name: &'static str,
+        copy_cmd: &'static [&'static str],
+        paste_cmd: &'static [&'static str],
+        clear_cmd: &'static [&'static str],
+    ) -> Self {
+        Self {
+            name,
+            copy_cmd,
+            paste_cmd,
+            clear_cmd,
+            html_paste_cmd: None,
+            list_types_cmd: None,
+            typed_paste_cmd: None,
+            utf16_input: false,
+            crlf_output: false,
+            primary_arg: None,
+            selection: Selection::Clipboard,
+        }
+    }
+
+    /// Argument added to every command to target the primary selection
+    pub const fn with_primary_arg(mut self, arg: &'static str) -> Self {
+        self.primary_arg = Some(arg);
+        self
+    }
+
+    /// Target the primary selection or both selections. Backends without a
+    /// primary argument only support the clipboard.
+    pub const fn with_selection(mut self, selection: Selection) -> Self {
+        self.selection = selection;
+        self
+    }
+
+    /// Command that prints the clipboard's HTML form, for `paste --plain`
+    pub const fn with_html_paste(mut self, cmd: &'static [&'static str]) -> Self {
+        self.html_paste_cmd = Some(cmd);
+        self
+    }
+
+    /// Commands that list the clipboard's MIME types (one per line) and
+    /// print one of them (the type is appended), for `--list-formats` and
+    /// `paste --format`
+    pub const fn with_formats(
+        mut self,
+        list_cmd: &'static [&'static str],
+        paste_cmd: &'static [&'static str],
+    ) -> Self {
+        self.list_types_cmd = Some(list_cmd);
+        self.typed_paste_cmd = Some(paste_cmd);
+        self
+    }
+
+    /// Feed copy commands UTF-16LE with a BOM instead of UTF-8
+    pub const fn with_utf16_input(mut self) -> Self {
+        self.utf16_input = true;
+        self
+    }
+
+    /// Convert CRLF line endings in paste output to LF
+    pub const fn with_crlf_output(mut self) -> Self {
+        self.crlf_output = true;
+        self
+    }
+
+    /// Wayland clipboard via wl-clipboard
+    pub const fn wayland() -> Self {
+        Self::new(
+            "wl",
+            &["wl-copy"],
+            &["wl-paste", "--no-newline"],
+            &["wl-copy", "--clear"],
+        )
+        .with_primary_arg("--primary")
+        .with_html_paste(&["wl-paste", "--no-newline", "--type", "text/html"])
+        .with_formats(
+            &["wl-paste", "--list-types"],
+            &["wl-paste", "--no-newline", "--type"],
+        )
+    }
+
+    /// tmux paste buffer
+    pub const fn tmux() -> Self {
+        Self::new(
+            "tmux",
+            &["tmux", "load-buffer", "-"],
+            &["tmux", "save-buffer", "-"],
+            &["tmux", "delete-buffer"],
+        )
+    }
+
+    /// Android clipboard via the Termux:API package
+    pub const fn termux() -> Self {
+        Self::new(
+            "termux",
+            &["termux-clipboard-set"],
+            &["termux-clipboard-get"],
+            &["termux-clipboard-set"],
+        )
+    }
+
+    /// Windows clipboard from inside WSL via clip.exe and PowerShell
+    pub const fn wsl() -> Self {
+        Self::new(
+            "wsl",
+            &["clip.exe"],
+            &[
+                "powershell.exe",
+                "-NoProfile",
+                "-NonInteractive",
+                "-Command",
+                WSL_PASTE_SCRIPT,
+            ],
+            &[
+                "powershell.exe",
+                "-NoProfile",
+                "-NonInteractive",
+                "-Command",
+                "Set-Clipboard -Value $null",
+            ],
+        )
+        .with_utf16_input()
+        .with_crlf_output()
+    }
+}
+
+impl ClipboardBackend for ExternalBackend {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn copy(&self, text: &str) -> Result<()> {
+        let input = if self.utf16_input {
+            encode_utf16le(text)
+        } else {
+            text.as_bytes().to_vec()
+        };
+        for cmd in self.commands(self.copy_cmd)? {
+            run(&cmd, Some(&input))?;
+        }
+        Ok(())
+    }
+
+    fn paste(&self) -> Result<String> {
+        let primary = self.selection == Selection::Primary;
+        let stdout = run(&self.command(self.paste_cmd, primary)?, None)?;
+        let text = String::from_utf8(stdout).context("Clipboard content is not valid UTF-8")?;
+        if self.crlf_output {
+            Ok(text.replace("\r\n", "\n"))
+        } else {
+            Ok(text)
+        }
+    }
+
+    fn paste_html(&self) -> Result<String> {
+        let Some(cmd) = self.html_paste_cmd else {
+            anyhow::bail!("The {} backend does not support HTML", self.name);
+        };
+        let primary = self.selection == Selection::Primary;
+        let stdout = run(&self.command(cmd, primary)?, None)?;
+        String::from_utf8(stdout).context("Clipboard HTML is not valid UTF-8")
+    }
+
+    fn formats(&self) -> Result<Vec<String>> {
+        let Some(cmd) = self.list_types_cmd else {
+            self.check()?;
+            let mut formats = Vec::new();
+            if self.paste().is_ok_and(|text| !text.is_empty()) {
+                formats.push("text/plain".to_string());
+            }
+            if self.paste_html().is_ok_and(|html| !html.is_empty()) {
+                formats.push("text/html".to_string());
+            }
+            return Ok(formats);
+        };
+        let primary = self.selection == Selection::Primary;
+        let stdout = run(&self.command(cmd, primary)?, None)?;
+        Ok(String::from_utf8_lossy(&stdout)
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect())
+    }
+
+    fn paste_format(&self, mime: &str) -> Result<Vec<u8>> {
+        let Some(cmd) = self.typed_paste_cmd else {
+            return match mime {
+                "text/plain" => self.paste().map(String::into_bytes),
+                "text/html" => self.paste_html().map(String::into_bytes),
+                _ => anyhow::bail!("The {} backend cannot read {}", self.name, mime),
+            };
+        };
+        let primary = self.selection == Selection::Primary;
+        let mut cmd: Vec<&str> = self.command(cmd, primary)?;
+        // The type goes right after the base command, before a primary argument
+        cmd.insert(self.typed_paste_cmd.map_or(0, <[_]>::len), mime);
+        run(&cmd, None)
+    }
+
+    fn clear(&self) -> Result<()> {
+        for cmd in self.commands(self.clear_cmd)? {
+            run(&cmd, None)?;
+        }
+        Ok(())
+    }
+
+    /// The copy program must be on PATH
+    fn check(&self) -> Result<()> {
+        let program = self.copy_cmd.first().copied().unwrap_or_default();
+        if find_in_path(program) {
+            Ok(())
+        } else {
+            Err(BackendError::NotInstalled(program.to_string()).into())
+        }
+    }
+}
+
+impl ExternalBackend {
+    /// Command line for one selection
+    fn command(&self, cmd: &'static [&'static str], primary: bool) -> Result<Vec<&'static str>> {
+        let mut cmd = cmd.to_vec();
+        if primary {
+            match self.primary_arg {
+                Some(arg) => cmd.push(arg),
+                None => anyhow::bail!(
+                    "The {} backend does not support the primary selection",
+                    self.name
+                ),
+            }
+        }
+        Ok(cmd)
+    }
+
+    /// Command lines for every selection a write targets
+    fn commands(&self, cmd: &'static [&'static str]) -> Result<Vec<Vec<&'static str>>> {
+        let mut commands = Vec::new();
+        if self.selection.includes_clipboard() {
+            commands.push(self.command(cmd, false)?);
+        }
+        if self.selection.includes_primary() {
+            commands.push(self.command(cmd, true)?);
+        }
+        Ok(commands)
+    }
+}
+
+/// Whether a program can be found in PATH
+fn find_in_path(program: &str) -> bool {
+    let Some(path) = std::env::var_os("PATH") else {
+        return false;
+    };
+    std::env::split_paths(&path).any(|dir| {
+        let candidate = dir.join(program);
+        candidate.is_file() || (cfg!(windows) && candidate.with_extension("exe").is_file())
+    })
+}
+
+/// UTF-16LE with a byte order mark, which clip.exe decodes reliably
+/// regardless of the console code page
+fn encode_utf16le(text: &str) -> Vec<u8> {
+    let mut bytes = vec![0xFF, 0xFE];
+    bytes.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+    bytes
+}
+
+/// Run a clipboard program, optionally feeding it stdin, and return its stdout.
+///
+/// When writing, stdout/stderr are not captured: tools like wl-copy fork a
+/// background process to serve the selection, which would otherwise keep the
+/// pipes open and block until the clipboard is replaced.
+fn run(cmd: &[&str], input: Option<&[u8]>) -> Result<Vec<u8>> {
+    let (program, args) = cmd
+        .split_first()
+        .context("External clipboard command is empty")?;
+    let command = cmd.join(" ");
+    debug!("Running '{}'", command);
+
+    let mut process = Command::new(program);
+    process.args(args);
+    if input.is_some() {
+        process
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+    } else {
+        process
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+    }
+
+    let mut child = process.spawn().map_err(|e| {
+        if e.kind() == std::io::ErrorKind::NotFound {
+            anyhow::Error::new(BackendError::NotInstalled(program.to_string()))
+        } else {
+            anyhow::Error::new(e).context(format!("Failed to run '{}'", command))
+        }
+    })?;
+
+    // A program that exits early closes its stdin; report its exit status
+    // rather than the resulting broken pipe
+    let write_result = match (input, child.stdin.take()) {
+        (Some(input), Some(mut stdin)) => stdin.write_all(input),
+        _ => Ok(()),
+    };
+
+    let output = child
+        .wait_with_output()
+        .with_context(|| format!("Failed to run '{}'", command))?;
+
+    if !output.status.success() {
+        return Err(BackendError::CommandFailed {
+            command,
+            status: output.status.code(),
+            stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        }
+        .into());
+    }
+
+    write_result.with_context(|| format!("Failed to write to '{}'", command))?;
+    Ok(output.stdout)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn backend_error(err: &anyhow::Error) -> &BackendError {
+        err.downcast_ref::<BackendError>().unwrap()
+    }
+
+    #[test]
+    fn test_paste_reads_stdout() {
+        let backend = ExternalBackend::new("test", &["true"], &["printf", "hello"], &["true"]);
+        assert_eq!(backend.paste().unwrap(), "hello");
+    }
+
+    #[test]
+    fn test_copy_feeds_stdin() {
+        let backend = ExternalBackend::new(
+            "test",
+            &["sh", "-c", "test \"$(cat)\" = hello"],
+            &["true"],
+            &["true"],
+        );
+        assert!(backend.copy("hello").is_ok());
+        assert!(backend.copy("other").is_err());
+    }
+
+    #[test]
+    fn test_missing_program_maps_to_not_installed() {
+        let backend = ExternalBackend::new("test", &["bc-missing-tool"], &["bc-missing-tool"], &[]);
+        let err = backend.copy("x").unwrap_err();
+        assert!(
+            matches!(backend_error(&err), BackendError::NotInstalled(p) if p == "bc-missing-tool")
+        );
+        assert!(backend.clear().is_err());
+    }
+
+    #[test]
+    fn test_failure_maps_to_command_failed() {
+        let backend = ExternalBackend::new(
+            "test",
+            &["true"],
+            &["sh", "-c", "echo 'Nothing is copied' >&2; exit 1"],
+            &["true"],
+        );
+        let err = backend.paste().unwrap_err();
+        match backend_error(&err) {
+            BackendError::CommandFailed { status, stderr, .. } => {
+                assert_eq!(*status, Some(1));
+                assert_eq!(stderr, "Nothing is copied");
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+        assert!(err.to_string().contains("Nothing is copied"));
+    }
+
+    #[test]
+    fn test_early_exit_reports_status_not_broken_pipe() {
+        let backend = ExternalBackend::new("test", &["sh", "-c", "exit 3"], &["true"], &["true"]);
+        let err = backend.copy(&"x".repeat(1 << 20)).unwrap_err();
+        assert!(matches!(
+            backend_error(&err),
+            BackendError::CommandFailed {
+                status: Some(3),
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_primary_selection_commands() {
+        let wl = ExternalBackend::wayland().with_selection(Selection::Both);
+        assert_eq!(
+            wl.commands(&["wl-copy"]).unwrap(),
+            [vec!["wl-copy"], vec!["wl-copy", "--primary"]]
+        );
+
+        let tmux = ExternalBackend::tmux().with_selection(Selection::Primary);
+        assert!(tmux.copy("x").is_err());
+        assert!(tmux.paste().is_err());
+    }
+
+    #[test]
+    fn test_wayland_backend_name() {
+        assert_eq!(ExternalBackend::wayland().name(), "wl");
+        assert_eq!(ExternalBackend::wsl().name(), "wsl");
+    }
+
+    #[test]
+    fn test_check_finds_program_in_path() {
+        assert!(ExternalBackend::new("test", &["sh"], &["sh"], &["sh"])
+            .check()
+            .is_ok());
+        let err = ExternalBackend::new("test", &["bc-missing-tool"], &[], &[])
+            .check()
+            .unwrap_err();
+        assert!(matches!(backend_error(&err), BackendError::NotInstalled(_)));
+    }
+
+    #[test]
+    fn test_encode_utf16le() {
+        assert_eq!(encode_utf16le(""), [0xFF, 0xFE]);
+        assert_eq!(encode_utf16le("Aé"), [0xFF, 0xFE, 0x41, 0x00, 0xE9, 0x00]);
+    }
+
+    #[test]
+    fn test_utf16_input_sent_to_copy_command() {
+        let backend = ExternalBackend::new(
+            "test",
+            &[
+                "sh",
+                "-c",
+                "test \"$(od -An -tx1 | tr -d ' \\n')\" = fffe6800",
+            ],
+            &["true"],
+            &["true"],
+        )
+        .with_utf16_input();
+        assert!(backend.copy("h").is_ok());
+    }
+
+    #[test]
+    fn test_html_paste_command() {
+        let backend = ExternalBackend::new("test", &["true"], &["true"], &["true"])
+            .with_html_paste(&["printf", "<b>hi</b>"]);
+        assert_eq!(backend.paste_html().unwrap(), "<b>hi</b>");
+        let plain = ExternalBackend::new("test", &["true"], &["true"], &["true"]);
+        assert!(plain.paste_html().is_err());
+    }
+
+    #[test]
+    fn test_format_commands() {
+        let backend = ExternalBackend::new("test", &["true"], &["true"], &["true"])
+            .with_formats(&["printf", "text/plain\\nimage/png\\n"], &["echo", "-n"]);
+        assert_eq!(backend.formats().unwrap(), ["text/plain", "image/png"]);
+        assert_eq!(backend.paste_format("image/png").unwrap(), b"image/png");
+        let plain = ExternalBackend::new("test", &["true"], &["printf", "hi"], &["true"]);
+        assert_eq!(plain.formats().unwrap(), ["text/plain"]);
+        assert_eq!(plain.paste_format("text/plain").unwrap(), b"hi");
+        assert!(plain.paste_format("image/png").is_err());
+    }
+
+    #[test]
+    fn test_crlf_output_normalized() {
+        let backend =
+            ExternalBackend::new("test", &["true"], &["printf", "a\\r\\nb\\r\\n"], &["true"])
+                .with_crlf_output();
+        assert_eq!(backend.paste().unwrap(), "a\nb\n");
+    }
+}
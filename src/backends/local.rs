@@ -0,0 +1,503 @@
+use anyhow::{Context, Result};
+use arboard::{Clipboard, ImageData};
+use std::borrow::Cow;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use super::{BackendOptions, ClipboardBackend};
+use crate::clipboard;
+use crate::error::{CodedError, ErrorCode};
+use crate::image::Image;
+use crate::selection::Selection;
+use crate::verbose;
+
+/// Local system clipboard via arboard
+#[derive(Debug, Clone, Copy)]
+pub struct ArboardBackend {
+    selection: Selection,
+    retries: u32,
+    retry_delay_ms: u64,
+    timeout: Option<Duration>,
+}
+
+impl Default for ArboardBackend {
+    fn default() -> Self {
+        Self {
+            selection: Selection::default(),
+            retries: Self::DEFAULT_RETRIES,
+            retry_delay_ms: Self::DEFAULT_RETRY_DELAY_MS,
+            timeout: Some(Duration::from_millis(Self::DEFAULT_TIMEOUT_MS)),
+        }
+    }
+}
+
+impl ArboardBackend {
+    /// Extra attempts after a failure that may pass (`--clipboard-retries`)
+    pub const DEFAULT_RETRIES: u32 = 2;
+    /// Wait before the first retry; doubled for each one after
+    /// (`--clipboard-retry-delay`)
+    pub const DEFAULT_RETRY_DELAY_MS: u64 = 50;
+    /// How long one operation may take before bc gives up on it
+    /// (`--clipboard-timeout`)
+    pub const DEFAULT_TIMEOUT_MS: u64 = 5000;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Backend configured from the shared backend options
+    pub fn from_options(options: &BackendOptions) -> Self {
+        Self::new()
+            .with_selection(options.selection)
+            .with_retries(options.clipboard_retries, options.clipboard_retry_delay_ms)
+            .with_timeout(options.clipboard_timeout)
+    }
+
+    /// Target the primary selection (Linux only) or both selections
+    pub fn with_selection(mut self, selection: Selection) -> Self {
+        self.selection = selection;
+        self
+    }
+
+    /// Try a failed operation again up to `retries` times, waiting
+    /// `delay_ms` before the first retry and twice as long before each
+    /// one after
+    pub fn with_retries(mut self, retries: u32, delay_ms: u64) -> Self {
+        self.retries = retries;
+        self.retry_delay_ms = delay_ms;
+        self
+    }
+
+    /// Give up on an operation that takes longer than `timeout` (`None`
+    /// waits forever)
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Run `op` with a new clipboard handle. arboard sporadically fails to
+    /// connect right after login or while the compositor is busy, so those
+    /// failures are retried with backoff; missing content never is.
+    fn with_clipboard<T, F>(&self, op: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: Fn(&mut Clipboard) -> Result<T> + Send + Sync + 'static,
+    {
+        let op = Arc::new(op);
+        let mut attempt = 0;
+        loop {
+            match self.attempt(Arc::clone(&op)) {
+                Err(e) if attempt < self.retries && is_transient(&e) => {
+                    let delay = retry_delay(self.retry_delay_ms, attempt);
+                    attempt += 1;
+                    verbose!(
+                        "The local clipboard failed ({:#}); retry {}/{} in {:?}",
+                        e,
+                        attempt,
+                        self.retries,
+                        delay
+                    );
+                    thread::sleep(delay);
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// One try at `op`. It runs on a worker thread when there is a timeout,
+    /// since `Clipboard::new()` or a read can hang forever on broken X
+    /// forwarding; a hung worker is left behind.
+    fn attempt<T, F>(&self, op: Arc<F>) -> Result<T>
+    where
+        T: Send + 'static,
+        F: Fn(&mut Clipboard) -> Result<T> + Send + Sync + 'static,
+    {
+        let run = move || {
+            Clipboard::new()
+                .context("Failed to initialize clipboard")
+                .and_then(|mut clipboard| op(&mut clipboard))
+        };
+        let Some(timeout) = self.timeout else {
+            return run();
+        };
+        let (sender, receiver) = mpsc::channel();
+        thread::Builder::new()
+            .name("clipboard".to_string())
+            .spawn(move || {
+                // Nobody listens once the wait timed out
+                let _ = sender.send(run());
+            })
+            .context("Failed to start the clipboard thread")?;
+        match receiver.recv_timeout(timeout) {
+            Ok(result) => result,
+            Err(RecvTimeoutError::Timeout) => Err(CodedError::new(
+                ErrorCode::ClipboardTimeout,
+                format!(
+                    "The local clipboard didn't respond within {:?} (see --clipboard-timeout)",
+                    timeout
+                ),
+            )
+            .with("timeout_ms", timeout.as_millis() as u64)
+            .into()),
+            Err(RecvTimeoutError::Disconnected) => {
+                anyhow::bail!("The clipboard thread stopped unexpectedly")
+            }
+        }
+    }
+
+    /// Whether a copy lasts only as long as the process that made it, unless
+    /// a clipboard manager takes it over (X11 and Wayland)
+    pub fn copies_need_owner() -> bool {
+        platform::COPIES_NEED_OWNER
+    }
+
+    /// Whether an X11 clipboard manager is running to take copies over when
+    /// their owner exits; `None` without an X display
+    pub fn clipboard_manager() -> Option<bool> {
+        platform::selection_owned("CLIPBOARD_MANAGER")
+    }
+
+    /// After a copy, whether every selection written still has an owner.
+    /// arboard asks the clipboard manager to take over when it lets go
+    /// (ICCCM `SAVE_TARGETS`); `Some(false)` means nobody did, so the copy
+    /// is already gone. `None` where copies don't need an owner, or without
+    /// an X display.
+    pub fn kept_after_exit(&self) -> Option<bool> {
+        let mut kept = true;
+        for kind in platform::kinds(self.selection).ok()? {
+            kept &= platform::selection_owned(platform::atom_name(kind))?;
+        }
+        Some(kept)
+    }
+
+    /// Set `text` and keep serving it until another application takes the
+    /// clipboard over (`copy --hold`). Where the system keeps copies itself
+    /// this only sets it.
+    pub fn hold(&self, text: &str) -> Result<()> {
+        let mut kinds = platform::kinds(self.selection)?;
+        let Some(last) = kinds.pop() else {
+            return Ok(());
+        };
+        let text = text.to_string();
+        // Holding lasts until someone else copies, so no timeout
+        let backend = self.with_timeout(None);
+        backend.with_clipboard(move |clipboard| {
+            for &kind in &kinds {
+                platform::set_text(clipboard, kind, &text)
+                    .context("Failed to write to local clipboard")?;
+            }
+            platform::set_text_and_wait(clipboard, last, &text)
+                .context("Failed to write to local clipboard")
+        })
+    }
+}
+
+impl ClipboardBackend for ArboardBackend {
+    fn name(&self) -> &'static str {
+        "local"
+    }
+
+    fn copy(&self, text: &str) -> Result<()> {
+        let kinds = platform::kinds(self.selection)?;
+        let text = text.to_string();
+        self.with_clipboard(move |clipboard| {
+            for &kind in &kinds {
+                platform::set_text(clipboard, kind, &text)
+                    .context("Failed to write to local clipboard")?;
+            }
+            Ok(())
+        })
+    }
+
+    fn paste(&self) -> Result<String> {
+        let kind = platform::read_kind(self.selection)?;
+        self.with_clipboard(move |clipboard| {
+            platform::get_text(clipboard, kind).context("Failed to read from clipboard")
+        })
+    }
+
+    fn clear(&self) -> Result<()> {
+        let kinds = platform::kinds(self.selection)?;
+        self.with_clipboard(move |clipboard| {
+            for &kind in &kinds {
+                platform::set_text(clipboard, kind, "")
+                    .context("Failed to clear local clipboard")?;
+            }
+            Ok(())
+        })
+    }
+
+    fn check(&self) -> Result<()> {
+        self.with_clipboard(|_| Ok(()))
+    }
+
+    fn copy_html(&self, html: &str, alt_text: &str) -> Result<()> {
+        let kinds = platform::kinds(self.selection)?;
+        let (html, alt_text) = (html.to_string(), alt_text.to_string());
+        self.with_clipboard(move |clipboard| {
+            for &kind in &kinds {
+                platform::set_html(clipboard, kind, &html, &alt_text)
+                    .context("Failed to write HTML to local clipboard")?;
+            }
+            Ok(())
+        })
+    }
+
+    fn paste_html(&self) -> Result<String> {
+        let kind = platform::read_kind(self.selection)?;
+        self.with_clipboard(move |clipboard| {
+            platform::get_html(clipboard, kind).context("Failed to read HTML from clipboard")
+        })
+    }
+
+    fn copy_files(&self, paths: &[PathBuf]) -> Result<()> {
+        let kinds = platform::kinds(self.selection)?;
+        let paths = paths.to_vec();
+        self.with_clipboard(move |clipboard| {
+            for &kind in &kinds {
+                platform::set_files(clipboard, kind, &paths)
+                    .context("Failed to write file list to local clipboard")?;
+            }
+            Ok(())
+        })
+    }
+
+    fn paste_files(&self) -> Result<Vec<PathBuf>> {
+        let kind = platform::read_kind(self.selection)?;
+        self.with_clipboard(move |clipboard| {
+            platform::get_files(clipboard, kind).context("Failed to read file list from clipboard")
+        })
+    }
+
+    fn copy_image(&self, image: &Image) -> Result<()> {
+        let image = image.clone();
+        self.with_clipboard(move |clipboard| {
+            clipboard
+                .set_image(ImageData {
+                    width: image.width,
+                    height: image.height,
+                    bytes: Cow::Borrowed(&image.bytes),
+                })
+                .context("Failed to write image to local clipboard")
+        })
+    }
+
+    fn paste_image(&self) -> Result<Image> {
+        let data = self.with_clipboard(|clipboard| {
+            clipboard
+                .get_image()
+                .context("Failed to read image from clipboard")
+        })?;
+        Ok(Image {
+            width: data.width,
+            height: data.height,
+            bytes: data.bytes.into_owned(),
+        })
+    }
+}
+
+/// Whether retrying might get past `e`: arboard couldn't reach the
+/// clipboard or found it busy. Without a display there is nothing to wait
+/// for.
+fn is_transient(e: &anyhow::Error) -> bool {
+    let error = e
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<arboard::Error>());
+    clipboard::has_display()
+        && matches!(
+            error,
+            Some(arboard::Error::ClipboardOccupied | arboard::Error::Unknown { .. })
+        )
+}
+
+/// How long to wait before retry number `attempt` (from 0)
+fn retry_delay(delay_ms: u64, attempt: u32) -> Duration {
+    Duration::from_millis(delay_ms.saturating_mul(1 << attempt.min(16)))
+}
+
+/// X11/Wayland: map selections onto arboard's clipboard kinds
+#[cfg(all(
+    unix,
+    not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))
+))]
+mod platform {
+    use anyhow::Result;
+    use arboard::{Clipboard, GetExtLinux, LinuxClipboardKind, SetExtLinux};
+    use std::path::PathBuf;
+
+    use crate::selection::Selection;
+
+    pub type Kind = LinuxClipboardKind;
+
+    pub const COPIES_NEED_OWNER: bool = true;
+
+    pub fn kinds(selection: Selection) -> Result<Vec<Kind>> {
+        Ok(match selection {
+            Selection::Clipboard => vec![LinuxClipboardKind::Clipboard],
+            Selection::Primary => vec![LinuxClipboardKind::Primary],
+            Selection::Both => vec![LinuxClipboardKind::Clipboard, LinuxClipboardKind::Primary],
+        })
+    }
+
+    pub fn read_kind(selection: Selection) -> Result<Kind> {
+        Ok(match selection {
+            Selection::Primary => LinuxClipboardKind::Primary,
+            Selection::Clipboard | Selection::Both => LinuxClipboardKind::Clipboard,
+        })
+    }
+
+    pub fn set_text(clipboard: &mut Clipboard, kind: Kind, text: &str) -> Result<()> {
+        Ok(clipboard.set().clipboard(kind).text(text)?)
+    }
+
+    /// The X11 selection behind `kind`
+    pub fn atom_name(kind: Kind) -> &'static str {
+        match kind {
+            LinuxClipboardKind::Clipboard => "CLIPBOARD",
+            LinuxClipboardKind::Primary => "PRIMARY",
+            LinuxClipboardKind::Secondary => "SECONDARY",
+        }
+    }
+
+    /// Whether some X client owns the selection `name`; `None` without an
+    /// X display
+    pub fn selection_owned(name: &str) -> Option<bool> {
+        use x11rb::protocol::xproto::ConnectionExt as _;
+
+        let (connection, _) = x11rb::connect(None).ok()?;
+        let atom = connection
+            .intern_atom(false, name.as_bytes())
+            .ok()?
+            .reply()
+            .ok()?
+            .atom;
+        let owner = connection.get_selection_owner(atom).ok()?.reply().ok()?;
+        Some(owner.owner != x11rb::NONE)
+    }
+
+    /// Blocks until another application owns the selection
+    pub fn set_text_and_wait(clipboard: &mut Clipboard, kind: Kind, text: &str) -> Result<()> {
+        Ok(clipboard.set().clipboard(kind).wait().text(text)?)
+    }
+
+    pub fn get_text(clipboard: &mut Clipboard, kind: Kind) -> Result<String> {
+        Ok(clipboard.get().clipboard(kind).text()?)
+    }
+
+    pub fn set_html(clipboard: &mut Clipboard, kind: Kind, html: &str, alt: &str) -> Result<()> {
+        Ok(clipboard.set().clipboard(kind).html(html, Some(alt))?)
+    }
+
+    pub fn get_html(clipboard: &mut Clipboard, kind: Kind) -> Result<String> {
+        Ok(clipboard.get().clipboard(kind).html()?)
+    }
+
+    pub fn set_files(clipboard: &mut Clipboard, kind: Kind, paths: &[PathBuf]) -> Result<()> {
+        Ok(clipboard.set().clipboard(kind).file_list(paths)?)
+    }
+
+    pub fn get_files(clipboard: &mut Clipboard, kind: Kind) -> Result<Vec<PathBuf>> {
+        Ok(clipboard.get().clipboard(kind).file_list()?)
+    }
+}
+
+/// macOS/Windows: there is only the clipboard
+#[cfg(not(all(
+    unix,
+    not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))
+)))]
+mod platform {
+    use anyhow::Result;
+    use arboard::Clipboard;
+    use std::path::PathBuf;
+
+    use crate::selection::Selection;
+
+    pub type Kind = ();
+
+    pub const COPIES_NEED_OWNER: bool = false;
+
+    pub fn kinds(selection: Selection) -> Result<Vec<Kind>> {
+        read_kind(selection).map(|kind| vec![kind])
+    }
+
+    pub fn read_kind(selection: Selection) -> Result<Kind> {
+        if selection == Selection::Primary {
+            anyhow::bail!("The primary selection is only available on Linux (X11/Wayland)");
+        }
+        Ok(())
+    }
+
+    pub fn atom_name(_kind: Kind) -> &'static str {
+        "CLIPBOARD"
+    }
+
+    pub fn selection_owned(_name: &str) -> Option<bool> {
+        None
+    }
+
+    pub fn set_text(clipboard: &mut Clipboard, _kind: Kind, text: &str) -> Result<()> {
+        Ok(clipboard.set_text(text)?)
+    }
+
+    pub fn set_text_and_wait(clipboard: &mut Clipboard, kind: Kind, text: &str) -> Result<()> {
+        set_text(clipboard, kind, text)
+    }
+
+    pub fn get_text(clipboard: &mut Clipboard, _kind: Kind) -> Result<String> {
+        Ok(clipboard.get_text()?)
+    }
+
+    pub fn set_html(clipboard: &mut Clipboard, _kind: Kind, html: &str, alt: &str) -> Result<()> {
+        Ok(clipboard.set_html(html, Some(alt))?)
+    }
+
+    pub fn get_html(clipboard: &mut Clipboard, _kind: Kind) -> Result<String> {
+        Ok(clipboard.get().html()?)
+    }
+
+    pub fn set_files(clipboard: &mut Clipboard, _kind: Kind, paths: &[PathBuf]) -> Result<()> {
+        Ok(clipboard.set().file_list(paths)?)
+    }
+
+    pub fn get_files(clipboard: &mut Clipboard, _kind: Kind) -> Result<Vec<PathBuf>> {
+        Ok(clipboard.get().file_list()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retries_from_options() {
+        let backend = ArboardBackend::new();
+        assert_eq!(backend.retries, ArboardBackend::DEFAULT_RETRIES);
+        let options = BackendOptions {
+            clipboard_retries: 5,
+            clipboard_retry_delay_ms: 10,
+            ..BackendOptions::default()
+        };
+        let backend = ArboardBackend::from_options(&options);
+        assert_eq!((backend.retries, backend.retry_delay_ms), (5, 10));
+        assert_eq!(
+            backend.timeout,
+            Some(Duration::from_millis(ArboardBackend::DEFAULT_TIMEOUT_MS))
+        );
+        assert_eq!(backend.with_timeout(None).timeout, None);
+
+        assert_eq!(retry_delay(50, 0), Duration::from_millis(50));
+        assert_eq!(retry_delay(50, 2), Duration::from_millis(200));
+    }
+
+    #[test]
+    fn test_missing_content_is_not_retried() {
+        let empty =
+            anyhow::Error::new(arboard::Error::ContentNotAvailable).context("Failed to read");
+        assert!(!is_transient(&empty));
+        assert!(!is_transient(&anyhow::anyhow!("unrelated")));
+    }
+}
@@ -0,0 +1,79 @@
+//! In-memory clipboard for tests.
+
+use anyhow::Result;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use super::ClipboardBackend;
+
+/// A clipboard that holds its content in memory and remembers every copy.
+/// Pastes can be scripted, and the clipboard made write-only.
+#[derive(Debug, Default)]
+pub struct Memory {
+    content: Mutex<String>,
+    copies: Mutex<Vec<String>>,
+    script: Mutex<VecDeque<Option<String>>>,
+    write_only: bool,
+}
+
+impl Memory {
+    /// A clipboard holding `content`
+    pub fn new(content: &str) -> Self {
+        Self {
+            content: Mutex::new(content.to_string()),
+            ..Self::default()
+        }
+    }
+
+    /// A clipboard whose first pastes return `pastes` in order, `None`
+    /// failing, before it returns its content
+    pub fn scripted(pastes: &[Option<&str>]) -> Self {
+        Self {
+            script: Mutex::new(pastes.iter().map(|paste| paste.map(String::from)).collect()),
+            ..Self::default()
+        }
+    }
+
+    /// Make every paste fail, as some clipboards can't be read back
+    pub fn write_only(mut self) -> Self {
+        self.write_only = true;
+        self
+    }
+
+    pub fn content(&self) -> String {
+        self.content.lock().unwrap().clone()
+    }
+
+    /// Everything copied, oldest first
+    pub fn copies(&self) -> Vec<String> {
+        self.copies.lock().unwrap().clone()
+    }
+}
+
+impl ClipboardBackend for Memory {
+    fn name(&self) -> &'static str {
+        "memory"
+    }
+
+    fn copy(&self, text: &str) -> Result<()> {
+        *self.content.lock().unwrap() = text.to_string();
+        self.copies.lock().unwrap().push(text.to_string());
+        Ok(())
+    }
+
+    fn paste(&self) -> Result<String> {
+        if self.write_only {
+            anyhow::bail!("write-only");
+        }
+        match self.script.lock().unwrap().pop_front() {
+            Some(Some(text)) => Ok(text),
+            Some(None) => anyhow::bail!("no text"),
+            None => Ok(self.content()),
+        }
+    }
+
+    fn clear(&self) -> Result<()> {
+        self.content.lock().unwrap().clear();
+        Ok(())
+    }
+}
@@ -0,0 +1,404 @@
+//! Clipboard backends.
+
+use anyhow::Result;
+use std::fmt;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::encoding;
+use crate::html;
+use crate::image::{self, Image};
+use crate::osc52::Output;
+use crate::selection::Selection;
+
+mod external;
+mod local;
+#[cfg(test)]
+mod memory;
+mod osc52;
+
+pub use external::ExternalBackend;
+pub use local::ArboardBackend;
+#[cfg(test)]
+pub(crate) use memory::Memory;
+pub use osc52::Osc52Backend;
+
+/// Settings applied to whichever backend is created
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackendOptions {
+    pub selection: Selection,
+    /// Wrap OSC 52 writes for tmux/screen when needed (`--no-passthrough` disables)
+    pub passthrough: bool,
+    /// Split large OSC 52 writes into appended chunks (`--chunked`)
+    pub chunked: bool,
+    /// Where OSC 52 sequences are written (`--tty`)
+    pub output: Output,
+    /// OSC 52 query timeout; None keeps the backend default
+    pub query_timeout_ms: Option<u64>,
+    /// Extra OSC 52 query attempts after one times out (`--osc52-retries`)
+    pub query_retries: u32,
+    /// Report progress on large writes (off with `--no-progress`)
+    pub progress: bool,
+    /// Extra attempts when arboard fails to reach the clipboard
+    /// (`--clipboard-retries`)
+    pub clipboard_retries: u32,
+    /// Wait before the first of those, doubled for each one after
+    /// (`--clipboard-retry-delay`)
+    pub clipboard_retry_delay_ms: u64,
+    /// Give up on an arboard operation after this long (`--clipboard-timeout`)
+    pub clipboard_timeout: Option<Duration>,
+}
+
+impl Default for BackendOptions {
+    fn default() -> Self {
+        Self {
+            selection: Selection::default(),
+            passthrough: true,
+            chunked: false,
+            output: Output::Auto,
+            query_timeout_ms: None,
+            query_retries: 0,
+            progress: false,
+            clipboard_retries: ArboardBackend::DEFAULT_RETRIES,
+            clipboard_retry_delay_ms: ArboardBackend::DEFAULT_RETRY_DELAY_MS,
+            clipboard_timeout: Some(Duration::from_millis(ArboardBackend::DEFAULT_TIMEOUT_MS)),
+        }
+    }
+}
+
+/// Backends selectable by name (`--backend`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackendKind {
+    /// System clipboard via arboard
+    Local,
+    /// Terminal clipboard via OSC 52
+    Osc52,
+    /// tmux paste buffer
+    Tmux,
+    /// Wayland clipboard via wl-clipboard
+    Wl,
+    /// Windows clipboard from WSL
+    Wsl,
+    /// Android clipboard via Termux:API
+    Termux,
+}
+
+impl BackendKind {
+    /// Every selectable backend, in the order shown to users
+    pub const ALL: [BackendKind; 6] = [
+        BackendKind::Local,
+        BackendKind::Osc52,
+        BackendKind::Tmux,
+        BackendKind::Wl,
+        BackendKind::Wsl,
+        BackendKind::Termux,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            BackendKind::Local => "local",
+            BackendKind::Osc52 => "osc52",
+            BackendKind::Tmux => "tmux",
+            BackendKind::Wl => "wl",
+            BackendKind::Wsl => "wsl",
+            BackendKind::Termux => "termux",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.name() == name)
+    }
+
+    /// Construct the backend
+    pub fn create(self, options: &BackendOptions) -> Box<dyn ClipboardBackend> {
+        let selection = options.selection;
+        match self {
+            BackendKind::Local => Box::new(ArboardBackend::from_options(options)),
+            BackendKind::Osc52 => Box::new(Osc52Backend::from_options(options)),
+            BackendKind::Tmux => Box::new(ExternalBackend::tmux().with_selection(selection)),
+            BackendKind::Wl => Box::new(ExternalBackend::wayland().with_selection(selection)),
+            BackendKind::Wsl => Box::new(ExternalBackend::wsl().with_selection(selection)),
+            BackendKind::Termux => Box::new(ExternalBackend::termux().with_selection(selection)),
+        }
+    }
+}
+
+/// One representation of the content in a multi-part copy (`--part`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Part {
+    Text(String),
+    Html(String),
+    Image(Image),
+    Files(Vec<PathBuf>),
+}
+
+impl Part {
+    /// The MIME type this part is offered as
+    pub fn mime(&self) -> &'static str {
+        match self {
+            Part::Text(_) => "text/plain",
+            Part::Html(_) => "text/html",
+            Part::Image(_) => "image/png",
+            Part::Files(_) => "text/uri-list",
+        }
+    }
+}
+
+/// Backend failures that callers map to specific exit codes
+#[derive(Debug)]
+pub enum BackendError {
+    /// The program backing this clipboard is not installed
+    NotInstalled(String),
+    /// The program ran but reported failure
+    CommandFailed {
+        command: String,
+        status: Option<i32>,
+        stderr: String,
+    },
+}
+
+impl fmt::Display for BackendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BackendError::NotInstalled(program) => write!(f, "'{}' is not installed", program),
+            BackendError::CommandFailed {
+                command,
+                status,
+                stderr,
+            } => {
+                match status {
+                    Some(code) => write!(f, "'{}' failed with exit code {}", command, code)?,
+                    None => write!(f, "'{}' was terminated by a signal", command)?,
+                }
+                if !stderr.is_empty() {
+                    write!(f, ": {}", stderr)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for BackendError {}
+
+/// A clipboard that can be written to, read from, and cleared.
+pub trait ClipboardBackend {
+    /// Short name used in diagnostics
+    fn name(&self) -> &'static str;
+
+    /// Copy text to the clipboard
+    fn copy(&self, text: &str) -> Result<()>;
+
+    /// Read text from the clipboard
+    fn paste(&self) -> Result<String>;
+
+    /// Clear the clipboard
+    fn clear(&self) -> Result<()>;
+
+    /// Check that the clipboard can be reached at all
+    fn check(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Copy HTML along with its plain-text form. Backends without HTML
+    /// support copy just the plain text.
+    fn copy_html(&self, _html: &str, alt_text: &str) -> Result<()> {
+        self.copy(alt_text)
+    }
+
+    /// Read the HTML representation of the clipboard
+    fn paste_html(&self) -> Result<String> {
+        anyhow::bail!("The {} backend does not support HTML", self.name())
+    }
+
+    /// Put a list of files on the clipboard, for pasting into a file manager
+    /// or upload dialog
+    fn copy_files(&self, _paths: &[PathBuf]) -> Result<()> {
+        anyhow::bail!("The {} backend does not support file lists", self.name())
+    }
+
+    /// Read the list of files on the clipboard
+    fn paste_files(&self) -> Result<Vec<PathBuf>> {
+        anyhow::bail!("The {} backend does not support file lists", self.name())
+    }
+
+    /// Put several representations of the same content on the clipboard at
+    /// once. By default only the combinations the other methods cover work:
+    /// any single part, or plain text with HTML.
+    fn copy_parts(&self, parts: &[Part]) -> Result<()> {
+        match parts {
+            [Part::Text(text)] => self.copy(text),
+            [Part::Html(html)] => self.copy_html(html, &html::to_text(html)),
+            [Part::Text(text), Part::Html(html)] | [Part::Html(html), Part::Text(text)] => {
+                self.copy_html(html, text)
+            }
+            [Part::Image(image)] => self.copy_image(image),
+            [Part::Files(paths)] => self.copy_files(paths),
+            _ => {
+                let mimes: Vec<_> = parts.iter().map(Part::mime).collect();
+                anyhow::bail!(
+                    "The {} backend cannot offer {} in one copy",
+                    self.name(),
+                    mimes.join(" and ")
+                )
+            }
+        }
+    }
+
+    /// MIME types the clipboard currently offers. By default these are the
+    /// formats the paste methods find; backends that can ask the clipboard
+    /// directly also list custom types.
+    fn formats(&self) -> Result<Vec<String>> {
+        self.check()?;
+        let mut formats = Vec::new();
+        if self.paste().is_ok_and(|text| !text.is_empty()) {
+            formats.push("text/plain".to_string());
+        }
+        if self.paste_html().is_ok_and(|html| !html.is_empty()) {
+            formats.push("text/html".to_string());
+        }
+        if self.paste_image().is_ok() {
+            formats.push("image/png".to_string());
+        }
+        if self.paste_files().is_ok_and(|paths| !paths.is_empty()) {
+            formats.push("text/uri-list".to_string());
+        }
+        Ok(formats)
+    }
+
+    /// Read one format as raw bytes. By default only the formats `formats`
+    /// reports are known.
+    fn paste_format(&self, mime: &str) -> Result<Vec<u8>> {
+        match mime {
+            "text/plain" => self.paste().map(String::into_bytes),
+            "text/html" => self.paste_html().map(String::into_bytes),
+            "image/png" => image::encode_png(&self.paste_image()?),
+            "text/uri-list" => {
+                let uris: Vec<_> = self.paste_files()?.iter().map(|p| file_uri(p)).collect();
+                Ok(uris.join("\r\n").into_bytes())
+            }
+            _ => anyhow::bail!("The {} backend cannot read {}", self.name(), mime),
+        }
+    }
+
+    /// Copy a bitmap to the clipboard
+    fn copy_image(&self, _image: &Image) -> Result<()> {
+        anyhow::bail!("The {} backend does not support images", self.name())
+    }
+
+    /// Read a bitmap from the clipboard
+    fn paste_image(&self) -> Result<Image> {
+        anyhow::bail!("The {} backend does not support images", self.name())
+    }
+}
+
+/// A `file://` URI for `path`, as text/uri-list holds it
+fn file_uri(path: &std::path::Path) -> String {
+    let path = path.to_string_lossy().replace('\\', "/");
+    // A Windows drive (`C:`) stays as it is
+    let segments: Vec<_> = path
+        .split('/')
+        .enumerate()
+        .map(|(i, segment)| match i {
+            0 if segment.ends_with(':') => segment.to_string(),
+            _ => encoding::url_encode(segment),
+        })
+        .collect();
+    let path = segments.join("/");
+    if path.starts_with('/') {
+        format!("file://{}", path)
+    } else {
+        format!("file:///{}", path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backends_are_object_safe() {
+        let backends: Vec<Box<dyn ClipboardBackend>> = vec![
+            Box::new(ArboardBackend::new()),
+            Box::new(Osc52Backend::new()),
+        ];
+        let names: Vec<_> = backends.iter().map(|b| b.name()).collect();
+        assert_eq!(names, ["local", "osc52"]);
+    }
+
+    #[test]
+    fn test_registry_names_roundtrip() {
+        for kind in BackendKind::ALL {
+            assert_eq!(BackendKind::from_name(kind.name()), Some(kind));
+            assert_eq!(kind.create(&BackendOptions::default()).name(), kind.name());
+        }
+        assert_eq!(BackendKind::from_name("auto"), None);
+    }
+
+    #[test]
+    fn test_images_unsupported_by_default() {
+        let err = Osc52Backend::new().paste_image().unwrap_err();
+        assert_eq!(err.to_string(), "The osc52 backend does not support images");
+    }
+
+    #[test]
+    fn test_html_paste_unsupported_by_default() {
+        let err = Osc52Backend::new().paste_html().unwrap_err();
+        assert_eq!(err.to_string(), "The osc52 backend does not support HTML");
+    }
+
+    #[test]
+    fn test_file_lists_unsupported_by_default() {
+        let err = Osc52Backend::new()
+            .copy_files(&[PathBuf::from("/tmp/a")])
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "The osc52 backend does not support file lists"
+        );
+    }
+
+    #[test]
+    fn test_file_uri() {
+        assert_eq!(
+            file_uri(std::path::Path::new("/tmp/a b/ü.txt")),
+            "file:///tmp/a%20b/%C3%BC.txt"
+        );
+        assert_eq!(
+            file_uri(std::path::Path::new("C:\\Users\\x.png")),
+            "file:///C:/Users/x.png"
+        );
+    }
+
+    #[test]
+    fn test_unknown_formats_unsupported_by_default() {
+        let err = Osc52Backend::new()
+            .paste_format("application/x-custom")
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "The osc52 backend cannot read application/x-custom"
+        );
+    }
+
+    #[test]
+    fn test_parts_fall_back_to_single_formats() {
+        let backend = Osc52Backend::new();
+        let err = backend
+            .copy_parts(&[
+                Part::Text("a".to_string()),
+                Part::Files(vec![PathBuf::from("/tmp/a")]),
+            ])
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "The osc52 backend cannot offer text/plain and text/uri-list in one copy"
+        );
+        let err = backend.copy_parts(&[Part::Image(Image {
+            width: 1,
+            height: 1,
+            bytes: vec![0; 4],
+        })]);
+        assert!(err.is_err());
+    }
+}
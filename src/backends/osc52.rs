@@ -0,0 +1,315 @@
+use anyhow::{Context, Result};
+use base64::Engine as _;
+use indicatif::HumanBytes;
+use std::thread;
+use std::time::Duration;
+
+use super::{BackendOptions, ClipboardBackend};
+use crate::error::{CodedError, ErrorCode};
+use crate::osc52;
+use crate::progress::{self, Progress};
+use crate::selection::Selection;
+use crate::{message, verbose};
+
+/// Default timeout for OSC 52 clipboard queries
+const DEFAULT_QUERY_TIMEOUT_MS: u64 = 2000;
+/// Wait before the first query retry; doubled for each one after
+const QUERY_RETRY_DELAY_MS: u64 = 250;
+
+/// Terminal clipboard via OSC 52 escape sequences
+#[derive(Debug, Clone)]
+pub struct Osc52Backend {
+    query_timeout_ms: u64,
+    query_retries: u32,
+    selection: Selection,
+    passthrough: bool,
+    chunked: bool,
+    output: osc52::Output,
+    progress: bool,
+}
+
+impl Osc52Backend {
+    pub fn new() -> Self {
+        Self {
+            query_timeout_ms: DEFAULT_QUERY_TIMEOUT_MS,
+            query_retries: 0,
+            selection: Selection::Clipboard,
+            passthrough: true,
+            chunked: false,
+            output: osc52::Output::Auto,
+            progress: false,
+        }
+    }
+
+    /// Backend configured from the shared backend options
+    pub fn from_options(options: &BackendOptions) -> Self {
+        let backend = Self::new()
+            .with_selection(options.selection)
+            .with_passthrough(options.passthrough)
+            .with_chunked(options.chunked)
+            .with_output(options.output.clone())
+            .with_query_retries(options.query_retries)
+            .with_progress(options.progress);
+        match options.query_timeout_ms {
+            Some(timeout_ms) => backend.with_query_timeout(timeout_ms),
+            None => backend,
+        }
+    }
+
+    /// Allow wrapping writes for terminal multiplexers (on by default;
+    /// see `osc52::Passthrough::detect`)
+    pub fn with_passthrough(mut self, passthrough: bool) -> Self {
+        self.passthrough = passthrough;
+        self
+    }
+
+    /// Split large writes into appended chunks when the terminal supports it
+    /// (see `osc52::supports_chunked_writes`)
+    pub fn with_chunked(mut self, chunked: bool) -> Self {
+        self.chunked = chunked;
+        self
+    }
+
+    /// Write sequences to a specific terminal instead of stdout/stderr
+    pub fn with_output(mut self, output: osc52::Output) -> Self {
+        self.output = output;
+        self
+    }
+
+    /// Report progress on large writes (off with `--no-progress`)
+    pub fn with_progress(mut self, progress: bool) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    /// Target the primary selection or both selections
+    pub fn with_selection(mut self, selection: Selection) -> Self {
+        self.selection = selection;
+        self
+    }
+
+    /// Set how long `paste` waits for the terminal to answer a query
+    pub fn with_query_timeout(mut self, timeout_ms: u64) -> Self {
+        self.query_timeout_ms = timeout_ms;
+        self
+    }
+
+    /// Query the terminal again up to `retries` times when it doesn't answer
+    pub fn with_query_retries(mut self, retries: u32) -> Self {
+        self.query_retries = retries;
+        self
+    }
+
+    /// The base64 clipboard content, retrying with exponential backoff
+    fn query(&self) -> Result<String> {
+        let target = self.selection.osc52_query_target();
+        let mut attempt = 0;
+        loop {
+            match osc52::query_clipboard(target, self.query_timeout_ms) {
+                Err(e) if attempt < self.query_retries && e.is::<osc52::NoResponse>() => {
+                    let delay = retry_delay(attempt);
+                    attempt += 1;
+                    verbose!(
+                        "OSC 52 query failed ({}); retry {}/{} in {:?}",
+                        e,
+                        attempt,
+                        self.query_retries,
+                        delay
+                    );
+                    thread::sleep(delay);
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// The multiplexer envelope for writes, if allowed
+    fn envelope(&self) -> osc52::Passthrough {
+        if self.passthrough {
+            osc52::Passthrough::detect()
+        } else {
+            osc52::Passthrough::None
+        }
+    }
+
+    /// Write sequences, each wrapped for the current multiplexer. Progress
+    /// is drawn between sequences, never inside one, where it would end up
+    /// in the clipboard.
+    fn write(&self, sequences: &[String]) -> Result<()> {
+        let passthrough = self.envelope();
+        let total: usize = sequences.iter().map(String::len).sum();
+        verbose!(
+            "OSC 52: {} sequence(s), {} bytes, passthrough {:?}",
+            sequences.len(),
+            total,
+            passthrough
+        );
+        let mut output = self.output.open()?;
+        let mut progress = Progress::new(
+            "Sending",
+            Some(total as u64),
+            self.progress && sequences.len() > 1,
+        );
+        for sequence in sequences {
+            osc52::write_sequence(&mut *output, &passthrough.wrap(sequence))?;
+            progress.advance(sequence.len());
+        }
+        Ok(())
+    }
+
+    /// Whether `encoded_len` bytes of base64 go out as chunked sequences
+    fn sends_chunked(&self, encoded_len: usize) -> bool {
+        self.chunked && encoded_len > osc52::OSC52_CHUNK_SIZE && osc52::supports_chunked_writes()
+    }
+
+    /// Fail with [`ErrorCode::Osc52TooLarge`] if `text` needs more than one
+    /// sequence can hold and can't be sent in chunks. `copy` checks this
+    /// itself; `--dry-run` calls it to fail the same way without writing.
+    pub fn check_size(&self, text: &str) -> Result<()> {
+        let encoded_len = text.len().div_ceil(3) * 4;
+        if self.sends_chunked(encoded_len) || encoded_len <= osc52::OSC52_MAX_SIZE {
+            return Ok(());
+        }
+        let message = format!(
+            "Content too large for OSC 52 clipboard ({} bytes, max {} bytes). \
+             Use --local flag or alternative transfer method{}.",
+            encoded_len,
+            osc52::OSC52_MAX_SIZE,
+            if self.chunked {
+                " (this terminal isn't known to support chunked writes)"
+            } else {
+                ", or --chunked in kitty or mosh"
+            }
+        );
+        anyhow::bail!(CodedError::new(ErrorCode::Osc52TooLarge, message)
+            .with("size", encoded_len)
+            .with("max", osc52::OSC52_MAX_SIZE))
+    }
+
+    /// Write one sequence for `data`, encoding it on the way out
+    fn write_streamed(&self, target: &str, data: &[u8], encoded_len: usize) -> Result<()> {
+        let passthrough = self.envelope();
+        verbose!(
+            "OSC 52: 1 sequence, {} bytes of base64, passthrough {:?}",
+            encoded_len,
+            passthrough
+        );
+        // Nothing can be drawn while the sequence is in flight
+        if self.progress && encoded_len as u64 >= progress::MIN_BYTES {
+            message!("Sending {} via OSC 52...", HumanBytes(encoded_len as u64));
+        }
+        osc52::write_streamed(&mut *self.output.open()?, target, data, passthrough)
+    }
+}
+
+impl Default for Osc52Backend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ClipboardBackend for Osc52Backend {
+    fn name(&self) -> &'static str {
+        "osc52"
+    }
+
+    fn copy(&self, text: &str) -> Result<()> {
+        let encoded_len = text.len().div_ceil(3) * 4;
+        let target = self.selection.osc52_target();
+
+        if self.sends_chunked(encoded_len) {
+            let encoded = base64::engine::general_purpose::STANDARD.encode(text);
+            return self.write(&osc52::build_chunked_sequences(target, &encoded));
+        }
+        self.check_size(text)?;
+        self.write_streamed(target, text.as_bytes(), encoded_len)
+    }
+
+    /// Query the terminal for its clipboard (experimental, limited terminal support)
+    fn paste(&self) -> Result<String> {
+        let encoded = self.query()?;
+        if encoded.is_empty() {
+            return Ok(String::new());
+        }
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(&encoded)
+            .context("Failed to decode base64 clipboard content")?;
+        String::from_utf8(bytes).context("Clipboard content is not valid UTF-8")
+    }
+
+    /// Clear via an empty OSC 52 write
+    fn clear(&self) -> Result<()> {
+        self.write(&[osc52::build_sequence(self.selection.osc52_target(), "")])
+    }
+}
+
+/// How long to wait before retry number `attempt` (from 0)
+fn retry_delay(attempt: u32) -> Duration {
+    Duration::from_millis(QUERY_RETRY_DELAY_MS.saturating_mul(1 << attempt.min(16)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_copy_rejects_oversized_content() {
+        // 8MB text exceeds 10MB when base64-encoded; rejected before writing
+        let large_text = "x".repeat(8 * 1024 * 1024);
+        assert!(Osc52Backend::new().check_size(&large_text).is_err());
+        assert!(Osc52Backend::new().check_size("small").is_ok());
+        let err = Osc52Backend::new().copy(&large_text).unwrap_err();
+        assert!(err.to_string().contains("too large"));
+        let (code, fields) = crate::error::classify(&err, ErrorCode::General);
+        assert_eq!(code, ErrorCode::Osc52TooLarge);
+        assert_eq!(fields["max"], osc52::OSC52_MAX_SIZE);
+    }
+
+    #[test]
+    fn test_query_timeout_builder() {
+        assert_eq!(
+            Osc52Backend::new().query_timeout_ms,
+            DEFAULT_QUERY_TIMEOUT_MS
+        );
+        assert_eq!(
+            Osc52Backend::new().with_query_timeout(500).query_timeout_ms,
+            500
+        );
+    }
+
+    #[test]
+    fn test_query_retries() {
+        assert_eq!(Osc52Backend::new().query_retries, 0);
+        let options = BackendOptions {
+            query_retries: 3,
+            ..BackendOptions::default()
+        };
+        assert_eq!(Osc52Backend::from_options(&options).query_retries, 3);
+
+        assert_eq!(retry_delay(0), Duration::from_millis(250));
+        assert_eq!(retry_delay(1), Duration::from_millis(500));
+        assert_eq!(retry_delay(3), Duration::from_millis(2000));
+    }
+
+    #[test]
+    fn test_chunked_builder() {
+        assert!(!Osc52Backend::new().chunked);
+        assert!(Osc52Backend::new().with_chunked(true).chunked);
+    }
+
+    #[test]
+    fn test_progress_builder() {
+        assert!(!Osc52Backend::new().progress);
+        let options = BackendOptions {
+            progress: true,
+            ..BackendOptions::default()
+        };
+        assert!(Osc52Backend::from_options(&options).progress);
+    }
+
+    #[test]
+    fn test_passthrough_builder() {
+        assert!(Osc52Backend::new().passthrough);
+        assert!(!Osc52Backend::new().with_passthrough(false).passthrough);
+    }
+}
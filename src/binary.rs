@@ -0,0 +1,60 @@
+//! Arbitrary bytes carried through the text clipboard as a base64 data URL
+//! (`data:<mime>;base64,<data>`), so they survive OSC 52 and SSH.
+
+use anyhow::{Context, Result};
+use base64::Engine as _;
+
+/// MIME type recorded when nothing more specific is known
+pub const DEFAULT_MIME: &str = "application/octet-stream";
+
+/// Encode `data` as a data URL tagged with `mime`
+pub fn encode(data: &[u8], mime: &str) -> String {
+    format!(
+        "data:{};base64,{}",
+        mime,
+        base64::engine::general_purpose::STANDARD.encode(data)
+    )
+}
+
+/// Decode a data URL from `encode`, returning its MIME type and bytes
+pub fn decode(text: &str) -> Result<(String, Vec<u8>)> {
+    let (mime, encoded) = text
+        .trim()
+        .strip_prefix("data:")
+        .and_then(|rest| rest.split_once(";base64,"))
+        .context("Clipboard does not hold binary data (copy it with --binary)")?;
+    let data = base64::engine::general_purpose::STANDARD
+        .decode(encoded)
+        .context("Failed to decode base64 clipboard content")?;
+    Ok((mime.to_string(), data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let data = [0u8, 159, 146, 150, b'\n', 255];
+        let text = encode(&data, DEFAULT_MIME);
+        assert!(text.starts_with("data:application/octet-stream;base64,"));
+        assert_eq!(
+            decode(&text).unwrap(),
+            (DEFAULT_MIME.to_string(), data.to_vec())
+        );
+    }
+
+    #[test]
+    fn test_decode_tolerates_trailing_newline() {
+        assert_eq!(
+            decode("data:image/png;base64,AAE=\n").unwrap().1,
+            vec![0, 1]
+        );
+    }
+
+    #[test]
+    fn test_decode_rejects_plain_text() {
+        assert!(decode("hello").is_err());
+        assert!(decode("data:text/plain,hello").is_err());
+    }
+}
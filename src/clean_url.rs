@@ -0,0 +1,290 @@
+//! Cleaning copied links (`--clean-url`): tracking parameters are dropped
+//! and redirector links are replaced by their destination.
+//!
+//! The built-in rules cover the common trackers; the config file's
+//! `[clean-url]` section adds more.
+
+use regex::Regex;
+
+use crate::config::{CleanUrlConfig, Redirector};
+use crate::encoding;
+
+/// Query parameters that only track the click; a trailing `*` matches any suffix
+const TRACKING_PARAMS: &[&str] = &[
+    "utm_*",
+    "fbclid",
+    "gclid",
+    "gclsrc",
+    "dclid",
+    "gbraid",
+    "wbraid",
+    "msclkid",
+    "yclid",
+    "twclid",
+    "ttclid",
+    "igshid",
+    "mc_cid",
+    "mc_eid",
+    "_hsenc",
+    "_hsmi",
+    "mkt_tok",
+    "ref_src",
+    "ref_url",
+    "__s",
+    "vero_id",
+    "oly_anon_id",
+    "oly_enc_id",
+];
+
+/// Links that bounce through a tracker, as (host, path, parameter holding
+/// the destination). A leading `*.` on the host matches any subdomain.
+const REDIRECTORS: &[(&str, &str, &str)] = &[
+    ("www.google.com", "/url", "q"),
+    ("google.com", "/url", "q"),
+    ("l.facebook.com", "/l.php", "u"),
+    ("lm.facebook.com", "/l.php", "u"),
+    ("l.instagram.com", "/", "u"),
+    ("www.youtube.com", "/redirect", "q"),
+    ("*.safelinks.protection.outlook.com", "/", "url"),
+    ("slack-redir.net", "/link", "url"),
+    ("href.li", "/", ""),
+];
+
+/// How many redirectors deep to unwrap
+const MAX_UNWRAP: usize = 4;
+
+/// Tracking and redirector rules
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cleaner {
+    params: Vec<String>,
+    redirectors: Vec<Redirector>,
+}
+
+impl Default for Cleaner {
+    fn default() -> Self {
+        Self::new(&CleanUrlConfig::default())
+    }
+}
+
+impl Cleaner {
+    /// The built-in rules plus the config file's additions
+    pub fn new(config: &CleanUrlConfig) -> Self {
+        let params = TRACKING_PARAMS
+            .iter()
+            .map(|param| param.to_string())
+            .chain(config.params.iter().cloned())
+            .collect();
+        let redirectors = REDIRECTORS
+            .iter()
+            .map(|(host, path, param)| Redirector {
+                host: host.to_string(),
+                path: path.to_string(),
+                param: param.to_string(),
+            })
+            .chain(config.redirectors.iter().cloned())
+            .collect();
+        Self {
+            params,
+            redirectors,
+        }
+    }
+
+    /// Clean every http(s) link in `text`, leaving the rest alone
+    pub fn apply(&self, text: &str) -> String {
+        let links = Regex::new(r#"https?://[^\s<>"'`]+"#).expect("URL pattern is valid");
+        links
+            .replace_all(text, |caps: &regex::Captures| {
+                // Sentence punctuation after a link isn't part of it
+                let url = caps[0].trim_end_matches(['.', ',', ';', ':', '!', '?', ')', ']']);
+                let trailing = &caps[0][url.len()..];
+                format!("{}{}", self.clean(url), trailing)
+            })
+            .into_owned()
+    }
+
+    /// Clean a single URL
+    pub fn clean(&self, url: &str) -> String {
+        let mut url = url.to_string();
+        for _ in 0..MAX_UNWRAP {
+            match self.unwrap_redirect(&url) {
+                Some(target) => url = target,
+                None => break,
+            }
+        }
+        self.strip_params(&url)
+    }
+
+    /// The destination of a redirector link
+    fn unwrap_redirect(&self, url: &str) -> Option<String> {
+        let parts = Parts::split(url)?;
+        let redirector = self
+            .redirectors
+            .iter()
+            .find(|r| host_matches(&r.host, parts.host) && parts.path.starts_with(&r.path))?;
+        let target = if redirector.param.is_empty() {
+            // The destination is the whole query (`href.li/?https://...`)
+            encoding::url_decode(parts.query?).ok()?
+        } else {
+            let value = parts.query?.split('&').find_map(|pair| {
+                let (key, value) = pair.split_once('=')?;
+                (key == redirector.param).then_some(value)
+            })?;
+            encoding::url_decode(&value.replace('+', " ")).ok()?
+        };
+        (target.starts_with("http://") || target.starts_with("https://")).then_some(target)
+    }
+
+    /// `url` without its tracking parameters
+    fn strip_params(&self, url: &str) -> String {
+        let Some(parts) = Parts::split(url) else {
+            return url.to_string();
+        };
+        let Some(query) = parts.query else {
+            return url.to_string();
+        };
+        let kept: Vec<&str> = query
+            .split('&')
+            .filter(|pair| {
+                let key = pair.split('=').next().unwrap_or("");
+                !pair.is_empty() && !self.params.iter().any(|param| param_matches(param, key))
+            })
+            .collect();
+        let mut out = parts.base.to_string();
+        if !kept.is_empty() {
+            out.push('?');
+            out.push_str(&kept.join("&"));
+        }
+        if let Some(fragment) = parts.fragment {
+            out.push('#');
+            out.push_str(fragment);
+        }
+        out
+    }
+}
+
+/// A URL split around its query
+struct Parts<'a> {
+    /// Scheme, host, and path
+    base: &'a str,
+    host: &'a str,
+    path: &'a str,
+    query: Option<&'a str>,
+    fragment: Option<&'a str>,
+}
+
+impl<'a> Parts<'a> {
+    fn split(url: &'a str) -> Option<Self> {
+        let (rest, fragment) = match url.split_once('#') {
+            Some((rest, fragment)) => (rest, Some(fragment)),
+            None => (url, None),
+        };
+        let (base, query) = match rest.split_once('?') {
+            Some((base, query)) => (base, Some(query)),
+            None => (rest, None),
+        };
+        let after_scheme = &base[base.find("://")? + 3..];
+        let (authority, path) = match after_scheme.find('/') {
+            Some(slash) => after_scheme.split_at(slash),
+            None => (after_scheme, "/"),
+        };
+        // Drop any user info and port
+        let host = authority.rsplit('@').next()?.split(':').next()?;
+        Some(Self {
+            base,
+            host,
+            path,
+            query,
+            fragment,
+        })
+    }
+}
+
+fn host_matches(pattern: &str, host: &str) -> bool {
+    let host = host.to_ascii_lowercase();
+    match pattern.strip_prefix("*.") {
+        Some(domain) => host.ends_with(&format!(".{}", domain)),
+        None => host == pattern,
+    }
+}
+
+fn param_matches(pattern: &str, key: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => key.starts_with(prefix),
+        None => key == pattern,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clean(url: &str) -> String {
+        Cleaner::default().clean(url)
+    }
+
+    #[test]
+    fn test_tracking_params_are_dropped() {
+        assert_eq!(
+            clean("https://example.com/post?id=7&utm_source=news&utm_medium=email&fbclid=abc#comments"),
+            "https://example.com/post?id=7#comments"
+        );
+        assert_eq!(
+            clean("https://shop.example/item?gclid=x"),
+            "https://shop.example/item"
+        );
+        assert_eq!(
+            clean("https://example.com/?q=utm_source"),
+            "https://example.com/?q=utm_source"
+        );
+        assert_eq!(clean("https://example.com/a"), "https://example.com/a");
+    }
+
+    #[test]
+    fn test_redirectors_are_unwrapped() {
+        assert_eq!(
+            clean("https://www.google.com/url?sa=t&q=https%3A%2F%2Fexample.com%2Fa%3Fb%3D1%26utm_campaign%3Dx&usg=AOv"),
+            "https://example.com/a?b=1"
+        );
+        assert_eq!(
+            clean("https://nam12.safelinks.protection.outlook.com/?url=https%3A%2F%2Fexample.org%2F&data=05"),
+            "https://example.org/"
+        );
+        assert_eq!(
+            clean("https://l.facebook.com/l.php?u=https%3A%2F%2Fl.facebook.com%2Fl.php%3Fu%3Dhttps%253A%252F%252Fexample.net%252F"),
+            "https://example.net/"
+        );
+        // Not a link to elsewhere: left as it is
+        assert_eq!(
+            clean("https://www.google.com/url?q=javascript:alert(1)"),
+            "https://www.google.com/url?q=javascript:alert(1)"
+        );
+    }
+
+    #[test]
+    fn test_links_in_text() {
+        let text =
+            "Read https://example.com/a?utm_source=x. Also (https://example.com/b?fbclid=1), ok";
+        assert_eq!(
+            Cleaner::default().apply(text),
+            "Read https://example.com/a. Also (https://example.com/b), ok"
+        );
+        assert_eq!(Cleaner::default().apply("no links here"), "no links here");
+    }
+
+    #[test]
+    fn test_config_rules() {
+        let config = CleanUrlConfig {
+            params: vec!["ref".to_string(), "src_*".to_string()],
+            redirectors: vec![Redirector {
+                host: "go.example.com".to_string(),
+                path: "/out".to_string(),
+                param: "to".to_string(),
+            }],
+        };
+        let cleaner = Cleaner::new(&config);
+        assert_eq!(
+            cleaner.clean("https://go.example.com/out?to=https%3A%2F%2Fa.io%2F%3Fref%3Dhn%26src_id%3D3%26x%3D1"),
+            "https://a.io/?x=1"
+        );
+    }
+}
@@ -0,0 +1,2269 @@
+//! Command-line interface: subcommands, shared settings, and the legacy
+//! flag shorthands (`bc -p`, `bc -c`, ...) that map onto them.
+
+use clap::builder::{BoolishValueParser, TypedValueParser};
+use clap::error::ErrorKind;
+use clap::parser::ValueSource;
+use clap::{ArgAction, Args, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use bc::backends::{ArboardBackend, BackendKind, BackendOptions};
+use bc::clipboard::{self, Mode};
+use bc::config::RemoteConfig;
+use bc::crypto::Key;
+use bc::fields::FieldList;
+use bc::hash::Algorithm;
+use bc::image::Size;
+use bc::osc52;
+use bc::redact::Redactor;
+use bc::server;
+use bc::shot::Area;
+use bc::transform::{self, Transform};
+use bc::unicode::Form;
+use bc::Selection;
+
+/// Boring Clipboard - A simple cross-platform clipboard tool
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+#[command(after_help = "\
+Examples:
+  echo \"Hello\" | bc           # Copy \"Hello\\n\" (same as: bc copy)
+  echo \"Hello\" | bc -t        # Copy \"Hello\" (trim newline)
+  cat file.txt | bc           # Copy file content
+  bc a.txt b.txt              # Copy both files, concatenated
+  make 2>&1 | bc --tee | less # Copy and pass the output through
+  bc --binary < notes.tar.gz  # Copy raw bytes (bc -p --binary writes them back)
+  bc --highlight rust < lib.rs  # Copy as syntax-highlighted rich text
+  bc --markdown < notes.md    # Copy Markdown as formatted rich text
+  bc --files a.png b.pdf      # Copy the files themselves, for pasting as attachments
+  echo more | bc -a           # Append to the current clipboard
+  bc paste                    # Paste clipboard content (shorthand: bc -p)
+  bc clear                    # Clear clipboard (shorthand: bc -c)
+  bc history                  # List recent copies
+  bc history 2 -p             # Paste the second most recent copy
+  bc pick                     # Fuzzy-find a past copy and copy it again
+  bc snippet greet Sam        # Copy snippet \"greet\" with {1} = Sam
+  echo \"x\" | bc copy -r work # Copy into register \"work\"
+  bc --encrypt me@example.com < secret.txt  # Copy gpg ciphertext (bc -p --decrypt reads it)
+  pass show db | bc --expire 30s  # Clear the clipboard again after 30 seconds
+  bc paste -r work            # Paste register \"work\"
+  bc -p --qr                  # Show the clipboard as a QR code for a phone
+  bc -p --ocr                 # Print the text in a copied screenshot
+  bc watch --once             # Wait for the next copy and print it
+  bc watch --exec 'notify-send \"$(cat {})\"'  # Run a command on every copy
+  bc watch --apply-rules      # Rewrite copies by the config's [rules.NAME]
+  bc run -- cargo test        # Run a command and copy its output and exit status
+  bc pipe --replace -- jq .   # Reformat the clipboard with jq, in place
+  bc edit                     # Edit the clipboard in $EDITOR
+  bc stat                     # Size, line count, and type of the clipboard
+  bc diff config.toml         # Show how the clipboard differs from a file
+  bc hash --verify 9f86d0...  # Check the clipboard's SHA-256
+  bc paste --backend tmux     # Paste the tmux buffer
+  bc push --peer laptop       # Send the clipboard to another machine (see bc peers)
+  bc probe                    # Check terminal OSC 52 support
+  bc doctor                   # Diagnose why copy or paste doesn't work
+  bc --list-exit-codes        # What each exit status and --json error code means
+  bc completions zsh > _bc    # Generate shell completions
+  echo \"x\" | bc --primary    # Copy to the primary selection
+  bc copy --image < shot.png  # Copy an image
+  bc paste --image > out.png  # Save the clipboard image as PNG
+  bc shot --region            # Screenshot a selection onto the clipboard")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    #[command(flatten)]
+    pub input: InputArgs,
+
+    #[command(flatten)]
+    pub legacy: LegacyArgs,
+
+    #[command(flatten)]
+    pub settings: Settings,
+}
+
+/// Operations; without one, `bc` copies stdin
+#[derive(Subcommand, Debug, PartialEq, Eq)]
+pub enum Command {
+    /// Copy stdin to the clipboard (the default)
+    Copy(CopyArgs),
+    /// Print the clipboard to stdout
+    Paste(PasteArgs),
+    /// Clear the clipboard
+    Clear(ClearArgs),
+    /// List copy history, or re-copy entry N
+    History(HistoryArgs),
+    /// Choose a history entry with a fuzzy filter and copy it again
+    Pick(PickArgs),
+    /// Watch the local clipboard and print each new value to stdout
+    Watch(WatchArgs),
+    /// Run a command, showing its output, then copy the output with an exit
+    /// status header (e.g. bc run -- cargo test)
+    Run(RunArgs),
+    /// Feed the clipboard to a command's stdin (e.g. bc pipe -- jq .), and
+    /// with --replace copy its output back
+    Pipe(PipeArgs),
+    /// Open the clipboard in $VISUAL or $EDITOR and copy the result when it exits
+    Edit,
+    /// Print the clipboard's size (bytes, characters, words, lines), encoding,
+    /// and content type, without the content (as a JSON object with --json)
+    Stat,
+    /// Print a unified diff from the clipboard to a file (`-` for stdin); exits
+    /// 0 if they are the same and 1 if they differ
+    Diff(DiffArgs),
+    /// Print a checksum of the clipboard, or check it against --verify
+    Hash(HashArgs),
+    /// List named registers
+    Registers,
+    /// Copy a saved template, filling in {date}, {time}, {hostname}, {user},
+    /// and {1}, {2}... from extra arguments
+    Snippet(SnippetArgs),
+    /// List the config's [rules.NAME] rewrites for watch --apply-rules, or
+    /// turn one on or off
+    Rules(RulesArgs),
+    /// Take a screenshot and copy it as an image (grim/slurp, maim, screencapture)
+    Shot(ShotArgs),
+    /// Copy the local clipboard to the terminal's via OSC 52, or back with
+    /// --reverse (e.g. from your laptop into a remote tmux buffer)
+    Bridge(BridgeArgs),
+    /// Keep this machine's clipboard and a remote host's in sync over SSH
+    /// until interrupted
+    Sync(SyncArgs),
+    /// Serve the clipboard over HTTP: GET /clip reads it, POST /clip sets it
+    Serve(ServeArgs),
+    /// List bc servers on the local network (started with `bc serve --advertise`)
+    Peers(PeersArgs),
+    /// Send the clipboard to a peer's
+    Push(PeerArgs),
+    /// Copy a peer's clipboard to this machine's
+    Pull(PeerArgs),
+    /// Generate a key for --key (printed, or written to a new file with -o)
+    Keygen(KeygenArgs),
+    /// Clear the clipboard after a delay if it still holds a copy (started by --expire)
+    #[command(hide = true)]
+    Expire(ExpireArgs),
+    /// Serve stdin as the clipboard until another application replaces it
+    /// (started by --hold)
+    #[command(hide = true)]
+    Hold,
+    /// Report what the terminal supports: OSC 52 write/read and multiplexer
+    /// passthrough (briefly overwrites the clipboard)
+    Probe,
+    /// Print the exit statuses and error codes (started by --list-exit-codes)
+    #[command(hide = true)]
+    ExitCodes,
+    /// Check what copy and paste depend on here (display server, local
+    /// clipboard, multiplexer, terminal OSC 52) and suggest fixes
+    Doctor(DoctorArgs),
+    /// Print a shell completion script to stdout
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+}
+
+/// Where copied content comes from
+#[derive(Args, Debug, Default, PartialEq, Eq)]
+pub struct InputArgs {
+    /// Files to copy, concatenated in order (`-` reads stdin); stdin when omitted
+    #[arg(value_name = "FILE")]
+    pub files: Vec<PathBuf>,
+
+    /// Text inserted between files
+    // Checked in Cli::validate: legacy `-p --repeat` takes it too
+    #[arg(long, value_name = "SEP")]
+    pub separator: Option<String>,
+
+    /// Also forward the input to stdout as it is read
+    #[arg(long)]
+    pub tee: bool,
+
+    /// Fail as soon as the input exceeds BYTES, instead of after reading it all
+    /// (default: `[limits] max_size` from the config, else unlimited for the
+    /// local clipboard and what fits in one OSC 52 sequence for the terminal)
+    #[arg(long, value_name = "BYTES")]
+    pub max_size: Option<usize>,
+
+    /// Add the input to the end of the current clipboard (or register) content
+    #[arg(short = 'a', long, conflicts_with_all = ["image", "binary"])]
+    pub append: bool,
+
+    /// Text inserted between the current content and the appended input
+    #[arg(long, value_name = "SEP", requires = "append")]
+    pub append_separator: Option<String>,
+}
+
+/// Transforms shared by copy (applied to the input) and paste (applied to the output)
+#[derive(Args, Debug, Default, PartialEq, Eq)]
+pub struct TransformArgs {
+    /// Remove terminal colors and other escape sequences
+    #[arg(long, conflicts_with_all = ["image", "binary"])]
+    pub strip_ansi: bool,
+
+    /// Remove zero-width spaces, BOMs, soft hyphens, and bidi control characters
+    #[arg(long, conflicts_with_all = ["image", "binary"])]
+    pub strip_invisible: bool,
+
+    /// Convert to Unicode normalization form FORM (nfc, or nfkc to also fold ligatures and fullwidth forms)
+    #[arg(long, value_name = "FORM", conflicts_with_all = ["image", "binary"])]
+    pub normalize: Option<Form>,
+
+    /// Replace curly quotes, dashes, ellipses, and no-break spaces with ASCII
+    #[arg(long, conflicts_with_all = ["image", "binary"])]
+    pub ascii_punctuation: bool,
+
+    /// Expand tabs in indentation to tab stops N apart (default 4); tabs inside lines stay
+    #[arg(long, value_name = "N", num_args = 0..=1, require_equals = true, default_missing_value = "4", value_parser = width(), conflicts_with_all = ["image", "binary"])]
+    pub tabs_to_spaces: Option<usize>,
+
+    /// Remove spaces and tabs at the end of each line
+    #[arg(long, conflicts_with_all = ["image", "binary"])]
+    pub strip_trailing_ws: bool,
+
+    /// Collapse runs of blank lines into one
+    #[arg(long, conflicts_with_all = ["image", "binary"])]
+    pub squeeze_blank: bool,
+
+    /// Convert line endings to CRLF (Windows)
+    #[arg(long, conflicts_with_all = ["image", "binary", "lf"])]
+    pub crlf: bool,
+
+    /// Convert CRLF line endings to LF
+    #[arg(long, conflicts_with_all = ["image", "binary"])]
+    pub lf: bool,
+
+    /// Decode %XX escapes first
+    #[arg(long, conflicts_with_all = ["image", "binary", "base64_decode", "hex_decode"])]
+    pub url_decode: bool,
+
+    /// Decode base64 first (whitespace ignored)
+    #[arg(long, conflicts_with_all = ["image", "binary", "hex_decode"])]
+    pub base64_decode: bool,
+
+    /// Decode hex first (whitespace ignored)
+    #[arg(long, conflicts_with_all = ["image", "binary"])]
+    pub hex_decode: bool,
+
+    /// Convert to uppercase
+    #[arg(long, conflicts_with_all = ["image", "binary", "lower"])]
+    pub upper: bool,
+
+    /// Convert to lowercase
+    #[arg(long, conflicts_with_all = ["image", "binary"])]
+    pub lower: bool,
+
+    /// Percent-encode the result (all but A-Z a-z 0-9 - . _ ~)
+    #[arg(long, conflicts_with_all = ["image", "binary", "base64", "hex"])]
+    pub url_encode: bool,
+
+    /// Encode the result as base64
+    #[arg(long, conflicts_with_all = ["image", "binary", "hex"])]
+    pub base64: bool,
+
+    /// Encode the result as lowercase hex
+    #[arg(long, conflicts_with_all = ["image", "binary"])]
+    pub hex: bool,
+
+    /// Drop tracking parameters (utm_*, fbclid, ...) from links and unwrap redirector links
+    #[arg(long, conflicts_with_all = ["image", "binary"])]
+    pub clean_url: bool,
+
+    /// Reformat JSON with indentation (fails if the content isn't JSON)
+    #[arg(long, conflicts_with_all = ["image", "binary", "json_minify"])]
+    pub json_pretty: bool,
+
+    /// Reformat JSON without whitespace (fails if the content isn't JSON)
+    #[arg(long, conflicts_with_all = ["image", "binary"])]
+    pub json_minify: bool,
+
+    /// Keep only fields LIST of each line, like awk (2,4 or ranges like 1-3 and 5-); fields split on runs of whitespace
+    #[arg(long, value_name = "LIST", conflicts_with_all = ["image", "binary"])]
+    pub fields: Option<FieldList>,
+
+    /// Split --fields on SEP instead, and join the kept ones with it (like cut -d)
+    #[arg(long, value_name = "SEP", requires = "fields", value_parser = clap::builder::NonEmptyStringValueParser::new())]
+    pub delimiter: Option<String>,
+
+    /// Strip whitespace from each --fields field
+    #[arg(long, requires = "fields")]
+    pub trim_fields: bool,
+
+    /// Sort lines by byte value, like LC_ALL=C sort
+    #[arg(long, conflicts_with_all = ["image", "binary", "sort_numeric"])]
+    pub sort: bool,
+
+    /// Sort lines by the number they start with, like sort -n
+    #[arg(long, conflicts_with_all = ["image", "binary"])]
+    pub sort_numeric: bool,
+
+    /// Reverse the order of lines (after --sort, sorts descending)
+    #[arg(long, conflicts_with_all = ["image", "binary"])]
+    pub reverse: bool,
+
+    /// Drop repeated lines, keeping the first of each (with --sort, like sort -u)
+    #[arg(long, conflicts_with_all = ["image", "binary"])]
+    pub unique: bool,
+
+    /// Apply a comma-separated chain of transforms in order: strip-ansi, strip-invisible, normalize[=FORM], ascii-punctuation, trim, dedent, indent[=N], fields[=N], sort, sort-numeric, reverse, unique, tabs-to-spaces[=N], strip-trailing-ws, squeeze-blank, lf, crlf, redact, clean-url, upper, lower, url-encode, url-decode, base64, base64-decode, hex, hex-decode, json-pretty, json-minify, template, or ext:NAME for an executable in ~/.config/bc/filters
+    #[arg(long, value_name = "LIST", value_delimiter = ',', conflicts_with_all = ["image", "binary"])]
+    pub filter: Vec<Transform>,
+}
+
+#[derive(Args, Debug, Default, PartialEq, Eq)]
+pub struct CopyArgs {
+    #[command(flatten)]
+    pub input: InputArgs,
+
+    #[command(flatten)]
+    pub transform: TransformArgs,
+
+    /// Copy a PNG/JPEG image from stdin to the local clipboard
+    #[arg(long)]
+    pub image: bool,
+
+    /// Copy raw bytes as base64 text, for `bc paste --binary` on the other end
+    #[arg(long, conflicts_with_all = ["image", "register"])]
+    pub binary: bool,
+
+    /// Clipboard format to set (text/html also sets a plain-text form)
+    #[arg(long, value_enum, value_name = "TYPE", default_value_t = Mime::Text, conflicts_with_all = ["image", "binary", "register"])]
+    pub mime: Mime,
+
+    /// Copy as HTML with a generated plain-text form (same as --mime text/html)
+    #[arg(long, conflicts_with_all = ["image", "binary", "register", "mime"])]
+    pub html: bool,
+
+    /// Use this file's contents as the plain-text form of an HTML copy
+    #[arg(long, value_name = "FILE")]
+    pub alt_text: Option<PathBuf>,
+
+    /// Copy as syntax-highlighted HTML for LANG (e.g. rust, py), with the source as plain text
+    #[arg(long, value_name = "LANG", conflicts_with_all = ["image", "binary", "register", "mime", "html", "alt_text"])]
+    pub highlight: Option<String>,
+
+    /// Render the input as Markdown and copy the HTML, with the Markdown as plain text
+    #[arg(long, conflicts_with_all = ["image", "binary", "register", "mime", "html", "alt_text", "highlight"])]
+    pub markdown: bool,
+
+    /// Copy the FILE arguments themselves as a file list (for pasting into a file manager)
+    #[arg(long = "files", requires = "files", conflicts_with_all = ["image", "binary", "register", "mime", "html", "highlight", "markdown", "append", "tee", "separator", "filter"])]
+    pub file_list: bool,
+
+    /// Encrypt to RECIPIENT with gpg and copy the armored result (repeatable)
+    #[arg(long, value_name = "RECIPIENT", conflicts_with_all = ["image", "binary", "mime", "html", "highlight", "markdown", "file_list", "append"])]
+    pub encrypt: Vec<String>,
+
+    /// Clear the clipboard after DURATION (e.g. 30s, 5m) unless something else was copied
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration, conflicts_with_all = ["image", "register", "file_list"])]
+    pub expire: Option<Duration>,
+
+    /// Copy even if the input looks like it contains a credential
+    #[arg(long, conflicts_with_all = ["image", "binary", "file_list"])]
+    pub allow_secrets: bool,
+
+    /// Expand {{env:VAR}}, {{date:FORMAT}}, {{uuid}}, and {{hostname}} in the input
+    #[arg(long, conflicts_with_all = ["image", "binary", "file_list"])]
+    pub template: bool,
+
+    /// Remove the leading whitespace all non-blank lines share
+    #[arg(long, conflicts_with_all = ["image", "binary", "file_list"])]
+    pub dedent: bool,
+
+    /// Prefix each non-blank line with N spaces (after --dedent)
+    #[arg(long, value_name = "N", value_parser = width(), conflicts_with_all = ["image", "binary", "file_list"])]
+    pub indent: Option<usize>,
+
+    /// Mask emails, IPs, keys and tokens with *** (RULES: all, or a list like email,ipv4,aws-key)
+    #[arg(long, value_name = "RULES", num_args = 0..=1, default_missing_value = "all", conflicts_with_all = ["image", "binary", "file_list"])]
+    pub redact: Option<Redactor>,
+
+    /// Also print the copied text as a QR code, to scan with a phone
+    #[arg(long, conflicts_with_all = ["image", "binary", "file_list", "encrypt", "tee"])]
+    pub qr: bool,
+
+    /// Keep the copy available after bc exits (X11/Wayland): a background bc
+    /// serves it until another application copies something
+    #[arg(long, conflicts_with_all = ["image", "binary", "mime", "html", "highlight", "markdown", "file_list", "register"])]
+    pub hold: bool,
+
+    /// Copy without recording it in the history
+    #[arg(long, conflicts_with = "register")]
+    pub private: bool,
+
+    /// Remove this copy from the history after DURATION (e.g. 1h, 7d)
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration, conflicts_with_all = ["private", "register"])]
+    pub ttl: Option<Duration>,
+
+    /// Read, transform, and check the input and report the bytes and backend, but copy nothing
+    #[arg(long, conflicts_with_all = ["hold", "expire"])]
+    pub dry_run: bool,
+
+    /// Offer FILE's contents as TYPE (text/plain, text/html, image/png, image/jpeg); repeat to put several formats on the clipboard at once
+    #[arg(long, value_name = "TYPE=FILE", value_parser = parse_part, conflicts_with_all = ["files", "separator", "tee", "append", "image", "binary", "mime", "html", "alt_text", "highlight", "markdown", "file_list", "encrypt", "expire", "hold", "qr", "register"])]
+    pub part: Vec<PartSpec>,
+
+    /// Copy into the named register (stored on disk) instead of the system clipboard
+    #[arg(short = 'r', long, value_name = "NAME", conflicts_with = "image")]
+    pub register: Option<String>,
+}
+
+#[derive(Args, Debug, Default, PartialEq, Eq)]
+pub struct PasteArgs {
+    #[command(flatten)]
+    pub transform: TransformArgs,
+
+    /// Write the clipboard image to stdout as PNG
+    #[arg(long)]
+    pub image: bool,
+
+    /// Write bytes copied with `--binary` to stdout
+    #[arg(long, conflicts_with_all = ["image", "register"])]
+    pub binary: bool,
+
+    /// Clipboard format to print
+    #[arg(long, value_enum, value_name = "TYPE", default_value_t = Mime::Text, conflicts_with_all = ["image", "binary", "register"])]
+    pub mime: Mime,
+
+    /// Write to FILE (replaced atomically) instead of stdout
+    #[arg(short = 'o', long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+
+    /// Append to the --output file instead of replacing it
+    #[arg(long, requires = "output")]
+    pub append_file: bool,
+
+    /// Decrypt clipboard content copied with --encrypt (runs gpg)
+    #[arg(long, conflicts_with_all = ["image", "binary", "mime"])]
+    pub decrypt: bool,
+
+    /// Print the HTML form as readable text (links as `text (url)`), or the plain text if there's no HTML
+    #[arg(long, conflicts_with_all = ["image", "binary", "mime", "decrypt", "register"])]
+    pub plain: bool,
+
+    /// Print the text as a QR code (Unicode blocks) to scan with a phone
+    #[arg(long, conflicts_with_all = ["image", "binary", "mime", "plain"])]
+    pub qr: bool,
+
+    /// Type the text into the focused window as key presses instead of printing it, for fields that block pasting
+    #[arg(long = "type", conflicts_with_all = ["image", "binary", "output", "qr"])]
+    pub type_keys: bool,
+
+    /// Pause between typed keys, in milliseconds (default 12)
+    #[arg(long, value_name = "MS", requires = "type_keys")]
+    pub delay_ms: Option<u64>,
+
+    /// Paste from the named register instead of the system clipboard
+    #[arg(short = 'r', long, value_name = "NAME", conflicts_with = "image")]
+    pub register: Option<String>,
+
+    /// Paste the history entry pinned as NAME (`bc history pin --name NAME`)
+    #[arg(long, value_name = "NAME", conflicts_with_all = ["image", "binary", "mime", "plain", "register"])]
+    pub pinned: Option<String>,
+
+    /// List the formats the clipboard offers (MIME types), one per line
+    #[arg(long, conflicts_with_all = ["image", "binary", "mime", "output", "decrypt", "plain", "qr", "type_keys", "register", "pinned"])]
+    pub list_formats: bool,
+
+    /// Print the clipboard's TYPE form (a MIME type from --list-formats) as raw bytes; with --image, the image format (png or jpeg)
+    #[arg(long, value_name = "TYPE", conflicts_with_all = ["binary", "mime", "decrypt", "plain", "qr", "type_keys", "register", "pinned", "list_formats"])]
+    pub format: Option<String>,
+
+    /// Print the text in the clipboard image, recognized by tesseract
+    #[arg(long, conflicts_with_all = ["image", "binary", "mime", "decrypt", "plain", "register", "pinned", "list_formats", "format"])]
+    pub ocr: bool,
+
+    /// Language(s) for --ocr as tesseract codes (e.g. deu, eng+fra)
+    #[arg(long, value_name = "LANG", requires = "ocr")]
+    pub ocr_lang: Option<String>,
+
+    /// JPEG quality for --image --format jpeg, 1-100 (default 90)
+    #[arg(long, value_name = "N", requires = "image", value_parser = clap::value_parser!(u8).range(1..=100))]
+    pub quality: Option<u8>,
+
+    /// Scale the --image down to fit SIZE (800x, x600, or 800x600), keeping its aspect ratio
+    #[arg(long, value_name = "SIZE", requires = "image")]
+    pub resize: Option<Size>,
+
+    /// Print the content N times, written out as it goes rather than built up in memory
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..), conflicts_with_all = ["image", "qr", "type_keys", "list_formats"])]
+    pub repeat: Option<u64>,
+
+    /// Text between --repeat copies (default: a newline)
+    #[arg(long, value_name = "SEP", requires = "repeat")]
+    pub separator: Option<String>,
+}
+
+#[derive(Args, Debug, Default, PartialEq, Eq)]
+pub struct ClearArgs {
+    /// Delete the named register instead of clearing the system clipboard
+    #[arg(short = 'r', long, value_name = "NAME")]
+    pub register: Option<String>,
+}
+
+#[derive(Args, Debug, Default, PartialEq, Eq)]
+#[command(args_conflicts_with_subcommands = true)]
+pub struct HistoryArgs {
+    #[command(subcommand)]
+    pub action: Option<HistoryAction>,
+
+    /// Entry to re-copy (1 = most recent); lists history when omitted
+    #[arg(value_name = "N")]
+    pub index: Option<usize>,
+
+    /// Print the entry instead of copying it
+    #[arg(short = 'p', long, requires = "index")]
+    pub print: bool,
+}
+
+/// Changes to the history
+#[derive(Subcommand, Debug, PartialEq, Eq)]
+pub enum HistoryAction {
+    /// Keep entry N through rotation, optionally under a name for `paste --pinned`
+    Pin(PinArgs),
+    /// Let entry N rotate out again
+    Unpin(UnpinArgs),
+    /// Write the whole history to stdout or a file, to back it up or move it
+    Export(ExportArgs),
+    /// Add the entries from an export, skipping ones already present
+    Import(ImportArgs),
+}
+
+#[derive(Args, Debug, PartialEq, Eq)]
+pub struct PinArgs {
+    /// Entry to pin (1 = most recent)
+    #[arg(value_name = "N", default_value_t = 1)]
+    pub index: usize,
+
+    /// Name to recall the entry by (`bc -p --pinned NAME`)
+    #[arg(long, value_name = "NAME")]
+    pub name: Option<String>,
+}
+
+#[derive(Args, Debug, PartialEq, Eq)]
+pub struct ExportArgs {
+    /// Export format
+    #[arg(long, value_enum, value_name = "FORMAT", default_value_t = ExportFormat::Json)]
+    pub format: ExportFormat,
+
+    /// Write to FILE (replaced atomically) instead of stdout
+    #[arg(short = 'o', long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+
+    /// Encrypt the export with the --key key
+    #[arg(long)]
+    pub encrypt: bool,
+}
+
+/// Format for `bc history export`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    /// A JSON array of {"timestamp", "content", "pinned", "name", "expires"} objects
+    Json,
+    /// A timestamp,pinned,name,content,expires header and one row per entry
+    Csv,
+}
+
+#[derive(Args, Debug, PartialEq, Eq)]
+pub struct ImportArgs {
+    /// An export in either format, encrypted or not (`-` reads stdin)
+    #[arg(value_name = "FILE")]
+    pub file: PathBuf,
+}
+
+#[derive(Args, Debug, PartialEq, Eq)]
+pub struct UnpinArgs {
+    /// Entry to unpin (1 = most recent)
+    #[arg(value_name = "N", required_unless_present = "name")]
+    pub index: Option<usize>,
+
+    /// Unpin the entry with this name instead
+    #[arg(long, value_name = "NAME", conflicts_with = "index")]
+    pub name: Option<String>,
+}
+
+#[derive(Args, Debug, Default, PartialEq, Eq)]
+pub struct PickArgs {
+    /// Print the chosen entry instead of copying it
+    #[arg(short = 'p', long)]
+    pub print: bool,
+}
+
+#[derive(Args, Debug, Default, PartialEq, Eq)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+pub struct SnippetArgs {
+    #[command(subcommand)]
+    pub action: Option<SnippetAction>,
+
+    /// Snippet to copy
+    #[arg(value_name = "NAME", required = true)]
+    pub name: Option<String>,
+
+    /// Values for {1}, {2}, ... in the snippet
+    #[arg(value_name = "ARG")]
+    pub args: Vec<String>,
+
+    /// Print the expanded snippet instead of copying it
+    #[arg(short = 'p', long)]
+    pub print: bool,
+}
+
+/// Changes to the snippet library
+#[derive(Subcommand, Debug, PartialEq, Eq)]
+pub enum SnippetAction {
+    /// Save stdin (or FILE) as snippet NAME, replacing any with that name
+    Add(SnippetAddArgs),
+    /// List snippets with the first line of each
+    List,
+}
+
+#[derive(Args, Debug, PartialEq, Eq)]
+pub struct SnippetAddArgs {
+    #[arg(value_name = "NAME")]
+    pub name: String,
+
+    /// Read the template from FILE instead of stdin
+    #[arg(value_name = "FILE")]
+    pub file: Option<PathBuf>,
+}
+
+#[derive(Args, Debug, Default, PartialEq, Eq)]
+pub struct RulesArgs {
+    #[command(subcommand)]
+    pub action: Option<RulesAction>,
+}
+
+/// What `bc rules` does; listing is the default
+#[derive(Subcommand, Debug, PartialEq, Eq)]
+pub enum RulesAction {
+    /// List rules, whether each is on, and what it does
+    List,
+    /// Turn rule NAME on
+    Enable(RuleNameArgs),
+    /// Turn rule NAME off
+    Disable(RuleNameArgs),
+}
+
+#[derive(Args, Debug, PartialEq, Eq)]
+pub struct RuleNameArgs {
+    #[arg(value_name = "NAME")]
+    pub name: String,
+}
+
+#[derive(Args, Debug, Default, PartialEq, Eq)]
+pub struct ShotArgs {
+    /// Capture every screen (the default)
+    #[arg(long, conflicts_with_all = ["region", "window"])]
+    pub screen: bool,
+
+    /// Select a rectangle with the mouse
+    #[arg(long, conflicts_with = "window")]
+    pub region: bool,
+
+    /// Capture the active window (on macOS, click one)
+    #[arg(long)]
+    pub window: bool,
+
+    /// Also save the screenshot to FILE as PNG
+    #[arg(short = 'o', long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Args, Debug, PartialEq, Eq)]
+pub struct WatchArgs {
+    /// Output format
+    #[arg(long, value_enum, value_name = "FORMAT", default_value_t = WatchFormat::Raw)]
+    pub format: WatchFormat,
+
+    /// Exit after the first change
+    #[arg(long)]
+    pub once: bool,
+
+    /// Run COMMAND through the shell on each change instead of printing it; {} becomes a temp file holding the new content
+    #[arg(long, value_name = "COMMAND", conflicts_with = "format")]
+    pub exec: Option<String>,
+
+    /// Pipe the new content to the --exec command's stdin instead
+    #[arg(long, requires = "exec")]
+    pub exec_stdin: bool,
+
+    /// Only act on changes matching REGEX (repeatable: any may match)
+    #[arg(long = "match", value_name = "REGEX")]
+    pub matches: Vec<String>,
+
+    /// Skip changes matching REGEX (repeatable)
+    #[arg(long = "ignore", value_name = "REGEX")]
+    pub ignores: Vec<String>,
+
+    /// Rewrite changes with the enabled [rules.NAME] in the config and copy the result back (see bc rules)
+    #[arg(long)]
+    pub apply_rules: bool,
+}
+
+#[derive(Args, Debug, Default, PartialEq, Eq)]
+pub struct RunArgs {
+    /// Only copy the output if the command fails
+    #[arg(long)]
+    pub on_fail: bool,
+
+    /// The command to run and its arguments
+    #[arg(
+        value_name = "COMMAND",
+        required = true,
+        trailing_var_arg = true,
+        allow_hyphen_values = true
+    )]
+    pub command: Vec<String>,
+}
+
+#[derive(Args, Debug, Default, PartialEq, Eq)]
+pub struct PipeArgs {
+    /// Copy the command's output to the clipboard instead of printing it (if the command succeeds)
+    #[arg(long)]
+    pub replace: bool,
+
+    /// The command to run and its arguments
+    #[arg(
+        value_name = "COMMAND",
+        required = true,
+        trailing_var_arg = true,
+        allow_hyphen_values = true
+    )]
+    pub command: Vec<String>,
+}
+
+#[derive(Args, Debug, PartialEq, Eq)]
+pub struct DiffArgs {
+    /// The file to compare with (`-` reads stdin)
+    #[arg(value_name = "FILE")]
+    pub file: PathBuf,
+
+    /// Unchanged lines shown around each change
+    #[arg(short = 'U', long, value_name = "N", default_value_t = 3)]
+    pub context: usize,
+}
+
+#[derive(Args, Debug, Default, PartialEq, Eq)]
+pub struct HashArgs {
+    /// Digest algorithm: sha256, md5, or blake3
+    #[arg(long, value_name = "ALGO", default_value_t)]
+    pub algo: Algorithm,
+
+    /// Check the digest is HASH (hex, either case) instead of printing it;
+    /// exits 1 if not
+    #[arg(long, value_name = "HASH")]
+    pub verify: Option<String>,
+}
+
+#[derive(Args, Debug, Default, PartialEq, Eq)]
+pub struct DoctorArgs {
+    /// Skip the terminal OSC 52 check, which briefly overwrites the clipboard
+    #[arg(long)]
+    pub no_terminal: bool,
+}
+
+#[derive(Args, Debug, Default, PartialEq, Eq)]
+pub struct BridgeArgs {
+    /// Read the terminal clipboard (OSC 52 query) and copy it locally
+    #[arg(long)]
+    pub reverse: bool,
+}
+
+#[derive(Args, Debug, Default, PartialEq, Eq)]
+pub struct SyncArgs {
+    /// Host to sync with (anything ssh accepts, e.g. user@host)
+    #[arg(long, value_name = "HOST", required_unless_present = "serve")]
+    pub remote: Option<String>,
+
+    /// bc's name on the remote PATH (`bc` there is often the calculator)
+    #[arg(long, value_name = "PROGRAM", default_value = "bc")]
+    pub remote_bc: String,
+
+    /// Path of the key file on the remote host (default: the same path as --key)
+    #[arg(long, value_name = "PATH", requires = "key")]
+    pub remote_key: Option<String>,
+
+    /// Sync over stdin/stdout (run on the remote end by `bc sync --remote`)
+    #[arg(long, hide = true, conflicts_with = "remote")]
+    pub serve: bool,
+}
+
+#[derive(Args, Debug, PartialEq, Eq)]
+pub struct ServeArgs {
+    /// Address to listen on (use 0.0.0.0:PORT to reach it from the LAN)
+    #[arg(long, value_name = "ADDR", default_value = "127.0.0.1:9111")]
+    pub listen: String,
+
+    /// Token clients must send (Authorization: Bearer, or ?token=); random when omitted
+    #[arg(
+        long,
+        value_name = "TOKEN",
+        env = "BC_SERVE_TOKEN",
+        hide_env_values = true,
+        value_parser = clap::builder::NonEmptyStringValueParser::new()
+    )]
+    pub token: Option<String>,
+
+    /// Largest accepted upload, in bytes
+    #[arg(long, value_name = "BYTES", default_value_t = server::DEFAULT_MAX_SIZE)]
+    pub max_size: usize,
+
+    /// PEM certificate file, to serve HTTPS
+    #[arg(long, value_name = "FILE", requires = "tls_key")]
+    pub tls_cert: Option<PathBuf>,
+
+    /// PEM private key file for --tls-cert
+    #[arg(long, value_name = "FILE", requires = "tls_cert")]
+    pub tls_key: Option<PathBuf>,
+
+    /// Announce the server over mDNS so `bc peers`, `bc push` and `bc pull` find it
+    #[arg(long)]
+    pub advertise: bool,
+
+    /// Name to advertise (default: this machine's hostname)
+    #[arg(long, value_name = "NAME", requires = "advertise")]
+    pub name: Option<String>,
+}
+
+#[derive(Args, Debug, Default, PartialEq, Eq)]
+pub struct KeygenArgs {
+    /// Write the key to FILE (created readable only by you) instead of stdout
+    #[arg(short = 'o', long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Args, Debug, PartialEq, Eq)]
+pub struct ExpireArgs {
+    /// Delay before clearing, in milliseconds
+    #[arg(long, value_name = "MS")]
+    pub after_ms: u64,
+}
+
+#[derive(Args, Debug, PartialEq, Eq)]
+pub struct PeersArgs {
+    /// How long to wait for answers, in milliseconds
+    #[arg(long, value_name = "MS", default_value_t = 2000)]
+    pub timeout: u64,
+}
+
+#[derive(Args, Debug, PartialEq, Eq)]
+pub struct PeerArgs {
+    /// Peer name as listed by `bc peers`, or HOST:PORT
+    #[arg(long, value_name = "NAME")]
+    pub peer: String,
+
+    /// The peer's `bc serve` token
+    #[arg(
+        long,
+        value_name = "TOKEN",
+        env = "BC_SERVE_TOKEN",
+        hide_env_values = true
+    )]
+    pub token: Option<String>,
+
+    /// How long to look for the peer, in milliseconds
+    #[arg(long, value_name = "MS", default_value_t = 2000)]
+    pub timeout: u64,
+}
+
+/// Clipboard format for --mime
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Mime {
+    /// Plain text
+    #[default]
+    #[value(name = "text/plain")]
+    Text,
+    /// HTML, with a plain-text form alongside
+    #[value(name = "text/html")]
+    Html,
+}
+
+/// A `--part TYPE=FILE` argument
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PartSpec {
+    pub mime: PartMime,
+    pub path: PathBuf,
+}
+
+/// Formats `--part` accepts
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum PartMime {
+    #[value(name = "text/plain")]
+    Text,
+    #[value(name = "text/html")]
+    Html,
+    #[value(name = "image/png")]
+    Png,
+    #[value(name = "image/jpeg")]
+    Jpeg,
+}
+
+/// Output format for watch mode
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum WatchFormat {
+    /// One JSON object per change: {"timestamp": ..., "content": ...}
+    Json,
+    /// Content followed by a newline
+    Raw,
+}
+
+/// Flag spellings from before subcommands existed. `-p` and `-c` stay
+/// documented shorthands; the rest are hidden but keep working.
+#[derive(Args, Debug)]
+pub struct LegacyArgs {
+    #[command(flatten)]
+    pub transform: TransformArgs,
+
+    /// Read from clipboard and print to stdout (shorthand for `bc paste`)
+    #[arg(short = 'p', long, conflicts_with = "clear")]
+    pub paste: bool,
+
+    /// Clear the clipboard (shorthand for `bc clear`)
+    #[arg(short = 'c', long)]
+    pub clear: bool,
+
+    #[arg(long, value_name = "N", num_args = 0..=1, hide = true, conflicts_with_all = ["clear", "image", "register"])]
+    pub history: Option<Option<usize>>,
+
+    #[arg(long, hide = true, conflicts_with = "clear")]
+    pub image: bool,
+
+    /// Copy raw bytes as base64 text; with -p, write them back out
+    #[arg(long, conflicts_with_all = ["clear", "history", "image", "register"])]
+    pub binary: bool,
+
+    /// Clipboard format to copy, or with -p to print
+    #[arg(long, value_enum, value_name = "TYPE", default_value_t = Mime::Text, conflicts_with_all = ["clear", "history", "image", "binary", "register"])]
+    pub mime: Mime,
+
+    /// Copy as HTML with a generated plain-text form (same as --mime text/html)
+    #[arg(long, conflicts_with_all = ["paste", "clear", "history", "image", "binary", "register", "mime"])]
+    pub html: bool,
+
+    /// Use this file's contents as the plain-text form of an HTML copy
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["paste", "clear", "history"])]
+    pub alt_text: Option<PathBuf>,
+
+    /// With -p, write to FILE (replaced atomically) instead of stdout
+    #[arg(
+        short = 'o',
+        long,
+        value_name = "FILE",
+        requires = "paste",
+        conflicts_with = "history"
+    )]
+    pub output: Option<PathBuf>,
+
+    /// Append to the --output file instead of replacing it
+    #[arg(long, requires = "output")]
+    pub append_file: bool,
+
+    /// Copy as syntax-highlighted HTML for LANG (e.g. rust, py), with the source as plain text
+    #[arg(long, value_name = "LANG", conflicts_with_all = ["paste", "clear", "history", "image", "binary", "register", "mime", "html", "alt_text"])]
+    pub highlight: Option<String>,
+
+    /// Render the input as Markdown and copy the HTML, with the Markdown as plain text
+    #[arg(long, conflicts_with_all = ["paste", "clear", "history", "image", "binary", "register", "mime", "html", "alt_text", "highlight"])]
+    pub markdown: bool,
+
+    /// Copy the FILE arguments themselves as a file list (for pasting into a file manager)
+    #[arg(long = "files", requires = "files", conflicts_with_all = ["paste", "clear", "history", "image", "binary", "register", "mime", "html", "highlight", "markdown", "append", "tee", "separator", "filter"])]
+    pub file_list: bool,
+
+    /// Encrypt to RECIPIENT with gpg before copying (repeatable)
+    #[arg(long, value_name = "RECIPIENT", conflicts_with_all = ["paste", "clear", "history", "image", "binary", "mime", "html", "highlight", "markdown", "file_list", "append"])]
+    pub encrypt: Vec<String>,
+
+    /// With -p, decrypt content copied with --encrypt
+    #[arg(long, requires = "paste", conflicts_with_all = ["history", "image", "binary", "mime"])]
+    pub decrypt: bool,
+
+    /// With -p, print the HTML form as readable text (links as `text (url)`)
+    #[arg(long, requires = "paste", conflicts_with_all = ["history", "image", "binary", "mime", "decrypt", "register"])]
+    pub plain: bool,
+
+    /// Print the text as a QR code: the copied text, or with -p the clipboard's
+    #[arg(long, conflicts_with_all = ["clear", "history", "image", "binary", "mime", "plain", "file_list", "encrypt", "tee"])]
+    pub qr: bool,
+
+    /// With -p, type the text into the focused window as key presses instead of printing it
+    #[arg(long = "type", requires = "paste", conflicts_with_all = ["history", "image", "binary", "output", "qr"])]
+    pub type_keys: bool,
+
+    /// With --type, pause between typed keys, in milliseconds (default 12)
+    #[arg(long, value_name = "MS", requires = "type_keys")]
+    pub delay_ms: Option<u64>,
+
+    /// Clear the clipboard after DURATION (e.g. 30s, 5m) unless something else was copied
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration, conflicts_with_all = ["paste", "clear", "history", "image", "register", "file_list"])]
+    pub expire: Option<Duration>,
+
+    /// Keep the copy available after bc exits (X11/Wayland): a background bc
+    /// serves it until another application copies something
+    #[arg(long, conflicts_with_all = ["paste", "clear", "history", "image", "binary", "mime", "html", "highlight", "markdown", "file_list", "register"])]
+    pub hold: bool,
+
+    /// Copy without recording it in the history
+    #[arg(long, conflicts_with_all = ["paste", "clear", "history", "register"])]
+    pub private: bool,
+
+    /// Remove this copy from the history after DURATION (e.g. 1h, 7d)
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration, conflicts_with_all = ["paste", "clear", "history", "private", "register"])]
+    pub ttl: Option<Duration>,
+
+    /// Copy even if the input looks like it contains a credential
+    #[arg(long, conflicts_with_all = ["paste", "clear", "history", "image", "binary", "file_list"])]
+    pub allow_secrets: bool,
+
+    /// Expand {{env:VAR}}, {{date:FORMAT}}, {{uuid}}, and {{hostname}} in the input
+    #[arg(long, conflicts_with_all = ["paste", "clear", "history", "image", "binary", "file_list"])]
+    pub template: bool,
+
+    /// Remove the leading whitespace all non-blank lines share
+    #[arg(long, conflicts_with_all = ["paste", "clear", "history", "image", "binary", "file_list"])]
+    pub dedent: bool,
+
+    /// Prefix each non-blank line with N spaces (after --dedent)
+    #[arg(long, value_name = "N", value_parser = width(), conflicts_with_all = ["paste", "clear", "history", "image", "binary", "file_list"])]
+    pub indent: Option<usize>,
+
+    /// Mask emails, IPs, keys and tokens with *** (RULES: all, or a list like email,ipv4,aws-key)
+    #[arg(long, value_name = "RULES", num_args = 0..=1, default_missing_value = "all", conflicts_with_all = ["paste", "clear", "history", "image", "binary", "file_list"])]
+    pub redact: Option<Redactor>,
+
+    #[arg(
+        short = 'r',
+        long,
+        value_name = "NAME",
+        hide = true,
+        conflicts_with = "image"
+    )]
+    pub register: Option<String>,
+
+    /// With -p, paste the history entry pinned as NAME
+    #[arg(long, value_name = "NAME", requires = "paste", conflicts_with_all = ["history", "image", "binary", "mime", "plain", "register"])]
+    pub pinned: Option<String>,
+
+    /// Read, transform, and check the input and report the bytes and backend, but copy nothing
+    #[arg(long, conflicts_with_all = ["paste", "clear", "history", "hold", "expire"])]
+    pub dry_run: bool,
+
+    /// Offer FILE's contents as TYPE (text/plain, text/html, image/png, image/jpeg); repeatable
+    #[arg(long, value_name = "TYPE=FILE", value_parser = parse_part, conflicts_with_all = ["paste", "clear", "history", "files", "separator", "tee", "append", "image", "binary", "mime", "html", "alt_text", "highlight", "markdown", "file_list", "encrypt", "expire", "hold", "qr", "register"])]
+    pub part: Vec<PartSpec>,
+
+    /// With -p, list the formats the clipboard offers (MIME types)
+    #[arg(long, requires = "paste", conflicts_with_all = ["history", "image", "binary", "mime", "output", "decrypt", "plain", "qr", "type_keys", "register", "pinned"])]
+    pub list_formats: bool,
+
+    /// With -p, print the clipboard's TYPE form (a MIME type from --list-formats) as raw bytes; with --image, the image format (png or jpeg)
+    #[arg(long, value_name = "TYPE", requires = "paste", conflicts_with_all = ["history", "binary", "mime", "decrypt", "plain", "qr", "type_keys", "register", "pinned", "list_formats"])]
+    pub format: Option<String>,
+
+    /// With -p, print the text in the clipboard image, recognized by tesseract
+    #[arg(long, requires = "paste", conflicts_with_all = ["history", "image", "binary", "mime", "decrypt", "plain", "register", "pinned", "list_formats", "format"])]
+    pub ocr: bool,
+
+    /// Language(s) for --ocr as tesseract codes (e.g. deu, eng+fra)
+    #[arg(long, value_name = "LANG", requires = "ocr")]
+    pub ocr_lang: Option<String>,
+
+    /// JPEG quality for --image --format jpeg, 1-100 (default 90)
+    #[arg(long, value_name = "N", requires = "image", value_parser = clap::value_parser!(u8).range(1..=100))]
+    pub quality: Option<u8>,
+
+    /// Scale the --image down to fit SIZE (800x, x600, or 800x600), keeping its aspect ratio
+    #[arg(long, value_name = "SIZE", requires = "image")]
+    pub resize: Option<Size>,
+
+    /// With -p, print the content N times, joined by --separator (default: a newline)
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..), requires = "paste", conflicts_with_all = ["history", "image", "qr", "type_keys", "list_formats"])]
+    pub repeat: Option<u64>,
+
+    #[arg(long, hide = true, conflicts_with_all = ["paste", "clear", "history", "image", "register"])]
+    pub registers: bool,
+
+    #[arg(long, hide = true, conflicts_with_all = ["paste", "clear", "history", "image", "register", "registers"])]
+    pub watch: bool,
+
+    #[arg(long, value_enum, value_name = "FORMAT", default_value_t = WatchFormat::Raw, hide = true, requires = "watch")]
+    pub watch_format: WatchFormat,
+
+    #[arg(long, hide = true, requires = "watch")]
+    pub once: bool,
+
+    #[arg(
+        long,
+        value_name = "COMMAND",
+        hide = true,
+        requires = "watch",
+        conflicts_with = "watch_format"
+    )]
+    pub exec: Option<String>,
+
+    #[arg(long, hide = true, requires = "exec")]
+    pub exec_stdin: bool,
+
+    #[arg(long = "match", value_name = "REGEX", hide = true, requires = "watch")]
+    pub matches: Vec<String>,
+
+    #[arg(long = "ignore", value_name = "REGEX", hide = true, requires = "watch")]
+    pub ignores: Vec<String>,
+
+    #[arg(long, hide = true, requires = "watch")]
+    pub apply_rules: bool,
+
+    #[arg(long, hide = true, conflicts_with_all = ["paste", "clear", "history", "image", "register", "registers", "watch"])]
+    pub probe: bool,
+
+    /// Print the exit statuses and the error codes reported with --json
+    #[arg(long, conflicts_with_all = ["paste", "clear", "history", "image", "register", "registers", "watch", "probe"])]
+    pub list_exit_codes: bool,
+}
+
+/// Settings shared by every command
+#[derive(Args, Debug)]
+pub struct Settings {
+    /// Print nothing on stderr; only the exit code reports the outcome
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    pub quiet: bool,
+
+    /// Log backend choice, detection results, sizes, and timing (-vv for every attempt)
+    #[arg(short, long, global = true, action = ArgAction::Count)]
+    pub verbose: u8,
+
+    /// Trim trailing newline from input
+    #[arg(short, long, global = true, env = "BC_TRIM", value_parser = BoolishValueParser::new())]
+    pub trim: bool,
+
+    /// Force local clipboard usage (disable remote detection)
+    #[arg(short, long, global = true, env = "BC_LOCAL", value_parser = BoolishValueParser::new())]
+    pub local: bool,
+
+    /// Treat the session as remote when detection misses it (a mosh or et
+    /// setup it doesn't know, sudo without SSH_* variables, ...): copy via OSC 52
+    #[arg(long, global = true, env = "BC_FORCE_REMOTE", value_parser = BoolishValueParser::new())]
+    pub force_remote: bool,
+
+    /// Always use OSC 52, never a local clipboard (containers, serial
+    /// consoles, VS Code remote); same as --backend osc52
+    #[arg(long, global = true, env = "BC_OSC52", value_parser = BoolishValueParser::new())]
+    pub osc52: bool,
+
+    /// Explain which clipboard backend is used and why (detection results)
+    #[arg(long, global = true)]
+    pub why: bool,
+
+    /// Force copy even if binary data is detected
+    #[arg(short, long, global = true, env = "BC_FORCE", value_parser = BoolishValueParser::new())]
+    pub force: bool,
+
+    /// Show preview of copied content
+    #[arg(short = 'P', long, global = true, env = "BC_PREVIEW", value_parser = BoolishValueParser::new())]
+    pub preview: bool,
+
+    /// Preview the first N lines as they are (control characters escaped) instead of one line; implies --preview
+    #[arg(long, global = true, value_name = "N", env = "BC_PREVIEW_LINES")]
+    pub preview_lines: Option<usize>,
+
+    /// Attempt OSC 52 clipboard query for remote paste (experimental, limited terminal support)
+    #[arg(long, global = true)]
+    pub force_paste: bool,
+
+    /// Clipboard backend to use instead of automatic detection
+    #[arg(long, global = true, value_name = "BACKEND", env = "BC_BACKEND", default_value = "auto", value_parser = backend_names())]
+    pub backend: String,
+
+    /// Selection to use: the regular clipboard, the primary selection, or both
+    #[arg(short = 's', long, global = true, value_name = "SELECTION", env = "BC_SELECTION", default_value = "clipboard", value_parser = selection_names())]
+    pub selection: Selection,
+
+    /// Use the primary selection (middle-click paste); overrides --selection
+    #[arg(long, global = true, conflicts_with = "both")]
+    pub primary: bool,
+
+    /// Use both the clipboard and the primary selection; overrides --selection
+    #[arg(long, global = true)]
+    pub both: bool,
+
+    /// Write OSC 52 sequences directly, without tmux/screen passthrough wrapping
+    #[arg(long, global = true, env = "BC_NO_PASSTHROUGH", value_parser = BoolishValueParser::new())]
+    pub no_passthrough: bool,
+
+    /// Split large OSC 52 copies into appended chunks (kitty, mosh)
+    #[arg(long, global = true, env = "BC_CHUNKED", value_parser = BoolishValueParser::new())]
+    pub chunked: bool,
+
+    /// Don't show progress bars for large input reads and OSC 52 writes
+    #[arg(long, global = true, env = "BC_NO_PROGRESS", value_parser = BoolishValueParser::new())]
+    pub no_progress: bool,
+
+    /// How long to wait for the terminal to answer an OSC 52 query, in milliseconds
+    #[arg(long, global = true, value_name = "MS", env = "BC_OSC52_TIMEOUT")]
+    pub osc52_timeout: Option<u64>,
+
+    /// Ask again this many times if an OSC 52 query goes unanswered, waiting
+    /// longer before each attempt (for slow SSH links)
+    #[arg(
+        long,
+        global = true,
+        value_name = "N",
+        env = "BC_OSC52_RETRIES",
+        default_value_t = 0
+    )]
+    pub osc52_retries: u32,
+
+    /// Try the local clipboard again this many times when it can't be
+    /// reached (arboard fails sporadically right after login or while the
+    /// compositor is busy)
+    #[arg(
+        long,
+        global = true,
+        value_name = "N",
+        env = "BC_CLIPBOARD_RETRIES",
+        default_value_t = ArboardBackend::DEFAULT_RETRIES
+    )]
+    pub clipboard_retries: u32,
+
+    /// Wait before the first local clipboard retry, in milliseconds; doubled
+    /// for each one after
+    #[arg(
+        long,
+        global = true,
+        value_name = "MS",
+        env = "BC_CLIPBOARD_RETRY_DELAY",
+        default_value_t = ArboardBackend::DEFAULT_RETRY_DELAY_MS
+    )]
+    pub clipboard_retry_delay: u64,
+
+    /// Give up on a local clipboard operation after this many milliseconds
+    /// (broken X forwarding can hang it forever); auto mode then copies via
+    /// OSC 52. 0 waits forever.
+    #[arg(
+        long,
+        global = true,
+        value_name = "MS",
+        env = "BC_CLIPBOARD_TIMEOUT",
+        default_value_t = ArboardBackend::DEFAULT_TIMEOUT_MS
+    )]
+    pub clipboard_timeout: u64,
+
+    /// Write OSC 52 sequences to a terminal device (default: the controlling terminal)
+    #[arg(long, global = true, value_name = "PATH", env = "BC_TTY", num_args = 0..=1, default_missing_value = osc52::CONTROLLING_TTY)]
+    pub tty: Option<PathBuf>,
+
+    /// Remove unpinned history entries older than DURATION (e.g. 7d); checked
+    /// whenever bc runs
+    #[arg(long, global = true, value_name = "DURATION", env = "BC_HISTORY_TTL", value_parser = parse_duration)]
+    pub history_ttl: Option<Duration>,
+
+    /// Encrypt sync, serve, push/pull, and history content with this key file (see `bc keygen`)
+    #[arg(long, global = true, value_name = "FILE", env = "BC_KEY")]
+    pub key: Option<PathBuf>,
+
+    /// Report errors as JSON objects on stderr, with a stable `error` code
+    /// (see --list-exit-codes); stat prints JSON too
+    #[arg(long, global = true)]
+    pub json: bool,
+
+    /// `[remote]` from the config file, filled in by `main`
+    #[arg(skip)]
+    pub remote_config: RemoteConfig,
+}
+
+impl Cli {
+    /// Parse arguments. `BC_*` environment variables provide defaults, so a
+    /// conflict between one and an explicit flag resolves to the flag.
+    /// Errors include `--help` and `--version` (see `clap::Error::kind`).
+    pub fn parse_with_env() -> Result<Self, clap::Error> {
+        let matches = Cli::command().try_get_matches()?;
+        let mut cli = Cli::from_arg_matches(&matches)?;
+        cli.validate()?;
+        let from_env = |id: &str| matches.value_source(id) == Some(ValueSource::EnvVariable);
+        let settings = &mut cli.settings;
+        // Drop environment-provided mode choices until one is left
+        for id in ["local", "force_remote", "osc52", "backend"] {
+            if settings.mode_choices() > 1 && from_env(id) {
+                match id {
+                    "local" => settings.local = false,
+                    "force_remote" => settings.force_remote = false,
+                    "osc52" => settings.osc52 = false,
+                    _ => settings.backend = "auto".to_string(),
+                }
+            }
+        }
+        Ok(cli)
+    }
+
+    /// Reject legacy operation flags combined with a subcommand (settings
+    /// are global and fine anywhere)
+    fn validate(&self) -> Result<(), clap::Error> {
+        let legacy = &self.legacy;
+        let legacy_used = legacy.paste
+            || legacy.clear
+            || legacy.history.is_some()
+            || legacy.image
+            || legacy.binary
+            || legacy.mime != Mime::Text
+            || legacy.html
+            || legacy.alt_text.is_some()
+            || legacy.highlight.is_some()
+            || legacy.markdown
+            || legacy.file_list
+            || !legacy.encrypt.is_empty()
+            || legacy.decrypt
+            || legacy.plain
+            || legacy.qr
+            || legacy.type_keys
+            || legacy.expire.is_some()
+            || legacy.hold
+            || legacy.private
+            || legacy.ttl.is_some()
+            || legacy.allow_secrets
+            || legacy.template
+            || legacy.dedent
+            || legacy.indent.is_some()
+            || legacy.redact.is_some()
+            || legacy.output.is_some()
+            || legacy.register.is_some()
+            || legacy.pinned.is_some()
+            || !legacy.part.is_empty()
+            || legacy.dry_run
+            || legacy.list_formats
+            || legacy.format.is_some()
+            || legacy.ocr
+            || legacy.ocr_lang.is_some()
+            || legacy.quality.is_some()
+            || legacy.resize.is_some()
+            || legacy.repeat.is_some()
+            || legacy.registers
+            || legacy.watch
+            || legacy.probe
+            || legacy.list_exit_codes
+            || legacy.transform != TransformArgs::default();
+        let not_copy = legacy.paste
+            || legacy.clear
+            || legacy.history.is_some()
+            || legacy.registers
+            || legacy.watch
+            || legacy.probe
+            || legacy.list_exit_codes;
+        // --separator goes between FILE arguments, or -p --repeat copies
+        let separator_alone =
+            |input: &InputArgs| input.separator.is_some() && input.files.is_empty();
+        let stray_separator = match &self.command {
+            Some(Command::Copy(args)) => separator_alone(&args.input),
+            _ => separator_alone(&self.input) && legacy.repeat.is_none(),
+        };
+        if stray_separator {
+            return Err(Cli::command().error(
+                ErrorKind::MissingRequiredArgument,
+                "--separator needs FILE arguments to go between (or -p --repeat N)",
+            ));
+        }
+        let has_input = self.input
+            != InputArgs {
+                separator: self
+                    .input
+                    .separator
+                    .clone()
+                    .filter(|_| legacy.repeat.is_some()),
+                ..InputArgs::default()
+            };
+        match &self.command {
+            Some(_) if legacy_used => Err(Cli::command().error(
+                ErrorKind::ArgumentConflict,
+                "operation flags (-p, -c, --history, ...) cannot be combined with a subcommand",
+            )),
+            Some(_) if has_input => Err(Cli::command().error(
+                ErrorKind::ArgumentConflict,
+                "file arguments go after the subcommand (bc copy FILE...)",
+            )),
+            None if has_input && not_copy => Err(Cli::command().error(
+                ErrorKind::ArgumentConflict,
+                "file arguments only apply to copying",
+            )),
+            None if legacy.transform != TransformArgs::default() && not_copy && !legacy.paste => {
+                Err(Cli::command().error(
+                    ErrorKind::ArgumentConflict,
+                    "transform flags (--filter, ...) only apply to copying and pasting",
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// The operation to run: the subcommand, or the one the legacy flags spell
+    pub fn into_command(self) -> (Command, Settings) {
+        let command = self
+            .command
+            .unwrap_or_else(|| self.legacy.into_command(self.input));
+        let mut settings = self.settings;
+        settings.preview |= settings.preview_lines.is_some();
+        if settings.primary {
+            settings.selection = Selection::Primary;
+        } else if settings.both {
+            settings.selection = Selection::Both;
+        }
+        (command, settings)
+    }
+}
+
+impl LegacyArgs {
+    fn into_command(self, input: InputArgs) -> Command {
+        if self.probe {
+            Command::Probe
+        } else if self.list_exit_codes {
+            Command::ExitCodes
+        } else if self.watch {
+            Command::Watch(WatchArgs {
+                format: self.watch_format,
+                once: self.once,
+                exec: self.exec,
+                exec_stdin: self.exec_stdin,
+                matches: self.matches,
+                ignores: self.ignores,
+                apply_rules: self.apply_rules,
+            })
+        } else if self.registers {
+            Command::Registers
+        } else if let Some(index) = self.history {
+            Command::History(HistoryArgs {
+                action: None,
+                index,
+                print: self.paste,
+            })
+        } else if self.paste {
+            Command::Paste(PasteArgs {
+                transform: self.transform,
+                image: self.image,
+                binary: self.binary,
+                mime: self.mime,
+                output: self.output,
+                append_file: self.append_file,
+                decrypt: self.decrypt,
+                plain: self.plain,
+                qr: self.qr,
+                type_keys: self.type_keys,
+                delay_ms: self.delay_ms,
+                register: self.register,
+                pinned: self.pinned,
+                list_formats: self.list_formats,
+                format: self.format,
+                ocr: self.ocr,
+                ocr_lang: self.ocr_lang,
+                quality: self.quality,
+                resize: self.resize,
+                repeat: self.repeat,
+                separator: input.separator,
+            })
+        } else if self.clear {
+            Command::Clear(ClearArgs {
+                register: self.register,
+            })
+        } else {
+            Command::Copy(CopyArgs {
+                input,
+                transform: self.transform,
+                image: self.image,
+                binary: self.binary,
+                mime: self.mime,
+                html: self.html,
+                alt_text: self.alt_text,
+                highlight: self.highlight,
+                markdown: self.markdown,
+                file_list: self.file_list,
+                encrypt: self.encrypt,
+                expire: self.expire,
+                allow_secrets: self.allow_secrets,
+                template: self.template,
+                dedent: self.dedent,
+                indent: self.indent,
+                redact: self.redact,
+                qr: self.qr,
+                hold: self.hold,
+                private: self.private,
+                ttl: self.ttl,
+                dry_run: self.dry_run,
+                part: self.part,
+                register: self.register,
+            })
+        }
+    }
+}
+
+impl ShotArgs {
+    /// What --screen, --region, or --window chose
+    pub fn area(&self) -> Area {
+        if self.region {
+            Area::Region
+        } else if self.window {
+            Area::Window
+        } else {
+            Area::Screen
+        }
+    }
+}
+
+impl Settings {
+    /// Backend selection from --local, --force-remote, --osc52, and --backend
+    pub fn mode(&self) -> Mode {
+        match BackendKind::from_name(&self.backend) {
+            Some(kind) => Mode::Backend(kind),
+            None if self.local => Mode::Local,
+            None if self.force_remote => Mode::Remote,
+            None if self.osc52 => Mode::Backend(BackendKind::Osc52),
+            None => Mode::Auto,
+        }
+    }
+
+    /// How many of --local, --force-remote, --osc52, and --backend are
+    /// given; more than one is an error
+    pub fn mode_choices(&self) -> usize {
+        [
+            self.local,
+            self.force_remote,
+            self.osc52,
+            self.backend != "auto",
+        ]
+        .iter()
+        .filter(|&&chosen| chosen)
+        .count()
+    }
+
+    /// Backend choice and settings for clipboard operations
+    pub fn options(&self) -> clipboard::Options {
+        clipboard::Options {
+            mode: self.mode(),
+            backend: BackendOptions {
+                selection: self.selection,
+                passthrough: !self.no_passthrough,
+                chunked: self.chunked,
+                query_timeout_ms: self.osc52_timeout,
+                query_retries: self.osc52_retries,
+                clipboard_retries: self.clipboard_retries,
+                clipboard_retry_delay_ms: self.clipboard_retry_delay,
+                clipboard_timeout: (self.clipboard_timeout > 0)
+                    .then(|| Duration::from_millis(self.clipboard_timeout)),
+                progress: !self.no_progress,
+                output: match &self.tty {
+                    Some(path) => osc52::Output::Tty(path.clone()),
+                    None => osc52::Output::Auto,
+                },
+            },
+            force_paste: self.force_paste,
+            remote: self.remote_config.clone(),
+        }
+    }
+
+    /// The key from the --key file, if one was given
+    pub fn key(&self) -> anyhow::Result<Option<Key>> {
+        self.key.as_deref().map(Key::from_file).transpose()
+    }
+}
+
+/// The completion script for `shell`
+pub fn completions(shell: Shell) -> Vec<u8> {
+    let mut script = Vec::new();
+    clap_complete::generate(shell, &mut Cli::command(), "bc", &mut script);
+    script
+}
+
+/// Parse a duration like `30s`, `5m`, `1h`, `7d`, or `500ms` (bare numbers are seconds)
+fn parse_duration(value: &str) -> Result<Duration, String> {
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("'{}' is not a duration (e.g. 30s, 5m)", value))?;
+    let millis = match unit {
+        "ms" => 1,
+        "" | "s" => 1000,
+        "m" => 60 * 1000,
+        "h" => 60 * 60 * 1000,
+        "d" => 24 * 60 * 60 * 1000,
+        _ => return Err(format!("unknown unit '{}' (use ms, s, m, h, or d)", unit)),
+    };
+    Ok(Duration::from_millis(number.saturating_mul(millis)))
+}
+
+/// Parse a `--part` argument: `TYPE=FILE`
+fn parse_part(value: &str) -> Result<PartSpec, String> {
+    let (mime, path) = value
+        .split_once('=')
+        .ok_or_else(|| format!("'{}' is not TYPE=FILE (e.g. text/html=note.html)", value))?;
+    let mime = PartMime::from_str(mime, false).map_err(|_| {
+        format!(
+            "unsupported part type '{}' (use text/plain, text/html, image/png, or image/jpeg)",
+            mime
+        )
+    })?;
+    if path.is_empty() {
+        return Err(format!("no file given for {}", value));
+    }
+    Ok(PartSpec {
+        mime,
+        path: PathBuf::from(path),
+    })
+}
+
+/// Whether `-q` and `--json` are among the raw `args`, for reporting an
+/// error that kept them from being parsed. Only standalone flags before
+/// `--` count.
+pub fn error_flags(args: impl IntoIterator<Item = std::ffi::OsString>) -> (bool, bool) {
+    let (mut quiet, mut json) = (false, false);
+    for arg in args {
+        match arg.to_str() {
+            Some("--") => break,
+            Some("-q" | "--quiet") => quiet = true,
+            Some("--json") => json = true,
+            _ => {}
+        }
+    }
+    (quiet, json)
+}
+
+/// Values accepted by --backend: "auto" plus every registered backend
+fn backend_names() -> clap::builder::PossibleValuesParser {
+    let names = std::iter::once("auto").chain(BackendKind::ALL.iter().map(|kind| kind.name()));
+    clap::builder::PossibleValuesParser::new(names)
+}
+
+/// Values accepted by --selection
+fn selection_names() -> impl TypedValueParser<Value = Selection> {
+    clap::builder::PossibleValuesParser::new(Selection::NAMES)
+        .map(|name| name.parse::<Selection>().expect("validated by clap"))
+}
+
+/// A width for --indent or --tabs-to-spaces, up to `transform::MAX_WIDTH`
+fn width() -> impl TypedValueParser<Value = usize> {
+    clap::value_parser!(u64)
+        .range(0..=transform::MAX_WIDTH as u64)
+        .map(|width| width as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn try_parse(args: &[&str]) -> Result<Cli, clap::Error> {
+        let cli = Cli::try_parse_from(std::iter::once("bc").chain(args.iter().copied()))?;
+        cli.validate().map(|_| cli)
+    }
+
+    fn parse(args: &[&str]) -> Command {
+        try_parse(args).unwrap().into_command().0
+    }
+
+    #[test]
+    fn test_error_flags() {
+        let flags = |args: &[&str]| error_flags(args.iter().map(|arg| arg.into()));
+        assert_eq!(flags(&["--json", "--bogus"]), (false, true));
+        assert_eq!(flags(&["-q", "paste", "--bogus"]), (true, false));
+        assert_eq!(flags(&["--bogus", "--", "-q", "--json"]), (false, false));
+    }
+
+    #[test]
+    fn test_env_defaults_declared() {
+        let command = Cli::command();
+        let env_of = |id: &str| {
+            command
+                .get_arguments()
+                .find(|arg| arg.get_id() == id)
+                .and_then(|arg| arg.get_env())
+                .map(|env| env.to_string_lossy().into_owned())
+        };
+        assert_eq!(env_of("trim").as_deref(), Some("BC_TRIM"));
+        assert_eq!(env_of("backend").as_deref(), Some("BC_BACKEND"));
+        assert_eq!(env_of("osc52_timeout").as_deref(), Some("BC_OSC52_TIMEOUT"));
+        assert_eq!(env_of("osc52_retries").as_deref(), Some("BC_OSC52_RETRIES"));
+        assert_eq!(
+            env_of("clipboard_retries").as_deref(),
+            Some("BC_CLIPBOARD_RETRIES")
+        );
+        // Operations are never taken from the environment
+        assert_eq!(env_of("paste"), None);
+        assert_eq!(env_of("clear"), None);
+    }
+
+    #[test]
+    fn test_bare_invocation_copies() {
+        assert_eq!(parse(&[]), Command::Copy(CopyArgs::default()));
+        assert_eq!(parse(&["-t"]), Command::Copy(CopyArgs::default()));
+    }
+
+    #[test]
+    fn test_legacy_flags_match_subcommands() {
+        assert_eq!(parse(&["-p"]), parse(&["paste"]));
+        assert_eq!(parse(&["-c"]), parse(&["clear"]));
+        assert_eq!(
+            parse(&["-p", "-r", "work"]),
+            parse(&["paste", "-r", "work"])
+        );
+        assert_eq!(
+            parse(&["--history", "2", "-p"]),
+            parse(&["history", "2", "-p"])
+        );
+        assert_eq!(parse(&["--history"]), parse(&["history"]));
+        assert_eq!(parse(&["--watch", "--once"]), parse(&["watch", "--once"]));
+        assert_eq!(
+            parse(&["--watch", "--exec", "wc -c", "--exec-stdin"]),
+            parse(&["watch", "--exec", "wc -c", "--exec-stdin"])
+        );
+        assert_eq!(
+            parse(&["--watch", "--match", "^magnet:", "--ignore", "x"]),
+            parse(&["watch", "--match", "^magnet:", "--ignore", "x"])
+        );
+        assert_eq!(
+            parse(&["--watch", "--apply-rules"]),
+            parse(&["watch", "--apply-rules"])
+        );
+        assert_eq!(parse(&["--registers"]), Command::Registers);
+        assert_eq!(parse(&["--probe"]), Command::Probe);
+        assert_eq!(parse(&["--list-exit-codes"]), Command::ExitCodes);
+        assert_eq!(parse(&["--binary"]), parse(&["copy", "--binary"]));
+        assert_eq!(parse(&["-p", "--binary"]), parse(&["paste", "--binary"]));
+        assert_eq!(
+            parse(&["--mime", "text/html"]),
+            parse(&["copy", "--mime", "text/html"])
+        );
+        assert_eq!(
+            parse(&["-p", "--mime", "text/html"]),
+            parse(&["paste", "--mime", "text/html"])
+        );
+        assert_eq!(
+            parse(&["--html", "--alt-text", "a.txt"]),
+            parse(&["copy", "--html", "--alt-text", "a.txt"])
+        );
+        assert_eq!(
+            parse(&["--highlight", "rust"]),
+            parse(&["copy", "--highlight", "rust"])
+        );
+        assert_eq!(parse(&["--markdown"]), parse(&["copy", "--markdown"]));
+        assert!(try_parse(&["copy", "--markdown", "--highlight", "rs"]).is_err());
+        assert_eq!(
+            parse(&["--files", "a.png", "b.pdf"]),
+            parse(&["copy", "--files", "a.png", "b.pdf"])
+        );
+        assert!(try_parse(&["--files"]).is_err());
+        assert_eq!(
+            parse(&["--encrypt", "me@example.com", "--encrypt", "you"]),
+            parse(&["copy", "--encrypt", "me@example.com", "--encrypt", "you"])
+        );
+        assert_eq!(parse(&["-p", "--decrypt"]), parse(&["paste", "--decrypt"]));
+        let repeated = Command::Paste(PasteArgs {
+            repeat: Some(3),
+            separator: Some(",".to_string()),
+            ..PasteArgs::default()
+        });
+        assert_eq!(
+            parse(&["-p", "--repeat", "3", "--separator", ","]),
+            repeated
+        );
+        assert_eq!(
+            parse(&["paste", "--repeat", "3", "--separator", ","]),
+            repeated
+        );
+        assert!(try_parse(&["paste", "--separator", ","]).is_err());
+        assert!(try_parse(&["paste", "--repeat", "0"]).is_err());
+        assert!(try_parse(&["--repeat", "2"]).is_err());
+        assert!(try_parse(&["--decrypt"]).is_err());
+        assert_eq!(parse(&["-p", "--plain"]), parse(&["paste", "--plain"]));
+        assert!(try_parse(&["--plain"]).is_err());
+        assert_eq!(parse(&["-p", "--qr"]), parse(&["paste", "--qr"]));
+        assert_eq!(parse(&["--qr"]), parse(&["copy", "--qr"]));
+        assert_eq!(parse(&["--hold"]), parse(&["copy", "--hold"]));
+        assert!(try_parse(&["copy", "--hold", "--html"]).is_err());
+        assert!(try_parse(&["-p", "--hold"]).is_err());
+        assert_eq!(parse(&["--private"]), parse(&["copy", "--private"]));
+        assert!(try_parse(&["-p", "--private"]).is_err());
+        assert_eq!(parse(&["--template"]), parse(&["copy", "--template"]));
+        assert_eq!(parse(&["--ttl", "1h"]), parse(&["copy", "--ttl", "1h"]));
+        assert!(try_parse(&["copy", "--ttl", "1h", "--private"]).is_err());
+        assert!(try_parse(&["paste", "--qr", "--image"]).is_err());
+        assert!(try_parse(&["copy", "--qr", "--tee"]).is_err());
+        assert!(try_parse(&["paste", "--plain", "--mime", "text/html"]).is_err());
+        assert_eq!(
+            parse(&["--expire", "30s"]),
+            parse(&["copy", "--expire", "30s"])
+        );
+        assert!(try_parse(&["-p", "--expire", "30s"]).is_err());
+        assert_eq!(
+            parse(&["--allow-secrets"]),
+            parse(&["copy", "--allow-secrets"])
+        );
+        assert!(try_parse(&["copy", "--encrypt", "me", "-a"]).is_err());
+        assert_eq!(
+            parse(&["--redact", "email,ipv4"]),
+            parse(&["copy", "--redact", "email,ipv4"])
+        );
+        assert!(matches!(parse(&["copy", "--redact"]),
+            Command::Copy(args) if args.redact == Some(Redactor::default())));
+        assert!(try_parse(&["copy", "--redact", "bogus"]).is_err());
+        assert_eq!(
+            parse(&["--filter", "trim,dedent", "--filter", "lf"]),
+            parse(&["copy", "--filter", "trim,dedent,lf"])
+        );
+        assert!(matches!(parse(&["-p", "--filter", "crlf"]),
+            Command::Paste(args) if args.transform.filter == [Transform::Crlf]));
+        assert!(try_parse(&["--filter", "bogus"]).is_err());
+        assert_eq!(parse(&["-p", "--crlf"]), parse(&["paste", "--crlf"]));
+        assert_eq!(parse(&["--lf"]), parse(&["copy", "--lf"]));
+        assert!(try_parse(&["copy", "--lf", "--crlf"]).is_err());
+        assert!(matches!(parse(&["--dedent", "--indent", "2"]),
+            Command::Copy(args) if args.dedent && args.indent == Some(2)));
+        assert!(try_parse(&["--indent", "99999999999"]).is_err());
+        assert!(try_parse(&["copy", "--indent", "1001"]).is_err());
+        assert!(try_parse(&["-p", "--dedent"]).is_err());
+        assert!(
+            matches!(parse(&["-p", "--tabs-to-spaces", "--squeeze-blank"]),
+            Command::Paste(args) if args.transform.tabs_to_spaces == Some(4) && args.transform.squeeze_blank)
+        );
+        assert!(matches!(parse(&["copy", "--tabs-to-spaces=2", "a.txt"]),
+            Command::Copy(args) if args.transform.tabs_to_spaces == Some(2) && args.input.files.len() == 1));
+        assert!(try_parse(&["-p", "--tabs-to-spaces=99999999999"]).is_err());
+        assert!(matches!(parse(&["-p", "--base64-decode", "--hex"]),
+            Command::Paste(args) if args.transform.base64_decode && args.transform.hex));
+        assert!(try_parse(&["copy", "--base64", "--hex"]).is_err());
+        assert!(try_parse(&["copy", "--upper", "--lower"]).is_err());
+        assert!(try_parse(&["copy", "--json-pretty", "--json-minify"]).is_err());
+        assert!(
+            matches!(parse(&["--fields", "2,4", "--delimiter", ",", "--trim-fields"]),
+            Command::Copy(args) if args.transform.fields == Some("2,4".parse().unwrap())
+                && args.transform.delimiter.as_deref() == Some(",") && args.transform.trim_fields)
+        );
+        assert_eq!(
+            parse(&["-p", "--fields", "2"]),
+            parse(&["paste", "--fields", "2"])
+        );
+        assert!(try_parse(&["--fields", "0"]).is_err());
+        assert!(try_parse(&["--delimiter", ","]).is_err());
+        assert!(try_parse(&["--fields", "1", "--delimiter", ""]).is_err());
+        assert!(matches!(parse(&["--sort", "--unique", "--reverse"]),
+            Command::Copy(args) if args.transform.sort && args.transform.unique && args.transform.reverse));
+        assert!(matches!(parse(&["-p", "--sort-numeric"]),
+            Command::Paste(args) if args.transform.sort_numeric && !args.transform.sort));
+        assert!(try_parse(&["copy", "--sort", "--sort-numeric"]).is_err());
+        assert_eq!(parse(&["--clean-url"]), parse(&["copy", "--clean-url"]));
+        assert!(
+            matches!(parse(&["--strip-invisible", "--normalize", "nfkc"]),
+            Command::Copy(args) if args.transform.strip_invisible && args.transform.normalize == Some(Form::Nfkc))
+        );
+        assert!(try_parse(&["-p", "--normalize", "nfd"]).is_err());
+        assert_eq!(
+            parse(&["-p", "--ascii-punctuation"]),
+            parse(&["paste", "--ascii-punctuation"])
+        );
+        assert_eq!(
+            parse(&["-p", "--json-pretty"]),
+            parse(&["paste", "--json-pretty"])
+        );
+        assert_eq!(
+            parse(&["--strip-trailing-ws"]),
+            parse(&["copy", "--strip-trailing-ws"])
+        );
+        assert!(matches!(parse(&["copy", "--filter", "indent=8"]),
+            Command::Copy(args) if args.transform.filter == [Transform::Indent(8)]));
+        assert_eq!(
+            parse(&["-p", "--strip-ansi"]),
+            parse(&["paste", "--strip-ansi"])
+        );
+        assert!(try_parse(&["-c", "--filter", "trim"]).is_err());
+        assert!(try_parse(&["--filter", "trim", "paste"]).is_err());
+        assert_eq!(
+            parse(&["-p", "-o", "out.txt", "--append-file"]),
+            parse(&["paste", "-o", "out.txt", "--append-file"])
+        );
+        assert!(try_parse(&["-o", "out.txt"]).is_err());
+        assert!(try_parse(&["sync"]).is_err());
+        assert!(matches!(parse(&["serve"]),
+            Command::Serve(args) if args.listen == "127.0.0.1:9111" && args.tls_cert.is_none()));
+        assert!(try_parse(&["serve", "--tls-cert", "cert.pem"]).is_err());
+        assert!(try_parse(&["serve", "--name", "desk"]).is_err());
+        assert!(try_parse(&["serve", "--token", ""]).is_err());
+        assert!(matches!(parse(&["serve", "--advertise", "--name", "desk"]),
+            Command::Serve(args) if args.advertise && args.name.as_deref() == Some("desk")));
+        assert!(matches!(parse(&["pull", "--peer", "laptop"]),
+            Command::Pull(args) if args.peer == "laptop" && args.timeout == 2000));
+        assert!(try_parse(&["push"]).is_err());
+        assert!(matches!(parse(&["keygen", "-o", "k"]),
+            Command::Keygen(args) if args.output == Some(PathBuf::from("k"))));
+        assert!(try_parse(&["sync", "--remote", "dev", "--remote-key", "k"]).is_err());
+        assert!(try_parse(&["sync", "--remote", "dev", "--key", "k", "--remote-key", "k"]).is_ok());
+        assert!(matches!(parse(&["sync", "--remote", "dev"]),
+            Command::Sync(args) if args.remote.as_deref() == Some("dev") && args.remote_bc == "bc"));
+        assert!(
+            matches!(parse(&["run", "--", "cargo", "test", "--release"]),
+            Command::Run(args) if args.command == ["cargo", "test", "--release"] && !args.on_fail)
+        );
+        assert!(matches!(parse(&["run", "--on-fail", "make", "-j4"]),
+            Command::Run(args) if args.command == ["make", "-j4"] && args.on_fail));
+        assert!(try_parse(&["run"]).is_err());
+        assert!(
+            matches!(parse(&["pipe", "--replace", "--", "jq", "-S", "."]),
+            Command::Pipe(args) if args.replace && args.command == ["jq", "-S", "."])
+        );
+        assert_eq!(parse(&["edit"]), Command::Edit);
+        assert_eq!(parse(&["stat", "--json"]), Command::Stat);
+        assert!(try_parse(&["stat", "--json"]).unwrap().settings.json);
+        assert_eq!(
+            parse(&["diff", "-U", "1", "-"]),
+            Command::Diff(DiffArgs {
+                file: PathBuf::from("-"),
+                context: 1
+            })
+        );
+        assert!(matches!(parse(&["diff", "a.txt"]), Command::Diff(args) if args.context == 3));
+        assert!(try_parse(&["diff"]).is_err());
+        assert_eq!(parse(&["hash"]), Command::Hash(HashArgs::default()));
+        assert_eq!(
+            parse(&["hash", "--algo", "blake3", "--verify", "af13"]),
+            Command::Hash(HashArgs {
+                algo: Algorithm::Blake3,
+                verify: Some("af13".to_string())
+            })
+        );
+        assert!(try_parse(&["hash", "--algo", "crc32"]).is_err());
+        assert_eq!(
+            parse(&["pick", "-p"]),
+            Command::Pick(PickArgs { print: true })
+        );
+        assert_eq!(
+            parse(&["snippet", "greet", "alice", "-p"]),
+            Command::Snippet(SnippetArgs {
+                action: None,
+                name: Some("greet".to_string()),
+                args: vec!["alice".to_string()],
+                print: true,
+            })
+        );
+        assert_eq!(
+            parse(&["snippet", "add", "greet"]),
+            Command::Snippet(SnippetArgs {
+                action: Some(SnippetAction::Add(SnippetAddArgs {
+                    name: "greet".to_string(),
+                    file: None,
+                })),
+                ..SnippetArgs::default()
+            })
+        );
+        assert!(matches!(
+            parse(&["snippet", "list"]),
+            Command::Snippet(SnippetArgs {
+                action: Some(SnippetAction::List),
+                ..
+            })
+        ));
+        assert!(try_parse(&["snippet"]).is_err());
+        assert_eq!(
+            parse(&["rules"]),
+            Command::Rules(RulesArgs { action: None })
+        );
+        assert_eq!(
+            parse(&["rules", "disable", "clean-links"]),
+            Command::Rules(RulesArgs {
+                action: Some(RulesAction::Disable(RuleNameArgs {
+                    name: "clean-links".to_string()
+                }))
+            })
+        );
+        assert!(try_parse(&["rules", "enable"]).is_err());
+        assert!(matches!(parse(&["shot"]), Command::Shot(args) if args.area() == Area::Screen));
+        assert!(matches!(parse(&["shot", "--region", "-o", "a.png"]),
+            Command::Shot(args) if args.area() == Area::Region && args.output.is_some()));
+        assert!(try_parse(&["shot", "--region", "--window"]).is_err());
+        assert_eq!(
+            parse(&["doctor", "--no-terminal"]),
+            Command::Doctor(DoctorArgs { no_terminal: true })
+        );
+        assert!(matches!(parse(&["pipe", "wc", "-l"]),
+            Command::Pipe(args) if !args.replace && args.command == ["wc", "-l"]));
+        assert_eq!(
+            parse(&["bridge", "--reverse"]),
+            Command::Bridge(BridgeArgs { reverse: true })
+        );
+    }
+
+    #[test]
+    fn test_settings_are_global() {
+        for args in [
+            &["paste", "--local", "-s", "primary"][..],
+            &["--local", "-s", "primary", "paste"],
+        ] {
+            let (command, settings) = try_parse(args).unwrap().into_command();
+            assert_eq!(command, Command::Paste(PasteArgs::default()));
+            assert!(settings.local);
+            assert_eq!(settings.selection, Selection::Primary);
+        }
+    }
+
+    #[test]
+    fn test_paste_type() {
+        let expected = Command::Paste(PasteArgs {
+            type_keys: true,
+            delay_ms: Some(50),
+            ..PasteArgs::default()
+        });
+        assert_eq!(parse(&["-p", "--type", "--delay-ms", "50"]), expected);
+        assert_eq!(parse(&["paste", "--type", "--delay-ms", "50"]), expected);
+        assert!(try_parse(&["paste", "--delay-ms", "50"]).is_err());
+        assert!(try_parse(&["--type"]).is_err());
+        assert!(try_parse(&["paste", "--type", "-o", "out.txt"]).is_err());
+    }
+
+    #[test]
+    fn test_history_pin() {
+        let history = |args: &[&str]| match parse(args) {
+            Command::History(args) => args,
+            other => panic!("{:?}", other),
+        };
+        assert_eq!(
+            history(&["history", "pin", "3", "--name", "sshkey"]).action,
+            Some(HistoryAction::Pin(PinArgs {
+                index: 3,
+                name: Some("sshkey".to_string()),
+            }))
+        );
+        assert_eq!(
+            history(&["history", "pin"]).action,
+            Some(HistoryAction::Pin(PinArgs {
+                index: 1,
+                name: None
+            }))
+        );
+        assert_eq!(
+            history(&["history", "unpin", "--name", "sshkey"]).action,
+            Some(HistoryAction::Unpin(UnpinArgs {
+                index: None,
+                name: Some("sshkey".to_string()),
+            }))
+        );
+        assert_eq!(
+            history(&["history", "export", "--format", "csv", "-o", "h.csv"]).action,
+            Some(HistoryAction::Export(ExportArgs {
+                format: ExportFormat::Csv,
+                output: Some(PathBuf::from("h.csv")),
+                encrypt: false,
+            }))
+        );
+        assert_eq!(
+            history(&["history", "import", "-"]).action,
+            Some(HistoryAction::Import(ImportArgs {
+                file: PathBuf::from("-")
+            }))
+        );
+        assert_eq!(history(&["history", "2"]).index, Some(2));
+        assert!(try_parse(&["history", "unpin"]).is_err());
+        assert!(try_parse(&["history", "2", "pin"]).is_err());
+
+        let expected = Command::Paste(PasteArgs {
+            pinned: Some("sshkey".to_string()),
+            ..PasteArgs::default()
+        });
+        assert_eq!(parse(&["-p", "--pinned", "sshkey"]), expected);
+        assert_eq!(parse(&["paste", "--pinned", "sshkey"]), expected);
+        assert!(try_parse(&["--pinned", "sshkey"]).is_err());
+    }
+
+    #[test]
+    fn test_selection_shorthands() {
+        let selection = |args: &[&str]| try_parse(args).unwrap().into_command().1.selection;
+        assert_eq!(selection(&["paste", "--primary"]), Selection::Primary);
+        assert_eq!(selection(&["--both"]), Selection::Both);
+        assert_eq!(selection(&[]), Selection::Clipboard);
+        assert!(try_parse(&["--primary", "--both"]).is_err());
+        // Over BC_SELECTION too, so they win whatever the environment says
+        assert_eq!(selection(&["--primary", "-s", "both"]), Selection::Primary);
+    }
+
+    #[test]
+    fn test_remote_mode() {
+        let (_, settings) = try_parse(&["paste", "--force-remote"])
+            .unwrap()
+            .into_command();
+        assert_eq!(settings.mode(), Mode::Remote);
+        let (_, settings) = try_parse(&["--force-remote", "--backend", "tmux"])
+            .unwrap()
+            .into_command();
+        // An explicit backend wins (main rejects the combination)
+        assert_eq!(settings.mode(), Mode::Backend(BackendKind::Tmux));
+        assert_eq!(
+            try_parse(&["copy"]).unwrap().into_command().1.mode(),
+            Mode::Auto
+        );
+
+        let (_, settings) = try_parse(&["--osc52"]).unwrap().into_command();
+        assert_eq!(settings.mode(), Mode::Backend(BackendKind::Osc52));
+        assert_eq!(settings.mode_choices(), 1);
+        let (_, settings) = try_parse(&["--osc52", "--local"]).unwrap().into_command();
+        assert_eq!(settings.mode_choices(), 2);
+    }
+
+    #[test]
+    fn test_preview_lines_implies_preview() {
+        let (_, settings) = try_parse(&["--preview-lines", "5"]).unwrap().into_command();
+        assert!(settings.preview);
+        assert_eq!(settings.preview_lines, Some(5));
+        let (_, settings) = try_parse(&["copy"]).unwrap().into_command();
+        assert!(!settings.preview);
+    }
+
+    #[test]
+    fn test_file_arguments() {
+        let expected = Command::Copy(CopyArgs {
+            input: InputArgs {
+                files: vec![PathBuf::from("a.txt"), PathBuf::from("-")],
+                separator: Some(",".to_string()),
+                ..InputArgs::default()
+            },
+            ..CopyArgs::default()
+        });
+        assert_eq!(parse(&["a.txt", "-", "--separator", ","]), expected);
+        assert_eq!(parse(&["copy", "a.txt", "-", "--separator", ","]), expected);
+        assert!(try_parse(&["-p", "a.txt"]).is_err());
+        assert!(try_parse(&["--separator", ","]).is_err());
+        assert!(try_parse(&["copy", "--separator", ","]).is_err());
+        assert!(try_parse(&["-p", "--separator", ","]).is_err());
+
+        assert!(matches!(parse(&["--tee"]), Command::Copy(args) if args.input.tee));
+        assert!(try_parse(&["paste", "--tee"]).is_err());
+
+        let appended = parse(&["-a", "--append-separator", "\n"]);
+        assert!(matches!(&appended, Command::Copy(args)
+            if args.input.append && args.input.append_separator.as_deref() == Some("\n")));
+        assert_eq!(parse(&["copy", "-a", "--append-separator", "\n"]), appended);
+        assert!(try_parse(&["-a", "--image"]).is_err());
+    }
+
+    #[test]
+    fn test_completions_cover_subcommands() {
+        assert_eq!(
+            parse(&["completions", "fish"]),
+            Command::Completions { shell: Shell::Fish }
+        );
+        let script = String::from_utf8(completions(Shell::Bash)).unwrap();
+        assert!(script.contains("paste"));
+        assert!(script.contains("--backend"));
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("30s"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration("45"), Ok(Duration::from_secs(45)));
+        assert_eq!(parse_duration("5m"), Ok(Duration::from_secs(300)));
+        assert_eq!(parse_duration("1h"), Ok(Duration::from_secs(3600)));
+        assert_eq!(parse_duration("250ms"), Ok(Duration::from_millis(250)));
+        assert!(parse_duration("s").is_err());
+        assert_eq!(parse_duration("7d"), Ok(Duration::from_secs(7 * 86400)));
+        assert!(parse_duration("3w").is_err());
+    }
+
+    #[test]
+    fn test_dry_run() {
+        assert_eq!(
+            parse(&["--dry-run", "--strip-ansi"]),
+            parse(&["copy", "--dry-run", "--strip-ansi"])
+        );
+        assert!(matches!(
+            parse(&["--dry-run"]),
+            Command::Copy(CopyArgs { dry_run: true, .. })
+        ));
+        assert!(try_parse(&["-p", "--dry-run"]).is_err());
+        assert!(try_parse(&["copy", "--dry-run", "--hold"]).is_err());
+    }
+
+    #[test]
+    fn test_parts() {
+        let expected = vec![
+            PartSpec {
+                mime: PartMime::Text,
+                path: PathBuf::from("note.txt"),
+            },
+            PartSpec {
+                mime: PartMime::Html,
+                path: PathBuf::from("note.html"),
+            },
+        ];
+        let args = [
+            "--part",
+            "text/plain=note.txt",
+            "--part",
+            "text/html=note.html",
+        ];
+        for command in [parse(&args), parse(&[&["copy"][..], &args].concat())] {
+            assert!(matches!(command, Command::Copy(CopyArgs { part, .. }) if part == expected));
+        }
+        assert!(try_parse(&["--part", "text/rtf=a.rtf"]).is_err());
+        assert!(try_parse(&["--part", "a.txt"]).is_err());
+        assert!(try_parse(&["--part", "text/plain=a.txt", "b.txt"]).is_err());
+        assert!(try_parse(&["--part", "text/plain=a.txt", "--html"]).is_err());
+
+        assert_eq!(
+            parse(&["-p", "--list-formats"]),
+            parse(&["paste", "--list-formats"])
+        );
+        assert!(try_parse(&["--list-formats"]).is_err());
+        assert_eq!(
+            parse(&["-p", "--format", "image/png", "-o", "a.png"]),
+            parse(&["paste", "--format", "image/png", "-o", "a.png"])
+        );
+        assert!(try_parse(&["paste", "--format", "text/html", "--list-formats"]).is_err());
+
+        assert_eq!(
+            parse(&["-p", "--ocr", "--ocr-lang", "deu"]),
+            parse(&["paste", "--ocr", "--ocr-lang", "deu"])
+        );
+        assert!(try_parse(&["--ocr"]).is_err());
+        assert!(try_parse(&["paste", "--ocr-lang", "deu"]).is_err());
+        assert!(try_parse(&["paste", "--ocr", "--image"]).is_err());
+
+        let args = [
+            "--image",
+            "--format",
+            "jpeg",
+            "--quality",
+            "80",
+            "--resize",
+            "800x",
+        ];
+        let command = parse(&[&["paste"][..], &args].concat());
+        assert_eq!(parse(&[&["-p"][..], &args].concat()), command);
+        assert!(matches!(
+            command,
+            Command::Paste(PasteArgs {
+                quality: Some(80),
+                resize: Some(_),
+                ..
+            })
+        ));
+        assert!(try_parse(&["paste", "--quality", "80"]).is_err());
+        assert!(try_parse(&["paste", "--image", "--quality", "0"]).is_err());
+        assert!(try_parse(&["paste", "--image", "--resize", "big"]).is_err());
+        assert!(try_parse(&["paste", "--list-formats", "--image"]).is_err());
+    }
+
+    #[test]
+    fn test_conflicts_are_rejected() {
+        for args in [
+            &["-p", "-c"][..],
+            &["--history", "-c"],
+            &["--image", "-c"],
+            &["-r", "x", "--image"],
+            &["--watch", "-p"],
+            &["-p", "paste"],
+            &["--probe", "clear"],
+            &["--list-exit-codes", "-p"],
+            &["copy", "--image", "-r", "x"],
+            &["history", "-p"],
+            &["watch", "--exec-stdin"],
+            &["watch", "--exec", "true", "--format", "json"],
+            &["--exec", "true"],
+            &["--match", "x"],
+        ] {
+            assert!(try_parse(args).is_err(), "{:?}", args);
+        }
+    }
+}
@@ -1,11 +1,16 @@
-use anyhow::{Context, Result};
-use arboard::Clipboard;
-use base64::Engine as _;
+//! Session detection and clipboard operations with local/remote fallback.
+
+use anyhow::Result;
 use is_terminal::IsTerminal;
-use std::{env, io};
+use std::path::PathBuf;
+use std::{env, fmt, io};
 
-use crate::osc52;
-use crate::Args;
+use crate::backends::{
+    ArboardBackend, BackendError, BackendKind, BackendOptions, ClipboardBackend, Osc52Backend, Part,
+};
+use crate::config::RemoteConfig;
+use crate::image::Image;
+use crate::{debug, message, verbose};
 
 /// Environment variables that indicate a remote session
 const REMOTE_SESSION_VARS: &[&str] = &[
@@ -14,165 +19,781 @@ const REMOTE_SESSION_VARS: &[&str] = &[
     "SSH_CONNECTION",
     "AWS_SSM_SESSION_ID",
     "SSM_SESSION_ID",
+    "MOSH_CONNECTION",
+    // VS Code dev containers and GitHub Codespaces
+    "REMOTE_CONTAINERS",
+    "CODESPACES",
 ];
 
-/// Error messages for remote paste operations
+/// Files that only exist inside a container (Docker, Podman)
+const CONTAINER_FILES: &[&str] = &["/.dockerenv", "/run/.containerenv"];
+
+/// Environment variables set inside containers: Kubernetes pods, and
+/// `container` from systemd-nspawn, Podman, and others
+const CONTAINER_VARS: &[&str] = &["KUBERNETES_SERVICE_HOST", "container"];
+
+/// Servers of remote sessions that leave no environment variables behind,
+/// found among our ancestor processes: mosh and Eternal Terminal
+const REMOTE_SESSION_PROCESSES: &[&str] = &["mosh-server", "etserver"];
+
+/// Error messages for remote paste operations; `bc doctor` explains the
+/// setup in detail
 const REMOTE_PASTE_ERROR: &str = "\
-Clipboard reading is not supported in remote sessions (SSH detected).
+Clipboard reading is not supported in remote sessions or headless containers
+(run with --why to see what was detected).
+Run `bc doctor` for what works here, or try the experimental OSC 52 query
+with --force-paste.";
+
+const REMOTE_PASTE_UNSUPPORTED: &str =
+    "Run `bc doctor` to check whether this terminal allows clipboard reading.";
+
+/// How copy, paste, and clear choose a backend
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Mode {
+    /// Detect remote sessions and fall back between local and OSC 52
+    #[default]
+    Auto,
+    /// Local backends only, no remote detection (`--local`)
+    Local,
+    /// Behave as in a detected remote session (`--force-remote`), for sessions
+    /// detection misses
+    Remote,
+    /// One explicitly chosen backend, no fallback (`--backend`)
+    Backend(BackendKind),
+}
+
+/// Backend choice and settings for clipboard operations
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Options {
+    pub mode: Mode,
+    pub backend: BackendOptions,
+    /// Allow the experimental OSC 52 query in remote sessions
+    pub force_paste: bool,
+    /// Extra remote session signs from the config file
+    pub remote: RemoteConfig,
+}
+
+impl Options {
+    /// The mode after detection: `Auto` becomes `Remote` in a remote
+    /// session or a container without a display
+    pub fn resolved_mode(&self) -> Mode {
+        match self.mode {
+            Mode::Auto if classify(&self.remote).is_remote() => Mode::Remote,
+            mode => mode,
+        }
+    }
+}
+
+/// What kind of place bc runs in, as far as choosing a clipboard goes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Environment {
+    /// A remote session, with the sign that gave it away
+    Remote(String),
+    /// A container with no display server, where arboard can only fail (or
+    /// hang on some X setups), with the sign
+    HeadlessContainer(String),
+    /// Neither: the local clipboard should work
+    Local,
+}
+
+impl Environment {
+    /// Whether text should go to the terminal via OSC 52
+    pub fn is_remote(&self) -> bool {
+        !matches!(self, Environment::Local)
+    }
+}
+
+impl fmt::Display for Environment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Environment::Remote(sign) => write!(f, "remote session ({})", sign),
+            Environment::HeadlessContainer(sign) => {
+                write!(f, "container without a display ({})", sign)
+            }
+            Environment::Local => f.write_str("local session"),
+        }
+    }
+}
+
+/// Classify the environment, checking the built-in signs and the ones
+/// `extra` lists
+pub fn classify(extra: &RemoteConfig) -> Environment {
+    if let Some(sign) = remote_sign(extra) {
+        return Environment::Remote(sign);
+    }
+    if let Some(sign) = container_sign() {
+        if !has_display() {
+            return Environment::HeadlessContainer(sign);
+        }
+    }
+    Environment::Local
+}
+
+/// Why copy, paste, and clear will use the backends they do (`--why`)
+pub fn explain(options: &Options) -> String {
+    let local_chain = || {
+        local_backends(&options.backend)
+            .iter()
+            .map(|backend| backend.name())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    match options.mode {
+        Mode::Backend(kind) => format!(
+            "The {} backend was chosen explicitly; no detection, no fallback",
+            kind.name()
+        ),
+        Mode::Local => format!(
+            "--local: trying the local backends ({}), never OSC 52",
+            local_chain()
+        ),
+        Mode::Remote => "--force-remote: text goes to the terminal via OSC 52".to_string(),
+        Mode::Auto => match classify(&options.remote) {
+            Environment::Local => format!(
+                "Local session (no remote or container signs): trying {}, then OSC 52",
+                local_chain()
+            ),
+            environment => format!(
+                "Detected a {}: text goes to the terminal via OSC 52, skipping the local clipboard",
+                environment
+            ),
+        },
+    }
+}
+
+/// Copy text using the backend(s) chosen by `options.mode`.
+/// Returns the name of the backend that took the copy.
+pub fn copy(text: &str, options: &Options) -> Result<&'static str> {
+    let backend_options = &options.backend;
+    match log_session(options) {
+        Mode::Backend(kind) => {
+            let backend = kind.create(backend_options);
+            backend.copy(text)?;
+            Ok(backend.name())
+        }
+        Mode::Local => try_local(backend_options, |backend| {
+            backend.copy(text).map(|_| backend.name())
+        }),
+        Mode::Remote => copy_osc52(text, backend_options),
+        Mode::Auto => try_local(backend_options, |backend| {
+            backend.copy(text).map(|_| backend.name())
+        })
+        .or_else(|e| {
+            verbose!(
+                "Local clipboard unavailable ({}), falling back to OSC 52",
+                e
+            );
+            copy_osc52(text, backend_options)
+        }),
+    }
+}
+
+fn copy_osc52(text: &str, options: &BackendOptions) -> Result<&'static str> {
+    let backend = Osc52Backend::from_options(options);
+    backend.copy(text)?;
+    Ok(backend.name())
+}
+
+/// The backend `copy` would use, found without writing anything
+/// (`--dry-run`): the first local backend whose `check` passes, falling back
+/// to OSC 52 in auto mode
+pub fn copy_target(options: &Options) -> Result<&'static str> {
+    match options.resolved_mode() {
+        Mode::Backend(kind) => Ok(kind.name()),
+        Mode::Remote => Ok(BackendKind::Osc52.name()),
+        mode => match local_backends(&options.backend)
+            .into_iter()
+            .find(|backend| backend.check().is_ok())
+        {
+            Some(backend) => Ok(backend.name()),
+            None if mode == Mode::Auto => Ok(BackendKind::Osc52.name()),
+            None => anyhow::bail!("No local clipboard is reachable"),
+        },
+    }
+}
+
+/// Fail as a copy of `text` to `target` (from `copy_target`) would on
+/// content the backend can't take, without writing anything. Only OSC 52
+/// has a size limit.
+pub fn check_copy(text: &str, target: &str, options: &Options) -> Result<()> {
+    if target == BackendKind::Osc52.name() {
+        Osc52Backend::from_options(&options.backend).check_size(text)?;
+    }
+    Ok(())
+}
 
-OSC 52 clipboard querying has limited terminal support and is disabled
-by default in most terminals for security reasons.
+/// Paste text using the backend(s) chosen by `options.mode`. In remote
+/// sessions the experimental OSC 52 query only runs with `force_paste`.
+pub fn paste(options: &Options) -> Result<String> {
+    let backend_options = &options.backend;
+    match log_session(options) {
+        Mode::Backend(BackendKind::Osc52) => handle_remote_paste(true, backend_options),
+        Mode::Backend(kind) => kind.create(backend_options).paste(),
+        Mode::Local => paste_local(backend_options),
+        Mode::Remote => handle_remote_paste(options.force_paste, backend_options),
+        Mode::Auto => paste_local(backend_options),
+    }
+}
 
-Alternatives:
-  - Use X11 forwarding: ssh -X host
-  - Copy file to remote: scp file.txt host:/tmp/ && cat /tmp/file.txt
-  - Force local clipboard with --local flag (if display available)
-  - Try experimental OSC 52 query: bc -p --force-paste";
+/// Clear the clipboard using the backend(s) chosen by `options.mode`.
+/// Returns Ok(true) if OSC 52 was used.
+pub fn clear(options: &Options) -> Result<bool> {
+    let backend_options = &options.backend;
+    match log_session(options) {
+        Mode::Backend(kind) => kind
+            .create(backend_options)
+            .clear()
+            .map(|_| kind == BackendKind::Osc52),
+        Mode::Local => clear_with_fallback(false, true, backend_options),
+        Mode::Remote => clear_with_fallback(true, false, backend_options),
+        Mode::Auto => clear_with_fallback(false, false, backend_options),
+    }
+}
 
-const REMOTE_PASTE_UNSUPPORTED: &str = "\
-OSC 52 query requires:
-  - A terminal (stdin must be a TTY, not piped input)
-  - Terminal that supports clipboard reading (XTerm, kitty, tmux)
-  - Proper terminal configuration
+/// Copy HTML with `alt_text` as its plain-text form. Remote sessions get
+/// only the plain text, since OSC 52 has no notion of formats.
+pub fn copy_html(html: &str, alt_text: &str, options: &Options) -> Result<&'static str> {
+    match options.resolved_mode() {
+        Mode::Backend(kind) => {
+            let backend = kind.create(&options.backend);
+            backend.copy_html(html, alt_text).map(|_| backend.name())
+        }
+        Mode::Remote => {
+            verbose!("Remote session: copying the plain-text form only");
+            copy(alt_text, options)
+        }
+        Mode::Local | Mode::Auto => {
+            let backend = ArboardBackend::from_options(&options.backend);
+            backend.copy_html(html, alt_text).map(|_| backend.name())
+        }
+    }
+}
 
-Most terminals (WezTerm, iTerm2, Alacritty, Ghostty) do NOT support
-clipboard reading for security reasons.
+/// Put several formats on the clipboard at once. Remote sessions get only
+/// the plain-text part, like `copy_html`.
+pub fn copy_parts(parts: &[Part], options: &Options) -> Result<&'static str> {
+    match options.resolved_mode() {
+        Mode::Backend(kind) => {
+            let backend = kind.create(&options.backend);
+            backend.copy_parts(parts).map(|_| backend.name())
+        }
+        Mode::Remote => {
+            let Some(text) = parts.iter().find_map(|part| match part {
+                Part::Text(text) => Some(text),
+                _ => None,
+            }) else {
+                anyhow::bail!("OSC 52 only carries text; add a text/plain part");
+            };
+            verbose!("Remote session: copying the plain-text part only");
+            copy(text, options)
+        }
+        Mode::Local | Mode::Auto => {
+            let backend = ArboardBackend::from_options(&options.backend);
+            backend.copy_parts(parts).map(|_| backend.name())
+        }
+    }
+}
+
+/// The MIME types the clipboard offers (see `format_backend`)
+pub fn formats(options: &Options) -> Result<Vec<String>> {
+    format_backend(options).formats()
+}
+
+/// Read one format of the clipboard as raw bytes (see `format_backend`)
+pub fn paste_format(mime: &str, options: &Options) -> Result<Vec<u8>> {
+    format_backend(options).paste_format(mime)
+}
 
-Currently supported terminals:
-  - XTerm (set 'XTerm*allowWindowOps: true' in ~/.Xresources)
-  - kitty (enable 'clipboard_control read' in kitty.conf)
-  - tmux 3.0+ (set 'set -s set-clipboard on' in tmux.conf)
+/// Backend for format listing and reads: the explicit backend, else
+/// wl-clipboard in a Wayland session (it sees every type the compositor
+/// offers), else arboard
+fn format_backend(options: &Options) -> Box<dyn ClipboardBackend> {
+    if let Mode::Backend(kind) = options.mode {
+        return kind.create(&options.backend);
+    }
+    if is_wayland_session() {
+        let wayland = BackendKind::Wl.create(&options.backend);
+        if wayland.check().is_ok() {
+            return wayland;
+        }
+    }
+    BackendKind::Local.create(&options.backend)
+}
+
+/// Read the clipboard's HTML (see `copy_image` for backend choice)
+pub fn paste_html(options: &Options) -> Result<String> {
+    match options.mode {
+        Mode::Backend(kind) => kind.create(&options.backend).paste_html(),
+        Mode::Local | Mode::Auto | Mode::Remote => {
+            ArboardBackend::from_options(&options.backend).paste_html()
+        }
+    }
+}
+
+/// Put a file list on the clipboard (see `copy_image` for backend choice)
+pub fn copy_files(paths: &[PathBuf], options: &Options) -> Result<()> {
+    match options.mode {
+        Mode::Backend(kind) => kind.create(&options.backend).copy_files(paths),
+        Mode::Local | Mode::Auto | Mode::Remote => {
+            ArboardBackend::from_options(&options.backend).copy_files(paths)
+        }
+    }
+}
+
+/// Copy an image. Only an explicit backend changes where images go;
+/// otherwise they always use arboard (OSC 52 only carries text).
+pub fn copy_image(image: &Image, options: &Options) -> Result<()> {
+    match options.mode {
+        Mode::Backend(kind) => kind.create(&options.backend).copy_image(image),
+        Mode::Local | Mode::Auto | Mode::Remote => {
+            ArboardBackend::from_options(&options.backend).copy_image(image)
+        }
+    }
+}
+
+/// Read an image (see `copy_image` for backend choice)
+pub fn paste_image(options: &Options) -> Result<Image> {
+    match options.mode {
+        Mode::Backend(kind) => kind.create(&options.backend).paste_image(),
+        Mode::Local | Mode::Auto | Mode::Remote => {
+            ArboardBackend::from_options(&options.backend).paste_image()
+        }
+    }
+}
+
+/// Single backend for repeated reads (watch mode): the explicit backend, or
+/// the first reachable local backend
+pub fn polling_backend(options: &Options) -> Box<dyn ClipboardBackend> {
+    if let Mode::Backend(kind) = options.mode {
+        return kind.create(&options.backend);
+    }
+    local_backends(&options.backend)
+        .into_iter()
+        .find(|backend| backend.check().is_ok())
+        .unwrap_or_else(|| BackendKind::Local.create(&options.backend))
+}
 
-Alternatives:
-  - X11 forwarding: ssh -X host
-  - File transfer: scp file.txt host:/tmp/ && cat /tmp/file.txt
-  - Force local clipboard: bc -p --local";
+/// Report the mode and detection results that drive backend choice (`-v`),
+/// and return the resolved mode
+fn log_session(options: &Options) -> Mode {
+    let resolved = options.resolved_mode();
+    verbose!(
+        "Mode {:?} (resolved {:?}); termux: {}, wsl: {}, wayland: {}",
+        options.mode,
+        resolved,
+        is_termux(),
+        is_wsl(),
+        is_wayland_session()
+    );
+    resolved
+}
 
-/// Detect if running in a remote session (SSH, AWS SSM, etc.)
+/// Detect if running in a remote session (SSH, AWS SSM, mosh, Eternal
+/// Terminal, etc.)
 pub fn is_remote_session() -> bool {
-    REMOTE_SESSION_VARS.iter().any(|var| env::var(var).is_ok())
+    is_remote_session_with(&RemoteConfig::default())
 }
 
-/// Copy text to local clipboard via arboard
-pub fn copy_local(text: &str) -> Result<()> {
-    Clipboard::new()
-        .context("Failed to initialize clipboard")?
-        .set_text(text)
-        .context("Failed to write to local clipboard")
+/// `is_remote_session`, also checking the variables and processes `extra`
+/// lists
+pub fn is_remote_session_with(extra: &RemoteConfig) -> bool {
+    remote_sign(extra).is_some()
 }
 
-/// Copy text to remote clipboard via OSC 52
-pub fn copy_remote(text: &str) -> Result<()> {
-    let encoded = base64::engine::general_purpose::STANDARD.encode(text);
+/// The first sign of a remote session, described
+fn remote_sign(extra: &RemoteConfig) -> Option<String> {
+    let mut vars = REMOTE_SESSION_VARS
+        .iter()
+        .copied()
+        .chain(extra.env.iter().map(String::as_str));
+    if let Some(var) = vars.find(|var| env::var_os(var).is_some()) {
+        return Some(format!("${} is set", var));
+    }
+    if is_vscode_remote() {
+        return Some("VS Code remote terminal".to_string());
+    }
+    ancestor_processes()
+        .into_iter()
+        .find(|name| {
+            REMOTE_SESSION_PROCESSES.contains(&name.as_str()) || extra.processes.contains(name)
+        })
+        .map(|name| format!("running under {}", name))
+}
 
-    if encoded.len() > osc52::OSC52_MAX_SIZE {
-        anyhow::bail!(
-            "Content too large for OSC 52 clipboard ({} bytes, max {} bytes). \
-             Use --local flag or alternative transfer method.",
-            encoded.len(),
-            osc52::OSC52_MAX_SIZE
-        );
+/// The first sign of running in a container, described
+fn container_sign() -> Option<String> {
+    if let Some(var) = CONTAINER_VARS.iter().find(|var| env::var_os(var).is_some()) {
+        return Some(format!("${} is set", var));
+    }
+    CONTAINER_FILES
+        .iter()
+        .find(|path| std::path::Path::new(path).exists())
+        .map(|path| format!("{} exists", path))
+}
+
+/// Whether an X11 or Wayland display is there to hold a clipboard. Only
+/// Linux and the BSDs need one; elsewhere the OS provides the clipboard.
+pub(crate) fn has_display() -> bool {
+    let unix_desktop = cfg!(all(
+        unix,
+        not(target_os = "macos"),
+        not(target_os = "android")
+    ));
+    !unix_desktop || env::var_os("DISPLAY").is_some_and(|v| !v.is_empty()) || is_wayland_session()
+}
+
+/// Names of our ancestor processes, nearest first, up to a few levels (the
+/// shell and a multiplexer or two sit between us and a session server).
+/// Empty where /proc isn't available.
+#[cfg(target_os = "linux")]
+pub fn ancestor_processes() -> Vec<String> {
+    let mut names = Vec::new();
+    let mut pid = std::process::id();
+    for _ in 0..8 {
+        let Ok(stat) = std::fs::read_to_string(format!("/proc/{}/stat", pid)) else {
+            break;
+        };
+        // Format: pid (comm) state ppid ...; comm may contain spaces
+        let Some((comm, rest)) = stat
+            .split_once('(')
+            .and_then(|(_, rest)| rest.rsplit_once(')'))
+        else {
+            break;
+        };
+        if pid != std::process::id() {
+            names.push(comm.to_string());
+        }
+        match rest
+            .split_whitespace()
+            .nth(1)
+            .and_then(|ppid| ppid.parse().ok())
+        {
+            Some(ppid) if ppid > 1 => pid = ppid,
+            _ => break,
+        }
+    }
+    names
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn ancestor_processes() -> Vec<String> {
+    Vec::new()
+}
+
+/// Names of the other processes with our stdin pipe open, normally the
+/// program writing into it (`pass show x | bc` gives `pass`). Empty if
+/// stdin isn't a pipe, or where /proc isn't available.
+#[cfg(target_os = "linux")]
+pub fn stdin_writers() -> Vec<String> {
+    use rustix::event::{poll, PollFd, PollFlags};
+
+    /// How long to wait for the writer's first output
+    const OUTPUT_WAIT_MS: i32 = 500;
+
+    let Ok(pipe) = std::fs::read_link("/proc/self/fd/0") else {
+        return Vec::new();
+    };
+    if !pipe.to_string_lossy().starts_with("pipe:") {
+        return Vec::new();
+    }
+    // The shell starts both sides of a pipeline at once, so the writer may
+    // not have exec'd yet; once it has written (or taken a while) it has
+    let stdin = std::io::stdin();
+    let _ = poll(&mut [PollFd::new(&stdin, PollFlags::IN)], OUTPUT_WAIT_MS);
+    let own = std::process::id().to_string();
+    let Ok(processes) = std::fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+    let mut names = Vec::new();
+    for process in processes.flatten() {
+        let pid = process.file_name();
+        let pid = pid.to_string_lossy();
+        if pid == own || !pid.bytes().all(|b| b.is_ascii_digit()) {
+            continue;
+        }
+        // Other users' processes can't be read, and can't be writing to us
+        let Ok(fds) = std::fs::read_dir(process.path().join("fd")) else {
+            continue;
+        };
+        let holds_pipe = fds
+            .flatten()
+            .any(|fd| std::fs::read_link(fd.path()).is_ok_and(|target| target == pipe));
+        if holds_pipe {
+            if let Ok(comm) = std::fs::read_to_string(process.path().join("comm")) {
+                names.push(comm.trim_end().to_string());
+            }
+        }
+    }
+    names
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn stdin_writers() -> Vec<String> {
+    Vec::new()
+}
+
+/// Detect a VS Code Remote (SSH, WSL, tunnel) terminal: VS Code sets
+/// `VSCODE_IPC_HOOK_CLI` in local integrated terminals too, so it only counts
+/// on Linux with no display to copy to (and not under WSL, whose clip.exe
+/// reaches the right clipboard)
+fn is_vscode_remote() -> bool {
+    cfg!(target_os = "linux")
+        && env::var_os("VSCODE_IPC_HOOK_CLI").is_some()
+        && !has_display()
+        && !is_wsl()
+}
+
+/// Detect a Wayland session, where arboard may lack the data-control protocol
+pub fn is_wayland_session() -> bool {
+    env::var_os("WAYLAND_DISPLAY").is_some_and(|v| !v.is_empty())
+}
+
+/// Detect Windows Subsystem for Linux, where the Windows clipboard is
+/// reachable through clip.exe and PowerShell
+pub fn is_wsl() -> bool {
+    if env::var_os("WSL_DISTRO_NAME").is_some() {
+        return true;
+    }
+    cfg!(target_os = "linux")
+        && std::fs::read_to_string("/proc/version")
+            .is_ok_and(|version| version.to_lowercase().contains("microsoft"))
+}
+
+/// Detect Termux on Android, which has no X11/Wayland clipboard
+pub fn is_termux() -> bool {
+    env::var_os("TERMUX_VERSION").is_some()
+}
+
+/// Local clipboard backends in order of preference: Termux:API on Android,
+/// the Windows clipboard under WSL, arboard, then wl-clipboard in Wayland
+/// sessions. In auto mode OSC 52 remains the fallback when all of them fail
+/// (e.g. the Termux:API package isn't installed).
+pub fn local_backends(options: &BackendOptions) -> Vec<Box<dyn ClipboardBackend>> {
+    let mut kinds = Vec::new();
+    if is_termux() {
+        kinds.push(BackendKind::Termux);
+    }
+    if is_wsl() {
+        kinds.push(BackendKind::Wsl);
+    }
+    kinds.push(BackendKind::Local);
+    if is_wayland_session() {
+        kinds.push(BackendKind::Wl);
+    }
+    kinds.into_iter().map(|kind| kind.create(options)).collect()
+}
+
+/// Run an operation against each local backend until one succeeds.
+/// On failure, reports the first error unless a fallback failed for a
+/// reason other than not being installed.
+fn try_local<T>(
+    options: &BackendOptions,
+    op: impl Fn(&dyn ClipboardBackend) -> Result<T>,
+) -> Result<T> {
+    let mut error: Option<anyhow::Error> = None;
+    for backend in local_backends(options) {
+        debug!("Trying the {} backend", backend.name());
+        let result = op(backend.as_ref());
+        if let Err(e) = &result {
+            verbose!("The {} backend failed: {}", backend.name(), e);
+        }
+        match result {
+            Ok(value) => {
+                verbose!("Using the {} backend", backend.name());
+                return Ok(value);
+            }
+            Err(e) if error.is_none() => error = Some(e),
+            Err(e) if !matches!(e.downcast_ref(), Some(BackendError::NotInstalled(_))) => {
+                error = Some(e)
+            }
+            Err(_) => {}
+        }
     }
+    Err(error.unwrap_or_else(|| anyhow::anyhow!("No local clipboard available")))
+}
 
-    osc52::write_sequence(&osc52::build_sequence_raw(&encoded))
+/// Copy text to the local clipboard
+pub fn copy_local(text: &str, options: &BackendOptions) -> Result<()> {
+    try_local(options, |backend| backend.copy(text))
 }
 
-/// Clear local clipboard
-pub fn clear_local() -> Result<()> {
-    Clipboard::new()
-        .context("Failed to initialize clipboard")?
-        .set_text("")
-        .context("Failed to clear local clipboard")
+/// Read text from the local clipboard
+pub fn paste_local(options: &BackendOptions) -> Result<String> {
+    try_local(options, |backend| backend.paste())
 }
 
-/// Clear remote clipboard via OSC 52 (empty write)
-pub fn clear_remote() -> Result<()> {
-    osc52::write_sequence(&osc52::build_sequence_raw(""))
+/// Clear the local clipboard
+pub fn clear_local(options: &BackendOptions) -> Result<()> {
+    try_local(options, |backend| backend.clear())
 }
 
 /// Clear clipboard with automatic fallback logic
 /// Returns Ok(true) if OSC 52 was used, Ok(false) if local only
 pub fn clear_clipboard(prefer_remote: bool, force_local: bool) -> Result<bool> {
-    let remote_result = clear_remote().map(|_| true);
+    clear_with_fallback(prefer_remote, force_local, &BackendOptions::default())
+}
+
+fn clear_with_fallback(
+    prefer_remote: bool,
+    force_local: bool,
+    options: &BackendOptions,
+) -> Result<bool> {
+    let remote_result = Osc52Backend::from_options(options).clear().map(|_| true);
 
     if prefer_remote {
         if force_local {
-            return clear_local().map(|_| false);
+            return clear_local(options).map(|_| false);
         }
         if remote_result.is_ok() {
             return remote_result;
         }
         // Fallback to local if remote failed
-        return clear_local().map(|_| false);
+        return clear_local(options).map(|_| false);
     }
 
     // Prefer local: try local first, fallback to remote
-    clear_local()
+    clear_local(options)
         .map(|_| false)
         .or_else(|e| if force_local { Err(e) } else { remote_result })
 }
 
-/// Paste from clipboard (supports local and experimental OSC 52 query)
-pub fn paste_clipboard(args: &Args) -> Result<String> {
-    if !args.local && is_remote_session() {
-        return handle_remote_paste(args);
-    }
-
-    Clipboard::new()
-        .context("Failed to initialize clipboard")?
-        .get_text()
-        .context("Failed to read from clipboard")
-}
-
 /// Handle paste in remote sessions
-fn handle_remote_paste(args: &Args) -> Result<String> {
-    if !args.force_paste {
+fn handle_remote_paste(force_paste: bool, options: &BackendOptions) -> Result<String> {
+    if !force_paste {
         return Err(anyhow::anyhow!(REMOTE_PASTE_ERROR));
     }
 
-    eprintln!("Warning: --force-paste is experimental");
-    eprintln!("OSC 52 clipboard querying requires terminal support (XTerm, kitty, tmux)");
-    eprintln!("Most terminals (WezTerm, iTerm2, etc.) do not support clipboard reading");
+    message!("Warning: --force-paste is experimental");
 
     if !io::stdin().is_terminal() {
         return Err(anyhow::anyhow!(
-            "OSC 52 query requires a terminal (stdin is not a TTY).\n\n{}",
-            REMOTE_PASTE_UNSUPPORTED
+            "OSC 52 query requires a terminal (stdin is not a TTY)."
         ));
     }
 
-    if env::var("TMUX").is_ok() || env::var("STY").is_ok() {
-        eprintln!("WARNING: Detected terminal multiplexer (tmux/screen).");
-        eprintln!("OSC 52 query requires: set-clipboard on (tmux) or passthrough config.");
-    }
-
-    if env::var("KITTY_WINDOW_ID").is_ok() {
-        eprintln!("Detected kitty terminal.");
-        eprintln!("OSC 52 query requires: 'clipboard_control read' in kitty.conf");
-    }
-
-    osc52::query_clipboard(2000)
-        .and_then(|encoded| {
-            if encoded.is_empty() {
-                return Ok(String::new());
-            }
-            let bytes = base64::engine::general_purpose::STANDARD
-                .decode(&encoded)
-                .context("Failed to decode base64 clipboard content")?;
-            String::from_utf8(bytes).context("Clipboard content is not valid UTF-8")
-        })
-        .map_err(|e| anyhow::anyhow!("OSC 52 query failed: {}\n\n{}", e, REMOTE_PASTE_UNSUPPORTED))
+    Osc52Backend::from_options(options)
+        .paste()
+        .map_err(|e| anyhow::anyhow!("OSC 52 query failed: {}\n{}", e, REMOTE_PASTE_UNSUPPORTED))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::osc52;
 
     #[test]
     fn test_osc52_clear_sequence() {
         assert_eq!(osc52::build_sequence_raw(""), "\x1b]52;c;\x07");
     }
 
+    #[test]
+    fn test_local_backends_include_arboard() {
+        let names: Vec<_> = local_backends(&BackendOptions::default())
+            .iter()
+            .map(|b| b.name())
+            .collect();
+        assert!(names.contains(&"local"));
+        assert_eq!(names.contains(&"wsl"), is_wsl());
+        assert_eq!(names[0] == "termux", is_termux());
+    }
+
+    #[test]
+    fn test_default_mode_is_auto() {
+        assert_eq!(Mode::default(), Mode::Auto);
+    }
+
+    #[test]
+    fn test_resolved_mode() {
+        let forced = Options {
+            mode: Mode::Remote,
+            ..Options::default()
+        };
+        assert_eq!(forced.resolved_mode(), Mode::Remote);
+        let local = Options {
+            mode: Mode::Local,
+            remote: RemoteConfig {
+                env: vec!["PATH".to_string()],
+                ..RemoteConfig::default()
+            },
+            ..Options::default()
+        };
+        assert_eq!(local.resolved_mode(), Mode::Local);
+        // Configured variables count like the built-in ones
+        let auto = Options {
+            mode: Mode::Auto,
+            ..local
+        };
+        assert_eq!(auto.resolved_mode(), Mode::Remote);
+    }
+
+    #[test]
+    fn test_classify_and_explain() {
+        let extra = RemoteConfig {
+            env: vec!["PATH".to_string()],
+            ..RemoteConfig::default()
+        };
+        let environment = classify(&extra);
+        assert!(environment.is_remote());
+        assert_eq!(environment.to_string(), "remote session ($PATH is set)");
+        assert_eq!(
+            Environment::HeadlessContainer("/.dockerenv exists".to_string()).to_string(),
+            "container without a display (/.dockerenv exists)"
+        );
+        assert!(!Environment::Local.is_remote());
+
+        let auto = Options {
+            remote: extra,
+            ..Options::default()
+        };
+        assert!(explain(&auto).starts_with("Detected a remote session ($PATH is set)"));
+        let tmux = Options {
+            mode: Mode::Backend(BackendKind::Tmux),
+            ..Options::default()
+        };
+        assert!(explain(&tmux).contains("tmux backend was chosen explicitly"));
+        let local = Options {
+            mode: Mode::Local,
+            ..Options::default()
+        };
+        assert!(explain(&local).contains("local"), "{}", explain(&local));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_configured_remote_processes() {
+        // The test runner always has a parent (cargo, a shell, ...)
+        let parent = ancestor_processes().remove(0);
+        assert!(is_remote_session_with(&RemoteConfig {
+            processes: vec![parent],
+            ..RemoteConfig::default()
+        }));
+    }
+
+    #[test]
+    fn test_explicit_backend_has_no_fallback() {
+        // A missing program fails instead of falling back to another backend
+        let options = Options {
+            mode: Mode::Backend(BackendKind::Termux),
+            ..Options::default()
+        };
+        if crate::backends::ExternalBackend::termux().check().is_err() {
+            let err = copy("x", &options).unwrap_err();
+            assert!(matches!(
+                err.downcast_ref(),
+                Some(BackendError::NotInstalled(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_polling_backend_respects_explicit_choice() {
+        let options = Options {
+            mode: Mode::Backend(BackendKind::Tmux),
+            ..Options::default()
+        };
+        assert_eq!(polling_backend(&options).name(), "tmux");
+    }
+
     #[test]
     fn test_clear_clipboard_force_local_override() {
         // force_local=true should use local even when prefer_remote=true
@@ -0,0 +1,292 @@
+//! The optional config file, `config.toml` in [`paths::config_dir`].
+//!
+//! Every section and key is optional; a missing file is the same as an
+//! empty one.
+
+use anyhow::Result;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::paths;
+
+/// Config file name inside the config directory
+const CONFIG_FILE: &str = "config.toml";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub secrets: SecretsConfig,
+    #[serde(rename = "clean-url")]
+    pub clean_url: CleanUrlConfig,
+    pub remote: RemoteConfig,
+    pub limits: LimitsConfig,
+    pub history: HistoryConfig,
+    pub hooks: HooksConfig,
+    /// `[rules.NAME]` tables, by name
+    pub rules: BTreeMap<String, RuleConfig>,
+}
+
+/// `[secrets]`: what the pre-copy secret scan looks for
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SecretsConfig {
+    /// Extra rules, as name = "regex"
+    pub patterns: BTreeMap<String, String>,
+    /// Built-in rules to turn off, by name
+    pub disable: Vec<String>,
+}
+
+/// `[clean-url]`: extra rules for `--clean-url`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CleanUrlConfig {
+    /// Tracking parameters to drop; a trailing `*` matches any suffix
+    pub params: Vec<String>,
+    /// Redirector links to unwrap
+    pub redirectors: Vec<Redirector>,
+}
+
+/// `[remote]`: extra signs of a remote session, for setups the built-in
+/// detection misses
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RemoteConfig {
+    /// Environment variables whose presence means a remote session
+    pub env: Vec<String>,
+    /// Ancestor process names that mean a remote session (Linux only)
+    pub processes: Vec<String>,
+}
+
+/// `[limits]`: how much input a copy reads
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LimitsConfig {
+    /// Bytes of input before a copy gives up, like `--max-size`; replaces
+    /// the OSC 52 default
+    pub max_size: Option<usize>,
+}
+
+/// `[history]`: what stays out of the copy history
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HistoryConfig {
+    /// Programs whose output isn't recorded: the command of `bc run` and
+    /// `bc pipe`, or (Linux only) the program piping into `bc`
+    pub ignore_commands: Vec<String>,
+    /// Regexes; content matching any of them isn't recorded
+    pub ignore_patterns: Vec<String>,
+    /// Content longer than this many bytes isn't recorded
+    pub max_entry_size: Option<usize>,
+}
+
+/// `[hooks]`: shell commands run around copies and pastes (see
+/// [`crate::hooks`])
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HooksConfig {
+    /// Filters the content of each copy; a failure cancels the copy
+    pub pre_copy: Option<String>,
+    /// Runs after each copy
+    pub post_copy: Option<String>,
+    /// Runs after each paste
+    pub post_paste: Option<String>,
+}
+
+/// `[rules.NAME]`: a rewrite `bc watch --apply-rules` makes to clipboard
+/// content (see [`crate::rules`])
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RuleConfig {
+    /// Regex the content must match
+    #[serde(rename = "match")]
+    pub pattern: String,
+    /// `--filter` transforms to apply, in order
+    pub transforms: Vec<String>,
+    /// Whether the rule runs, unless `bc rules enable|disable` overrides it
+    #[serde(default = "enabled")]
+    pub enabled: bool,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+/// A link that bounces through `host` with the destination in `param`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Redirector {
+    /// Host name; a leading `*.` matches any subdomain
+    pub host: String,
+    /// Path prefix the link must have
+    #[serde(default = "root_path")]
+    pub path: String,
+    /// Query parameter holding the destination
+    pub param: String,
+}
+
+fn root_path() -> String {
+    "/".to_string()
+}
+
+impl Config {
+    /// Default location of the config file
+    pub fn default_path() -> Result<PathBuf> {
+        Ok(paths::config_dir()?.join(CONFIG_FILE))
+    }
+
+    /// Load the config from its default location
+    pub fn load() -> Result<Self> {
+        Self::load_from(&Self::default_path()?)
+    }
+
+    /// Load the config from `path`; a missing file is an empty config
+    pub fn load_from(path: &Path) -> Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(text) => Self::parse(&text)
+                .map_err(|e| anyhow::anyhow!("Invalid config {}: {}", path.display(), e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(anyhow::anyhow!(
+                "Failed to read config {}: {}",
+                path.display(),
+                e
+            )),
+        }
+    }
+
+    fn parse(text: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_file_is_default() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config::load_from(&dir.path().join(CONFIG_FILE)).unwrap();
+        assert_eq!(config, Config::default());
+    }
+
+    #[test]
+    fn test_secrets_section() {
+        let config = Config::parse(
+            r#"
+            [secrets]
+            disable = ["high-entropy"]
+            patterns = { internal = "itk_[a-z0-9]{8}" }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.secrets.disable, ["high-entropy"]);
+        assert_eq!(config.secrets.patterns["internal"], "itk_[a-z0-9]{8}");
+    }
+
+    #[test]
+    fn test_clean_url_section() {
+        let config = Config::parse(
+            r#"
+            [clean-url]
+            params = ["ref"]
+            redirectors = [{ host = "go.example.com", param = "to" }]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.clean_url.params, ["ref"]);
+        assert_eq!(config.clean_url.redirectors[0].path, "/");
+        assert!(Config::parse("[clean-url]\nredirectors = [{ host = \"x\" }]").is_err());
+    }
+
+    #[test]
+    fn test_remote_section() {
+        let config = Config::parse(
+            r#"
+            [remote]
+            env = ["DEVPOD"]
+            processes = ["teleport"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.remote.env, ["DEVPOD"]);
+        assert_eq!(config.remote.processes, ["teleport"]);
+        assert_eq!(Config::parse("").unwrap().remote, RemoteConfig::default());
+    }
+
+    #[test]
+    fn test_hooks_section() {
+        let config = Config::parse(
+            r#"
+            [hooks]
+            pre_copy = "sed 's/foo/bar/'"
+            post_paste = "logger -t bc pasted"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.hooks.pre_copy.as_deref(), Some("sed 's/foo/bar/'"));
+        assert_eq!(config.hooks.post_copy, None);
+        assert!(Config::parse(
+            "[hooks]
+pre-copy = \"x\"\n"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_limits_section() {
+        let config = Config::parse("[limits]\nmax_size = 1048576\n").unwrap();
+        assert_eq!(config.limits.max_size, Some(1048576));
+        assert_eq!(Config::parse("").unwrap().limits.max_size, None);
+        assert!(Config::parse("[limits]\nmax_size = -1\n").is_err());
+    }
+
+    #[test]
+    fn test_history_section() {
+        let config = Config::parse(
+            r#"
+            [history]
+            ignore_commands = ["pass"]
+            ignore_patterns = ["(?i)password"]
+            max_entry_size = 4096
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.history.ignore_commands, ["pass"]);
+        assert_eq!(config.history.ignore_patterns, ["(?i)password"]);
+        assert_eq!(config.history.max_entry_size, Some(4096));
+    }
+
+    #[test]
+    fn test_rules_section() {
+        let config = Config::parse(
+            r#"
+            [rules.clean-links]
+            match = "^https?://"
+            transforms = ["clean-url", "trim"]
+
+            [rules.shout]
+            match = "."
+            transforms = ["upper"]
+            enabled = false
+            "#,
+        )
+        .unwrap();
+        let rule = &config.rules["clean-links"];
+        assert_eq!(rule.pattern, "^https?://");
+        assert_eq!(rule.transforms, ["clean-url", "trim"]);
+        assert!(rule.enabled);
+        assert!(!config.rules["shout"].enabled);
+        assert!(Config::parse("[rules.x]\ntransforms = []\n").is_err());
+    }
+
+    #[test]
+    fn test_unknown_keys_are_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(CONFIG_FILE);
+        fs::write(&path, "[secrets]\npaterns = {}\n").unwrap();
+        let err = Config::load_from(&path).unwrap_err();
+        assert!(err.to_string().contains("Invalid config"));
+    }
+}
@@ -0,0 +1,197 @@
+//! Authenticated encryption (XChaCha20-Poly1305) for clipboard content that
+//! leaves the process: sync updates, `bc serve` requests, and history on disk.
+//!
+//! A key is 32 random bytes, stored base64-encoded in a file made by
+//! `bc keygen` and selected with `--key`. Sealed text is
+//! `bc1:<base64(nonce || ciphertext)>`, so it travels anywhere plain text can.
+
+use anyhow::{Context, Result};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// Key size in bytes
+pub const KEY_LEN: usize = 32;
+/// Random nonce prepended to each ciphertext
+const NONCE_LEN: usize = 24;
+/// Marks text produced by [`Key::seal_text`]
+const PREFIX: &str = "bc1:";
+
+/// A symmetric key shared by everyone who may read the content
+#[derive(Clone, PartialEq, Eq)]
+pub struct Key([u8; KEY_LEN]);
+
+impl fmt::Debug for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Key(..)")
+    }
+}
+
+impl Key {
+    /// A new random key
+    pub fn generate() -> Result<Self> {
+        let mut bytes = [0u8; KEY_LEN];
+        getrandom::getrandom(&mut bytes)
+            .map_err(|e| anyhow::anyhow!("Failed to generate a key: {}", e))?;
+        Ok(Self(bytes))
+    }
+
+    /// Parse the base64 form written by [`Key::encoded`]
+    pub fn parse(text: &str) -> Result<Self> {
+        let bytes = BASE64
+            .decode(text.trim())
+            .ok()
+            .and_then(|bytes| <[u8; KEY_LEN]>::try_from(bytes).ok())
+            .ok_or_else(|| {
+                anyhow::anyhow!("Not a bc key (expected {} base64-encoded bytes)", KEY_LEN)
+            })?;
+        Ok(Self(bytes))
+    }
+
+    /// Read a key file made by `bc keygen`
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read key {}: {}", path.display(), e))?;
+        Self::parse(&text).with_context(|| format!("Invalid key file {}", path.display()))
+    }
+
+    /// Write the key to a new file readable only by the current user
+    pub fn write_new(&self, path: &Path) -> Result<()> {
+        use std::io::Write;
+
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options.open(path).map_err(|e| match e.kind() {
+            std::io::ErrorKind::AlreadyExists => anyhow::anyhow!(
+                "{} already exists; remove it first to replace the key",
+                path.display()
+            ),
+            _ => anyhow::anyhow!("Failed to create {}: {}", path.display(), e),
+        })?;
+        writeln!(file, "{}", self.encoded())
+            .map_err(|e| anyhow::anyhow!("Failed to write {}: {}", path.display(), e))
+    }
+
+    /// The key as base64 text
+    pub fn encoded(&self) -> String {
+        BASE64.encode(self.0)
+    }
+
+    fn cipher(&self) -> XChaCha20Poly1305 {
+        XChaCha20Poly1305::new(&self.0.into())
+    }
+
+    /// Encrypt `plaintext` under a fresh random nonce
+    pub fn seal(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        let mut nonce = [0u8; NONCE_LEN];
+        getrandom::getrandom(&mut nonce)
+            .map_err(|e| anyhow::anyhow!("Failed to generate a nonce: {}", e))?;
+        let ciphertext = self
+            .cipher()
+            .encrypt(XNonce::from_slice(&nonce), plaintext)
+            .map_err(|_| anyhow::anyhow!("Encryption failed"))?;
+        Ok([nonce.as_slice(), &ciphertext].concat())
+    }
+
+    /// Decrypt and authenticate output of [`Key::seal`]
+    pub fn open(&self, sealed: &[u8]) -> Result<Vec<u8>> {
+        if sealed.len() < NONCE_LEN {
+            anyhow::bail!("Encrypted content is truncated");
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        self.cipher()
+            .decrypt(XNonce::from_slice(nonce), ciphertext)
+            .map_err(|_| anyhow::anyhow!("Decryption failed (wrong key or tampered content)"))
+    }
+
+    /// Encrypt text into the printable `bc1:` form
+    pub fn seal_text(&self, text: &str) -> Result<String> {
+        Ok(format!(
+            "{}{}",
+            PREFIX,
+            BASE64.encode(self.seal(text.as_bytes())?)
+        ))
+    }
+
+    /// Decrypt text made by [`Key::seal_text`]
+    pub fn open_text(&self, sealed: &str) -> Result<String> {
+        let encoded = sealed
+            .trim_end()
+            .strip_prefix(PREFIX)
+            .ok_or_else(|| anyhow::anyhow!("Content is not encrypted"))?;
+        let sealed = BASE64
+            .decode(encoded)
+            .map_err(|_| anyhow::anyhow!("Encrypted content is corrupt"))?;
+        String::from_utf8(self.open(&sealed)?)
+            .map_err(|_| anyhow::anyhow!("Decrypted content is not valid UTF-8"))
+    }
+}
+
+/// Whether `text` looks like output of [`Key::seal_text`]
+pub fn is_sealed(text: &str) -> bool {
+    text.starts_with(PREFIX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_roundtrip() {
+        let key = Key::generate().unwrap();
+        let sealed = key.seal_text("secret\n").unwrap();
+        assert!(is_sealed(&sealed));
+        assert!(!sealed.contains("secret"));
+        assert_eq!(key.open_text(&sealed).unwrap(), "secret\n");
+        // Fresh nonce each time
+        assert_ne!(sealed, key.seal_text("secret\n").unwrap());
+    }
+
+    #[test]
+    fn test_wrong_key_and_tampering_fail() {
+        let key = Key::generate().unwrap();
+        let other = Key::generate().unwrap();
+        let sealed = key.seal(b"data").unwrap();
+        assert!(other.open(&sealed).is_err());
+
+        let mut tampered = sealed.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(key.open(&tampered).is_err());
+        assert!(key.open(&sealed[..10]).is_err());
+        assert!(key.open_text("plain").is_err());
+    }
+
+    #[test]
+    fn test_key_file_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("key");
+        let key = Key::generate().unwrap();
+        key.write_new(&path).unwrap();
+        assert_eq!(Key::from_file(&path).unwrap(), key);
+        let err = key.write_new(&path).unwrap_err();
+        assert!(err.to_string().contains("already exists"));
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+
+    #[test]
+    fn test_parse_rejects_bad_keys() {
+        assert!(Key::parse("not base64!").is_err());
+        assert!(Key::parse(&BASE64.encode([0u8; 16])).is_err());
+        assert!(format!("{:?}", Key::generate().unwrap()) == "Key(..)");
+    }
+}
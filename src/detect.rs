@@ -0,0 +1,343 @@
+//! Guessing what clipboard text is: a URL, JSON, code in some language, ...
+//!
+//! Cheap heuristics, checked from the most to the least specific. The result
+//! is shown by `--preview`, `bc stat`, and `bc watch --format json`.
+
+use regex::Regex;
+use std::fmt;
+
+/// What a piece of text looks like
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Url,
+    Email,
+    Path,
+    Json,
+    Xml,
+    Html,
+    Markdown,
+    /// Source code, with the language if one stands out
+    Code(Option<&'static str>),
+    Text,
+}
+
+impl Kind {
+    /// Lowercase name for JSON output (`url`, `json`, `code`, ...)
+    pub fn name(self) -> &'static str {
+        match self {
+            Kind::Url => "url",
+            Kind::Email => "email",
+            Kind::Path => "path",
+            Kind::Json => "json",
+            Kind::Xml => "xml",
+            Kind::Html => "html",
+            Kind::Markdown => "markdown",
+            Kind::Code(_) => "code",
+            Kind::Text => "text",
+        }
+    }
+
+    /// The guessed language of code
+    pub fn language(self) -> Option<&'static str> {
+        match self {
+            Kind::Code(language) => language,
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Kind::Url => f.write_str("URL"),
+            Kind::Email => f.write_str("email address"),
+            Kind::Path => f.write_str("file path"),
+            Kind::Json => f.write_str("JSON"),
+            Kind::Xml => f.write_str("XML"),
+            Kind::Html => f.write_str("HTML"),
+            Kind::Markdown => f.write_str("Markdown"),
+            Kind::Code(Some(language)) => write!(f, "{} code", language),
+            Kind::Code(None) => f.write_str("code"),
+            Kind::Text => f.write_str("text"),
+        }
+    }
+}
+
+/// Substrings that suggest a language, one point each
+const LANGUAGE_HINTS: &[(&str, &[&str])] = &[
+    (
+        "rust",
+        &[
+            "fn ", "let mut ", "impl ", "pub fn ", "use std", "println!", "-> ",
+        ],
+    ),
+    (
+        "python",
+        &[
+            "def ", "import ", "self.", "elif ", "print(", "__init__", "None",
+        ],
+    ),
+    (
+        "javascript",
+        &[
+            "function ",
+            "const ",
+            "=> ",
+            "console.log",
+            "require(",
+            "===",
+            "export ",
+        ],
+    ),
+    (
+        "go",
+        &["func ", "package ", ":= ", "fmt.", "go func", "err != nil"],
+    ),
+    (
+        "c",
+        &["#include", "int main", "printf(", "malloc(", "->", "NULL"],
+    ),
+    (
+        "java",
+        &[
+            "public class",
+            "public static void",
+            "System.out",
+            "private ",
+            "@Override",
+        ],
+    ),
+    (
+        "shell",
+        &["echo ", "$(", "fi\n", "then\n", "done\n", "export ", "&& "],
+    ),
+    (
+        "sql",
+        &[
+            "SELECT ",
+            "FROM ",
+            "WHERE ",
+            "INSERT INTO",
+            "CREATE TABLE",
+            "JOIN ",
+        ],
+    ),
+];
+
+/// Classify `text`
+pub fn detect(text: &str) -> Kind {
+    let trimmed = text.trim();
+    if trimmed.is_empty() {
+        return Kind::Text;
+    }
+    if (trimmed.starts_with('{') || trimmed.starts_with('['))
+        && serde_json::from_str::<serde_json::Value>(trimmed).is_ok()
+    {
+        return Kind::Json;
+    }
+    if let Some(kind) = markup(trimmed) {
+        return kind;
+    }
+    if !trimmed.contains(['\n', '\r']) {
+        if let Some(kind) = single_line(trimmed) {
+            return kind;
+        }
+    }
+    if is_markdown(trimmed) {
+        return Kind::Markdown;
+    }
+    if let Some(language) = shebang(trimmed).or_else(|| guess_language(trimmed)) {
+        return Kind::Code(Some(language));
+    }
+    if looks_like_code(trimmed) {
+        return Kind::Code(None);
+    }
+    Kind::Text
+}
+
+/// XML or HTML documents and fragments
+fn markup(text: &str) -> Option<Kind> {
+    let lower: String = text
+        .chars()
+        .take(64)
+        .collect::<String>()
+        .to_ascii_lowercase();
+    if lower.starts_with("<!doctype html") || lower.starts_with("<html") {
+        return Some(Kind::Html);
+    }
+    if lower.starts_with("<?xml")
+        || (text.starts_with('<') && text.ends_with('>') && text.contains("</"))
+    {
+        return Some(Kind::Xml);
+    }
+    None
+}
+
+/// URLs, email addresses, and paths, which are one line
+fn single_line(text: &str) -> Option<Kind> {
+    let url = Regex::new(r"^(https?|ftp|file)://\S+$").expect("URL pattern is valid");
+    let email =
+        Regex::new(r"^[\w.+-]+@[\w-]+(\.[\w-]+)*\.[A-Za-z]{2,}$").expect("email pattern is valid");
+    // Absolute, home, explicitly relative, or Windows paths; a bare `a/b` only
+    // with a file extension or a trailing slash
+    let path = Regex::new(
+        r#"^(~?/|\.\.?/|[A-Za-z]:\\|\\\\)[^\x00<>"|?*]*$|^[\w.-]+(/[\w.-]+)*/[\w-]*\.\w+$|^[\w.-]+(/[\w.-]+)*/$"#,
+    )
+    .expect("path pattern is valid");
+    if url.is_match(text) {
+        Some(Kind::Url)
+    } else if email.is_match(text) {
+        Some(Kind::Email)
+    } else if path.is_match(text) {
+        Some(Kind::Path)
+    } else {
+        None
+    }
+}
+
+/// At least two kinds of Markdown syntax, or a code fence
+fn is_markdown(text: &str) -> bool {
+    let patterns = [
+        r"(?m)^#{1,6} \S",
+        r"(?m)^\s*([-*+]|\d+\.) \S",
+        r"\[[^\]\n]+\]\([^)\s]+\)",
+        r"\*\*[^*\n]+\*\*|__[^_\n]+__",
+        r"(?m)^> ",
+        r"`[^`\n]+`",
+    ];
+    if text
+        .lines()
+        .any(|line| line.trim_start().starts_with("```"))
+    {
+        return true;
+    }
+    let found = patterns
+        .iter()
+        .filter(|pattern| {
+            Regex::new(pattern)
+                .expect("Markdown pattern is valid")
+                .is_match(text)
+        })
+        .count();
+    found >= 2
+}
+
+/// The language a `#!` line names
+fn shebang(text: &str) -> Option<&'static str> {
+    let first = text.lines().next()?.strip_prefix("#!")?;
+    let interpreter = first.split_whitespace().last()?.rsplit('/').next()?;
+    match interpreter {
+        "sh" | "bash" | "zsh" | "dash" | "ksh" => Some("shell"),
+        i if i.starts_with("python") => Some("python"),
+        "node" => Some("javascript"),
+        _ => None,
+    }
+}
+
+/// The language with the most hints, if it has at least two and no other
+/// language ties with it
+fn guess_language(text: &str) -> Option<&'static str> {
+    let mut scores: Vec<(&str, usize)> = LANGUAGE_HINTS
+        .iter()
+        .map(|(language, hints)| {
+            let score = hints.iter().filter(|hint| text.contains(*hint)).count();
+            (*language, score)
+        })
+        .collect();
+    scores.sort_by_key(|&(_, score)| std::cmp::Reverse(score));
+    match scores.as_slice() {
+        [(language, best), (_, next), ..] if *best >= 2 && best > next => Some(language),
+        _ => None,
+    }
+}
+
+/// A good share of lines end in braces or semicolons
+fn looks_like_code(text: &str) -> bool {
+    let lines: Vec<&str> = text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect();
+    let code_like = lines
+        .iter()
+        .filter(|line| line.ends_with([';', '{', '}']))
+        .count();
+    lines.len() > 1 && code_like * 3 >= lines.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_single_line_kinds() {
+        assert_eq!(detect("https://example.com/a?b=1\n"), Kind::Url);
+        assert_eq!(detect("jane.doe+bc@mail.example.org"), Kind::Email);
+        assert_eq!(detect("/etc/ssh/sshd_config"), Kind::Path);
+        assert_eq!(detect("~/projects/bc/"), Kind::Path);
+        assert_eq!(detect("src/main.rs"), Kind::Path);
+        assert_eq!(detect(r"C:\Users\me\notes.txt"), Kind::Path);
+        assert_eq!(detect("see https://example.com"), Kind::Text);
+        assert_eq!(detect("and/or"), Kind::Text);
+        assert_eq!(detect("Just a sentence."), Kind::Text);
+        assert_eq!(detect(""), Kind::Text);
+    }
+
+    #[test]
+    fn test_structured_kinds() {
+        assert_eq!(detect("{\"a\": [1, 2]}\n"), Kind::Json);
+        assert_eq!(detect("[1, 2, 3]"), Kind::Json);
+        assert_eq!(detect("{not json"), Kind::Text);
+        assert_eq!(detect("<?xml version=\"1.0\"?>\n<a><b/></a>"), Kind::Xml);
+        assert_eq!(detect("<item id=\"1\">x</item>"), Kind::Xml);
+        assert_eq!(
+            detect("<!DOCTYPE html>\n<html><body>hi</body></html>"),
+            Kind::Html
+        );
+    }
+
+    #[test]
+    fn test_markdown() {
+        assert_eq!(
+            detect("# Notes\n\n- one\n- two\n\nSee [docs](https://x.io).\n"),
+            Kind::Markdown
+        );
+        assert_eq!(detect("Run this:\n\n```\nmake\n```\n"), Kind::Markdown);
+        // One list alone is just text
+        assert_eq!(detect("- milk\n- eggs\n"), Kind::Text);
+    }
+
+    #[test]
+    fn test_code_languages() {
+        assert_eq!(
+            detect("fn main() {\n    let mut x = 1;\n    println!(\"{}\", x);\n}\n"),
+            Kind::Code(Some("rust"))
+        );
+        assert_eq!(
+            detect("import os\n\ndef main():\n    print(os.getcwd())\n"),
+            Kind::Code(Some("python"))
+        );
+        assert_eq!(
+            detect("package main\n\nfunc main() {\n\tx := 1\n\tfmt.Println(x)\n}\n"),
+            Kind::Code(Some("go"))
+        );
+        assert_eq!(
+            detect("SELECT id, name\nFROM users\nWHERE active = 1;\n"),
+            Kind::Code(Some("sql"))
+        );
+        assert_eq!(
+            detect("#!/usr/bin/env bash\nset -e\n"),
+            Kind::Code(Some("shell"))
+        );
+        assert_eq!(detect("x {\n  y;\n}\n"), Kind::Code(None));
+    }
+
+    #[test]
+    fn test_names() {
+        assert_eq!(Kind::Code(Some("rust")).to_string(), "rust code");
+        assert_eq!(Kind::Code(Some("rust")).name(), "code");
+        assert_eq!(Kind::Code(Some("rust")).language(), Some("rust"));
+        assert_eq!(Kind::Json.name(), "json");
+        assert_eq!(Kind::Json.language(), None);
+    }
+}
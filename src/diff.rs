@@ -0,0 +1,100 @@
+//! Comparing the clipboard with other text (`bc diff`).
+
+use similar::TextDiff;
+
+/// A unified diff from `clipboard` to `other`, or `None` if they are the
+/// same. `other_name` labels the `+++` side; `context` is the number of
+/// unchanged lines around each change. In color (red removals, green
+/// additions, cyan hunk headers) if `color`.
+pub fn unified(
+    clipboard: &str,
+    other: &str,
+    other_name: &str,
+    context: usize,
+    color: bool,
+) -> Option<String> {
+    if clipboard == other {
+        return None;
+    }
+    let diff = TextDiff::from_lines(clipboard, other)
+        .unified_diff()
+        .context_radius(context)
+        .header("clipboard", other_name)
+        .to_string();
+    if !color {
+        return Some(diff);
+    }
+    let colored = diff
+        .split_inclusive('\n')
+        .map(|line| {
+            let code = if line.starts_with("---") || line.starts_with("+++") {
+                "1"
+            } else if line.starts_with("@@") {
+                "36"
+            } else if line.starts_with('-') {
+                "31"
+            } else if line.starts_with('+') {
+                "32"
+            } else {
+                return line.to_string();
+            };
+            let (text, newline) = match line.strip_suffix('\n') {
+                Some(text) => (text, "\n"),
+                None => (line, ""),
+            };
+            format!("\x1b[{}m{}\x1b[0m{}", code, text, newline)
+        })
+        .collect();
+    Some(colored)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identical() {
+        assert_eq!(unified("a\nb\n", "a\nb\n", "f", 3, false), None);
+        assert_eq!(unified("", "", "f", 3, true), None);
+    }
+
+    #[test]
+    fn test_unified_diff() {
+        let diff = unified(
+            "one\ntwo\nthree\n",
+            "one\n2\nthree\n",
+            "notes.txt",
+            3,
+            false,
+        )
+        .unwrap();
+        assert_eq!(
+            diff,
+            "--- clipboard\n+++ notes.txt\n@@ -1,3 +1,3 @@\n one\n-two\n+2\n three\n"
+        );
+        // A missing final newline is a difference too
+        let diff = unified("x\n", "x", "f", 3, false).unwrap();
+        assert!(diff.contains("\\ No newline at end of file"), "{}", diff);
+    }
+
+    #[test]
+    fn test_context_and_color() {
+        let old: String = (1..=10).map(|n| format!("{}\n", n)).collect();
+        let new = old.replace("5\n", "five\n");
+        let diff = unified(&old, &new, "f", 1, false).unwrap();
+        assert!(
+            diff.contains("@@ -4,3 +4,3 @@\n 4\n-5\n+five\n 6\n"),
+            "{}",
+            diff
+        );
+
+        let diff = unified("a\n", "b\n", "f", 3, true).unwrap();
+        assert!(diff.contains("\x1b[31m-a\x1b[0m\n"), "{}", diff);
+        assert!(diff.contains("\x1b[32m+b\x1b[0m\n"), "{}", diff);
+        assert!(
+            diff.starts_with("\x1b[1m--- clipboard\x1b[0m\n"),
+            "{}",
+            diff
+        );
+    }
+}
@@ -0,0 +1,295 @@
+//! Setup diagnosis (`bc doctor`): one pass/fail line per thing copy and
+//! paste depend on, each failure with a suggested fix.
+
+use std::env;
+use std::fmt;
+
+use crate::backends::ArboardBackend;
+use crate::clipboard::{self, Environment, Mode, Options};
+use crate::config::Config;
+use crate::osc52;
+use crate::{probe, terminal};
+
+/// SSH variables reported by the session check
+const SSH_VARS: &[&str] = &["SSH_CLIENT", "SSH_CONNECTION", "SSH_TTY"];
+
+/// Outcome of one check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Pass,
+    /// Works, but something is likely to go wrong
+    Warn,
+    Fail,
+    /// Not checked, or doesn't matter here
+    Skip,
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Status::Pass => "ok",
+            Status::Warn => "warn",
+            Status::Fail => "FAIL",
+            Status::Skip => "skip",
+        })
+    }
+}
+
+/// One diagnosis line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Check {
+    pub name: String,
+    pub status: Status,
+    pub detail: String,
+    /// What to do about a warning or failure
+    pub fix: Option<String>,
+}
+
+impl Check {
+    fn new(name: impl Into<String>, status: Status, detail: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            status,
+            detail: detail.into(),
+            fix: None,
+        }
+    }
+
+    fn fix(mut self, fix: impl Into<String>) -> Self {
+        self.fix = Some(fix.into());
+        self
+    }
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:<7}{}: {}",
+            format!("[{}]", self.status),
+            self.name,
+            self.detail
+        )?;
+        if let Some(fix) = &self.fix {
+            write!(f, "\n       fix: {}", fix)?;
+        }
+        Ok(())
+    }
+}
+
+/// Run every check. `probe_terminal` allows the OSC 52 queries, which need
+/// a terminal on stdin and may briefly overwrite (then restore) the
+/// clipboard.
+pub fn run(options: &Options, probe_terminal: bool) -> Vec<Check> {
+    let environment = clipboard::classify(&options.remote);
+    let mode = options.resolved_mode();
+    // Whether a failing local clipboard matters
+    let local = matches!(mode, Mode::Auto | Mode::Local);
+    let mut checks = vec![session(&environment, mode), config(), display(local)];
+    checks.extend(local_backends(options, local));
+    if local {
+        checks.extend(clipboard_manager());
+    }
+    checks.push(multiplexer());
+    checks.push(terminal_support(options, probe_terminal));
+    checks
+}
+
+/// Whether any check failed
+pub fn failed(checks: &[Check]) -> bool {
+    checks.iter().any(|check| check.status == Status::Fail)
+}
+
+fn session(environment: &Environment, mode: Mode) -> Check {
+    let ssh: Vec<&str> = SSH_VARS
+        .iter()
+        .copied()
+        .filter(|var| env::var_os(var).is_some())
+        .collect();
+    let ssh = if ssh.is_empty() {
+        "no SSH variables".to_string()
+    } else {
+        format!("SSH variables: {}", ssh.join(", "))
+    };
+    let route = match mode {
+        Mode::Remote => "copies go to the terminal via OSC 52".to_string(),
+        Mode::Backend(kind) => format!("copies go to the {} backend (--backend)", kind.name()),
+        Mode::Auto | Mode::Local => "copies go to the local clipboard".to_string(),
+    };
+    Check::new(
+        "Session",
+        Status::Pass,
+        format!("{}; {}; {}", environment, ssh, route),
+    )
+}
+
+fn config() -> Check {
+    let path = Config::default_path()
+        .map(|path| path.display().to_string())
+        .unwrap_or_else(|_| "config file".to_string());
+    match Config::load() {
+        Ok(_) => Check::new("Config", Status::Pass, path),
+        Err(e) => Check::new("Config", Status::Fail, format!("{:#}", e))
+            .fix("fix or remove the file; see the README for its sections"),
+    }
+}
+
+fn display(local: bool) -> Check {
+    let needs_display = cfg!(all(
+        unix,
+        not(target_os = "macos"),
+        not(target_os = "android")
+    ));
+    if !needs_display {
+        return Check::new("Display server", Status::Pass, "provided by the OS");
+    }
+    let wayland = env::var("WAYLAND_DISPLAY").ok().filter(|v| !v.is_empty());
+    let x11 = env::var("DISPLAY").ok().filter(|v| !v.is_empty());
+    match (wayland, x11) {
+        (Some(wayland), _) => Check::new(
+            "Display server",
+            Status::Pass,
+            format!("Wayland ({})", wayland),
+        ),
+        (None, Some(x11)) => Check::new("Display server", Status::Pass, format!("X11 ({})", x11)),
+        (None, None) if !local => Check::new(
+            "Display server",
+            Status::Skip,
+            "none, which is fine: copies don't need it here",
+        ),
+        (None, None) => Check::new(
+            "Display server",
+            Status::Fail,
+            "none ($DISPLAY and $WAYLAND_DISPLAY are unset)",
+        )
+        .fix("run bc in a desktop session, use ssh -X, or copy via the terminal with --osc52"),
+    }
+}
+
+/// Each local backend bc would try, reachable or not
+fn local_backends(options: &Options, local: bool) -> Vec<Check> {
+    let backends = clipboard::local_backends(&options.backend);
+    let any_works = backends.iter().any(|backend| backend.check().is_ok());
+    backends
+        .iter()
+        .map(|backend| {
+            let name = format!("Local clipboard ({})", backend.name());
+            match backend.check() {
+                Ok(()) => Check::new(name, Status::Pass, "reachable"),
+                Err(e) if !local || any_works => Check::new(name, Status::Skip, format!("{:#}", e)),
+                Err(e) => {
+                    Check::new(name, Status::Fail, format!("{:#}", e)).fix(match backend.name() {
+                        "wl" => "install wl-clipboard",
+                        "termux" => {
+                            "install the Termux:API app and package (pkg install termux-api)"
+                        }
+                        _ => {
+                            "check the display server above, or copy via the terminal with --osc52"
+                        }
+                    })
+                }
+            }
+        })
+        .collect()
+}
+
+/// X11 only: who keeps copies once bc exits
+fn clipboard_manager() -> Option<Check> {
+    let detail = if ArboardBackend::clipboard_manager()? {
+        "running; copies are handed to it when bc exits"
+    } else {
+        "none; a background bc holds each copy until the next one"
+    };
+    Some(Check::new("Clipboard manager", Status::Pass, detail))
+}
+
+fn multiplexer() -> Check {
+    if env::var_os("TMUX").is_some() {
+        let set_clipboard = osc52::tmux_option("set-clipboard");
+        let passthrough = osc52::tmux_option("allow-passthrough");
+        let detail = format!(
+            "tmux (set-clipboard {}, allow-passthrough {})",
+            set_clipboard.as_deref().unwrap_or("unknown"),
+            passthrough.as_deref().unwrap_or("unknown")
+        );
+        match (set_clipboard.as_deref(), passthrough.as_deref()) {
+            (Some("on"), _) => Check::new("Multiplexer", Status::Pass, detail),
+            (_, Some("on" | "all")) => Check::new("Multiplexer", Status::Pass, detail),
+            (None, None) => Check::new("Multiplexer", Status::Warn, detail)
+                .fix("couldn't ask tmux; make sure `tmux` is on PATH"),
+            _ => Check::new("Multiplexer", Status::Fail, detail).fix(
+                "add `set -s set-clipboard on` (or `set -g allow-passthrough on`) to ~/.tmux.conf",
+            ),
+        }
+    } else if env::var_os("STY").is_some() {
+        Check::new(
+            "Multiplexer",
+            Status::Pass,
+            "GNU screen (OSC 52 is sent in passthrough chunks)",
+        )
+    } else {
+        Check::new("Multiplexer", Status::Pass, "none")
+    }
+}
+
+fn terminal_support(options: &Options, probe_terminal: bool) -> Check {
+    const NAME: &str = "Terminal OSC 52";
+    if !probe_terminal {
+        return Check::new(NAME, Status::Skip, "not checked (--no-terminal)");
+    }
+    if !terminal::is_stdin_tty() {
+        return Check::new(NAME, Status::Skip, "not checked (stdin is not a terminal)");
+    }
+    match probe::run(options.backend.passthrough) {
+        Ok(report) => {
+            let name = report.version.as_deref().unwrap_or("unknown terminal");
+            match (report.osc52_read, report.osc52_write) {
+                (true, Some(true)) => Check::new(NAME, Status::Pass, format!("{}: write and read work", name)),
+                (true, _) => Check::new(NAME, Status::Fail, format!("{}: reads work, but a write didn't arrive", name))
+                    .fix("check the multiplexer above, or try --no-passthrough"),
+                (false, _) => Check::new(
+                    NAME,
+                    Status::Warn,
+                    format!("{}: doesn't answer OSC 52 queries, so writes can't be verified and paste needs a local clipboard", name),
+                )
+                .fix("allow clipboard reads (kitty: clipboard_control read; XTerm: allowWindowOps) to use paste --force-paste"),
+            }
+        }
+        Err(e) => Check::new(NAME, Status::Fail, format!("{:#}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_display() {
+        let check = Check::new("Display server", Status::Fail, "none").fix("use --osc52");
+        assert_eq!(
+            check.to_string(),
+            "[FAIL] Display server: none\n       fix: use --osc52"
+        );
+        assert_eq!(
+            Check::new("Multiplexer", Status::Pass, "none").to_string(),
+            "[ok]   Multiplexer: none"
+        );
+    }
+
+    #[test]
+    fn test_failed() {
+        let pass = Check::new("a", Status::Pass, "");
+        let warn = Check::new("b", Status::Warn, "");
+        assert!(!failed(&[pass.clone(), warn]));
+        assert!(failed(&[pass, Check::new("c", Status::Fail, "")]));
+    }
+
+    #[test]
+    fn test_run_without_terminal() {
+        let checks = run(&Options::default(), false);
+        let names: Vec<&str> = checks.iter().map(|check| check.name.as_str()).collect();
+        assert_eq!(names[..3], ["Session", "Config", "Display server"]);
+        let terminal = checks.last().unwrap();
+        assert_eq!(terminal.status, Status::Skip);
+    }
+}
@@ -0,0 +1,140 @@
+//! Editing clipboard text in the user's editor (`bc edit`).
+//!
+//! The text goes into a fresh temp file readable only by the current user,
+//! which is removed again however the edit ends.
+
+use anyhow::{anyhow, bail, Context, Result};
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::Command;
+
+use crate::debug;
+
+/// The editor to run: `$VISUAL`, then `$EDITOR` (split on whitespace, so
+/// `code --wait` works), then a platform default
+pub fn editor() -> Vec<String> {
+    ["VISUAL", "EDITOR"]
+        .iter()
+        .filter_map(|var| env::var(var).ok())
+        .map(|value| {
+            value
+                .split_whitespace()
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        })
+        .find(|command| !command.is_empty())
+        .unwrap_or_else(|| vec![if cfg!(windows) { "notepad" } else { "vi" }.to_string()])
+}
+
+/// Open `text` in `editor` and return the saved result. Fails if the editor
+/// exits unsuccessfully.
+pub fn edit(text: &str, editor: &[String]) -> Result<String> {
+    let (program, args) = editor.split_first().context("No editor given")?;
+    let file = TempFile::create("edit", "txt", text.as_bytes())?;
+    debug!("Editing {} with {}", file.path.display(), editor.join(" "));
+    let status = Command::new(program)
+        .args(args)
+        .arg(&file.path)
+        .status()
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => {
+                anyhow!("Editor not found: {} (set VISUAL or EDITOR)", program)
+            }
+            _ => anyhow!("Failed to run {}: {}", program, e),
+        })?;
+    if !status.success() {
+        bail!("{} failed ({})", program, status);
+    }
+    // Editors that save by renaming replace the file, so read it by path
+    fs::read_to_string(&file.path).with_context(|| {
+        format!(
+            "Failed to read the edited text from {}",
+            file.path.display()
+        )
+    })
+}
+
+/// A private temp file, deleted on drop
+pub(crate) struct TempFile {
+    pub(crate) path: PathBuf,
+}
+
+impl TempFile {
+    /// Create `bc-NAME-<random>.EXTENSION` holding `contents`
+    pub(crate) fn create(name: &str, extension: &str, contents: &[u8]) -> Result<Self> {
+        let mut suffix = [0u8; 8];
+        getrandom::getrandom(&mut suffix).map_err(|e| anyhow!("No randomness: {}", e))?;
+        let suffix: String = suffix.iter().map(|byte| format!("{:02x}", byte)).collect();
+        let path = env::temp_dir().join(format!("bc-{}-{}.{}", name, suffix, extension));
+
+        // create_new: never reuse (or follow a link planted at) an existing path
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options
+            .open(&path)
+            .with_context(|| format!("Failed to create {}", path.display()))?;
+        // From here on, dropping `temp` removes the file
+        let temp = Self { path };
+        file.write_all(contents)
+            .with_context(|| format!("Failed to write {}", temp.path.display()))?;
+        Ok(temp)
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_file(&self.path) {
+            debug!("Failed to remove {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_edit_returns_saved_text() {
+        // The "editor" checks the file is private, then rewrites it
+        let editor = command(&[
+            "sh",
+            "-c",
+            r#"[ "$(ls -l "$1" | cut -c1-10)" = -rw------- ] && sed 's/old/new/' "$1" > "$1.x" && mv "$1.x" "$1""#,
+            "sh",
+        ]);
+        assert_eq!(edit("old text\n", &editor).unwrap(), "new text\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_failed_editor_and_cleanup() {
+        let dir = tempfile::tempdir().unwrap();
+        let marker = dir.path().join("edited");
+        let script = format!(r#"printf %s "$1" > '{}'; exit 1"#, marker.display());
+        let err = edit("x", &command(&["sh", "-c", &script, "sh"])).unwrap_err();
+        assert!(err.to_string().contains("exit status: 1"), "{}", err);
+
+        // The temp file is gone even though the edit failed
+        let edited = fs::read_to_string(&marker).unwrap();
+        assert!(edited.contains("bc-edit-"), "{}", edited);
+        assert!(!std::path::Path::new(&edited).exists());
+    }
+
+    #[test]
+    fn test_missing_editor() {
+        let err = edit("x", &command(&["bc-no-such-editor"])).unwrap_err();
+        assert!(err.to_string().contains("VISUAL or EDITOR"), "{}", err);
+    }
+}
@@ -0,0 +1,116 @@
+//! Text encodings for the `--base64`, `--hex`, and `--url-encode` transforms
+//! and their decoding counterparts.
+
+use anyhow::{anyhow, Result};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+
+/// Characters percent-encoding leaves alone (RFC 3986 unreserved)
+fn is_unreserved(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || b"-._~".contains(&byte)
+}
+
+/// Percent-encode everything but unreserved characters
+pub fn url_encode(text: &str) -> String {
+    text.bytes()
+        .map(|byte| {
+            if is_unreserved(byte) {
+                char::from(byte).to_string()
+            } else {
+                format!("%{:02X}", byte)
+            }
+        })
+        .collect()
+}
+
+/// Decode `%XX` escapes (`+` stays a plus sign)
+pub fn url_decode(text: &str) -> Result<String> {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let byte = bytes
+                .get(i + 1..i + 3)
+                .and_then(|pair| u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok())
+                .ok_or_else(|| anyhow!("Invalid percent escape at byte {}", i))?;
+            out.push(byte);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    utf8(out)
+}
+
+pub fn base64_encode(text: &str) -> String {
+    BASE64.encode(text)
+}
+
+/// Decode base64, ignoring whitespace (so wrapped output decodes too)
+pub fn base64_decode(text: &str) -> Result<String> {
+    let compact: String = text.split_whitespace().collect();
+    let bytes = BASE64
+        .decode(compact)
+        .map_err(|e| anyhow!("Not valid base64: {}", e))?;
+    utf8(bytes)
+}
+
+/// Lowercase hex, two digits per byte
+pub fn hex_encode(text: &str) -> String {
+    text.bytes().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Decode hex digits in either case, ignoring whitespace
+pub fn hex_decode(text: &str) -> Result<String> {
+    let digits: Vec<u8> = text.bytes().filter(|b| !b.is_ascii_whitespace()).collect();
+    if !digits.len().is_multiple_of(2) {
+        return Err(anyhow!("Not valid hex: odd number of digits"));
+    }
+    let bytes = digits
+        .chunks(2)
+        .map(|pair| {
+            std::str::from_utf8(pair)
+                .ok()
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                .ok_or_else(|| anyhow!("Not valid hex: '{}'", String::from_utf8_lossy(pair)))
+        })
+        .collect::<Result<Vec<u8>>>()?;
+    utf8(bytes)
+}
+
+/// Decoded bytes as text; raw binary belongs to `--binary` instead
+fn utf8(bytes: Vec<u8>) -> Result<String> {
+    String::from_utf8(bytes).map_err(|_| anyhow!("Decoded content is not valid UTF-8 text"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_url_roundtrip() {
+        assert_eq!(url_encode("a b&c=d/é~"), "a%20b%26c%3Dd%2F%C3%A9~");
+        assert_eq!(url_decode("a%20b%26c%3dd%2F%C3%A9~").unwrap(), "a b&c=d/é~");
+        assert_eq!(url_decode("a+b").unwrap(), "a+b");
+        assert!(url_decode("100%").is_err());
+        assert!(url_decode("%zz").is_err());
+        assert!(url_decode("%ff").is_err());
+    }
+
+    #[test]
+    fn test_base64_roundtrip() {
+        assert_eq!(base64_encode("hello\n"), "aGVsbG8K");
+        assert_eq!(base64_decode("aGVs\nbG8K\n").unwrap(), "hello\n");
+        assert!(base64_decode("not base64!").is_err());
+    }
+
+    #[test]
+    fn test_hex_roundtrip() {
+        assert_eq!(hex_encode("Hi\n"), "48690a");
+        assert_eq!(hex_decode("48 69\n0A").unwrap(), "Hi\n");
+        assert!(hex_decode("486").is_err());
+        assert!(hex_decode("zz").is_err());
+    }
+}
@@ -0,0 +1,310 @@
+//! Exit statuses and stable error codes, so scripts can tell failures apart
+//! without parsing messages (`bc --list-exit-codes`, and `--json` errors
+//! such as `{"error":"osc52_too_large","max":10485760}`).
+
+use serde_json::{Map, Value};
+use std::fmt;
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::backends::BackendError;
+use crate::message;
+
+/// Exit statuses
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum BcExitCode {
+    Success = 0,
+    GeneralError = 1,
+    EmptyInput = 2,
+    ClipboardUnavailable = 3,
+    InvalidInput = 4,
+}
+
+impl BcExitCode {
+    pub const ALL: [BcExitCode; 5] = [
+        BcExitCode::Success,
+        BcExitCode::GeneralError,
+        BcExitCode::EmptyInput,
+        BcExitCode::ClipboardUnavailable,
+        BcExitCode::InvalidInput,
+    ];
+
+    /// What the status means, for `--list-exit-codes`
+    pub fn description(self) -> &'static str {
+        match self {
+            BcExitCode::Success => "Success",
+            BcExitCode::GeneralError => "General error",
+            BcExitCode::EmptyInput => "Empty input (nothing to copy)",
+            BcExitCode::ClipboardUnavailable => {
+                "Clipboard unavailable or empty, or no such history entry"
+            }
+            BcExitCode::InvalidInput => "Invalid input (binary data, a secret, or too large)",
+        }
+    }
+}
+
+impl From<BcExitCode> for ExitCode {
+    fn from(code: BcExitCode) -> Self {
+        ExitCode::from(code as u8)
+    }
+}
+
+/// Why a command failed. The names are stable; each code always exits
+/// with the same status.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    General,
+    Usage,
+    TerminalOutput,
+    CommandFailed,
+    HashMismatch,
+    Locked,
+    EmptyInput,
+    NoPeers,
+    ClipboardUnavailable,
+    NotInstalled,
+    ClipboardTimeout,
+    ClipboardEmpty,
+    NotFound,
+    InvalidInput,
+    BinaryInput,
+    SecretDetected,
+    NoText,
+    InputTooLarge,
+    Osc52TooLarge,
+}
+
+impl ErrorCode {
+    pub const ALL: [ErrorCode; 19] = [
+        ErrorCode::General,
+        ErrorCode::Usage,
+        ErrorCode::TerminalOutput,
+        ErrorCode::CommandFailed,
+        ErrorCode::HashMismatch,
+        ErrorCode::Locked,
+        ErrorCode::EmptyInput,
+        ErrorCode::NoPeers,
+        ErrorCode::ClipboardUnavailable,
+        ErrorCode::NotInstalled,
+        ErrorCode::ClipboardTimeout,
+        ErrorCode::ClipboardEmpty,
+        ErrorCode::NotFound,
+        ErrorCode::InvalidInput,
+        ErrorCode::BinaryInput,
+        ErrorCode::SecretDetected,
+        ErrorCode::NoText,
+        ErrorCode::InputTooLarge,
+        ErrorCode::Osc52TooLarge,
+    ];
+
+    /// The name scripts match on
+    pub fn name(self) -> &'static str {
+        match self {
+            ErrorCode::General => "general",
+            ErrorCode::Usage => "usage",
+            ErrorCode::TerminalOutput => "terminal_output",
+            ErrorCode::CommandFailed => "command_failed",
+            ErrorCode::HashMismatch => "hash_mismatch",
+            ErrorCode::Locked => "locked",
+            ErrorCode::EmptyInput => "empty_input",
+            ErrorCode::NoPeers => "no_peers",
+            ErrorCode::ClipboardUnavailable => "clipboard_unavailable",
+            ErrorCode::NotInstalled => "not_installed",
+            ErrorCode::ClipboardTimeout => "clipboard_timeout",
+            ErrorCode::ClipboardEmpty => "clipboard_empty",
+            ErrorCode::NotFound => "not_found",
+            ErrorCode::InvalidInput => "invalid_input",
+            ErrorCode::BinaryInput => "binary_input",
+            ErrorCode::SecretDetected => "secret_detected",
+            ErrorCode::NoText => "no_text",
+            ErrorCode::InputTooLarge => "input_too_large",
+            ErrorCode::Osc52TooLarge => "osc52_too_large",
+        }
+    }
+
+    pub fn exit_code(self) -> BcExitCode {
+        match self {
+            ErrorCode::General
+            | ErrorCode::Usage
+            | ErrorCode::TerminalOutput
+            | ErrorCode::CommandFailed
+            | ErrorCode::HashMismatch
+            | ErrorCode::Locked => BcExitCode::GeneralError,
+            ErrorCode::EmptyInput | ErrorCode::NoPeers => BcExitCode::EmptyInput,
+            ErrorCode::ClipboardUnavailable
+            | ErrorCode::NotInstalled
+            | ErrorCode::ClipboardTimeout
+            | ErrorCode::ClipboardEmpty
+            | ErrorCode::NotFound => BcExitCode::ClipboardUnavailable,
+            ErrorCode::InvalidInput
+            | ErrorCode::BinaryInput
+            | ErrorCode::SecretDetected
+            | ErrorCode::NoText
+            | ErrorCode::InputTooLarge
+            | ErrorCode::Osc52TooLarge => BcExitCode::InvalidInput,
+        }
+    }
+
+    /// What the code means, for `--list-exit-codes`
+    pub fn description(self) -> &'static str {
+        match self {
+            ErrorCode::General => "Any failure without a more specific code",
+            ErrorCode::Usage => "Options that don't combine or don't apply here",
+            ErrorCode::TerminalOutput => "Refused to write binary or image data to a terminal",
+            ErrorCode::CommandFailed => "The pipe --replace command failed (status)",
+            ErrorCode::HashMismatch => "The clipboard doesn't match hash --verify",
+            ErrorCode::Locked => "Another bc kept the history locked too long (path)",
+            ErrorCode::EmptyInput => "Nothing to copy: empty input, command output, or edit",
+            ErrorCode::NoPeers => "No peers found on the network",
+            ErrorCode::ClipboardUnavailable => "No clipboard backend could be used",
+            ErrorCode::NotInstalled => "The clipboard program isn't installed (program)",
+            ErrorCode::ClipboardTimeout => {
+                "The local clipboard didn't respond in time (timeout_ms)"
+            }
+            ErrorCode::ClipboardEmpty => "The clipboard holds nothing to paste",
+            ErrorCode::NotFound => "No history entry, pin, register, or rule by that name",
+            ErrorCode::InvalidInput => {
+                "Input that couldn't be read or decoded, or a config rule that's invalid (rule)"
+            }
+            ErrorCode::BinaryInput => "Binary data or escape codes without --force",
+            ErrorCode::SecretDetected => "Input looks like a secret, without --allow-secrets",
+            ErrorCode::NoText => "OCR found no text in the clipboard image",
+            ErrorCode::InputTooLarge => "Input went past the size limit (max)",
+            ErrorCode::Osc52TooLarge => "Content too large for one OSC 52 sequence (size, max)",
+        }
+    }
+
+    /// What the text form of the message starts with
+    fn label(self) -> &'static str {
+        match self {
+            ErrorCode::CommandFailed
+            | ErrorCode::HashMismatch
+            | ErrorCode::ClipboardEmpty
+            | ErrorCode::NoPeers
+            | ErrorCode::NoText => "",
+            ErrorCode::BinaryInput | ErrorCode::SecretDetected => "Warning: ",
+            _ => "Error: ",
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// An error with a stable code and details for scripts
+#[derive(Debug)]
+pub struct CodedError {
+    pub code: ErrorCode,
+    pub message: String,
+    pub fields: Map<String, Value>,
+}
+
+impl CodedError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            fields: Map::new(),
+        }
+    }
+
+    /// Add a detail, shown next to the code in `--json` mode
+    pub fn with(mut self, key: &str, value: impl Into<Value>) -> Self {
+        self.fields.insert(key.to_string(), value.into());
+        self
+    }
+}
+
+impl fmt::Display for CodedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for CodedError {}
+
+/// The code and details `e` carries anywhere in its chain, or `fallback`
+pub fn classify(e: &anyhow::Error, fallback: ErrorCode) -> (ErrorCode, Map<String, Value>) {
+    for cause in e.chain() {
+        if let Some(coded) = cause.downcast_ref::<CodedError>() {
+            return (coded.code, coded.fields.clone());
+        }
+        if let Some(BackendError::NotInstalled(program)) = cause.downcast_ref() {
+            let mut fields = Map::new();
+            fields.insert("program".to_string(), program.as_str().into());
+            return (ErrorCode::NotInstalled, fields);
+        }
+    }
+    (fallback, Map::new())
+}
+
+static JSON: AtomicBool = AtomicBool::new(false);
+
+/// Report errors as JSON objects (`--json`)
+pub fn set_json(json: bool) {
+    JSON.store(json, Ordering::Relaxed);
+}
+
+/// Report a failure on stderr, unless `-q` was given: as text, or as one
+/// JSON object under `--json`
+pub fn report(code: ErrorCode, message: impl fmt::Display, fields: Map<String, Value>) {
+    if JSON.load(Ordering::Relaxed) {
+        message!("{}", to_json(code, &message.to_string(), fields));
+    } else {
+        message!("{}{}", code.label(), message);
+    }
+}
+
+fn to_json(code: ErrorCode, message: &str, fields: Map<String, Value>) -> Value {
+    let mut object = Map::new();
+    object.insert("error".to_string(), code.name().into());
+    object.insert("message".to_string(), message.into());
+    object.extend(fields);
+    Value::Object(object)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_names_are_unique() {
+        let mut names: Vec<_> = ErrorCode::ALL.iter().map(|code| code.name()).collect();
+        names.sort_unstable();
+        names.dedup();
+        assert_eq!(names.len(), ErrorCode::ALL.len());
+    }
+
+    #[test]
+    fn test_classify() {
+        let e = anyhow::Error::new(
+            CodedError::new(ErrorCode::Osc52TooLarge, "too large").with("max", 10),
+        )
+        .context("Copy failed");
+        let (code, fields) = classify(&e, ErrorCode::General);
+        assert_eq!(code, ErrorCode::Osc52TooLarge);
+        assert_eq!(fields["max"], 10);
+
+        let e = anyhow::Error::new(BackendError::NotInstalled("xclip".to_string()));
+        let (code, fields) = classify(&e, ErrorCode::General);
+        assert_eq!(code, ErrorCode::NotInstalled);
+        assert_eq!(fields["program"], "xclip");
+
+        let e = anyhow::anyhow!("something else");
+        assert_eq!(classify(&e, ErrorCode::Usage).0, ErrorCode::Usage);
+    }
+
+    #[test]
+    fn test_to_json() {
+        let mut fields = Map::new();
+        fields.insert("max".to_string(), 10485760.into());
+        assert_eq!(
+            to_json(ErrorCode::Osc52TooLarge, "Too large", fields).to_string(),
+            r#"{"error":"osc52_too_large","message":"Too large","max":10485760}"#
+        );
+    }
+}
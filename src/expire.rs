@@ -0,0 +1,70 @@
+//! Clearing a sensitive copy after a timeout (`copy --expire`).
+//!
+//! The copying process starts a detached `bc expire` that sleeps, then
+//! clears the clipboard if it still holds the copied text. Only a digest of
+//! the text is handed over, on the child's stdin rather than its arguments:
+//! an unsalted 64-bit hash in a process listing would let other local users
+//! brute-force a short secret offline.
+
+use anyhow::Result;
+
+use crate::backends::ClipboardBackend;
+use crate::debug;
+
+/// Fingerprint of `text` (FNV-1a), ignoring trailing newlines since some
+/// clipboard programs add or strip one on the way through
+pub fn digest(text: &str) -> String {
+    let hash = text
+        .trim_end_matches('\n')
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+        });
+    format!("{:016x}", hash)
+}
+
+/// Clear the clipboard unless it changed since the copy with `expected`
+/// digest. A clipboard that can't be read back (OSC 52 without query
+/// support) is cleared regardless. Returns whether it was cleared.
+pub fn clear_if_unchanged(backend: &dyn ClipboardBackend, expected: &str) -> Result<bool> {
+    match backend.paste() {
+        Ok(current) if digest(&current) != expected => {
+            debug!("Clipboard changed since the copy; leaving it");
+            Ok(false)
+        }
+        Ok(_) => backend.clear().map(|_| true),
+        Err(e) => {
+            debug!("Cannot read the clipboard back ({}); clearing it anyway", e);
+            backend.clear().map(|_| true)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backends::Memory;
+
+    #[test]
+    fn test_digest() {
+        assert_eq!(digest("secret"), digest("secret\n"));
+        assert_ne!(digest("secret"), digest("Secret"));
+        assert_eq!(digest("").len(), 16);
+    }
+
+    #[test]
+    fn test_clears_only_unchanged_content() {
+        let backend = Memory::new("password\n");
+        assert!(!clear_if_unchanged(&backend, &digest("other")).unwrap());
+        assert_eq!(backend.content(), "password\n");
+        assert!(clear_if_unchanged(&backend, &digest("password")).unwrap());
+        assert!(backend.content().is_empty());
+    }
+
+    #[test]
+    fn test_unreadable_clipboard_is_cleared() {
+        let backend = Memory::new("password").write_only();
+        assert!(clear_if_unchanged(&backend, &digest("other")).unwrap());
+        assert!(backend.content().is_empty());
+    }
+}
@@ -0,0 +1,153 @@
+//! awk-style field selection for `--fields`: `ps aux | bc --fields 2`
+//! copies the PID column.
+//!
+//! Lines split on runs of whitespace and the kept fields are joined with a
+//! space, like awk; with a delimiter they split on it and are joined with
+//! it, like `cut -d`.
+
+use std::str::FromStr;
+
+/// Fields to keep, 1-based and in the order given: `2,4`, `1-3`, `5-`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldList(Vec<(usize, Option<usize>)>);
+
+impl FieldList {
+    /// Indices (0-based) into a line of `count` fields
+    fn indices(&self, count: usize) -> impl Iterator<Item = usize> + '_ {
+        self.0.iter().flat_map(move |&(start, end)| {
+            let end = end.map_or(count, |end| end.min(count));
+            start - 1..end
+        })
+    }
+}
+
+impl FromStr for FieldList {
+    type Err = String;
+
+    fn from_str(list: &str) -> Result<Self, Self::Err> {
+        let number = |n: &str| match n.trim().parse::<usize>() {
+            Ok(n) if n > 0 => Ok(n),
+            _ => Err(format!(
+                "expects field numbers from 1 like 2,4 or 1-3, not '{}'",
+                list
+            )),
+        };
+        list.split(',')
+            .map(|part| match part.split_once('-') {
+                Some((start, "")) => Ok((number(start)?, None)),
+                Some(("", end)) => Ok((1, Some(number(end)?))),
+                Some((start, end)) => {
+                    let (start, end) = (number(start)?, number(end)?);
+                    if start > end {
+                        return Err(format!("has a backwards range '{}'", part));
+                    }
+                    Ok((start, Some(end)))
+                }
+                None => number(part).map(|n| (n, Some(n))),
+            })
+            .collect::<Result<_, _>>()
+            .map(FieldList)
+    }
+}
+
+/// `--fields` with `--delimiter` and `--trim-fields`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fields {
+    list: FieldList,
+    delimiter: Option<String>,
+    trim: bool,
+}
+
+impl Fields {
+    /// Keep `list`, splitting on `delimiter` (whitespace runs when `None`
+    /// or empty), and with `trim` stripping whitespace from each field
+    pub fn new(list: FieldList, delimiter: Option<String>, trim: bool) -> Self {
+        Self {
+            list,
+            delimiter: delimiter.filter(|delimiter| !delimiter.is_empty()),
+            trim,
+        }
+    }
+
+    /// Select the fields of every line, keeping line endings. Fields a line
+    /// doesn't have are skipped.
+    pub fn apply(&self, text: &str) -> String {
+        text.split_inclusive('\n')
+            .map(|line| {
+                let content = line.trim_end_matches(['\r', '\n']);
+                self.select(content) + &line[content.len()..]
+            })
+            .collect()
+    }
+
+    fn select(&self, line: &str) -> String {
+        let fields: Vec<&str> = match &self.delimiter {
+            Some(delimiter) => line.split(delimiter.as_str()).collect(),
+            None => line.split_whitespace().collect(),
+        };
+        let picked: Vec<&str> = self
+            .list
+            .indices(fields.len())
+            .map(|i| {
+                if self.trim {
+                    fields[i].trim()
+                } else {
+                    fields[i]
+                }
+            })
+            .collect();
+        picked.join(self.delimiter.as_deref().unwrap_or(" "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(list: &str, delimiter: Option<&str>, trim: bool) -> Fields {
+        Fields::new(list.parse().unwrap(), delimiter.map(str::to_string), trim)
+    }
+
+    #[test]
+    fn test_parse_list() {
+        assert_eq!(
+            "2,4".parse::<FieldList>().unwrap(),
+            FieldList(vec![(2, Some(2)), (4, Some(4))])
+        );
+        assert_eq!(
+            "1-3,5-,-2".parse::<FieldList>().unwrap(),
+            FieldList(vec![(1, Some(3)), (5, None), (1, Some(2))])
+        );
+        for bad in ["", "0", "a", "3-1", "1,,2", "-"] {
+            assert!(bad.parse::<FieldList>().is_err(), "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn test_whitespace_fields() {
+        let ps = "USER  PID %CPU\nroot    1  0.0\r\nme   4242  1.5\n";
+        assert_eq!(fields("2", None, false).apply(ps), "PID\n1\r\n4242\n");
+        assert_eq!(
+            fields("3,1", None, false).apply(ps),
+            "%CPU USER\n0.0 root\r\n1.5 me\n"
+        );
+        // Missing fields are skipped
+        assert_eq!(fields("2-", None, false).apply("a b c\nd"), "b c\n");
+    }
+
+    #[test]
+    fn test_delimited_fields() {
+        let csv = "name, age, city\nSam, 30, Oslo";
+        assert_eq!(
+            fields("1,3", Some(","), false).apply(csv),
+            "name, city\nSam, Oslo"
+        );
+        assert_eq!(
+            fields("1,3", Some(","), true).apply(csv),
+            "name,city\nSam,Oslo"
+        );
+        // Empty fields count, as in cut
+        assert_eq!(fields("3", Some(":"), false).apply("a::c"), "c");
+        assert_eq!(fields("2", Some(""), false).apply("a  b"), "b");
+    }
+}
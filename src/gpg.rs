@@ -0,0 +1,91 @@
+//! GPG encryption of clipboard text (`copy --encrypt`, `paste --decrypt`).
+//!
+//! Content goes through the `gpg` program as ASCII armor, so the ciphertext
+//! is ordinary text that any backend, OSC 52 included, can carry.
+
+use anyhow::{Context, Result};
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::thread;
+
+use crate::debug;
+
+/// First line of the armored messages `encrypt` produces
+const ARMOR_HEADER: &str = "-----BEGIN PGP MESSAGE-----";
+
+/// Encrypt `text` to each of `recipients` (key IDs, fingerprints, or emails)
+pub fn encrypt(text: &str, recipients: &[String]) -> Result<String> {
+    let mut args = vec!["--batch", "--armor", "--encrypt"];
+    for recipient in recipients {
+        args.extend(["--recipient", recipient]);
+    }
+    let armored = run(&args, text.as_bytes())?;
+    String::from_utf8(armored).context("gpg produced invalid armor")
+}
+
+/// Decrypt an armored message. gpg may ask for a passphrase via pinentry.
+pub fn decrypt(armored: &str) -> Result<String> {
+    if !is_armored(armored) {
+        anyhow::bail!("Clipboard does not hold a PGP message (copy it with --encrypt)");
+    }
+    let plain = run(&["--quiet", "--decrypt"], armored.as_bytes())?;
+    String::from_utf8(plain).context("Decrypted content is not valid UTF-8")
+}
+
+/// Whether `text` looks like an armored PGP message
+pub fn is_armored(text: &str) -> bool {
+    text.trim_start().starts_with(ARMOR_HEADER)
+}
+
+/// Run gpg with `input` on stdin and return its stdout
+fn run(args: &[&str], input: &[u8]) -> Result<Vec<u8>> {
+    debug!("Running 'gpg {}'", args.join(" "));
+    let mut child = Command::new("gpg")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => {
+                anyhow::anyhow!("gpg is not installed (needed for --encrypt and --decrypt)")
+            }
+            _ => anyhow::anyhow!("Failed to run gpg: {}", e),
+        })?;
+
+    // Feed stdin from another thread so a large output can't fill the pipe
+    // while we are still writing
+    let mut stdin = child.stdin.take().context("Failed to open gpg's stdin")?;
+    let output = thread::scope(|scope| {
+        scope.spawn(move || {
+            // An early exit closes stdin; gpg's status explains why
+            let _ = stdin.write_all(input);
+        });
+        child.wait_with_output()
+    })
+    .context("Failed to run gpg")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!("gpg failed: {}", stderr.trim());
+    }
+    Ok(output.stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_armored() {
+        assert!(is_armored("-----BEGIN PGP MESSAGE-----\n\nhQEM...\n"));
+        assert!(is_armored("\n-----BEGIN PGP MESSAGE-----\n"));
+        assert!(!is_armored("plain text"));
+    }
+
+    #[test]
+    fn test_decrypt_rejects_plain_text() {
+        let err = decrypt("hello").unwrap_err();
+        assert!(err.to_string().contains("--encrypt"));
+    }
+}
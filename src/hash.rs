@@ -0,0 +1,100 @@
+//! Checksums of clipboard text (`bc hash`), to check that a long token or key
+//! arrived intact.
+
+use md5::Md5;
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::str::FromStr;
+
+/// Digest algorithm for `bc hash --algo`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Algorithm {
+    #[default]
+    Sha256,
+    /// Only for comparing with tools that print MD5; not collision resistant
+    Md5,
+    Blake3,
+}
+
+impl FromStr for Algorithm {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "sha256" => Ok(Algorithm::Sha256),
+            "md5" => Ok(Algorithm::Md5),
+            "blake3" => Ok(Algorithm::Blake3),
+            _ => Err(format!(
+                "unknown algorithm '{}' (use sha256, md5, blake3)",
+                value
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Algorithm::Sha256 => "sha256",
+            Algorithm::Md5 => "md5",
+            Algorithm::Blake3 => "blake3",
+        })
+    }
+}
+
+/// Lowercase hex digest of `bytes`, as `sha256sum`, `md5sum`, and `b3sum`
+/// print it
+pub fn digest(bytes: &[u8], algorithm: Algorithm) -> String {
+    let raw: Vec<u8> = match algorithm {
+        Algorithm::Sha256 => Sha256::digest(bytes).to_vec(),
+        Algorithm::Md5 => Md5::digest(bytes).to_vec(),
+        Algorithm::Blake3 => blake3::hash(bytes).as_bytes().to_vec(),
+    };
+    raw.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Whether `expected` (hex in either case, surrounding whitespace ignored,
+/// or a `sha256sum` line with the file name after it) is `bytes`' digest
+pub fn verify(bytes: &[u8], algorithm: Algorithm, expected: &str) -> bool {
+    let expected = expected.split_whitespace().next().unwrap_or("");
+    expected.eq_ignore_ascii_case(&digest(bytes, algorithm))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_known_digests() {
+        assert_eq!(
+            digest(b"abc", Algorithm::Sha256),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            digest(b"abc", Algorithm::Md5),
+            "900150983cd24fb0d6963f7d28e17f72"
+        );
+        assert_eq!(
+            digest(b"", Algorithm::Blake3),
+            "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262"
+        );
+    }
+
+    #[test]
+    fn test_verify() {
+        let sum = "900150983CD24FB0D6963F7D28E17F72";
+        assert!(verify(b"abc", Algorithm::Md5, sum));
+        assert!(verify(b"abc", Algorithm::Md5, &format!("  {}  -\n", sum)));
+        assert!(!verify(b"abc\n", Algorithm::Md5, sum));
+        assert!(!verify(b"abc", Algorithm::Sha256, sum));
+        assert!(!verify(b"abc", Algorithm::Md5, ""));
+    }
+
+    #[test]
+    fn test_parse_algorithm() {
+        assert_eq!("SHA256".parse(), Ok(Algorithm::Sha256));
+        assert_eq!("blake3".parse(), Ok(Algorithm::Blake3));
+        assert!("crc32".parse::<Algorithm>().is_err());
+        assert_eq!(Algorithm::default().to_string(), "sha256");
+    }
+}
@@ -0,0 +1,907 @@
+//! Persistent clipboard history stored as JSON lines.
+//!
+//! With a key ([`History::with_key`]) new entries are stored sealed. Sealed
+//! entries read without the right key are hidden but kept on disk.
+//!
+//! Changes hold a lock on `history.jsonl.lock` (see [`crate::lock`]) so
+//! concurrent copies don't lose each other's entries, and rewrites replace
+//! the file by renaming so readers never see it half written.
+
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::config::HistoryConfig;
+use crate::crypto::{self, Key};
+use crate::lock::{self, FileLock};
+use crate::paths::{self, FileMode};
+
+/// History file name inside the data directory
+const HISTORY_FILE: &str = "history.jsonl";
+/// Default number of entries kept before the oldest are rotated out
+pub const DEFAULT_MAX_ENTRIES: usize = 100;
+
+/// A single recorded copy
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entry {
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+    pub content: String,
+    /// Kept through rotation (`bc history pin`)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pinned: bool,
+    /// Name a pinned entry is recalled by (`paste --pinned NAME`); stored
+    /// in the clear even when the content is sealed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Seconds since the Unix epoch after which the entry is pruned
+    /// (`copy --ttl`), whether pinned or not
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires: Option<u64>,
+}
+
+impl Entry {
+    pub fn new(content: &str) -> Self {
+        Self {
+            timestamp: now(),
+            content: content.to_string(),
+            pinned: false,
+            name: None,
+            expires: None,
+        }
+    }
+
+    /// Whether the entry is past its own expiry, or unpinned and older than
+    /// `ttl`, at `now`
+    pub fn expired(&self, ttl: Option<Duration>, now: u64) -> bool {
+        let aged = ttl
+            .is_some_and(|ttl| !self.pinned && self.timestamp.saturating_add(ttl.as_secs()) <= now);
+        aged || self.expires.is_some_and(|expires| expires <= now)
+    }
+}
+
+/// Clipboard history backed by a JSON lines file (oldest entry first)
+#[derive(Debug, Clone)]
+pub struct History {
+    path: PathBuf,
+    max_entries: usize,
+    key: Option<Key>,
+    ttl: Option<Duration>,
+    lock_timeout: Duration,
+}
+
+impl History {
+    /// History at the default location (`~/.local/share/bc/history.jsonl`)
+    pub fn open_default() -> Result<Self> {
+        Ok(Self::at(paths::data_dir()?.join(HISTORY_FILE)))
+    }
+
+    /// History stored at an explicit path
+    pub fn at(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            max_entries: DEFAULT_MAX_ENTRIES,
+            key: None,
+            ttl: None,
+            lock_timeout: lock::DEFAULT_TIMEOUT,
+        }
+    }
+
+    pub fn with_max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = max_entries.max(1);
+        self
+    }
+
+    /// Encrypt new entries with `key`, and decrypt the ones it sealed
+    pub fn with_key(mut self, key: Option<Key>) -> Self {
+        self.key = key;
+        self
+    }
+
+    /// Prune unpinned entries older than `ttl` (`--history-ttl`)
+    pub fn with_ttl(mut self, ttl: Option<Duration>) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// How long changes wait for another process's lock before failing
+    pub fn with_lock_timeout(mut self, timeout: Duration) -> Self {
+        self.lock_timeout = timeout;
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// All entries, oldest first. A missing file is an empty history;
+    /// unreadable lines, and entries sealed with another key, are skipped.
+    pub fn entries(&self) -> Result<Vec<Entry>> {
+        Ok(self
+            .stored()?
+            .into_iter()
+            .filter_map(|entry| self.reveal(entry))
+            .collect())
+    }
+
+    /// Decrypt a stored entry if it is sealed
+    fn reveal(&self, entry: Entry) -> Option<Entry> {
+        if !crypto::is_sealed(&entry.content) {
+            return Some(entry);
+        }
+        let content = self.key.as_ref()?.open_text(&entry.content).ok()?;
+        Some(Entry { content, ..entry })
+    }
+
+    /// Entries as they are on disk
+    fn stored(&self) -> Result<Vec<Entry>> {
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("Failed to open history {}", self.path.display()))
+            }
+        };
+
+        let mut entries = Vec::new();
+        for line in BufReader::new(file).lines() {
+            let line = line.context("Failed to read history")?;
+            if let Ok(entry) = serde_json::from_str(&line) {
+                entries.push(entry);
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Entry by position counting back from the newest (1 = most recent)
+    pub fn get(&self, index: usize) -> Result<Option<Entry>> {
+        if index == 0 {
+            return Ok(None);
+        }
+        let mut entries = self.entries()?;
+        Ok(entries
+            .len()
+            .checked_sub(index)
+            .map(|i| entries.swap_remove(i)))
+    }
+
+    /// When `content` was last copied, if it is in the history
+    pub fn copied_at(&self, content: &str) -> Result<Option<u64>> {
+        Ok(self
+            .entries()?
+            .into_iter()
+            .rev()
+            .find(|entry| entry.content == content)
+            .map(|entry| entry.timestamp))
+    }
+
+    /// The pinned entry called `name`
+    pub fn pinned(&self, name: &str) -> Result<Option<Entry>> {
+        Ok(self
+            .entries()?
+            .into_iter()
+            .rfind(|entry| entry.pinned && entry.name.as_deref() == Some(name)))
+    }
+
+    /// Position (1 = most recent) of the pinned entry called `name`
+    pub fn position_of(&self, name: &str) -> Result<Option<usize>> {
+        let entries = self.entries()?;
+        Ok(entries
+            .iter()
+            .rposition(|entry| entry.pinned && entry.name.as_deref() == Some(name))
+            .map(|i| entries.len() - i))
+    }
+
+    /// Pin entry `index` (1 = most recent) so rotation keeps it, naming it
+    /// `name` if given (taking the name from any entry that had it).
+    /// Returns the entry, or `None` if there is no such entry.
+    pub fn pin(&self, index: usize, name: Option<&str>) -> Result<Option<Entry>> {
+        self.update(index, |entries, i| {
+            if let Some(name) = name {
+                for entry in entries.iter_mut() {
+                    if entry.name.as_deref() == Some(name) {
+                        entry.name = None;
+                    }
+                }
+            }
+            entries[i].pinned = true;
+            if name.is_some() {
+                entries[i].name = name.map(str::to_string);
+            }
+        })
+    }
+
+    /// Unpin entry `index` (1 = most recent), dropping its name
+    pub fn unpin(&self, index: usize) -> Result<Option<Entry>> {
+        self.update(index, |entries, i| {
+            entries[i].pinned = false;
+            entries[i].name = None;
+        })
+    }
+
+    /// Apply `change` to the stored entries, given the stored position of
+    /// readable entry `index` (1 = most recent), and save them
+    fn update(
+        &self,
+        index: usize,
+        change: impl FnOnce(&mut [Entry], usize),
+    ) -> Result<Option<Entry>> {
+        let _lock = self.lock()?;
+        let mut stored = self.stored()?;
+        let readable: Vec<usize> = stored
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| self.reveal((*entry).clone()).is_some())
+            .map(|(i, _)| i)
+            .collect();
+        let Some(&i) = index
+            .checked_sub(1)
+            .and_then(|back| readable.len().checked_sub(back + 1))
+            .and_then(|position| readable.get(position))
+        else {
+            return Ok(None);
+        };
+        change(&mut stored, i);
+        self.rewrite(&stored)?;
+        Ok(self.reveal(stored.swap_remove(i)))
+    }
+
+    /// Record a copy. Returns Ok(false) if it repeats the most recent entry.
+    pub fn record(&self, content: &str) -> Result<bool> {
+        self.record_expiring(content, None)
+    }
+
+    /// Record a copy that is pruned after `ttl` (`copy --ttl`). A repeat of
+    /// the most recent entry only updates that entry's expiry.
+    pub fn record_expiring(&self, content: &str, ttl: Option<Duration>) -> Result<bool> {
+        let _lock = self.lock()?;
+        let mut entries = self.stored()?;
+        let expires = ttl.map(|ttl| now().saturating_add(ttl.as_secs()));
+        let last = entries.last().cloned().and_then(|last| self.reveal(last));
+        if last.is_some_and(|last| last.content == content) {
+            if let Some(last) = entries.last_mut().filter(|last| last.expires != expires) {
+                last.expires = expires;
+                self.rewrite(&entries)?;
+            }
+            return Ok(false);
+        }
+        let mut entry = match &self.key {
+            Some(key) => Entry::new(&key.seal_text(content)?),
+            None => Entry::new(content),
+        };
+        entry.expires = expires;
+        entries.push(entry);
+
+        if self.rotate(&mut entries) {
+            self.rewrite(&entries)?;
+        } else {
+            // One write, so a reader never sees half a line
+            let line = format!("{}\n", serde_json::to_string(&entries[entries.len() - 1])?);
+            let mut file = self.open(&self.path, OpenOptions::new().create(true).append(true))?;
+            file.write_all(line.as_bytes())
+                .context("Failed to write history")?;
+        }
+        Ok(true)
+    }
+
+    /// Add entries from an export (see [`parse_export`]), skipping any
+    /// already present, then rotate as `record` does. Returns how many were
+    /// added.
+    pub fn import(&self, imported: Vec<Entry>) -> Result<usize> {
+        let _lock = self.lock()?;
+        let mut entries = self.stored()?;
+        let mut known: HashSet<(u64, String)> = entries
+            .iter()
+            .filter_map(|entry| self.reveal(entry.clone()))
+            .map(|entry| (entry.timestamp, entry.content))
+            .collect();
+        let mut added = 0;
+        for entry in imported {
+            if !known.insert((entry.timestamp, entry.content.clone())) {
+                continue;
+            }
+            let content = match &self.key {
+                Some(key) => key.seal_text(&entry.content)?,
+                None => entry.content,
+            };
+            entries.push(Entry { content, ..entry });
+            added += 1;
+        }
+        entries.sort_by_key(|entry| entry.timestamp);
+        // A name stays with its newest holder
+        let mut names = HashSet::new();
+        for entry in entries.iter_mut().rev() {
+            if let Some(name) = &entry.name {
+                if !names.insert(name.clone()) {
+                    entry.name = None;
+                }
+            }
+        }
+        self.rotate(&mut entries);
+        self.rewrite(&entries)?;
+        Ok(added)
+    }
+
+    /// Remove expired entries (see [`Entry::expired`]), including sealed
+    /// ones this key can't read. Returns how many were removed.
+    pub fn prune(&self) -> Result<usize> {
+        let _lock = self.lock()?;
+        let mut entries = self.stored()?;
+        let before = entries.len();
+        let now = now();
+        entries.retain(|entry| !entry.expired(self.ttl, now));
+        let removed = before - entries.len();
+        if removed > 0 {
+            self.rewrite(&entries)?;
+        }
+        Ok(removed)
+    }
+
+    /// Drop the oldest entries past `max_entries`. Pinned entries don't
+    /// count towards the limit and are never dropped. Returns whether any
+    /// were.
+    fn rotate(&self, entries: &mut Vec<Entry>) -> bool {
+        let unpinned = entries.iter().filter(|entry| !entry.pinned).count();
+        let Some(mut excess) = unpinned.checked_sub(self.max_entries).filter(|&n| n > 0) else {
+            return false;
+        };
+        entries.retain(|entry| {
+            let drop = excess > 0 && !entry.pinned;
+            excess -= usize::from(drop);
+            !drop
+        });
+        true
+    }
+
+    /// Lock the history against other processes' changes until dropped
+    fn lock(&self) -> Result<FileLock> {
+        FileLock::acquire(&self.path, self.lock_timeout)
+    }
+
+    /// Replace the file with `entries`, through a temp file renamed into
+    /// place. Only call with the lock held: the temp file name is fixed.
+    fn rewrite(&self, entries: &[Entry]) -> Result<()> {
+        let mut data = String::new();
+        for entry in entries {
+            data.push_str(&serde_json::to_string(entry)?);
+            data.push('\n');
+        }
+        paths::write_atomic(&self.path, FileMode::Private, |out| {
+            out.write_all(data.as_bytes())
+        })
+        .with_context(|| format!("Failed to replace history {}", self.path.display()))
+    }
+
+    /// Open a history file, creating its directory and restricting
+    /// permissions to the current user (clipboard contents may be sensitive)
+    fn open(&self, path: &Path, options: &mut OpenOptions) -> Result<File> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        options
+            .open(path)
+            .with_context(|| format!("Failed to open history {}", path.display()))
+    }
+}
+
+/// What `[history]` in the config keeps out of the history
+#[derive(Debug, Clone, Default)]
+pub struct Ignore {
+    commands: Vec<String>,
+    patterns: Vec<Regex>,
+    max_size: Option<usize>,
+}
+
+impl Ignore {
+    pub fn new(config: &HistoryConfig) -> Result<Self> {
+        let patterns = config
+            .ignore_patterns
+            .iter()
+            .map(|pattern| {
+                Regex::new(pattern).map_err(|e| {
+                    anyhow::anyhow!("Invalid history ignore pattern '{}': {}", pattern, e)
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            commands: config.ignore_commands.clone(),
+            patterns,
+            max_size: config.max_entry_size,
+        })
+    }
+
+    /// Whether any rule looks at the source command, which is worth looking
+    /// up only then
+    pub fn checks_commands(&self) -> bool {
+        !self.commands.is_empty()
+    }
+
+    /// Why `content`, produced by `sources` (program names or paths, as
+    /// far as they're known), stays out of the history, if it does
+    pub fn reason(&self, content: &str, sources: &[String]) -> Option<String> {
+        if let Some(max) = self.max_size.filter(|&max| content.len() > max) {
+            return Some(format!("larger than max_entry_size ({} bytes)", max));
+        }
+        if let Some(pattern) = self.patterns.iter().find(|regex| regex.is_match(content)) {
+            return Some(format!("matches ignore pattern '{}'", pattern));
+        }
+        sources.iter().find_map(|source| {
+            let program = Path::new(source)
+                .file_name()
+                .map_or(source.as_str(), |name| name.to_str().unwrap_or(source));
+            self.commands
+                .iter()
+                .any(|command| command == program)
+                .then(|| format!("from ignored command '{}'", program))
+        })
+    }
+}
+
+/// A CSV export row; CSV has no optional columns, so every field is here
+#[derive(Debug, Serialize, Deserialize)]
+struct CsvRow {
+    timestamp: u64,
+    pinned: bool,
+    name: Option<String>,
+    content: String,
+    /// Last, and missing from exports made before `copy --ttl`
+    #[serde(default)]
+    expires: Option<u64>,
+}
+
+/// Entries (oldest first) as a JSON array, for `bc history export`
+pub fn export_json(entries: &[Entry]) -> Result<String> {
+    Ok(serde_json::to_string_pretty(entries)? + "\n")
+}
+
+/// Entries (oldest first) as CSV with a
+/// `timestamp,pinned,name,content,expires` header
+pub fn export_csv(entries: &[Entry]) -> Result<String> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for entry in entries {
+        writer.serialize(CsvRow {
+            timestamp: entry.timestamp,
+            pinned: entry.pinned,
+            name: entry.name.clone(),
+            content: entry.content.clone(),
+            expires: entry.expires,
+        })?;
+    }
+    let bytes = writer.into_inner().context("Failed to write CSV")?;
+    String::from_utf8(bytes).context("CSV is not valid UTF-8")
+}
+
+/// Entries from `export_json` or `export_csv` output, told apart by the
+/// JSON array's opening bracket
+pub fn parse_export(text: &str) -> Result<Vec<Entry>> {
+    if text.trim_start().starts_with('[') {
+        return serde_json::from_str(text).context("Invalid JSON history export");
+    }
+    csv::Reader::from_reader(text.as_bytes())
+        .deserialize()
+        .map(|row| {
+            let row: CsvRow = row.context("Invalid CSV history export")?;
+            Ok(Entry {
+                timestamp: row.timestamp,
+                content: row.content,
+                pinned: row.pinned,
+                name: row.name.filter(|name| !name.is_empty()),
+                expires: row.expires,
+            })
+        })
+        .collect()
+}
+
+/// Current time in seconds since the Unix epoch
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Human-readable age of a timestamp, e.g. "5m ago"
+pub fn format_age(timestamp: u64) -> String {
+    let secs = now().saturating_sub(timestamp);
+    match secs {
+        0..=59 => format!("{}s ago", secs),
+        60..=3599 => format!("{}m ago", secs / 60),
+        3600..=86399 => format!("{}h ago", secs / 3600),
+        _ => format!("{}d ago", secs / 86400),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_history() -> (tempfile::TempDir, History) {
+        let dir = tempfile::tempdir().unwrap();
+        let history = History::at(dir.path().join("nested").join(HISTORY_FILE));
+        (dir, history)
+    }
+
+    fn contents(history: &History) -> Vec<String> {
+        history
+            .entries()
+            .unwrap()
+            .into_iter()
+            .map(|e| e.content)
+            .collect()
+    }
+
+    #[test]
+    fn test_missing_file_is_empty() {
+        let (_dir, history) = temp_history();
+        assert!(history.entries().unwrap().is_empty());
+        assert_eq!(history.get(1).unwrap(), None);
+    }
+
+    #[test]
+    fn test_record_and_get() {
+        let (_dir, history) = temp_history();
+        assert!(history.record("first").unwrap());
+        assert!(history.record("second").unwrap());
+
+        assert_eq!(contents(&history), ["first", "second"]);
+        assert_eq!(history.get(1).unwrap().unwrap().content, "second");
+        assert_eq!(history.get(2).unwrap().unwrap().content, "first");
+        assert_eq!(history.get(3).unwrap(), None);
+        assert_eq!(history.get(0).unwrap(), None);
+    }
+
+    #[test]
+    fn test_consecutive_duplicates_skipped() {
+        let (_dir, history) = temp_history();
+        assert!(history.record("same").unwrap());
+        assert!(!history.record("same").unwrap());
+        assert!(history.record("other").unwrap());
+        assert!(history.record("same").unwrap());
+
+        assert_eq!(contents(&history), ["same", "other", "same"]);
+    }
+
+    #[test]
+    fn test_copied_at_finds_newest() {
+        let (_dir, history) = temp_history();
+        let entry = |timestamp, content: &str| Entry {
+            timestamp,
+            ..Entry::new(content)
+        };
+        history
+            .rewrite(&[entry(10, "a"), entry(20, "b"), entry(30, "a")])
+            .unwrap();
+        assert_eq!(history.copied_at("a").unwrap(), Some(30));
+        assert_eq!(history.copied_at("b").unwrap(), Some(20));
+        assert_eq!(history.copied_at("c").unwrap(), None);
+    }
+
+    #[test]
+    fn test_rotation_drops_oldest() {
+        let (_dir, history) = temp_history();
+        let history = history.with_max_entries(3);
+        for text in ["a", "b", "c", "d", "e"] {
+            history.record(text).unwrap();
+        }
+
+        assert_eq!(contents(&history), ["c", "d", "e"]);
+    }
+
+    #[test]
+    fn test_pinned_entries_survive_rotation() {
+        let (_dir, history) = temp_history();
+        let history = history.with_max_entries(2);
+        history.record("keep").unwrap();
+        history.record("second").unwrap();
+        assert_eq!(
+            history.pin(2, Some("key")).unwrap().unwrap().content,
+            "keep"
+        );
+        for content in ["third", "fourth", "fifth"] {
+            history.record(content).unwrap();
+        }
+        assert_eq!(contents(&history), ["keep", "fourth", "fifth"]);
+        assert_eq!(history.pinned("key").unwrap().unwrap().content, "keep");
+        assert_eq!(history.position_of("key").unwrap(), Some(3));
+
+        // Unpinned, it is the oldest and goes first
+        history.unpin(3).unwrap().unwrap();
+        assert_eq!(history.pinned("key").unwrap(), None);
+        history.record("sixth").unwrap();
+        assert_eq!(contents(&history), ["fifth", "sixth"]);
+    }
+
+    #[test]
+    fn test_pin_names_are_unique() {
+        let (_dir, history) = temp_history();
+        history.record("old").unwrap();
+        history.record("new").unwrap();
+        history.pin(2, Some("x")).unwrap();
+        history.pin(1, Some("x")).unwrap();
+        assert_eq!(history.pinned("x").unwrap().unwrap().content, "new");
+        // The older entry stays pinned, without a name
+        let old = history.get(2).unwrap().unwrap();
+        assert!(old.pinned && old.name.is_none());
+
+        assert_eq!(history.pin(3, None).unwrap(), None);
+        assert_eq!(history.pin(0, None).unwrap(), None);
+    }
+
+    #[test]
+    fn test_pin_fields_are_optional_on_disk() {
+        let (_dir, history) = temp_history();
+        history.record("plain").unwrap();
+        let line = fs::read_to_string(history.path()).unwrap();
+        assert!(!line.contains("pinned"), "{}", line);
+
+        let entry: Entry = serde_json::from_str(r#"{"timestamp":1,"content":"a"}"#).unwrap();
+        assert!(!entry.pinned);
+        assert_eq!(entry.name, None);
+    }
+
+    #[test]
+    fn test_ignore_rules() {
+        let ignore = Ignore::new(&HistoryConfig {
+            ignore_commands: vec!["pass".to_string()],
+            ignore_patterns: vec!["(?i)password".to_string()],
+            max_entry_size: Some(16),
+        })
+        .unwrap();
+        assert!(ignore.checks_commands());
+        assert_eq!(ignore.reason("hello", &[]), None);
+        assert!(ignore
+            .reason("my Password: x", &[])
+            .unwrap()
+            .contains("pattern"));
+        assert!(ignore
+            .reason(&"x".repeat(17), &[])
+            .unwrap()
+            .contains("16 bytes"));
+        let from = |source: &str| ignore.reason("hello", &[source.to_string()]);
+        assert!(from("/usr/bin/pass").is_some());
+        assert!(from("pass").is_some());
+        assert_eq!(from("passwd"), None);
+
+        assert!(!Ignore::default().checks_commands());
+        let invalid = HistoryConfig {
+            ignore_patterns: vec!["(".to_string()],
+            ..HistoryConfig::default()
+        };
+        assert!(Ignore::new(&invalid).is_err());
+    }
+
+    #[test]
+    fn test_export_roundtrip() {
+        let entries = vec![
+            Entry {
+                timestamp: 5,
+                ..Entry::new("line one\nline \"two\", with a comma")
+            },
+            Entry {
+                timestamp: 9,
+                pinned: true,
+                name: Some("key".to_string()),
+                ..Entry::new("ssh-ed25519 AAAA")
+            },
+            Entry {
+                timestamp: 12,
+                expires: Some(99),
+                ..Entry::new("token")
+            },
+        ];
+        for export in [export_json(&entries), export_csv(&entries)] {
+            assert_eq!(parse_export(&export.unwrap()).unwrap(), entries);
+        }
+        let csv = export_csv(&entries).unwrap();
+        assert!(
+            csv.starts_with("timestamp,pinned,name,content,expires\n"),
+            "{}",
+            csv
+        );
+        assert!(parse_export("timestamp,pinned\nx,y\n").is_err());
+        let old = parse_export("timestamp,pinned,name,content\n5,false,,a\n").unwrap();
+        assert_eq!(
+            old,
+            [Entry {
+                timestamp: 5,
+                ..Entry::new("a")
+            }]
+        );
+    }
+
+    #[test]
+    fn test_import_merges_by_time() {
+        let (_dir, history) = temp_history();
+        let history = history.with_max_entries(3);
+        let entry = |timestamp, content: &str| Entry {
+            timestamp,
+            ..Entry::new(content)
+        };
+        history.rewrite(&[entry(10, "a"), entry(30, "c")]).unwrap();
+        let imported = vec![
+            entry(20, "b"),
+            entry(30, "c"),
+            entry(40, "d"),
+            entry(40, "d"),
+            Entry {
+                pinned: true,
+                ..entry(1, "old but pinned")
+            },
+        ];
+        assert_eq!(history.import(imported).unwrap(), 3);
+        // "a" rotates out; the pinned entry doesn't count
+        assert_eq!(contents(&history), ["old but pinned", "b", "c", "d"]);
+    }
+
+    #[test]
+    fn test_prune_expired() {
+        let (_dir, history) = temp_history();
+        let now = now();
+        let entry = |age: u64, content: &str| Entry {
+            timestamp: now - age,
+            ..Entry::new(content)
+        };
+        history
+            .rewrite(&[
+                entry(100, "old"),
+                Entry {
+                    pinned: true,
+                    ..entry(100, "old but pinned")
+                },
+                Entry {
+                    expires: Some(now - 1),
+                    pinned: true,
+                    ..entry(10, "expired")
+                },
+                Entry {
+                    expires: Some(now + 60),
+                    ..entry(10, "expiring")
+                },
+                entry(10, "recent"),
+            ])
+            .unwrap();
+        assert_eq!(history.prune().unwrap(), 1);
+        assert_eq!(
+            contents(&history),
+            ["old", "old but pinned", "expiring", "recent"]
+        );
+
+        let history = history.with_ttl(Some(Duration::from_secs(50)));
+        assert_eq!(history.prune().unwrap(), 1);
+        assert_eq!(contents(&history), ["old but pinned", "expiring", "recent"]);
+        assert_eq!(history.prune().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_record_expiring() {
+        let (_dir, history) = temp_history();
+        assert!(history
+            .record_expiring("token", Some(Duration::from_secs(60)))
+            .unwrap());
+        let expires = history.get(1).unwrap().unwrap().expires;
+        assert!(expires.is_some_and(|expires| expires >= now() + 59));
+        // Copying it again without a TTL clears the expiry
+        assert!(!history.record("token").unwrap());
+        assert_eq!(history.get(1).unwrap().unwrap().expires, None);
+    }
+
+    #[test]
+    fn test_multiline_content_roundtrip() {
+        let (_dir, history) = temp_history();
+        history.record("line1\nline2\n").unwrap();
+
+        assert_eq!(history.get(1).unwrap().unwrap().content, "line1\nline2\n");
+    }
+
+    #[test]
+    fn test_corrupt_lines_skipped() {
+        let (_dir, history) = temp_history();
+        history.record("good").unwrap();
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(history.path())
+            .unwrap();
+        writeln!(file, "not json").unwrap();
+
+        assert_eq!(contents(&history), ["good"]);
+    }
+
+    #[test]
+    fn test_encrypted_entries() {
+        let (_dir, history) = temp_history();
+        let key = Key::generate().unwrap();
+        history.record("plain").unwrap();
+        let sealed = history.clone().with_key(Some(key.clone()));
+        assert!(sealed.record("secret").unwrap());
+        assert!(!sealed.record("secret").unwrap());
+
+        let on_disk = fs::read_to_string(history.path()).unwrap();
+        assert!(!on_disk.contains("secret"));
+        assert_eq!(contents(&sealed), ["plain", "secret"]);
+        // Without the key the sealed entry is hidden, but rotation keeps it
+        assert_eq!(contents(&history), ["plain"]);
+        history.record("more").unwrap();
+        assert_eq!(contents(&sealed), ["plain", "secret", "more"]);
+    }
+
+    /// Record `per_writer` entries from each of `writers` threads at once,
+    /// each through its own `History` as separate processes would
+    fn record_concurrently(history: &History, writers: usize, per_writer: usize) {
+        let threads: Vec<_> = (0..writers)
+            .map(|writer| {
+                let history = history.clone();
+                std::thread::spawn(move || {
+                    for i in 0..per_writer {
+                        history.record(&format!("{}-{}", writer, i)).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_concurrent_writers_keep_every_entry() {
+        let (_dir, history) = temp_history();
+        let history = history.with_max_entries(1000);
+        record_concurrently(&history, 8, 25);
+
+        let mut recorded = contents(&history);
+        assert_eq!(recorded.len(), 200);
+        recorded.sort();
+        recorded.dedup();
+        assert_eq!(recorded.len(), 200);
+    }
+
+    #[test]
+    fn test_concurrent_writers_with_rotation() {
+        let (_dir, history) = temp_history();
+        let history = history.with_max_entries(10);
+        record_concurrently(&history, 8, 25);
+
+        // Every line is whole, and rotation kept exactly the limit
+        let on_disk = fs::read_to_string(history.path()).unwrap();
+        assert_eq!(on_disk.lines().count(), 10);
+        assert!(on_disk
+            .lines()
+            .all(|line| serde_json::from_str::<Entry>(line).is_ok()));
+    }
+
+    #[test]
+    fn test_changes_time_out_while_locked() {
+        let (_dir, history) = temp_history();
+        let history = history.with_lock_timeout(Duration::from_millis(20));
+        let held = FileLock::acquire(history.path(), lock::DEFAULT_TIMEOUT).unwrap();
+
+        let err = history.record("blocked").unwrap_err();
+        let (code, _) = crate::error::classify(&err, crate::error::ErrorCode::General);
+        assert_eq!(code, crate::error::ErrorCode::Locked);
+        // Reads don't wait for the lock
+        assert!(history.entries().unwrap().is_empty());
+
+        drop(held);
+        assert!(history.record("unblocked").unwrap());
+    }
+
+    #[test]
+    fn test_format_age() {
+        assert_eq!(format_age(now()), "0s ago");
+        assert_eq!(format_age(now() - 120), "2m ago");
+        assert_eq!(format_age(now() - 7200), "2h ago");
+        assert_eq!(format_age(now() - 3 * 86400), "3d ago");
+    }
+}
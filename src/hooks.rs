@@ -0,0 +1,149 @@
+//! User scripts run around copies and pastes, from the config file's
+//! `[hooks]` section. Each hook is a shell command line that receives the
+//! content on stdin and details in `BC_*` environment variables.
+
+use anyhow::{anyhow, bail, Context, Result};
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+use std::thread;
+
+use crate::config::HooksConfig;
+use crate::debug;
+
+/// When a hook runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hook {
+    /// Before a copy; its stdout replaces the content, and a failure
+    /// cancels the copy
+    PreCopy,
+    /// After a copy
+    PostCopy,
+    /// After a paste
+    PostPaste,
+}
+
+impl Hook {
+    /// The hook's key in `[hooks]`, also passed to it as `BC_HOOK`
+    pub fn name(self) -> &'static str {
+        match self {
+            Hook::PreCopy => "pre_copy",
+            Hook::PostCopy => "post_copy",
+            Hook::PostPaste => "post_paste",
+        }
+    }
+
+    /// The command configured for this hook, if any
+    pub fn command(self, config: &HooksConfig) -> Option<&str> {
+        match self {
+            Hook::PreCopy => config.pre_copy.as_deref(),
+            Hook::PostCopy => config.post_copy.as_deref(),
+            Hook::PostPaste => config.post_paste.as_deref(),
+        }
+        .filter(|command| !command.trim().is_empty())
+    }
+}
+
+/// Run the `pre_copy` hook `command` on `content` and return what it
+/// printed, which is copied instead. Fails if the hook does.
+pub fn pre_copy(command: &str, content: &str, env: &[(&str, String)]) -> Result<String> {
+    let output = run(Hook::PreCopy, command, content, env, true)?;
+    String::from_utf8(output).map_err(|_| anyhow!("The pre_copy hook's output is not valid UTF-8"))
+}
+
+/// Run a post hook `command` with `content` on its stdin. Its stdout goes
+/// to our stderr, so it can't mix with pasted output.
+pub fn post(hook: Hook, command: &str, content: &str, env: &[(&str, String)]) -> Result<()> {
+    run(hook, command, content, env, false).map(|_| ())
+}
+
+/// Run `command` through the shell with `input` on its stdin and `env`
+/// (plus `BC_HOOK`) set, returning its stdout if `capture` is set
+fn run(
+    hook: Hook,
+    command: &str,
+    input: &str,
+    env: &[(&str, String)],
+    capture: bool,
+) -> Result<Vec<u8>> {
+    debug!("Running {} hook '{}'", hook.name(), command);
+    let mut shell = shell(command);
+    shell
+        .env("BC_HOOK", hook.name())
+        .envs(env.iter().map(|(name, value)| (name, value)))
+        .stdin(Stdio::piped())
+        .stdout(if capture {
+            Stdio::piped()
+        } else {
+            Stdio::from(io::stderr())
+        });
+    let mut child = shell
+        .spawn()
+        .with_context(|| format!("Failed to run the {} hook", hook.name()))?;
+
+    // Write from another thread so a large output can't fill its pipe while
+    // we are still writing
+    let mut stdin = child
+        .stdin
+        .take()
+        .context("Failed to open the hook's stdin")?;
+    let output = thread::scope(|scope| {
+        scope.spawn(move || {
+            // A hook that doesn't read its input is fine
+            let _ = stdin.write_all(input.as_bytes());
+        });
+        child.wait_with_output()
+    })
+    .with_context(|| format!("Failed to wait for the {} hook", hook.name()))?;
+    if !output.status.success() {
+        bail!("The {} hook failed ({})", hook.name(), output.status);
+    }
+    Ok(output.stdout)
+}
+
+#[cfg(unix)]
+pub(crate) fn shell(command: &str) -> Command {
+    let mut shell = Command::new("sh");
+    shell.arg("-c").arg(command);
+    shell
+}
+
+#[cfg(not(unix))]
+pub(crate) fn shell(command: &str) -> Command {
+    let mut shell = Command::new("cmd");
+    shell.arg("/C").arg(command);
+    shell
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pre_copy_rewrites() {
+        let env = [("BC_BYTES", "5".to_string())];
+        assert_eq!(
+            pre_copy("tr a-z A-Z; echo \"$BC_HOOK $BC_BYTES\"", "hello", &env).unwrap(),
+            "HELLOpre_copy 5\n"
+        );
+        let err = pre_copy("cat >/dev/null; exit 3", "x", &[]).unwrap_err();
+        assert!(err.to_string().contains("pre_copy hook failed"), "{}", err);
+    }
+
+    #[test]
+    fn test_post_hook_status() {
+        assert!(post(Hook::PostCopy, "test \"$(cat)\" = hi", "hi", &[]).is_ok());
+        assert!(post(Hook::PostPaste, "false", "hi", &[]).is_err());
+    }
+
+    #[test]
+    fn test_configured_command() {
+        let config = HooksConfig {
+            pre_copy: Some("sort".to_string()),
+            post_copy: Some("  ".to_string()),
+            post_paste: None,
+        };
+        assert_eq!(Hook::PreCopy.command(&config), Some("sort"));
+        assert_eq!(Hook::PostCopy.command(&config), None);
+        assert_eq!(Hook::PostPaste.command(&config), None);
+    }
+}
@@ -0,0 +1,302 @@
+//! Plain-text rendering of HTML, for the text form that goes alongside a
+//! rich-text copy. Not a full parser: tags are dropped, block elements
+//! become line breaks, and common entities are decoded.
+
+/// Elements whose contents are never shown
+const HIDDEN: &[&str] = &["script", "style", "head", "title", "template"];
+
+/// Elements that start or end on a line of their own
+const BLOCKS: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "div",
+    "dl",
+    "dt",
+    "dd",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "li",
+    "main",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "table",
+    "tr",
+    "ul",
+];
+
+/// Render `html` as plain text
+pub fn to_text(html: &str) -> String {
+    render(html, false)
+}
+
+/// Render `html` as plain text for reading on its own (`paste --plain`):
+/// like [`to_text`], but links keep their target as `text (url)`
+pub fn to_readable_text(html: &str) -> String {
+    render(html, true)
+}
+
+fn render(html: &str, links: bool) -> String {
+    let mut out = String::new();
+    let mut hidden: Option<String> = None;
+    let mut pre = 0usize;
+    let mut lists = 0usize;
+    // Open links as (where their text starts in `out`, target)
+    let mut anchors: Vec<(usize, Option<String>)> = Vec::new();
+    let mut rest = html;
+
+    while !rest.is_empty() {
+        let Some(start) = rest.find('<') else {
+            if hidden.is_none() {
+                push_text(&mut out, rest, pre > 0);
+            }
+            break;
+        };
+        if hidden.is_none() {
+            push_text(&mut out, &rest[..start], pre > 0);
+        }
+        rest = &rest[start..];
+
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+            continue;
+        }
+        let Some(end) = rest.find('>') else {
+            // A stray '<' rather than a tag
+            if hidden.is_none() {
+                push_text(&mut out, rest, pre > 0);
+            }
+            break;
+        };
+        let tag = &rest[1..end];
+        rest = &rest[end + 1..];
+
+        let closing = tag.starts_with('/');
+        let name = tag
+            .trim_start_matches('/')
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or("")
+            .to_ascii_lowercase();
+
+        if let Some(open) = &hidden {
+            if closing && *open == name {
+                hidden = None;
+            }
+            continue;
+        }
+        if !closing && HIDDEN.contains(&name.as_str()) && !tag.ends_with('/') {
+            hidden = Some(name);
+            continue;
+        }
+
+        match name.as_str() {
+            "br" => out.push('\n'),
+            "pre" if closing => pre = pre.saturating_sub(1),
+            "pre" => pre += 1,
+            "td" | "th" if closing => out.push('\t'),
+            "ul" | "ol" if closing => lists = lists.saturating_sub(1),
+            "ul" | "ol" => lists += 1,
+            "a" if links && closing => {
+                if let Some((start, Some(href))) = anchors.pop() {
+                    let text = out[start..].trim();
+                    if !text.is_empty() && text != href.trim_start_matches("mailto:") {
+                        out.push_str(&format!(" ({})", href));
+                    }
+                }
+            }
+            "a" if links => anchors.push((out.len(), link_target(tag))),
+            _ => {}
+        }
+        if name == "li" && !closing {
+            line_break(&mut out);
+            out.push_str(&"  ".repeat(lists.saturating_sub(1)));
+            out.push_str("- ");
+        } else if BLOCKS.contains(&name.as_str()) {
+            line_break(&mut out);
+        }
+    }
+
+    out.lines()
+        .map(str::trim_end)
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim_matches('\n')
+        .to_string()
+}
+
+/// The `href` of an `<a ...>` tag worth showing: not an in-page anchor or
+/// a script
+fn link_target(tag: &str) -> Option<String> {
+    let lower = tag.to_ascii_lowercase();
+    let start = lower.find("href=")? + "href=".len();
+    let value = &tag[start..];
+    let href = match value.chars().next()? {
+        quote @ ('"' | '\'') => value[1..].split(quote).next()?,
+        _ => value
+            .split(|c: char| c.is_whitespace() || c == '>')
+            .next()?,
+    };
+    let href = decode_entities(href.trim());
+    let shown = !href.is_empty()
+        && !href.starts_with('#')
+        && !href.to_ascii_lowercase().starts_with("javascript:");
+    shown.then_some(href)
+}
+
+/// Append text content, collapsing whitespace outside `<pre>`
+fn push_text(out: &mut String, text: &str, preformatted: bool) {
+    let text = decode_entities(text);
+    if preformatted {
+        out.push_str(&text);
+        return;
+    }
+    for (i, word) in text.split_ascii_whitespace().enumerate() {
+        let boundary = i > 0 || text.starts_with(|c: char| c.is_ascii_whitespace());
+        if boundary && !out.is_empty() && !out.ends_with(['\n', ' ', '\t']) {
+            out.push(' ');
+        }
+        out.push_str(word);
+    }
+    if text.ends_with(|c: char| c.is_ascii_whitespace()) && !out.ends_with(['\n', ' ', '\t']) {
+        out.push(' ');
+    }
+}
+
+/// End the current line unless already at the start of one
+fn line_break(out: &mut String) {
+    while out.ends_with(' ') {
+        out.pop();
+    }
+    if !out.is_empty() && !out.ends_with('\n') {
+        out.push('\n');
+    }
+}
+
+/// Decode named entities used in everyday markup, and numeric references
+pub fn decode_entities(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let decoded = rest[1..]
+            .find(';')
+            .filter(|&end| end <= 10)
+            .and_then(|end| entity(&rest[1..end + 1]).map(|c| (c, end + 2)));
+        match decoded {
+            Some((c, len)) => {
+                out.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+fn entity(name: &str) -> Option<char> {
+    if let Some(number) = name.strip_prefix('#') {
+        let code = match number.strip_prefix(['x', 'X']) {
+            Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+            None => number.parse().ok()?,
+        };
+        return char::from_u32(code);
+    }
+    Some(match name {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => '\u{a0}',
+        "copy" => '©',
+        "reg" => '®',
+        "trade" => '™',
+        "hellip" => '…',
+        "mdash" => '—',
+        "ndash" => '–',
+        "lsquo" => '‘',
+        "rsquo" => '’',
+        "ldquo" => '“',
+        "rdquo" => '”',
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inline_markup_is_dropped() {
+        assert_eq!(to_text("<b>Hello</b>, <i>world</i>!"), "Hello, world!");
+        assert_eq!(to_text("<a href=\"x\">link</a> text"), "link text");
+    }
+
+    #[test]
+    fn test_blocks_become_lines() {
+        let html = "<h1>Title</h1>\n<p>First\n  paragraph</p><p>Second<br>line</p>";
+        assert_eq!(to_text(html), "Title\nFirst paragraph\nSecond\nline");
+        assert_eq!(to_text("<ul><li>one</li><li>two</li></ul>"), "- one\n- two");
+    }
+
+    #[test]
+    fn test_nested_lists_indent() {
+        assert_eq!(
+            to_text("<ul><li>a<ul><li>b</li></ul></li><li>c</li></ul>"),
+            "- a\n  - b\n- c"
+        );
+    }
+
+    #[test]
+    fn test_readable_text_keeps_links() {
+        let html = "<p>See <a href=\"https://example.com/a?x=1&amp;y=2\">the docs</a> or \
+                    <a href='https://example.com'>https://example.com</a>.</p>\
+                    <p><a href=\"#top\">Back</a> <a>bare</a></p>";
+        assert_eq!(
+            to_readable_text(html),
+            "See the docs (https://example.com/a?x=1&y=2) or https://example.com.\nBack bare"
+        );
+        assert_eq!(
+            to_text(html),
+            "See the docs or https://example.com.\nBack bare"
+        );
+    }
+
+    #[test]
+    fn test_hidden_elements_and_comments() {
+        let html = "<style>p { color: red }</style><!-- note --><p>shown</p><script>x()</script>";
+        assert_eq!(to_text(html), "shown");
+    }
+
+    #[test]
+    fn test_pre_keeps_whitespace() {
+        assert_eq!(
+            to_text("<pre>fn main() {\n    x\n}</pre>"),
+            "fn main() {\n    x\n}"
+        );
+    }
+
+    #[test]
+    fn test_entities() {
+        assert_eq!(to_text("a &lt;b&gt; &amp; &#39;c&#x27;"), "a <b> & 'c'");
+        assert_eq!(decode_entities("AT&T &bogus; &"), "AT&T &bogus; &");
+    }
+}
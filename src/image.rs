@@ -0,0 +1,209 @@
+//! Image decoding/encoding between file formats and raw clipboard bitmaps.
+
+use anyhow::{Context, Result};
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageFormat, RgbaImage};
+use std::io::Cursor;
+use std::str::FromStr;
+
+/// JPEG quality when `--quality` isn't given
+pub const DEFAULT_JPEG_QUALITY: u8 = 90;
+
+/// An RGBA bitmap, 4 bytes per pixel, row-major
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub bytes: Vec<u8>,
+}
+
+/// Decode PNG or JPEG data (format detected from content)
+pub fn decode(data: &[u8]) -> Result<Image> {
+    let format = image::guess_format(data).context("Unrecognized image format")?;
+    if !matches!(format, ImageFormat::Png | ImageFormat::Jpeg) {
+        anyhow::bail!(
+            "Unsupported image format {:?} (expected PNG or JPEG)",
+            format
+        );
+    }
+
+    let rgba = image::load_from_memory_with_format(data, format)
+        .context("Failed to decode image")?
+        .into_rgba8();
+    Ok(Image {
+        width: rgba.width() as usize,
+        height: rgba.height() as usize,
+        bytes: rgba.into_raw(),
+    })
+}
+
+/// Encode a bitmap as PNG
+pub fn encode_png(image: &Image) -> Result<Vec<u8>> {
+    let rgba = to_rgba(image)?;
+
+    let mut out = Cursor::new(Vec::new());
+    rgba.write_to(&mut out, ImageFormat::Png)
+        .context("Failed to encode PNG")?;
+    Ok(out.into_inner())
+}
+
+/// Encode a bitmap as JPEG at `quality` (1-100); transparency is dropped
+pub fn encode_jpeg(image: &Image, quality: u8) -> Result<Vec<u8>> {
+    let rgb = DynamicImage::ImageRgba8(to_rgba(image)?).to_rgb8();
+    let mut out = Vec::new();
+    rgb.write_with_encoder(JpegEncoder::new_with_quality(&mut out, quality))
+        .context("Failed to encode JPEG")?;
+    Ok(out)
+}
+
+/// A `--resize` target: `800x` (width), `x600` (height), or `800x600`
+/// (fit within both)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Size {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+}
+
+impl FromStr for Size {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("'{}' is not a size (e.g. 800x, x600, 800x600)", spec);
+        let (width, height) = spec.split_once(['x', 'X']).ok_or_else(invalid)?;
+        let parse = |n: &str| match n {
+            "" => Ok(None),
+            n => match n.parse::<u32>() {
+                Ok(0) | Err(_) => Err(invalid()),
+                Ok(n) => Ok(Some(n)),
+            },
+        };
+        let size = Size {
+            width: parse(width)?,
+            height: parse(height)?,
+        };
+        if size.width.is_none() && size.height.is_none() {
+            return Err(invalid());
+        }
+        Ok(size)
+    }
+}
+
+/// Downscale to fit `size`, keeping the aspect ratio. Images already small
+/// enough are returned as they are.
+pub fn resize(image: &Image, size: Size) -> Result<Image> {
+    let (width, height) = (image.width as f64, image.height as f64);
+    let scale = [
+        size.width.map(|w| w as f64 / width),
+        size.height.map(|h| h as f64 / height),
+    ]
+    .into_iter()
+    .flatten()
+    .fold(1.0, f64::min);
+    if scale >= 1.0 {
+        return Ok(image.clone());
+    }
+    let target_width = ((width * scale).round() as u32).max(1);
+    let target_height = ((height * scale).round() as u32).max(1);
+    let resized = image::imageops::resize(
+        &to_rgba(image)?,
+        target_width,
+        target_height,
+        FilterType::Lanczos3,
+    );
+    Ok(Image {
+        width: resized.width() as usize,
+        height: resized.height() as usize,
+        bytes: resized.into_raw(),
+    })
+}
+
+fn to_rgba(image: &Image) -> Result<RgbaImage> {
+    RgbaImage::from_raw(image.width as u32, image.height as u32, image.bytes.clone())
+        .context("Image data does not match its dimensions")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Image {
+        Image {
+            width: 2,
+            height: 1,
+            bytes: vec![255, 0, 0, 255, 0, 0, 255, 128],
+        }
+    }
+
+    #[test]
+    fn test_png_roundtrip() {
+        let png = encode_png(&sample()).unwrap();
+        assert!(png.starts_with(b"\x89PNG"));
+        assert_eq!(decode(&png).unwrap(), sample());
+    }
+
+    #[test]
+    fn test_decode_jpeg() {
+        let rgb = image::RgbImage::from_pixel(3, 2, image::Rgb([10, 20, 30]));
+        let mut jpeg = Cursor::new(Vec::new());
+        rgb.write_to(&mut jpeg, ImageFormat::Jpeg).unwrap();
+
+        let decoded = decode(jpeg.get_ref()).unwrap();
+        assert_eq!((decoded.width, decoded.height), (3, 2));
+        assert_eq!(decoded.bytes.len(), 3 * 2 * 4);
+    }
+
+    #[test]
+    fn test_encode_jpeg() {
+        let jpeg = encode_jpeg(&sample(), 80).unwrap();
+        assert!(jpeg.starts_with(&[0xFF, 0xD8]));
+        let decoded = decode(&jpeg).unwrap();
+        assert_eq!((decoded.width, decoded.height), (2, 1));
+    }
+
+    #[test]
+    fn test_parse_size() {
+        let size = |w, h| Size {
+            width: w,
+            height: h,
+        };
+        assert_eq!("800x".parse(), Ok(size(Some(800), None)));
+        assert_eq!("x600".parse(), Ok(size(None, Some(600))));
+        assert_eq!("800x600".parse(), Ok(size(Some(800), Some(600))));
+        for bad in ["800", "x", "0x10", "ax10", ""] {
+            assert!(bad.parse::<Size>().is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_resize_keeps_aspect_and_never_enlarges() {
+        let image = Image {
+            width: 40,
+            height: 20,
+            bytes: vec![255; 40 * 20 * 4],
+        };
+        let fit = |spec: &str| {
+            let resized = resize(&image, spec.parse().unwrap()).unwrap();
+            (resized.width, resized.height)
+        };
+        assert_eq!(fit("10x"), (10, 5));
+        assert_eq!(fit("x5"), (10, 5));
+        assert_eq!(fit("20x5"), (10, 5));
+        assert_eq!(fit("100x"), (40, 20));
+    }
+
+    #[test]
+    fn test_decode_rejects_non_image() {
+        assert!(decode(b"hello world").is_err());
+    }
+
+    #[test]
+    fn test_encode_rejects_mismatched_dimensions() {
+        let image = Image {
+            width: 10,
+            height: 10,
+            bytes: vec![0; 4],
+        };
+        assert!(encode_png(&image).is_err());
+    }
+}
@@ -0,0 +1,69 @@
+//! Boring Clipboard - remote-aware clipboard access.
+//!
+//! Copies to the local system clipboard via `arboard`, or to the terminal's
+//! clipboard via OSC 52 escape sequences when running in a remote session.
+//!
+//! ```no_run
+//! use bc::{ArboardBackend, ClipboardBackend, Osc52Backend};
+//!
+//! let backend: Box<dyn ClipboardBackend> = if bc::clipboard::is_remote_session() {
+//!     Box::new(Osc52Backend::new())
+//! } else {
+//!     Box::new(ArboardBackend::new())
+//! };
+//! backend.copy("Hello").unwrap();
+//! ```
+
+pub mod ansi;
+pub mod backends;
+pub mod binary;
+pub mod clean_url;
+pub mod clipboard;
+pub mod config;
+pub mod crypto;
+pub mod detect;
+pub mod diff;
+pub mod doctor;
+pub mod edit;
+pub mod encoding;
+pub mod error;
+pub mod expire;
+pub mod fields;
+pub mod gpg;
+pub mod hash;
+pub mod history;
+pub mod hooks;
+pub mod html;
+pub mod image;
+pub mod lock;
+pub mod log;
+pub mod ocr;
+pub mod osc52;
+pub mod paths;
+pub mod peers;
+pub mod pick;
+pub mod preview;
+pub mod probe;
+pub mod progress;
+pub mod qr;
+pub mod redact;
+pub mod registers;
+pub mod render;
+pub mod rules;
+pub mod run;
+pub mod secrets;
+pub mod selection;
+pub mod server;
+pub mod shot;
+pub mod snippets;
+pub mod stat;
+pub mod sync;
+pub mod template;
+mod terminal;
+pub mod transform;
+pub mod typing;
+pub mod unicode;
+pub mod watch;
+
+pub use backends::{ArboardBackend, ClipboardBackend, Osc52Backend};
+pub use selection::Selection;
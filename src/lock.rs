@@ -0,0 +1,129 @@
+//! Advisory locks for files that several bc processes update at once (for
+//! example parallel `make` jobs each piping into `bc`).
+//!
+//! The lock is taken on a `.lock` file next to the protected one, so the
+//! protected file can still be replaced by renaming over it. Only bc
+//! honours the lock; readers don't take it.
+
+use anyhow::{Context, Result};
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::error::{CodedError, ErrorCode};
+use crate::verbose;
+
+/// How long to wait for another process to release a lock by default
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+/// How often a busy lock is retried
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// An exclusive lock on a file, released when dropped
+#[derive(Debug)]
+pub struct FileLock {
+    // Closing the file releases the lock
+    _file: File,
+}
+
+impl FileLock {
+    /// Lock `path` (through `<path>.lock`), creating its directory if
+    /// needed. Waits up to `timeout` for another holder to let go, then
+    /// fails with [`ErrorCode::Locked`].
+    pub fn acquire(path: &Path, timeout: Duration) -> Result<Self> {
+        let lock_path = lock_path(path);
+        if let Some(dir) = lock_path.parent() {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        let mut options = OpenOptions::new();
+        options.create(true).truncate(false).write(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let file = options
+            .open(&lock_path)
+            .with_context(|| format!("Failed to open lock {}", lock_path.display()))?;
+
+        let deadline = Instant::now() + timeout;
+        let mut waiting = false;
+        loop {
+            match file.try_lock() {
+                Ok(()) => return Ok(Self { _file: file }),
+                Err(TryLockError::WouldBlock) if Instant::now() < deadline => {
+                    if !waiting {
+                        verbose!("Waiting for another bc to release {}", path.display());
+                        waiting = true;
+                    }
+                    thread::sleep(POLL_INTERVAL);
+                }
+                Err(TryLockError::WouldBlock) => {
+                    let message =
+                        format!("Another bc held {} for over {:?}", path.display(), timeout);
+                    return Err(CodedError::new(ErrorCode::Locked, message)
+                        .with("path", path.display().to_string())
+                        .into());
+                }
+                Err(TryLockError::Error(e)) => {
+                    return Err(e)
+                        .with_context(|| format!("Failed to lock {}", lock_path.display()))
+                }
+            }
+        }
+    }
+}
+
+/// The lock file guarding `path`: `history.jsonl` is guarded by
+/// `history.jsonl.lock`
+fn lock_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(".lock");
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::classify;
+
+    #[test]
+    fn test_lock_path() {
+        assert_eq!(
+            lock_path(Path::new("/data/history.jsonl")),
+            Path::new("/data/history.jsonl.lock")
+        );
+    }
+
+    #[test]
+    fn test_acquire_waits_then_times_out() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested").join("store");
+        let held = FileLock::acquire(&path, DEFAULT_TIMEOUT).unwrap();
+
+        let start = Instant::now();
+        let err = FileLock::acquire(&path, Duration::from_millis(50)).unwrap_err();
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        let (code, fields) = classify(&err, ErrorCode::General);
+        assert_eq!(code, ErrorCode::Locked);
+        assert_eq!(fields["path"], path.display().to_string());
+
+        // Released on drop
+        drop(held);
+        FileLock::acquire(&path, Duration::ZERO).unwrap();
+    }
+
+    #[test]
+    fn test_acquire_gets_lock_once_released() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("store");
+        let held = FileLock::acquire(&path, DEFAULT_TIMEOUT).unwrap();
+        let releaser = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            drop(held);
+        });
+        FileLock::acquire(&path, DEFAULT_TIMEOUT).unwrap();
+        releaser.join().unwrap();
+    }
+}
@@ -0,0 +1,101 @@
+//! Diagnostics on stderr, filtered by a process-wide verbosity level
+//! (`-q`, `-v`, `-vv`).
+//!
+//! Use the `message!` macro for what bc always printed (errors, warnings,
+//! confirmations), `verbose!` for decisions such as the chosen backend, and
+//! `debug!` for every attempt and detail.
+
+use std::fmt;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// How much bc reports on stderr
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    /// Nothing; only the exit code tells what happened (`-q`)
+    Quiet,
+    /// Errors, warnings, and confirmations
+    #[default]
+    Normal,
+    /// Backend choice, detection results, sizes, and timing (`-v`)
+    Verbose,
+    /// Every backend attempt and its outcome (`-vv`)
+    Debug,
+}
+
+impl Level {
+    /// Level for `-q` and the number of `-v` flags
+    pub fn from_flags(quiet: bool, verbose: u8) -> Self {
+        match (quiet, verbose) {
+            (true, _) => Level::Quiet,
+            (false, 0) => Level::Normal,
+            (false, 1) => Level::Verbose,
+            (false, _) => Level::Debug,
+        }
+    }
+}
+
+static LEVEL: AtomicU8 = AtomicU8::new(Level::Normal as u8);
+
+/// Set the process-wide level
+pub fn set_level(level: Level) {
+    LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+/// Whether messages at `level` are printed
+pub fn enabled(level: Level) -> bool {
+    level != Level::Quiet && level as u8 <= LEVEL.load(Ordering::Relaxed)
+}
+
+/// Print a message at `level`; used by the macros
+pub fn emit(level: Level, args: fmt::Arguments) {
+    if enabled(level) {
+        match level {
+            Level::Verbose | Level::Debug => eprintln!("bc: {}", args),
+            Level::Quiet | Level::Normal => eprintln!("{}", args),
+        }
+    }
+}
+
+/// Print to stderr unless `-q` was given
+#[macro_export]
+macro_rules! message {
+    ($($arg:tt)*) => {
+        $crate::log::emit($crate::log::Level::Normal, format_args!($($arg)*))
+    };
+}
+
+/// Print to stderr with `-v` or more
+#[macro_export]
+macro_rules! verbose {
+    ($($arg:tt)*) => {
+        $crate::log::emit($crate::log::Level::Verbose, format_args!($($arg)*))
+    };
+}
+
+/// Print to stderr with `-vv`
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        $crate::log::emit($crate::log::Level::Debug, format_args!($($arg)*))
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_level_from_flags() {
+        assert_eq!(Level::from_flags(false, 0), Level::Normal);
+        assert_eq!(Level::from_flags(false, 1), Level::Verbose);
+        assert_eq!(Level::from_flags(false, 3), Level::Debug);
+        assert_eq!(Level::from_flags(true, 2), Level::Quiet);
+    }
+
+    #[test]
+    fn test_levels_are_ordered() {
+        assert!(Level::Quiet < Level::Normal);
+        assert!(Level::Normal < Level::Verbose);
+        assert!(Level::Verbose < Level::Debug);
+    }
+}
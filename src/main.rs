@@ -1,194 +1,2531 @@
-mod clipboard;
-mod osc52;
-mod terminal;
+use anyhow::{Context, Result};
+use is_terminal::IsTerminal;
+use serde_json::{Map, Value};
+use std::fmt;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
+
+use bc::backends::{BackendKind, Part};
+use bc::clean_url::Cleaner;
+use bc::clipboard::{self, Mode};
+use bc::config::{Config, HooksConfig};
+use bc::crypto::Key;
+use bc::error::{BcExitCode, CodedError, ErrorCode};
+use bc::fields::Fields;
+use bc::history::{format_age, Entry, History};
+use bc::hooks::Hook;
+use bc::log::Level;
+use bc::osc52;
+use bc::paths::{write_atomic, FileMode};
+use bc::preview::{self, escape_control_chars};
+use bc::progress::Progress;
+use bc::registers::Registers;
+use bc::rules::{Rules, State};
+use bc::snippets::Snippets;
+use bc::transform::{Pipeline, Transform};
+use bc::watch::{Filter, Watcher};
+use bc::{debug, message, verbose};
+
+mod cli;
+
+use cli::{
+    BridgeArgs, ClearArgs, Cli, Command, CopyArgs, DiffArgs, DoctorArgs, ExpireArgs, ExportArgs,
+    ExportFormat, HashArgs, HistoryAction, HistoryArgs, ImportArgs, InputArgs, KeygenArgs, Mime,
+    PartMime, PasteArgs, PeerArgs, PeersArgs, PickArgs, PipeArgs, RuleNameArgs, RulesAction,
+    RulesArgs, RunArgs, ServeArgs, Settings, ShotArgs, SnippetAction, SnippetAddArgs, SnippetArgs,
+    SyncArgs, TransformArgs, UnpinArgs, WatchArgs, WatchFormat,
+};
+
+/// The stable code and details for an error: the ones it carries, else
+/// `fallback`
+fn error_code(e: &anyhow::Error, fallback: ErrorCode) -> (ErrorCode, Map<String, Value>) {
+    if let Some(InputTooLarge(limit)) = e.downcast_ref() {
+        let mut fields = Map::new();
+        fields.insert("max".to_string(), limit.bytes.into());
+        return (ErrorCode::InputTooLarge, fields);
+    }
+    bc::error::classify(e, fallback)
+}
+
+/// Report `e` on stderr (as JSON with `--json`) and return the exit status
+/// for its code
+fn fail(e: &anyhow::Error, fallback: ErrorCode) -> ExitCode {
+    let (code, fields) = error_code(e, fallback);
+    bc::error::report(code, e, fields);
+    code.exit_code().into()
+}
+
+/// Report a failure that isn't an `anyhow::Error`
+fn fail_with(code: ErrorCode, message: impl fmt::Display) -> ExitCode {
+    bc::error::report(code, message, Map::new());
+    code.exit_code().into()
+}
+
+/// Report arguments clap rejected as a usage error; `--help` and
+/// `--version` print as clap does. `-q` and `--json` are looked for in the
+/// raw arguments, since the parse that sets them failed.
+fn fail_parse(e: clap::Error) -> ExitCode {
+    if matches!(
+        e.kind(),
+        clap::error::ErrorKind::DisplayHelp
+            | clap::error::ErrorKind::DisplayVersion
+            | clap::error::ErrorKind::DisplayHelpOnMissingArgumentOrSubcommand
+    ) {
+        e.exit();
+    }
+    let (quiet, json) = cli::error_flags(std::env::args_os().skip(1));
+    bc::log::set_level(Level::from_flags(quiet, 0));
+    bc::error::set_json(json);
+    let rendered = e.render().to_string();
+    // JSON gets the error alone, without clap's usage and tips
+    let message = match json {
+        true => rendered.lines().next().unwrap_or_default(),
+        false => rendered.trim_end(),
+    };
+    fail_with(
+        ErrorCode::Usage,
+        message.strip_prefix("error: ").unwrap_or(message),
+    )
+}
+
+/// How much input a copy reads before giving up, and where that limit
+/// comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct InputLimit {
+    bytes: usize,
+    origin: &'static str,
+}
+
+impl InputLimit {
+    /// `--max-size`, else `[limits] max_size` from the config, else the most
+    /// a single OSC 52 sequence carries if the copy goes there (`osc52`)
+    fn for_copy(input: &InputArgs, config: &Config, osc52: bool) -> Option<Self> {
+        if let Some(bytes) = input.max_size {
+            Some(Self {
+                bytes,
+                origin: "--max-size",
+            })
+        } else if let Some(bytes) = config.limits.max_size {
+            Some(Self {
+                bytes,
+                origin: "max_size in the config file",
+            })
+        } else if osc52 {
+            Some(Self {
+                bytes: osc52::OSC52_MAX_INPUT,
+                origin: "the OSC 52 limit; --chunked lifts it in kitty and mosh",
+            })
+        } else {
+            None
+        }
+    }
+}
+
+/// Whether copies go to the terminal as a single OSC 52 sequence, which
+/// limits their size
+fn copies_via_osc52(settings: &Settings) -> bool {
+    let osc52 = matches!(
+        settings.options().resolved_mode(),
+        Mode::Remote | Mode::Backend(BackendKind::Osc52)
+    );
+    osc52 && !(settings.chunked && osc52::supports_chunked_writes())
+}
+
+/// Input that went past its `InputLimit`
+#[derive(Debug)]
+struct InputTooLarge(InputLimit);
+
+impl fmt::Display for InputTooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Input exceeds {} bytes ({})",
+            self.0.bytes, self.0.origin
+        )
+    }
+}
+
+impl std::error::Error for InputTooLarge {}
+
+const PREVIEW_LENGTH: usize = 50;
+/// Preview length for history and register listings
+const LISTING_PREVIEW_LENGTH: usize = 60;
+/// Allowed control characters in text input
+const ALLOWED_CONTROL_CHARS: [char; 4] = ['\n', '\r', '\t', '\x0c'];
+
+fn main() -> ExitCode {
+    let cli = match Cli::parse_with_env() {
+        Ok(cli) => cli,
+        Err(e) => return fail_parse(e),
+    };
+    let (command, mut settings) = cli.into_command();
+    bc::log::set_level(Level::from_flags(settings.quiet, settings.verbose));
+    bc::error::set_json(settings.json);
+    let started = Instant::now();
+
+    if settings.mode_choices() > 1 {
+        return fail_with(
+            ErrorCode::Usage,
+            "--local, --force-remote, --osc52, and --backend are mutually exclusive",
+        );
+    }
+    match Config::load() {
+        Ok(config) => settings.remote_config = config.remote,
+        Err(e) => message!("Warning: {}", e),
+    }
+    if settings.why {
+        message!("{}", clipboard::explain(&settings.options()));
+    }
+    prune_history(&settings);
+
+    let code = match command {
+        Command::Copy(args) => handle_copy(&settings, &args),
+        Command::Paste(
+            ref args @ PasteArgs {
+                register: Some(ref name),
+                ..
+            },
+        ) => handle_register_paste(name, args),
+        Command::Paste(
+            ref args @ PasteArgs {
+                pinned: Some(ref name),
+                ..
+            },
+        ) => handle_pinned_paste(&settings, name, args),
+        Command::Paste(PasteArgs {
+            list_formats: true, ..
+        }) => handle_list_formats(&settings),
+        Command::Paste(
+            ref args @ PasteArgs {
+                format: Some(ref mime),
+                image: false,
+                ..
+            },
+        ) => handle_paste_format(&settings, mime, args),
+        Command::Paste(ref args @ PasteArgs { ocr: true, .. }) => handle_paste_ocr(&settings, args),
+        Command::Paste(ref args @ PasteArgs { image: true, .. }) => {
+            handle_paste_image(&settings, args)
+        }
+        Command::Paste(ref args @ PasteArgs { binary: true, .. }) => {
+            handle_paste_binary(&settings, args)
+        }
+        Command::Paste(
+            ref args @ PasteArgs {
+                mime: Mime::Html, ..
+            },
+        ) => handle_paste_html(&settings, args),
+        Command::Paste(ref args @ PasteArgs { plain: true, .. }) => {
+            handle_paste_plain(&settings, args)
+        }
+        Command::Paste(ref args) => handle_paste(&settings, args),
+        Command::Clear(ClearArgs {
+            register: Some(name),
+        }) => handle_register_delete(&name),
+        Command::Clear(_) => handle_clear(&settings),
+        Command::History(args) => handle_history(&settings, &args),
+        Command::Pick(args) => handle_pick(&settings, &args),
+        Command::Watch(args) => handle_watch(&settings, &args),
+        Command::Run(args) => handle_run(&settings, &args),
+        Command::Pipe(args) => handle_pipe(&settings, &args),
+        Command::Edit => handle_edit(&settings),
+        Command::Stat => handle_stat(&settings),
+        Command::Diff(args) => handle_diff(&settings, &args),
+        Command::Hash(args) => handle_hash(&settings, &args),
+        Command::Registers => handle_list_registers(),
+        Command::Snippet(args) => handle_snippet(&settings, &args),
+        Command::Rules(args) => handle_rules(&args),
+        Command::Shot(args) => handle_shot(&settings, &args),
+        Command::Bridge(args) => handle_bridge(&settings, &args),
+        Command::Sync(args) => handle_sync(&settings, &args),
+        Command::Serve(args) => handle_serve(&settings, &args),
+        Command::Peers(args) => handle_peers(&args),
+        Command::Push(args) => handle_push(&settings, &args),
+        Command::Pull(args) => handle_pull(&settings, &args),
+        Command::Keygen(args) => handle_keygen(&args),
+        Command::Expire(args) => handle_expire(&settings, &args),
+        Command::Hold => handle_hold(&settings),
+        Command::Probe => handle_probe(&settings),
+        Command::ExitCodes => handle_list_exit_codes(&settings),
+        Command::Doctor(args) => handle_doctor(&args, &settings),
+        Command::Completions { shell } => handle_completions(shell),
+    };
+    verbose!("Finished in {:.1?}", started.elapsed());
+    code
+}
+
+/// Handle watch mode: stream clipboard changes to stdout until interrupted
+fn handle_watch(settings: &Settings, args: &WatchArgs) -> ExitCode {
+    let filter = match Filter::new(&args.matches, &args.ignores) {
+        Ok(filter) => filter,
+        Err(e) => return fail(&e, ErrorCode::Usage),
+    };
+    let mut rules = if args.apply_rules {
+        match Rules::load() {
+            Ok(rules) if rules.is_empty() => {
+                return fail_with(
+                    ErrorCode::Usage,
+                    "No rules to apply (add [rules.NAME] tables to the config; see bc rules)",
+                );
+            }
+            Ok(rules) => Some(rules),
+            // A bad rule carries invalid_input; an unreadable file is general
+            Err(e) => return fail(&e, ErrorCode::General),
+        }
+    } else {
+        None
+    };
+    let backend = clipboard::polling_backend(&settings.options());
+    if let Err(e) = backend.check() {
+        return fail(&e, ErrorCode::ClipboardUnavailable);
+    }
+
+    let mut watcher = Watcher::new(backend.as_ref());
+    let mut stdout = io::stdout();
+    loop {
+        let mut text = watcher.next_change();
+        if let Some(rules) = &mut rules {
+            // Reload so bc rules enable|disable takes effect right away
+            match Rules::load() {
+                Ok(latest) => *rules = latest,
+                Err(e) => message!("Warning: Keeping the previous rules: {:#}", e),
+            }
+            text = apply_rules(settings, rules, &mut watcher, text);
+        }
+        if !filter.allows(&text) {
+            verbose!("Skipping a change filtered out by --match/--ignore");
+            continue;
+        }
+        if let Some(command) = &args.exec {
+            // A failing command doesn't stop the watch
+            if let Err(e) = bc::watch::exec(command, &text, args.exec_stdin) {
+                message!("Warning: {:#}", e);
+            }
+            if args.once {
+                break;
+            }
+            continue;
+        }
+        let event = format_watch_event(args.format, &text);
+        // Stop quietly once the reading end of the pipe goes away
+        if writeln!(stdout, "{}", event)
+            .and_then(|_| stdout.flush())
+            .is_err()
+        {
+            break;
+        }
+        if args.once {
+            break;
+        }
+    }
+
+    BcExitCode::Success.into()
+}
+
+/// Rewrite a clipboard change with the enabled rules that match, copying
+/// the result back. Returns what the clipboard holds afterwards.
+fn apply_rules(settings: &Settings, rules: &Rules, watcher: &mut Watcher, text: String) -> String {
+    let rewritten = match rules.apply(&text) {
+        Ok((rewritten, applied)) if rewritten != text => {
+            verbose!("Applying rules: {}", applied.join(", "));
+            rewritten
+        }
+        Ok(_) => return text,
+        Err(e) => {
+            message!("Warning: {:#}", e);
+            return text;
+        }
+    };
+    match clipboard::copy(&rewritten, &settings.options()) {
+        Ok(_) => {
+            // Our own copy isn't a new change
+            watcher.expect(&rewritten);
+            record_history(settings, &rewritten);
+            rewritten
+        }
+        Err(e) => {
+            message!("Warning: Failed to copy the rewritten content: {:#}", e);
+            text
+        }
+    }
+}
+
+fn format_watch_event(format: WatchFormat, text: &str) -> String {
+    match format {
+        WatchFormat::Json => {
+            let kind = bc::detect::detect(text);
+            serde_json::json!({
+                "timestamp": bc::history::now(),
+                "content": text,
+                "type": kind.name(),
+                "language": kind.language(),
+            })
+        }
+        .to_string(),
+        WatchFormat::Raw => text.to_string(),
+    }
+}
+
+/// Handle paste operation
+fn handle_paste(settings: &Settings, args: &PasteArgs) -> ExitCode {
+    match clipboard::paste(&settings.options()) {
+        Ok(text) if text.is_empty() => fail_with(ErrorCode::ClipboardEmpty, "Clipboard is empty"),
+        Ok(text) => {
+            let code = write_text_paste(args, &text);
+            let hooks = Config::load().unwrap_or_default().hooks;
+            if let Some(command) = Hook::PostPaste.command(&hooks) {
+                if code == ExitCode::SUCCESS {
+                    run_post_hook(
+                        Hook::PostPaste,
+                        command,
+                        &text,
+                        &hook_env(settings, &text, None),
+                    );
+                }
+            }
+            code
+        }
+        Err(e) => fail(&e, ErrorCode::ClipboardUnavailable),
+    }
+}
+
+/// Details passed to hooks: `BC_BYTES`, `BC_SELECTION`, and `BC_BACKEND`
+/// once a copy knows it
+fn hook_env(
+    settings: &Settings,
+    content: &str,
+    backend: Option<&str>,
+) -> Vec<(&'static str, String)> {
+    let mut env = vec![
+        ("BC_BYTES", content.len().to_string()),
+        ("BC_SELECTION", settings.selection.to_string()),
+    ];
+    if let Some(backend) = backend {
+        env.push(("BC_BACKEND", backend.to_string()));
+    }
+    env
+}
+
+/// Run a post hook; failures only warn since the copy or paste succeeded
+fn run_post_hook(hook: Hook, command: &str, content: &str, env: &[(&str, String)]) {
+    if let Err(e) = bc::hooks::post(hook, command, content, env) {
+        message!("Warning: {:#}", e);
+    }
+}
+
+/// Write pasted text, first decrypting it with gpg for --decrypt, then
+/// applying the transform flags, and drawn as a QR code for --qr
+fn write_text_paste(args: &PasteArgs, text: &str) -> ExitCode {
+    let text = if args.decrypt {
+        match bc::gpg::decrypt(text) {
+            Ok(plain) => plain,
+            Err(e) => return fail(&e, ErrorCode::General),
+        }
+    } else {
+        text.to_string()
+    };
+    let result = transform_pipeline(&args.transform, Vec::new())
+        .and_then(|pipeline| pipeline.apply(text))
+        .and_then(|text| {
+            if args.qr {
+                bc::qr::render(&text)
+            } else {
+                Ok(text)
+            }
+        });
+    match result {
+        Ok(text) => write_paste(args, text.as_bytes(), true),
+        Err(e) => fail(&e, ErrorCode::General),
+    }
+}
+
+/// Handle --plain paste: the clipboard's HTML form rendered as readable
+/// text, falling back to its plain text when there is no HTML
+fn handle_paste_plain(settings: &Settings, args: &PasteArgs) -> ExitCode {
+    match clipboard::paste_html(&settings.options()) {
+        Ok(html) if !html.trim().is_empty() => {
+            write_text_paste(args, &bc::html::to_readable_text(&html))
+        }
+        Ok(_) => handle_paste(settings, args),
+        Err(e) => {
+            debug!("No HTML to render ({}); pasting the plain text", e);
+            handle_paste(settings, args)
+        }
+    }
+}
+
+/// Handle HTML paste: print the clipboard's HTML representation
+fn handle_paste_html(settings: &Settings, args: &PasteArgs) -> ExitCode {
+    match clipboard::paste_html(&settings.options()) {
+        Ok(html) => write_paste(args, html.as_bytes(), true),
+        Err(e) => fail(&e, ErrorCode::ClipboardUnavailable),
+    }
+}
+
+/// Handle --list-formats: print each MIME type the clipboard offers
+fn handle_list_formats(settings: &Settings) -> ExitCode {
+    match clipboard::formats(&settings.options()) {
+        Ok(formats) if formats.is_empty() => {
+            fail_with(ErrorCode::ClipboardEmpty, "Clipboard is empty")
+        }
+        Ok(formats) => {
+            for format in formats {
+                println!("{}", format);
+            }
+            BcExitCode::Success.into()
+        }
+        Err(e) => fail(&e, ErrorCode::ClipboardUnavailable),
+    }
+}
+
+/// Handle --format paste: write one clipboard format as it is. Only text
+/// types may go to a terminal.
+fn handle_paste_format(settings: &Settings, mime: &str, args: &PasteArgs) -> ExitCode {
+    if args.transform != TransformArgs::default() {
+        return fail_with(
+            ErrorCode::Usage,
+            "Transforms don't apply to --format pastes",
+        );
+    }
+    if !mime.starts_with("text/") && args.output.is_none() && io::stdout().is_terminal() {
+        return fail_with(
+            ErrorCode::TerminalOutput,
+            format_args!(
+                "Refusing to write {} data to a terminal (redirect stdout or use -o)",
+                mime
+            ),
+        );
+    }
+
+    match clipboard::paste_format(mime, &settings.options()) {
+        Ok(data) => {
+            verbose!("Clipboard {}: {} bytes", mime, data.len());
+            write_paste(args, &data, false)
+        }
+        Err(e) => fail(&e, ErrorCode::ClipboardUnavailable),
+    }
+}
+
+/// Handle --ocr paste: the text tesseract recognizes in the clipboard image,
+/// written like a text paste
+fn handle_paste_ocr(settings: &Settings, args: &PasteArgs) -> ExitCode {
+    let image = match clipboard::paste_image(&settings.options()) {
+        Ok(image) => image,
+        Err(e) => return fail(&e, ErrorCode::ClipboardUnavailable),
+    };
+    verbose!(
+        "Recognizing text in a {}x{} image",
+        image.width,
+        image.height
+    );
+    let text = bc::image::encode_png(&image)
+        .and_then(|png| bc::ocr::recognize(&png, args.ocr_lang.as_deref()));
+    match text {
+        Ok(text) if text.is_empty() => {
+            fail_with(ErrorCode::NoText, "No text found in the clipboard image")
+        }
+        Ok(text) => write_text_paste(args, &text),
+        Err(e) => fail(&e, ErrorCode::General),
+    }
+}
+
+/// Handle binary paste: decode data copied with `--binary` to stdout
+fn handle_paste_binary(settings: &Settings, args: &PasteArgs) -> ExitCode {
+    if args.output.is_none() && io::stdout().is_terminal() {
+        return fail_with(
+            ErrorCode::TerminalOutput,
+            "Refusing to write binary data to a terminal (redirect stdout or use -o)",
+        );
+    }
+
+    match clipboard::paste(&settings.options()).and_then(|text| bc::binary::decode(&text)) {
+        Ok((mime, data)) => {
+            verbose!("Binary clipboard content: {}, {} bytes", mime, data.len());
+            write_paste(args, &data, false)
+        }
+        Err(e) => fail(&e, ErrorCode::ClipboardUnavailable),
+    }
+}
+
+/// Handle image paste: write the clipboard image to stdout as PNG, or as
+/// JPEG with --format jpeg, first scaled down for --resize
+fn handle_paste_image(settings: &Settings, args: &PasteArgs) -> ExitCode {
+    if args.output.is_none() && io::stdout().is_terminal() {
+        return fail_with(
+            ErrorCode::TerminalOutput,
+            "Refusing to write image data to a terminal (redirect stdout or use -o)",
+        );
+    }
+
+    let jpeg = match args.format.as_deref() {
+        None | Some("png" | "image/png") => false,
+        Some("jpeg" | "jpg" | "image/jpeg") => true,
+        Some(format) => {
+            return fail_with(
+                ErrorCode::Usage,
+                format_args!("Unsupported image format '{}' (use png or jpeg)", format),
+            );
+        }
+    };
+    if args.quality.is_some() && !jpeg {
+        return fail_with(ErrorCode::Usage, "--quality only applies to --format jpeg");
+    }
+
+    let encoded = clipboard::paste_image(&settings.options()).and_then(|image| {
+        let image = match args.resize {
+            Some(size) => {
+                let resized = bc::image::resize(&image, size)?;
+                verbose!(
+                    "Resized {}x{} to {}x{}",
+                    image.width,
+                    image.height,
+                    resized.width,
+                    resized.height
+                );
+                resized
+            }
+            None => image,
+        };
+        if jpeg {
+            let quality = args.quality.unwrap_or(bc::image::DEFAULT_JPEG_QUALITY);
+            bc::image::encode_jpeg(&image, quality)
+        } else {
+            bc::image::encode_png(&image)
+        }
+    });
+    match encoded {
+        Ok(data) => write_paste(args, &data, false),
+        Err(e) => fail(&e, ErrorCode::ClipboardUnavailable),
+    }
+}
+
+/// Write pasted content to `-o FILE`, or to stdout (where text gets a
+/// trailing newline, CRLF with --crlf); with --type, type it as it is.
+/// `--repeat` copies are written one at a time, never joined in memory.
+fn write_paste(args: &PasteArgs, data: &[u8], text: bool) -> ExitCode {
+    if args.type_keys {
+        let delay = args
+            .delay_ms
+            .map_or(bc::typing::DEFAULT_DELAY, Duration::from_millis);
+        return match bc::typing::type_text(&String::from_utf8_lossy(data), delay) {
+            Ok(()) => BcExitCode::Success.into(),
+            Err(e) => fail(&e, ErrorCode::General),
+        };
+    }
+    let write = |out: &mut dyn Write| write_repeated(out, data, args);
+    let result = match &args.output {
+        Some(path) if args.append_file => append_file(path, write),
+        Some(path) => write_atomic(path, FileMode::Keep, write),
+        None => {
+            let mut stdout = io::BufWriter::new(io::stdout().lock());
+            let result = write(&mut stdout)
+                .and_then(|_| {
+                    if text {
+                        let newline: &[u8] = if args.transform.crlf { b"\r\n" } else { b"\n" };
+                        stdout.write_all(newline)
+                    } else {
+                        Ok(())
+                    }
+                })
+                .and_then(|_| stdout.flush());
+            // Stop quietly once the reading end of the pipe goes away
+            match result {
+                Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+                result => result,
+            }
+        }
+    };
+    match result {
+        Ok(()) => BcExitCode::Success.into(),
+        Err(e) => {
+            let target = match &args.output {
+                Some(path) => path.display().to_string(),
+                None => "stdout".to_string(),
+            };
+            fail_with(
+                ErrorCode::General,
+                format_args!("Failed to write {}: {}", target, e),
+            )
+        }
+    }
+}
+
+/// `data`, or `--repeat N` copies of it with `--separator` (default: a
+/// newline) between them
+fn write_repeated(out: &mut dyn Write, data: &[u8], args: &PasteArgs) -> io::Result<()> {
+    let separator = args.separator.as_deref().unwrap_or("\n");
+    for i in 0..args.repeat.unwrap_or(1) {
+        if i > 0 {
+            out.write_all(separator.as_bytes())?;
+        }
+        out.write_all(data)?;
+    }
+    Ok(())
+}
+
+/// Add what `write` writes to the end of `path`, creating it if needed
+fn append_file(
+    path: &Path,
+    write: impl FnOnce(&mut dyn Write) -> io::Result<()>,
+) -> io::Result<()> {
+    let file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    let mut file = io::BufWriter::new(file);
+    write(&mut file)?;
+    file.flush()
+}
+
+/// Handle bridge: move the clipboard between the local backends and the
+/// terminal (OSC 52) in one step
+fn handle_bridge(settings: &Settings, args: &BridgeArgs) -> ExitCode {
+    let options = settings.options();
+    let local = clipboard::Options {
+        mode: match options.mode {
+            // An explicit non-OSC 52 backend stands in for "local"
+            Mode::Backend(kind) if kind != BackendKind::Osc52 => options.mode,
+            _ => Mode::Local,
+        },
+        ..options.clone()
+    };
+    let terminal = clipboard::Options {
+        mode: Mode::Backend(BackendKind::Osc52),
+        ..options
+    };
+    let (from, to) = if args.reverse {
+        (&terminal, &local)
+    } else {
+        (&local, &terminal)
+    };
+
+    let text = match clipboard::paste(from) {
+        Ok(text) if text.is_empty() => {
+            return fail_with(ErrorCode::ClipboardEmpty, "Clipboard is empty");
+        }
+        Ok(text) => text,
+        Err(e) => return fail(&e, ErrorCode::ClipboardUnavailable),
+    };
+
+    match clipboard::copy(&text, to) {
+        Ok(backend) => {
+            verbose!("Bridged {} bytes to {}", text.len(), backend);
+            if settings.preview {
+                show_preview(settings, &text);
+            }
+            BcExitCode::Success.into()
+        }
+        Err(e) => fail(&e, ErrorCode::General),
+    }
+}
+
+/// Handle sync: connect to `bc sync --serve` on the remote host (or be
+/// that end) and sync clipboards until the connection closes
+fn handle_sync(settings: &Settings, args: &SyncArgs) -> ExitCode {
+    let mut options = settings.options();
+    if args.serve && options.mode == Mode::Auto {
+        // Sync with this host's own clipboard; OSC 52 has no terminal here
+        options.mode = Mode::Local;
+    }
+    let backends = || clipboard::polling_backend(&options);
+
+    let result = settings.key().and_then(|key| match &args.remote {
+        Some(host) if !args.serve => {
+            // The remote end reads its own copy of the key, by default at the same path
+            let remote_key = args
+                .remote_key
+                .clone()
+                .or_else(|| settings.key.as_ref().map(|p| p.display().to_string()));
+            sync_with(
+                host,
+                &args.remote_bc,
+                remote_key.as_deref(),
+                &backends,
+                key.as_ref(),
+            )
+        }
+        _ => bc::sync::serve(&backends, key.as_ref()),
+    });
+    match result {
+        Ok(()) => BcExitCode::Success.into(),
+        Err(e) => fail(&e, ErrorCode::General),
+    }
+}
+
+fn sync_with(
+    host: &str,
+    program: &str,
+    remote_key: Option<&str>,
+    backends: bc::sync::BackendFactory,
+    key: Option<&Key>,
+) -> Result<()> {
+    let mut child = bc::sync::spawn_remote(host, program, remote_key)?;
+    let (Some(stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+        anyhow::bail!("Failed to connect to ssh");
+    };
+    message!("Syncing the clipboard with {} (Ctrl-C to stop)", host);
+    let result = bc::sync::run(backends, key, io::BufReader::new(stdout), stdin);
+    let _ = child.kill();
+    let status = child.wait().context("Failed to wait for ssh")?;
+    match result {
+        Err(e) if !status.success() => {
+            Err(e.context(format!("ssh to {} failed ({})", host, status)))
+        }
+        result => result,
+    }
+}
+
+/// Handle serve: expose the local clipboard over HTTP until interrupted
+fn handle_serve(settings: &Settings, args: &ServeArgs) -> ExitCode {
+    let mut options = settings.options();
+    if options.mode == Mode::Auto {
+        // Serve this machine's clipboard; OSC 52 would target our own terminal
+        options.mode = Mode::Local;
+    }
+    let backend = clipboard::polling_backend(&options);
+
+    let result = (|| {
+        let token = match &args.token {
+            Some(token) => token.clone(),
+            None => {
+                let token = bc::server::generate_token()?;
+                // On stdout, so -q can't leave a server nobody can use
+                println!("Token: {}", token);
+                token
+            }
+        };
+        let config = bc::server::Config {
+            listen: args.listen.clone(),
+            token,
+            max_size: args.max_size,
+            tls: args.tls_cert.clone().zip(args.tls_key.clone()),
+            advertise: args
+                .advertise
+                .then(|| args.name.clone().unwrap_or_else(bc::peers::default_name)),
+            key: settings.key()?,
+        };
+        bc::server::run(&config, backend.as_ref())
+    })();
+    match result {
+        Ok(()) => BcExitCode::Success.into(),
+        Err(e) => fail(&e, ErrorCode::General),
+    }
+}
+
+/// List peers found over mDNS
+fn handle_peers(args: &PeersArgs) -> ExitCode {
+    match bc::peers::discover(Duration::from_millis(args.timeout)) {
+        Ok(peers) if peers.is_empty() => fail_with(
+            ErrorCode::NoPeers,
+            "No peers found (start one with: bc serve --advertise --listen 0.0.0.0:9111)",
+        ),
+        Ok(peers) => {
+            let mut stdout = io::stdout().lock();
+            for peer in &peers {
+                let _ = writeln!(stdout, "{}\t{}", peer.name, peer.url());
+            }
+            BcExitCode::Success.into()
+        }
+        Err(e) => fail(&e, ErrorCode::General),
+    }
+}
+
+/// Send the local clipboard to a peer
+fn handle_push(settings: &Settings, args: &PeerArgs) -> ExitCode {
+    let text = match clipboard::paste(&settings.options()) {
+        Ok(text) if text.is_empty() => {
+            return fail_with(ErrorCode::ClipboardEmpty, "Clipboard is empty");
+        }
+        Ok(text) => text,
+        Err(e) => return fail(&e, ErrorCode::ClipboardUnavailable),
+    };
+
+    let result =
+        bc::peers::resolve(&args.peer, Duration::from_millis(args.timeout)).and_then(|peer| {
+            let key = settings.key()?;
+            bc::peers::push(&peer, args.token.as_deref(), key.as_ref(), &text).map(|()| peer)
+        });
+    match result {
+        Ok(peer) => {
+            verbose!("Pushed {} bytes to {}", text.len(), peer.name);
+            BcExitCode::Success.into()
+        }
+        Err(e) => fail(&e, ErrorCode::General),
+    }
+}
+
+/// Run a command, pass its output through, and copy the transcript
+fn handle_run(settings: &Settings, args: &RunArgs) -> ExitCode {
+    let captured = match bc::run::capture(&args.command, &mut io::stdout()) {
+        Ok(captured) => captured,
+        Err(e) => return fail(&e, ErrorCode::General),
+    };
+    // The command's own exit code, so `bc run -- make && deploy` still works
+    let exit_code = command_exit_code(captured.status);
+    if args.on_fail && captured.status.success() {
+        verbose!("Command succeeded; nothing copied");
+        return exit_code;
+    }
+
+    let text = bc::run::transcript(&args.command, &captured);
+    // Escape codes only make sense on the terminal the output already went to
+    let text = bc::ansi::strip(&text);
+    match clipboard::copy(&text, &copy_options(settings, true)) {
+        Ok(backend) => {
+            verbose!("Copied {} bytes of output to {}", text.len(), backend);
+            record_history_from(settings, &text, &args.command[..1], None);
+            if settings.preview {
+                show_preview(settings, &text);
+            }
+            exit_code
+        }
+        Err(e) => fail(&e, ErrorCode::General),
+    }
+}
+
+/// Feed the clipboard to a command; with --replace, copy its output back
+fn handle_pipe(settings: &Settings, args: &PipeArgs) -> ExitCode {
+    let text = match clipboard::paste(&settings.options()) {
+        Ok(text) if text.is_empty() => {
+            return fail_with(ErrorCode::ClipboardEmpty, "Clipboard is empty");
+        }
+        Ok(text) => text,
+        Err(e) => return fail(&e, ErrorCode::ClipboardUnavailable),
+    };
+    let captured = match bc::run::feed(&args.command, &text, args.replace) {
+        Ok(captured) => captured,
+        Err(e) => return fail(&e, ErrorCode::General),
+    };
+    if !captured.status.success() {
+        if !args.replace {
+            return command_exit_code(captured.status);
+        }
+        // Like every error code, command_failed always exits 1; the
+        // command's own status is a detail
+        let mut fields = Map::new();
+        if let Some(status) = captured.status.code() {
+            fields.insert("status".to_string(), status.into());
+        }
+        bc::error::report(
+            ErrorCode::CommandFailed,
+            format_args!(
+                "Command failed ({}); clipboard left unchanged",
+                captured.status
+            ),
+            fields,
+        );
+        return ErrorCode::CommandFailed.exit_code().into();
+    }
+    if !args.replace {
+        return BcExitCode::Success.into();
+    }
+    if captured.output.is_empty() {
+        return fail_with(
+            ErrorCode::EmptyInput,
+            "The command printed nothing; clipboard left unchanged",
+        );
+    }
+
+    match clipboard::copy(&captured.output, &settings.options()) {
+        Ok(backend) => {
+            verbose!(
+                "Replaced the clipboard with {} bytes via {}",
+                captured.output.len(),
+                backend
+            );
+            record_history_from(settings, &captured.output, &args.command[..1], None);
+            if settings.preview {
+                show_preview(settings, &captured.output);
+            }
+            BcExitCode::Success.into()
+        }
+        Err(e) => fail(&e, ErrorCode::General),
+    }
+}
+
+/// Edit the clipboard in the user's editor and copy the saved text back
+fn handle_edit(settings: &Settings) -> ExitCode {
+    // Remote sessions need the OSC 52 query to read the clipboard
+    let options = clipboard::Options {
+        force_paste: true,
+        ..settings.options()
+    };
+    let original = match clipboard::paste(&options) {
+        Ok(text) => text,
+        Err(e) => return fail(&e, ErrorCode::ClipboardUnavailable),
+    };
+    let edited = match bc::edit::edit(&original, &bc::edit::editor()) {
+        Ok(edited) => edited,
+        Err(e) => {
+            return fail_with(
+                ErrorCode::General,
+                format_args!("{}; clipboard left unchanged", e),
+            );
+        }
+    };
+    if edited == original {
+        verbose!("No changes; clipboard left unchanged");
+        return BcExitCode::Success.into();
+    }
+    if edited.is_empty() {
+        return fail_with(
+            ErrorCode::EmptyInput,
+            "Edited text is empty; clipboard left unchanged",
+        );
+    }
+
+    match clipboard::copy(&edited, &settings.options()) {
+        Ok(backend) => {
+            verbose!("Copied {} edited bytes to {}", edited.len(), backend);
+            record_history(settings, &edited);
+            if settings.preview {
+                show_preview(settings, &edited);
+            }
+            BcExitCode::Success.into()
+        }
+        Err(e) => fail(&e, ErrorCode::General),
+    }
+}
+
+/// Describe the clipboard without printing it
+fn handle_stat(settings: &Settings) -> ExitCode {
+    let text = match clipboard::paste(&settings.options()) {
+        Ok(text) => text,
+        Err(e) => return fail(&e, ErrorCode::ClipboardUnavailable),
+    };
+    let copied_at = open_history(settings)
+        .and_then(|history| history.copied_at(&text))
+        .unwrap_or_else(|e| {
+            debug!("No copy time from the history: {}", e);
+            None
+        });
+    let stats = bc::stat::Stats::of(&text).with_copied_at(copied_at);
+    if settings.json {
+        match serde_json::to_string(&stats) {
+            Ok(json) => println!("{}", json),
+            Err(e) => return fail_with(ErrorCode::General, e),
+        }
+    } else {
+        print!("{}", stats);
+    }
+    BcExitCode::Success.into()
+}
+
+fn handle_diff(settings: &Settings, args: &DiffArgs) -> ExitCode {
+    let (name, other) = if args.file.as_os_str() == "-" {
+        let mut text = String::new();
+        match io::stdin().read_to_string(&mut text) {
+            Ok(_) => ("stdin".to_string(), text),
+            Err(e) => {
+                return fail_with(
+                    ErrorCode::InvalidInput,
+                    format_args!("Failed to read from stdin: {}", e),
+                );
+            }
+        }
+    } else {
+        match fs::read_to_string(&args.file) {
+            Ok(text) => (args.file.display().to_string(), text),
+            Err(e) => {
+                return fail_with(
+                    ErrorCode::InvalidInput,
+                    format_args!("Failed to read {}: {}", args.file.display(), e),
+                );
+            }
+        }
+    };
+    let text = match clipboard::paste(&settings.options()) {
+        Ok(text) => text,
+        Err(e) => return fail(&e, ErrorCode::ClipboardUnavailable),
+    };
+    let color = io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+    match bc::diff::unified(&text, &other, &name, args.context, color) {
+        None => {
+            verbose!("The clipboard matches {}", name);
+            BcExitCode::Success.into()
+        }
+        Some(diff) => {
+            print!("{}", diff);
+            // As with diff(1), 1 means "different" rather than an error
+            ExitCode::from(1)
+        }
+    }
+}
+
+fn handle_hash(settings: &Settings, args: &HashArgs) -> ExitCode {
+    let text = match clipboard::paste(&settings.options()) {
+        Ok(text) => text,
+        Err(e) => return fail(&e, ErrorCode::ClipboardUnavailable),
+    };
+    let digest = bc::hash::digest(text.as_bytes(), args.algo);
+    match &args.verify {
+        None => {
+            println!("{}", digest);
+            BcExitCode::Success.into()
+        }
+        Some(expected) if bc::hash::verify(text.as_bytes(), args.algo, expected) => {
+            verbose!("The clipboard's {} matches", args.algo);
+            BcExitCode::Success.into()
+        }
+        Some(_) => fail_with(
+            ErrorCode::HashMismatch,
+            format_args!("Hash mismatch: the clipboard's {} is {}", args.algo, digest),
+        ),
+    }
+}
+
+/// A child's exit code as ours; a command killed by a signal is a general error
+fn command_exit_code(status: std::process::ExitStatus) -> ExitCode {
+    match status.code() {
+        Some(code) => ExitCode::from(code as u8),
+        None => BcExitCode::GeneralError.into(),
+    }
+}
+
+/// Copy a peer's clipboard locally
+fn handle_pull(settings: &Settings, args: &PeerArgs) -> ExitCode {
+    let result = bc::peers::resolve(&args.peer, Duration::from_millis(args.timeout))
+        .and_then(|peer| bc::peers::pull(&peer, args.token.as_deref(), settings.key()?.as_ref()));
+    let text = match result {
+        Ok(text) if text.is_empty() => {
+            return fail_with(ErrorCode::EmptyInput, "The peer's clipboard is empty");
+        }
+        Ok(text) => text,
+        Err(e) => return fail(&e, ErrorCode::General),
+    };
+
+    match clipboard::copy(&text, &settings.options()) {
+        Ok(backend) => {
+            verbose!("Pulled {} bytes into {}", text.len(), backend);
+            record_history(settings, &text);
+            if settings.preview {
+                show_preview(settings, &text);
+            }
+            BcExitCode::Success.into()
+        }
+        Err(e) => fail(&e, ErrorCode::General),
+    }
+}
+
+/// Generate a key for --key
+fn handle_keygen(args: &KeygenArgs) -> ExitCode {
+    let result = Key::generate().and_then(|key| match &args.output {
+        Some(path) => {
+            key.write_new(path)?;
+            message!("Wrote a new key to {}", path.display());
+            Ok(())
+        }
+        None => {
+            println!("{}", key.encoded());
+            Ok(())
+        }
+    });
+    match result {
+        Ok(()) => BcExitCode::Success.into(),
+        Err(e) => fail(&e, ErrorCode::General),
+    }
+}
+
+/// Serve stdin as the local clipboard until something replaces it
+fn handle_hold(settings: &Settings) -> ExitCode {
+    let mut text = String::new();
+    let result = io::stdin()
+        .read_to_string(&mut text)
+        .context("Failed to read the copy to hold")
+        .and_then(|_| {
+            bc::backends::ArboardBackend::from_options(&settings.options().backend).hold(&text)
+        });
+    match result {
+        Ok(()) => {
+            verbose!("The held copy was replaced");
+            BcExitCode::Success.into()
+        }
+        Err(e) => fail(&e, ErrorCode::ClipboardUnavailable),
+    }
+}
+
+/// Wait out an --expire timeout, then clear the clipboard if the copy is
+/// still there
+fn handle_expire(settings: &Settings, args: &ExpireArgs) -> ExitCode {
+    // The copying bc writes the digest to stdin and closes it
+    let mut digest = String::new();
+    if let Err(e) = io::stdin().read_to_string(&mut digest) {
+        return fail(
+            &anyhow::Error::new(e).context("Failed to read the copy's digest"),
+            ErrorCode::General,
+        );
+    }
+    std::thread::sleep(Duration::from_millis(args.after_ms));
+    let backend = clipboard::polling_backend(&settings.options());
+    match bc::expire::clear_if_unchanged(backend.as_ref(), digest.trim()) {
+        Ok(cleared) => {
+            verbose!(
+                "Expired copy {}",
+                if cleared {
+                    "cleared"
+                } else {
+                    "already replaced"
+                }
+            );
+            BcExitCode::Success.into()
+        }
+        Err(e) => fail(&e, ErrorCode::General),
+    }
+}
+
+/// Handle clear operation
+fn handle_clear(settings: &Settings) -> ExitCode {
+    match clipboard::clear(&settings.options()) {
+        Ok(osc52_used) => {
+            if osc52_used {
+                message!("Clipboard cleared (via OSC 52)");
+            }
+            BcExitCode::Success.into()
+        }
+        Err(e) => fail(&e, ErrorCode::General),
+    }
+}
+
+/// Handle history listing and recall
+fn handle_history(settings: &Settings, args: &HistoryArgs) -> ExitCode {
+    let history = match open_history(settings) {
+        Ok(history) => history,
+        Err(e) => return fail(&e, ErrorCode::General),
+    };
+
+    if let Some(action) = &args.action {
+        return match action {
+            HistoryAction::Pin(args) => {
+                report_pin(args.index, history.pin(args.index, args.name.as_deref()))
+            }
+            HistoryAction::Unpin(args) => handle_unpin(&history, args),
+            HistoryAction::Export(args) => handle_history_export(settings, &history, args),
+            HistoryAction::Import(args) => handle_history_import(settings, &history, args),
+        };
+    }
+
+    let Some(index) = args.index else {
+        return match history.entries() {
+            Ok(entries) if entries.is_empty() => {
+                message!("History is empty");
+                BcExitCode::Success.into()
+            }
+            Ok(entries) => {
+                print_history(&entries);
+                BcExitCode::Success.into()
+            }
+            Err(e) => fail(&e, ErrorCode::General),
+        };
+    };
+
+    let entry = match history.get(index) {
+        Ok(Some(entry)) => entry,
+        Ok(None) => {
+            return fail_with(
+                ErrorCode::NotFound,
+                format_args!("History entry {} not found", index),
+            );
+        }
+        Err(e) => return fail(&e, ErrorCode::General),
+    };
+
+    recopy(settings, &entry.content, args.print)
+}
+
+/// Handle `bc history unpin`, by position or by name
+fn handle_unpin(history: &History, args: &UnpinArgs) -> ExitCode {
+    let index = match &args.name {
+        Some(name) => match history.position_of(name) {
+            Ok(Some(index)) => index,
+            Ok(None) => {
+                return fail_with(
+                    ErrorCode::NotFound,
+                    format_args!("No history entry is pinned as '{}'", name),
+                );
+            }
+            Err(e) => return fail(&e, ErrorCode::General),
+        },
+        None => args.index.unwrap_or(1),
+    };
+    report_pin(index, history.unpin(index))
+}
+
+/// Report the outcome of pinning or unpinning entry `index`
+fn report_pin(index: usize, result: Result<Option<Entry>>) -> ExitCode {
+    match result {
+        Ok(Some(entry)) => {
+            let what = if entry.pinned { "Pinned" } else { "Unpinned" };
+            verbose!("{} entry {}: {}", what, index, preview_line(&entry.content));
+            BcExitCode::Success.into()
+        }
+        Ok(None) => fail_with(
+            ErrorCode::NotFound,
+            format_args!("History entry {} not found", index),
+        ),
+        Err(e) => fail(&e, ErrorCode::General),
+    }
+}
+
+/// Handle `bc history export`
+fn handle_history_export(settings: &Settings, history: &History, args: &ExportArgs) -> ExitCode {
+    let result = history.entries().and_then(|entries| {
+        let export = match args.format {
+            ExportFormat::Json => bc::history::export_json(&entries)?,
+            ExportFormat::Csv => bc::history::export_csv(&entries)?,
+        };
+        if !args.encrypt {
+            return Ok(export);
+        }
+        let key = settings
+            .key()?
+            .context("--encrypt needs a key: pass --key FILE (see bc keygen)")?;
+        Ok(key.seal_text(&export)? + "\n")
+    });
+    match result {
+        Ok(export) => {
+            let paste = PasteArgs {
+                output: args.output.clone(),
+                ..PasteArgs::default()
+            };
+            write_paste(&paste, export.as_bytes(), false)
+        }
+        Err(e) => {
+            let (code, fields) = error_code(&e, ErrorCode::General);
+            bc::error::report(code, format_args!("{:#}", e), fields);
+            code.exit_code().into()
+        }
+    }
+}
+
+/// Handle `bc history import`
+fn handle_history_import(settings: &Settings, history: &History, args: &ImportArgs) -> ExitCode {
+    let result = read_export(&args.file).and_then(|text| {
+        let text = if bc::crypto::is_sealed(text.trim()) {
+            settings
+                .key()?
+                .context("The export is encrypted: pass the --key FILE it was made with")?
+                .open_text(text.trim())?
+        } else {
+            text
+        };
+        history.import(bc::history::parse_export(&text)?)
+    });
+    match result {
+        Ok(added) => {
+            message!("Imported {} entries", added);
+            BcExitCode::Success.into()
+        }
+        Err(e) => {
+            let (code, fields) = error_code(&e, ErrorCode::InvalidInput);
+            bc::error::report(code, format_args!("{:#}", e), fields);
+            code.exit_code().into()
+        }
+    }
+}
+
+/// The contents of `path`, or stdin for `-`
+fn read_export(path: &Path) -> Result<String> {
+    if path.as_os_str() == "-" {
+        let mut text = String::new();
+        io::stdin()
+            .read_to_string(&mut text)
+            .context("Failed to read from stdin")?;
+        return Ok(text);
+    }
+    fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))
+}
+
+/// Handle `paste --pinned NAME`
+fn handle_pinned_paste(settings: &Settings, name: &str, args: &PasteArgs) -> ExitCode {
+    match open_history(settings).and_then(|history| history.pinned(name)) {
+        Ok(Some(entry)) => write_text_paste(args, &entry.content),
+        Ok(None) => fail_with(
+            ErrorCode::NotFound,
+            format_args!("No history entry is pinned as '{}'", name),
+        ),
+        Err(e) => fail(&e, ErrorCode::General),
+    }
+}
+
+/// Copy a history entry or snippet again, or print it for `print`
+fn recopy(settings: &Settings, content: &str, print: bool) -> ExitCode {
+    if print {
+        println!("{}", content);
+        return BcExitCode::Success.into();
+    }
+
+    match clipboard::copy(content, &settings.options()) {
+        Ok(_) => {
+            record_history(settings, content);
+            if settings.preview {
+                show_preview(settings, content);
+            }
+            BcExitCode::Success.into()
+        }
+        Err(e) => fail(&e, ErrorCode::General),
+    }
+}
+
+fn handle_pick(settings: &Settings, args: &PickArgs) -> ExitCode {
+    let mut entries = match open_history(settings).and_then(|history| history.entries()) {
+        Ok(entries) => entries,
+        Err(e) => return fail(&e, ErrorCode::General),
+    };
+    if entries.is_empty() {
+        message!("History is empty");
+        return BcExitCode::Success.into();
+    }
+    entries.reverse();
+    let contents: Vec<&str> = entries.iter().map(|entry| entry.content.as_str()).collect();
+    match bc::pick::run(&contents) {
+        Ok(Some(index)) => recopy(settings, &entries[index].content, args.print),
+        Ok(None) => BcExitCode::GeneralError.into(),
+        Err(e) => fail(&e, ErrorCode::General),
+    }
+}
+
+/// Print history entries newest first, numbered for `bc history N`
+fn print_history(entries: &[Entry]) {
+    for (i, entry) in entries.iter().rev().enumerate() {
+        let pin = match (&entry.name, entry.pinned) {
+            (Some(name), true) => format!("[{}] ", name),
+            (None, true) => "[pinned] ".to_string(),
+            _ => String::new(),
+        };
+        println!(
+            "{:>4}  {:>8}  {}{}",
+            i + 1,
+            format_age(entry.timestamp),
+            pin,
+            preview_line(&entry.content)
+        );
+    }
+}
+
+/// Handle terminal capability probe
+fn handle_probe(settings: &Settings) -> ExitCode {
+    match bc::probe::run(!settings.no_passthrough) {
+        Ok(report) => {
+            println!("{}", report);
+            BcExitCode::Success.into()
+        }
+        Err(e) => fail(&e, ErrorCode::General),
+    }
+}
+
+/// Handle `--list-exit-codes`: the exit statuses, then the error codes
+/// `--json` reports and the status each exits with
+fn handle_list_exit_codes(settings: &Settings) -> ExitCode {
+    if settings.json {
+        let statuses: Vec<_> = BcExitCode::ALL
+            .iter()
+            .map(|status| {
+                serde_json::json!({
+                    "status": *status as u8,
+                    "description": status.description(),
+                })
+            })
+            .collect();
+        let codes: Vec<_> = ErrorCode::ALL
+            .iter()
+            .map(|code| {
+                serde_json::json!({
+                    "error": code.name(),
+                    "status": code.exit_code() as u8,
+                    "description": code.description(),
+                })
+            })
+            .collect();
+        println!(
+            "{}",
+            serde_json::json!({ "exit_codes": statuses, "errors": codes })
+        );
+    } else {
+        println!("Exit codes:");
+        for status in BcExitCode::ALL {
+            println!("  {}  {}", status as u8, status.description());
+        }
+        println!("\nError codes (--json):");
+        let width = ErrorCode::ALL
+            .iter()
+            .map(|code| code.name().len())
+            .max()
+            .unwrap_or(0);
+        for code in ErrorCode::ALL {
+            println!(
+                "  {:<width$}  {}  {}",
+                code.name(),
+                code.exit_code() as u8,
+                code.description(),
+                width = width
+            );
+        }
+    }
+    BcExitCode::Success.into()
+}
+
+/// Handle `bc doctor`: print each check, failing if any failed
+fn handle_doctor(args: &DoctorArgs, settings: &Settings) -> ExitCode {
+    let checks = bc::doctor::run(&settings.options(), !args.no_terminal);
+    for check in &checks {
+        println!("{}", check);
+    }
+    if bc::doctor::failed(&checks) {
+        BcExitCode::GeneralError.into()
+    } else {
+        BcExitCode::Success.into()
+    }
+}
+
+/// Handle completion script generation
+fn handle_completions(shell: clap_complete::Shell) -> ExitCode {
+    let mut stdout = io::stdout().lock();
+    match stdout
+        .write_all(&cli::completions(shell))
+        .and_then(|_| stdout.flush())
+    {
+        Ok(()) => BcExitCode::Success.into(),
+        // The reader (e.g. `head`) went away; nothing left to report
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => BcExitCode::Success.into(),
+        Err(e) => fail_with(
+            ErrorCode::General,
+            format_args!("Failed to write completions: {}", e),
+        ),
+    }
+}
+
+/// Handle register listing
+fn handle_list_registers() -> ExitCode {
+    let result = Registers::open_default().and_then(|registers| {
+        let names = registers.list()?;
+        if names.is_empty() {
+            message!("No registers");
+        }
+        let width = names.iter().map(|n| n.len()).max().unwrap_or(0);
+        for name in names {
+            let content = registers.get(&name)?.unwrap_or_default();
+            println!(
+                "{:<width$}  {}",
+                name,
+                preview_line(&content),
+                width = width
+            );
+        }
+        Ok(())
+    });
+
+    match result {
+        Ok(()) => BcExitCode::Success.into(),
+        Err(e) => fail(&e, ErrorCode::General),
+    }
+}
 
-use anyhow::{Context, Result};
-use clap::Parser;
-use is_terminal::IsTerminal;
-use std::io::{self, Read};
-use std::process::ExitCode;
+fn handle_snippet(settings: &Settings, args: &SnippetArgs) -> ExitCode {
+    let result = match &args.action {
+        Some(SnippetAction::Add(add)) => handle_snippet_add(settings, add),
+        Some(SnippetAction::List) => handle_list_snippets(),
+        None => {
+            let name = args.name.as_deref().unwrap_or_default();
+            let context = bc::snippets::Context::current(args.args.clone());
+            match Snippets::open_default().and_then(|snippets| snippets.get(name)) {
+                Ok(Some(template)) => match bc::snippets::expand(&template, &context) {
+                    Ok(text) => return recopy(settings, &text, args.print),
+                    Err(e) => return fail(&e, ErrorCode::InvalidInput),
+                },
+                Ok(None) => {
+                    return fail_with(
+                        ErrorCode::InvalidInput,
+                        format_args!("No snippet named '{}' (see bc snippet list)", name),
+                    );
+                }
+                Err(e) => Err(e),
+            }
+        }
+    };
+    match result {
+        Ok(code) => code.into(),
+        Err(e) => fail(&e, ErrorCode::General),
+    }
+}
 
-use clipboard::{clear_clipboard, copy_local, copy_remote, is_remote_session, paste_clipboard};
+/// Save stdin or a file as a snippet
+fn handle_snippet_add(settings: &Settings, args: &SnippetAddArgs) -> Result<BcExitCode> {
+    bc::snippets::validate_name(&args.name)?;
+    let input = InputArgs {
+        files: args.file.iter().cloned().collect(),
+        ..InputArgs::default()
+    };
+    let bytes = read_input_bytes(
+        &input,
+        None,
+        !settings.no_progress,
+        "Usage: bc snippet add NAME < template.txt, or bc snippet add NAME FILE",
+    )?;
+    let template = String::from_utf8(bytes).context("Snippet is not valid UTF-8")?;
+    if template.is_empty() {
+        anyhow::bail!(CodedError::new(ErrorCode::EmptyInput, "Input is empty"));
+    }
+    if Snippets::open_default()?.add(&args.name, &template)? {
+        verbose!("Replaced snippet '{}'", args.name);
+    } else {
+        verbose!("Saved snippet '{}'", args.name);
+    }
+    Ok(BcExitCode::Success)
+}
 
-/// Exit codes for different scenarios
-#[repr(i32)]
-enum BcExitCode {
-    Success = 0,
-    GeneralError = 1,
-    EmptyInput = 2,
-    ClipboardUnavailable = 3,
-    InvalidInput = 4,
+fn handle_list_snippets() -> Result<BcExitCode> {
+    let snippets = Snippets::open_default()?;
+    let names = snippets.list()?;
+    if names.is_empty() {
+        message!("No snippets");
+    }
+    let width = names.iter().map(|n| n.len()).max().unwrap_or(0);
+    for name in names {
+        let template = snippets.get(&name)?.unwrap_or_default();
+        println!(
+            "{:<width$}  {}",
+            name,
+            preview_line(&template),
+            width = width
+        );
+    }
+    Ok(BcExitCode::Success)
 }
 
-impl From<BcExitCode> for ExitCode {
-    fn from(code: BcExitCode) -> Self {
-        ExitCode::from(code as u8)
+fn handle_rules(args: &RulesArgs) -> ExitCode {
+    let result = match &args.action {
+        None | Some(RulesAction::List) => handle_list_rules(),
+        Some(RulesAction::Enable(rule)) => set_rule(rule, true),
+        Some(RulesAction::Disable(rule)) => set_rule(rule, false),
+    };
+    match result {
+        Ok(code) => code.into(),
+        Err(e) => fail(&e, ErrorCode::General),
     }
 }
 
-/// Boring Clipboard - A simple cross-platform clipboard tool
-#[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
-#[command(after_help = "\
-Examples:
-  echo \"Hello\" | bc           # Copy \"Hello\\n\"
-  echo \"Hello\" | bc -t        # Copy \"Hello\" (trim newline)
-  cat file.txt | bc           # Copy file content
-  bc -p                       # Paste clipboard content
-  bc -c                       # Clear clipboard")]
-struct Args {
-    /// Trim trailing newline from input
-    #[arg(short, long)]
-    trim: bool,
+fn handle_list_rules() -> Result<BcExitCode> {
+    let rules = Rules::load()?;
+    if rules.is_empty() {
+        message!(
+            "No rules (add [rules.NAME] tables to {})",
+            Config::default_path()?.display()
+        );
+    }
+    let width = rules.iter().map(|rule| rule.name.len()).max().unwrap_or(0);
+    for rule in rules.iter() {
+        println!(
+            "{:<width$}  {:<8}  {} -> {}",
+            rule.name,
+            if rule.enabled { "enabled" } else { "disabled" },
+            rule.pattern,
+            rule.transforms.join(", "),
+            width = width
+        );
+    }
+    Ok(BcExitCode::Success)
+}
 
-    /// Force local clipboard usage (disable remote detection)
-    #[arg(short, long)]
-    local: bool,
+/// Turn a configured rule on or off for every `watch --apply-rules`
+fn set_rule(args: &RuleNameArgs, enabled: bool) -> Result<BcExitCode> {
+    if !Config::load()?.rules.contains_key(&args.name) {
+        anyhow::bail!(CodedError::new(
+            ErrorCode::NotFound,
+            format!("No rule named '{}' (see bc rules list)", args.name)
+        ));
+    }
+    State::open_default()?.set(&args.name, enabled)?;
+    verbose!(
+        "{} rule '{}'",
+        if enabled { "Enabled" } else { "Disabled" },
+        args.name
+    );
+    Ok(BcExitCode::Success)
+}
 
-    /// Read from clipboard and print to stdout (instead of writing)
-    #[arg(short = 'p', long)]
-    paste: bool,
+/// Handle paste from a named register
+fn handle_register_paste(name: &str, args: &PasteArgs) -> ExitCode {
+    match Registers::open_default().and_then(|registers| registers.get(name)) {
+        Ok(Some(text)) if !text.is_empty() => write_text_paste(args, &text),
+        Ok(_) => fail_with(
+            ErrorCode::ClipboardEmpty,
+            format_args!("Register '{}' is empty", name),
+        ),
+        Err(e) => fail(&e, ErrorCode::General),
+    }
+}
 
-    /// Clear the clipboard
-    #[arg(short = 'c', long)]
-    clear: bool,
+/// Handle clear of a named register (deletes it)
+fn handle_register_delete(name: &str) -> ExitCode {
+    match Registers::open_default().and_then(|registers| registers.delete(name)) {
+        Ok(_) => BcExitCode::Success.into(),
+        Err(e) => fail(&e, ErrorCode::General),
+    }
+}
 
-    /// Force copy even if binary data is detected
-    #[arg(short, long)]
-    force: bool,
+/// History at the default location, sealed with the --key key if given
+fn open_history(settings: &Settings) -> Result<History> {
+    Ok(History::open_default()?.with_key(settings.key()?))
+}
 
-    /// Show preview of copied content
-    #[arg(short = 'P', long)]
-    preview: bool,
+/// Remove expired history entries (`--history-ttl`, `copy --ttl`); sealed
+/// ones too, since their times are stored in the clear
+fn prune_history(settings: &Settings) {
+    let pruned = History::open_default()
+        .map(|history| history.with_ttl(settings.history_ttl))
+        .and_then(|history| history.prune());
+    match pruned {
+        Ok(0) => {}
+        Ok(n) => verbose!("Pruned {} expired history entries", n),
+        Err(e) => message!("Warning: Failed to prune history: {}", e),
+    }
+}
 
-    /// Attempt OSC 52 clipboard query for remote paste (experimental, limited terminal support)
-    #[arg(long)]
-    force_paste: bool,
+/// Record a copy in history; failures only warn since the copy itself succeeded
+fn record_history(settings: &Settings, content: &str) {
+    record_history_from(settings, content, &[], None);
 }
 
-const PREVIEW_LENGTH: usize = 50;
-/// Allowed control characters in text input
-const ALLOWED_CONTROL_CHARS: [char; 4] = ['\n', '\r', '\t', '\x0c'];
+/// Record a copy produced by `sources` (for `[history] ignore_commands`),
+/// pruned after `ttl`, unless a `[history]` rule keeps it out
+fn record_history_from(
+    settings: &Settings,
+    content: &str,
+    sources: &[String],
+    ttl: Option<Duration>,
+) {
+    let ignore = Config::load().and_then(|config| bc::history::Ignore::new(&config.history));
+    match ignore.map(|ignore| ignore.reason(content, sources)) {
+        Ok(Some(reason)) => {
+            verbose!("Not recorded in history: {}", reason);
+            return;
+        }
+        Ok(None) => {}
+        Err(e) => {
+            // A broken rule might be the one meant to keep this out
+            message!("Warning: Not recorded in history: {}", e);
+            return;
+        }
+    }
+    if let Err(e) = open_history(settings).and_then(|history| history.record_expiring(content, ttl))
+    {
+        message!("Warning: Failed to record history: {}", e);
+    }
+}
 
-fn main() -> ExitCode {
-    let args = Args::parse();
+/// Handle copy operation
+fn handle_copy(settings: &Settings, args: &CopyArgs) -> ExitCode {
+    let result = if args.image {
+        copy_image_to_clipboard(settings, args)
+    } else if args.binary {
+        copy_binary_to_clipboard(settings, args)
+    } else if args.file_list {
+        copy_files_to_clipboard(settings, args)
+    } else if !args.part.is_empty() {
+        copy_parts_to_clipboard(settings, args)
+    } else {
+        copy_to_clipboard(settings, args)
+    };
 
-    if args.paste && args.clear {
-        eprintln!("Error: --paste and --clear are mutually exclusive");
-        return BcExitCode::GeneralError.into();
+    match result {
+        Ok(BcExitCode::Success) => BcExitCode::Success.into(),
+        Ok(code) => code.into(),
+        Err(e) => fail(&e, ErrorCode::General),
     }
+}
 
-    if args.paste {
-        return handle_paste(&args);
+fn copy_to_clipboard(settings: &Settings, args: &CopyArgs) -> Result<BcExitCode> {
+    let input = &args.input;
+    let register = args.register.as_deref();
+    let config = Config::load()?;
+    let limit = InputLimit::for_copy(
+        input,
+        &config,
+        register.is_none() && copies_via_osc52(settings),
+    );
+    // Looked up before reading, while the writer still has the pipe open
+    let reads_stdin = input.files.is_empty() || input.files.iter().any(|f| f.as_os_str() == "-");
+    let sources = match bc::history::Ignore::new(&config.history) {
+        Ok(ignore) if ignore.checks_commands() && reads_stdin && !args.private => {
+            clipboard::stdin_writers()
+        }
+        _ => Vec::new(),
+    };
+    let mut buffer = read_input(input, limit, !settings.no_progress)?;
+    // Before the transforms, so an encoding sees the text without its newline
+    if settings.trim && buffer.ends_with('\n') {
+        buffer.truncate(buffer.trim_end_matches(['\r', '\n']).len());
+    }
+    // Transforms first: stripped escape codes shouldn't trip the binary check
+    buffer = copy_pipeline(args)?.apply(buffer)?;
+    // Register copies stay inside bc, so hooks don't see them
+    let hooks = if register.is_none() {
+        config.hooks.clone()
+    } else {
+        HooksConfig::default()
+    };
+    if let Some(command) = Hook::PreCopy.command(&hooks) {
+        buffer = bc::hooks::pre_copy(command, &buffer, &hook_env(settings, &buffer, None))?;
     }
 
-    if args.clear {
-        return handle_clear(&args);
+    if contains_binary_data(&buffer) && !settings.force {
+        let message = if buffer.contains('\x1b') {
+            "Input contains terminal escape codes. Use --strip-ansi to remove them, or --force to proceed."
+        } else {
+            "Input contains binary/control characters. Use --force to proceed."
+        };
+        anyhow::bail!(CodedError::new(ErrorCode::BinaryInput, message));
     }
 
-    handle_copy(&args)
-}
+    // Encrypted copies never hold the plaintext, so there's nothing to flag
+    if !args.allow_secrets && args.encrypt.is_empty() {
+        let scanner = bc::secrets::Scanner::new(&config.secrets)?;
+        let found = scanner.scan(&buffer);
+        if !found.is_empty() {
+            anyhow::bail!(CodedError::new(
+                ErrorCode::SecretDetected,
+                format!(
+                    "Input looks like it contains a secret ({}). Use --allow-secrets to copy it anyway.",
+                    found.join(", ")
+                )
+            ));
+        }
+    }
 
-/// Handle paste operation
-fn handle_paste(args: &Args) -> ExitCode {
-    match paste_clipboard(args) {
-        Ok(text) if text.is_empty() => {
-            eprintln!("Clipboard is empty");
-            BcExitCode::ClipboardUnavailable.into()
+    if buffer.is_empty() {
+        anyhow::bail!(CodedError::new(ErrorCode::EmptyInput, "Input is empty"));
+    }
+
+    if input.append {
+        let current = match register {
+            Some(name) => Registers::open_default()?.get(name)?.unwrap_or_default(),
+            None => {
+                // Remote sessions need the OSC 52 query to read what's there
+                let options = clipboard::Options {
+                    force_paste: true,
+                    ..settings.options()
+                };
+                clipboard::paste(&options).context("Failed to read the clipboard to append to")?
+            }
+        };
+        let separator = input.append_separator.as_deref().unwrap_or("");
+        buffer = append_to(current, &buffer, separator);
+    }
+
+    if !args.encrypt.is_empty() {
+        buffer = bc::gpg::encrypt(&buffer, &args.encrypt)?;
+        verbose!("Encrypted to {}", args.encrypt.join(", "));
+    }
+
+    // Before copying, so content too large for a code isn't copied either
+    let qr = if args.qr {
+        Some(bc::qr::render(&buffer)?)
+    } else {
+        None
+    };
+
+    // The HTML for a rich copy, and its text form if not `buffer`. Rendered
+    // before a dry run returns, so a bad language or alt text fails it too.
+    let rich = if let Some(language) = &args.highlight {
+        Some((bc::render::highlight_html(&buffer, language)?, None))
+    } else if args.markdown {
+        Some((bc::render::markdown_html(&buffer), None))
+    } else if args.html || args.mime == Mime::Html {
+        let alt_text = match &args.alt_text {
+            Some(path) => fs::read_to_string(path)
+                .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path.display(), e))?,
+            None => bc::html::to_text(&buffer),
+        };
+        Some((buffer.clone(), Some(alt_text)))
+    } else {
+        None
+    };
+
+    if args.dry_run {
+        let (what, target) = match (register, &rich) {
+            (Some(name), _) => (
+                format!("{} bytes", buffer.len()),
+                format!("register '{}'", name),
+            ),
+            (None, Some((html, alt_text))) => {
+                let text = alt_text.as_deref().unwrap_or(&buffer);
+                let target = rich_copy_target(settings, true);
+                clipboard::check_copy(text, target, &settings.options())?;
+                // OSC 52 only gets the text form
+                let what = if target == BackendKind::Osc52.name() {
+                    format!("{} bytes of text", text.len())
+                } else {
+                    format!(
+                        "{} bytes of HTML with {} bytes of text",
+                        html.len(),
+                        text.len()
+                    )
+                };
+                (what, format!("the {} backend", target))
+            }
+            (None, None) => {
+                let target = clipboard::copy_target(&settings.options())?;
+                clipboard::check_copy(&buffer, target, &settings.options())?;
+                (
+                    format!("{} bytes", buffer.len()),
+                    format!("the {} backend", target),
+                )
+            }
+        };
+        report_dry_run(&what, &target);
+        return Ok(BcExitCode::Success);
+    }
+
+    if let Some(name) = register {
+        Registers::open_default()?.set(name, &buffer)?;
+    } else {
+        let options = copy_options(settings, input.tee || qr.is_some());
+        // The backend used, and the text form a paste will return if not `buffer`
+        let (backend, alt_text) = match rich {
+            Some((html, alt_text)) => {
+                let text = alt_text.as_deref().unwrap_or(&buffer);
+                (clipboard::copy_html(&html, text, &options)?, alt_text)
+            }
+            None => (clipboard::copy(&buffer, &options)?, None),
+        };
+        if args.private {
+            verbose!("Not recorded in history: --private");
+        } else {
+            record_history_from(settings, &buffer, &sources, args.ttl);
         }
-        Ok(text) => {
-            println!("{}", text);
-            BcExitCode::Success.into()
+        if let Some(command) = Hook::PostCopy.command(&hooks) {
+            run_post_hook(
+                Hook::PostCopy,
+                command,
+                &buffer,
+                &hook_env(settings, &buffer, Some(backend)),
+            );
         }
-        Err(e) => {
-            eprintln!("Error: {}", e);
-            BcExitCode::ClipboardUnavailable.into()
+        if args.hold || !kept_after_exit(settings, backend) {
+            hold_copy(settings, backend, alt_text.as_deref().unwrap_or(&buffer))?;
+        }
+        if let Some(after) = args.expire {
+            schedule_expiry(
+                settings,
+                backend,
+                alt_text.as_deref().unwrap_or(&buffer),
+                after,
+            )?;
         }
     }
+
+    if settings.preview {
+        show_preview(settings, &buffer);
+    }
+    if let Some(qr) = qr {
+        print!("{}", qr);
+    }
+
+    Ok(BcExitCode::Success)
 }
 
-/// Handle clear operation
-fn handle_clear(args: &Args) -> ExitCode {
-    let prefer_remote = !args.local && is_remote_session();
+/// Say what a `--dry-run` copy would have put where
+fn report_dry_run(what: &str, target: &str) {
+    message!(
+        "Dry run: would copy {} to {} (nothing written)",
+        what,
+        target
+    );
+}
 
-    match clear_clipboard(prefer_remote, args.local) {
-        Ok(osc52_used) => {
-            if osc52_used {
-                eprintln!("Clipboard cleared (via OSC 52)");
+/// The backend a copy of rich content (HTML, images, files, parts) goes to:
+/// the explicit backend, else arboard, except that remote sessions get the
+/// text form alone via OSC 52 when there is one
+fn rich_copy_target(settings: &Settings, has_text: bool) -> &'static str {
+    match settings.options().resolved_mode() {
+        Mode::Backend(kind) => kind.name(),
+        Mode::Remote if has_text => BackendKind::Osc52.name(),
+        _ => BackendKind::Local.name(),
+    }
+}
+
+/// Start a detached `bc expire` that clears `text` from `backend` after
+/// `after`, unless it has been replaced by then
+fn schedule_expiry(settings: &Settings, backend: &str, text: &str, after: Duration) -> Result<()> {
+    let program = std::env::current_exe().context("Failed to locate the bc executable")?;
+    let mut command = std::process::Command::new(program);
+    command.args([
+        "--backend",
+        backend,
+        "--selection",
+        settings.selection.name(),
+    ]);
+    if let Some(tty) = &settings.tty {
+        command.arg("--tty").arg(tty);
+    }
+    if settings.no_passthrough {
+        command.arg("--no-passthrough");
+    }
+    command
+        .args(["expire", "--after-ms", &after.as_millis().to_string()])
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null());
+    #[cfg(unix)]
+    {
+        // Outlive the shell's job control: Ctrl-C in the terminal shouldn't cancel it
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+    let mut child = command
+        .spawn()
+        .context("Failed to start the expiry timer")?;
+    // Through a pipe like the held copy, so the digest isn't in process listings
+    child
+        .stdin
+        .take()
+        .expect("stdin is piped")
+        .write_all(bc::expire::digest(text).as_bytes())
+        .context("Failed to hand the digest to the expiry timer")?;
+    verbose!("Clipboard will be cleared in {:?}", after);
+    Ok(())
+}
+
+/// Whether an arboard copy outlived its owner: on X11 it's handed to the
+/// clipboard manager, if one is running, when arboard lets go
+fn kept_after_exit(settings: &Settings, backend: &str) -> bool {
+    if backend != "local" {
+        return true;
+    }
+    let kept = bc::backends::ArboardBackend::new()
+        .with_selection(settings.selection)
+        .kept_after_exit()
+        .unwrap_or(true);
+    if !kept {
+        verbose!("No clipboard manager took the copy over; holding it instead");
+    }
+    kept
+}
+
+/// Start a detached `bc hold` that keeps serving `text` after this process
+/// exits, when `backend` is arboard on a system where copies need an owner
+fn hold_copy(settings: &Settings, backend: &str, text: &str) -> Result<()> {
+    if backend != "local" || !bc::backends::ArboardBackend::copies_need_owner() {
+        verbose!("--hold: the {} clipboard keeps copies by itself", backend);
+        return Ok(());
+    }
+    let program = std::env::current_exe().context("Failed to locate the bc executable")?;
+    let mut command = std::process::Command::new(program);
+    command
+        .args(["--selection", settings.selection.name(), "hold"])
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null());
+    #[cfg(unix)]
+    {
+        // Outlive the shell's job control, like the expiry timer
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+    let mut child = command
+        .spawn()
+        .context("Failed to start the clipboard holder")?;
+    // The text goes through a pipe, so it never shows up in process listings
+    child
+        .stdin
+        .take()
+        .expect("stdin is piped")
+        .write_all(text.as_bytes())
+        .context("Failed to hand the copy to the clipboard holder")?;
+    verbose!("Holding the copy in process {}", child.id());
+    Ok(())
+}
+
+/// Transforms the copy flags ask for
+fn copy_pipeline(args: &CopyArgs) -> Result<Pipeline> {
+    let mut steps = Vec::new();
+    // First, so the other steps see the text as it will be copied
+    if args.template {
+        steps.push(Transform::Template);
+    }
+    if args.dedent {
+        steps.push(Transform::Dedent);
+    }
+    if let Some(width) = args.indent {
+        steps.push(Transform::Indent(width));
+    }
+    if let Some(redactor) = &args.redact {
+        steps.push(Transform::Redact(redactor.clone()));
+    }
+    transform_pipeline(&args.transform, steps)
+}
+
+/// The pipeline for the flags shared by copy and paste, with `steps` for
+/// the command's own flags. Decoding comes first and encoding last, so the
+/// other steps see plain text; escape codes and JSON layout are dealt with
+/// before anything measures indentation; cleanup, line endings, and case
+/// follow the command's steps, and the --filter chain runs last as given.
+fn transform_pipeline(args: &TransformArgs, steps: Vec<Transform>) -> Result<Pipeline> {
+    let mut pipeline = Pipeline::default();
+    if args.url_decode {
+        pipeline.push(Transform::UrlDecode);
+    } else if args.base64_decode {
+        pipeline.push(Transform::Base64Decode);
+    } else if args.hex_decode {
+        pipeline.push(Transform::HexDecode);
+    }
+    if args.strip_ansi {
+        pipeline.push(Transform::StripAnsi);
+    }
+    if args.strip_invisible {
+        pipeline.push(Transform::StripInvisible);
+    }
+    if let Some(form) = args.normalize {
+        pipeline.push(Transform::Normalize(form));
+    }
+    if args.ascii_punctuation {
+        pipeline.push(Transform::AsciiPunctuation);
+    }
+    if let Some(list) = &args.fields {
+        pipeline.push(Transform::Fields(Fields::new(
+            list.clone(),
+            args.delimiter.clone(),
+            args.trim_fields,
+        )));
+    }
+    if args.sort {
+        pipeline.push(Transform::Sort);
+    } else if args.sort_numeric {
+        pipeline.push(Transform::SortNumeric);
+    }
+    if args.reverse {
+        pipeline.push(Transform::Reverse);
+    }
+    if args.unique {
+        pipeline.push(Transform::Unique);
+    }
+    if args.json_pretty {
+        pipeline.push(Transform::JsonPretty);
+    } else if args.json_minify {
+        pipeline.push(Transform::JsonMinify);
+    }
+    if let Some(width) = args.tabs_to_spaces {
+        pipeline.push(Transform::TabsToSpaces(width));
+    }
+    pipeline.extend(steps);
+    if args.clean_url {
+        let config = Config::load()?;
+        pipeline.push(Transform::CleanUrl(Cleaner::new(&config.clean_url)));
+    }
+    if args.strip_trailing_ws {
+        pipeline.push(Transform::StripTrailingWs);
+    }
+    if args.squeeze_blank {
+        pipeline.push(Transform::SqueezeBlank);
+    }
+    if args.crlf {
+        pipeline.push(Transform::Crlf);
+    } else if args.lf {
+        pipeline.push(Transform::Lf);
+    }
+    if args.upper {
+        pipeline.push(Transform::Upper);
+    } else if args.lower {
+        pipeline.push(Transform::Lower);
+    }
+    if args.url_encode {
+        pipeline.push(Transform::UrlEncode);
+    } else if args.base64 {
+        pipeline.push(Transform::Base64);
+    } else if args.hex {
+        pipeline.push(Transform::Hex);
+    }
+    pipeline.extend(args.filter.iter().cloned());
+    Ok(pipeline)
+}
+
+/// `addition` after `current`, with `separator` between them unless
+/// `current` is empty
+fn append_to(mut current: String, addition: &str, separator: &str) -> String {
+    if !current.is_empty() {
+        current.push_str(separator);
+    }
+    current.push_str(addition);
+    current
+}
+
+/// Clipboard options for a copy; `passthrough` when stdout carries the content too
+fn copy_options(settings: &Settings, passthrough: bool) -> clipboard::Options {
+    let mut options = settings.options();
+    let tty_available = || {
+        fs::OpenOptions::new()
+            .write(true)
+            .open(osc52::CONTROLLING_TTY)
+            .is_ok()
+    };
+    if passthrough && options.backend.output == osc52::Output::Auto && tty_available() {
+        // stdout carries the passed-through content; keep OSC 52 off it
+        options.backend.output = osc52::Output::Tty(osc52::CONTROLLING_TTY.into());
+    }
+    options
+}
+
+/// Put the FILE arguments on the clipboard as a file list
+fn copy_files_to_clipboard(settings: &Settings, args: &CopyArgs) -> Result<BcExitCode> {
+    let paths = args
+        .input
+        .files
+        .iter()
+        .map(|path| {
+            fs::canonicalize(path)
+                .map_err(|e| anyhow::anyhow!("Cannot copy {}: {}", path.display(), e))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    if args.dry_run {
+        let target = format!("the {} backend", rich_copy_target(settings, false));
+        report_dry_run(&format!("a list of {} file(s)", paths.len()), &target);
+        return Ok(BcExitCode::Success);
+    }
+    clipboard::copy_files(&paths, &settings.options())?;
+
+    if settings.preview {
+        let names: Vec<_> = paths
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect();
+        message!("Copied: <{} file(s)> {}", paths.len(), names.join(", "));
+    }
+
+    Ok(BcExitCode::Success)
+}
+
+/// Put every `--part` file on the clipboard at once, each as its format
+fn copy_parts_to_clipboard(settings: &Settings, args: &CopyArgs) -> Result<BcExitCode> {
+    let transforms = args.transform != TransformArgs::default()
+        || args.template
+        || args.dedent
+        || args.indent.is_some()
+        || args.redact.is_some();
+    if transforms {
+        anyhow::bail!("Transforms don't apply to --part copies");
+    }
+    let mut parts: Vec<Part> = Vec::new();
+    for spec in &args.part {
+        let path = spec.path.display();
+        let data =
+            fs::read(&spec.path).map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path, e))?;
+        let part = match spec.mime {
+            PartMime::Text | PartMime::Html => {
+                let mut text = String::from_utf8(data)
+                    .map_err(|_| anyhow::anyhow!("{} is not UTF-8 text", path))?;
+                if settings.trim && text.ends_with('\n') {
+                    text.truncate(text.trim_end_matches(['\r', '\n']).len());
+                }
+                if contains_binary_data(&text) && !settings.force {
+                    anyhow::bail!(CodedError::new(
+                        ErrorCode::BinaryInput,
+                        format!(
+                            "{} contains binary/control characters. Use --force to proceed.",
+                            path
+                        )
+                    ));
+                }
+                if spec.mime == PartMime::Text {
+                    Part::Text(text)
+                } else {
+                    Part::Html(text)
+                }
             }
-            BcExitCode::Success.into()
+            PartMime::Png | PartMime::Jpeg => match bc::image::decode(&data) {
+                Ok(image) => Part::Image(image),
+                Err(e) => {
+                    anyhow::bail!(CodedError::new(
+                        ErrorCode::InvalidInput,
+                        format!("{}: {}", path, e)
+                    ));
+                }
+            },
+        };
+        if parts.iter().any(|other| other.mime() == part.mime()) {
+            anyhow::bail!("More than one {} part", part.mime());
         }
-        Err(e) => {
-            eprintln!("Error: {}", e);
-            BcExitCode::GeneralError.into()
+        parts.push(part);
+    }
+    let text = parts.iter().find_map(|part| match part {
+        Part::Text(text) => Some(text.as_str()),
+        _ => None,
+    });
+
+    if !args.allow_secrets {
+        let scanner = bc::secrets::Scanner::new(&Config::load()?.secrets)?;
+        for part in &parts {
+            let found = match part {
+                Part::Text(text) | Part::Html(text) => scanner.scan(text),
+                _ => continue,
+            };
+            if !found.is_empty() {
+                anyhow::bail!(CodedError::new(
+                    ErrorCode::SecretDetected,
+                    format!(
+                        "The {} part looks like it contains a secret ({}). Use --allow-secrets to copy it anyway.",
+                        part.mime(),
+                        found.join(", ")
+                    )
+                ));
+            }
         }
     }
-}
 
-/// Handle copy operation
-fn handle_copy(args: &Args) -> ExitCode {
-    match copy_to_clipboard(args) {
-        Ok(BcExitCode::Success) => BcExitCode::Success.into(),
-        Ok(code) => code.into(),
-        Err(e) => {
-            eprintln!("Error: {}", e);
-            BcExitCode::GeneralError.into()
+    if args.dry_run {
+        let mimes: Vec<_> = parts.iter().map(Part::mime).collect();
+        let target = format!("the {} backend", rich_copy_target(settings, text.is_some()));
+        report_dry_run(&mimes.join(", "), &target);
+        return Ok(BcExitCode::Success);
+    }
+    let backend = clipboard::copy_parts(&parts, &settings.options())?;
+    verbose!("Copied {} part(s) via {}", parts.len(), backend);
+    match text {
+        Some(_) if args.private => verbose!("Not recorded in history: --private"),
+        Some(text) => record_history_from(settings, text, &[], args.ttl),
+        None => {}
+    }
+
+    if settings.preview {
+        let mimes: Vec<_> = parts.iter().map(Part::mime).collect();
+        match text {
+            Some(text) => show_preview(settings, text),
+            None => message!("Copied: <{} part(s)> {}", parts.len(), mimes.join(", ")),
         }
     }
+
+    Ok(BcExitCode::Success)
 }
 
-fn copy_to_clipboard(args: &Args) -> Result<BcExitCode> {
-    let mut buffer = read_input()?;
+/// Copy raw bytes as a base64 data URL (see `bc::binary`)
+fn copy_binary_to_clipboard(settings: &Settings, args: &CopyArgs) -> Result<BcExitCode> {
+    let input = &args.input;
+    let limit = InputLimit::for_copy(input, &Config::load()?, copies_via_osc52(settings));
+    let data = read_input_bytes(
+        input,
+        limit,
+        !settings.no_progress,
+        "Usage: bc copy --binary < file.tar.gz",
+    )?;
 
-    if contains_binary_data(&buffer) && !args.force {
-        eprintln!("Warning: Input contains binary/control characters. Use --force to proceed.");
-        return Ok(BcExitCode::InvalidInput);
+    if data.is_empty() {
+        anyhow::bail!(CodedError::new(ErrorCode::EmptyInput, "Input is empty"));
     }
 
-    if args.trim && buffer.ends_with('\n') {
-        buffer.truncate(buffer.trim_end_matches('\n').len());
+    let text = bc::binary::encode(&data, bc::binary::DEFAULT_MIME);
+    if args.dry_run {
+        let target = format!(
+            "the {} backend",
+            clipboard::copy_target(&settings.options())?
+        );
+        report_dry_run(
+            &format!("{} bytes ({} encoded)", data.len(), text.len()),
+            &target,
+        );
+        return Ok(BcExitCode::Success);
     }
+    clipboard::copy(&text, &copy_options(settings, input.tee))?;
 
-    if buffer.is_empty() {
-        eprintln!("Error: Input is empty");
-        return Ok(BcExitCode::EmptyInput);
+    if settings.preview {
+        message!(
+            "Copied: <binary {}> ({} bytes, {} encoded)",
+            bc::binary::DEFAULT_MIME,
+            data.len(),
+            text.len()
+        );
     }
 
-    if !args.local && is_remote_session() {
-        copy_remote(&buffer)?;
-    } else {
-        copy_local(&buffer).or_else(|e| {
-            if !args.local {
-                copy_remote(&buffer)?;
-                Ok(())
-            } else {
-                Err(e)
-            }
-        })?;
+    Ok(BcExitCode::Success)
+}
+
+/// Decode an image from stdin and place it on the local clipboard
+/// (OSC 52 only carries text)
+fn copy_image_to_clipboard(settings: &Settings, args: &CopyArgs) -> Result<BcExitCode> {
+    let input = &args.input;
+    if input.files.len() > 1 {
+        anyhow::bail!("--image takes a single file");
     }
+    let limit = InputLimit::for_copy(input, &Config::load()?, false);
+    let data = read_input_bytes(
+        input,
+        limit,
+        !settings.no_progress,
+        "Usage: bc copy --image < image.png",
+    )?;
 
-    if args.preview {
-        show_preview(&buffer);
+    if data.is_empty() {
+        anyhow::bail!(CodedError::new(ErrorCode::EmptyInput, "Input is empty"));
+    }
+
+    let image = match bc::image::decode(&data) {
+        Ok(image) => image,
+        Err(e) => {
+            anyhow::bail!(CodedError::new(ErrorCode::InvalidInput, e.to_string()));
+        }
+    };
+
+    if args.dry_run {
+        let target = format!("the {} backend", rich_copy_target(settings, false));
+        report_dry_run(
+            &format!("a {}x{} image", image.width, image.height),
+            &target,
+        );
+        return Ok(BcExitCode::Success);
+    }
+    clipboard::copy_image(&image, &settings.options())?;
+
+    if settings.preview {
+        message!(
+            "Copied: <image {}x{}> ({} bytes)",
+            image.width,
+            image.height,
+            data.len()
+        );
     }
 
     Ok(BcExitCode::Success)
 }
 
-/// Read input from stdin, or show usage if not piped
-fn read_input() -> Result<String> {
-    if !io::stdin().is_terminal() {
-        let mut buffer = String::new();
-        io::stdin()
-            .read_to_string(&mut buffer)
+/// Handle `bc shot`: take a screenshot, optionally save it, and copy it as
+/// an image
+fn handle_shot(settings: &Settings, args: &ShotArgs) -> ExitCode {
+    let result = bc::shot::capture(args.area()).and_then(|png| {
+        if let Some(path) = &args.output {
+            write_atomic(path, FileMode::Keep, |file| file.write_all(&png))
+                .map_err(|e| anyhow::anyhow!("Failed to write {}: {}", path.display(), e))?;
+            verbose!("Saved the screenshot to {}", path.display());
+        }
+        let image = bc::image::decode(&png)?;
+        clipboard::copy_image(&image, &settings.options())?;
+        Ok(image)
+    });
+    match result {
+        Ok(image) => {
+            if settings.preview {
+                message!("Copied: <image {}x{}>", image.width, image.height);
+            }
+            BcExitCode::Success.into()
+        }
+        Err(e) => fail(&e, ErrorCode::General),
+    }
+}
+
+/// Read the named files (`-` is stdin) joined by the separator, or stdin
+/// when no files are given; with `--tee` everything read is also forwarded
+/// to stdout. Stops with `InputTooLarge` as soon as the total passes
+/// `limit`.
+fn read_input_bytes(
+    input: &InputArgs,
+    limit: Option<InputLimit>,
+    progress: bool,
+    usage: &str,
+) -> Result<Vec<u8>> {
+    let mut stdout = io::stdout();
+    let mut tee = input.tee.then_some(&mut stdout);
+    let mut buffer = Vec::new();
+    let mut progress = Progress::new("Reading", None, progress);
+    let check_size = |buffer: &Vec<u8>| match limit {
+        Some(limit) if buffer.len() > limit.bytes => Err(anyhow::Error::new(InputTooLarge(limit))),
+        _ => Ok(()),
+    };
+    let limit = limit.map_or(usize::MAX, |limit| limit.bytes);
+
+    if input.files.is_empty() {
+        if io::stdin().is_terminal() {
+            message!("{}", usage);
+            message!("Try 'bc --help' for more information.");
+            return Err(anyhow::anyhow!("No input provided"));
+        }
+        read_into(&mut io::stdin(), &mut buffer, tee, limit, &mut progress)
             .context("Failed to read from stdin")?;
-        Ok(buffer)
-    } else {
-        eprintln!("Usage: echo 'text' | bc");
-        eprintln!("Try 'bc --help' for more information.");
-        Err(anyhow::anyhow!("No input provided"))
+        check_size(&buffer)?;
+        return Ok(buffer);
+    }
+
+    let separator = input.separator.as_deref().unwrap_or("");
+    for (i, path) in input.files.iter().enumerate() {
+        if i > 0 {
+            read_into(
+                &mut separator.as_bytes(),
+                &mut buffer,
+                tee.as_deref_mut(),
+                limit,
+                &mut progress,
+            )?;
+        }
+        if path.as_os_str() == "-" {
+            read_into(
+                &mut io::stdin(),
+                &mut buffer,
+                tee.as_deref_mut(),
+                limit,
+                &mut progress,
+            )
+            .context("Failed to read from stdin")?;
+        } else {
+            fs::File::open(path)
+                .and_then(|mut file| {
+                    read_into(
+                        &mut file,
+                        &mut buffer,
+                        tee.as_deref_mut(),
+                        limit,
+                        &mut progress,
+                    )
+                })
+                .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path.display(), e))?;
+        }
+        check_size(&buffer)?;
+    }
+    Ok(buffer)
+}
+
+/// Append everything from `reader` to `buffer`, forwarding each chunk to
+/// `tee` as it arrives. A closed tee (e.g. `| head`) stops forwarding but
+/// not reading. Stops early once `buffer` holds more than `limit` bytes.
+fn read_into<W: Write>(
+    reader: &mut dyn Read,
+    buffer: &mut Vec<u8>,
+    mut tee: Option<&mut W>,
+    limit: usize,
+    progress: &mut Progress,
+) -> io::Result<()> {
+    let mut chunk = [0; 8192];
+    loop {
+        if buffer.len() > limit {
+            return Ok(());
+        }
+        let n = match reader.read(&mut chunk) {
+            Ok(0) => return Ok(()),
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        buffer.extend_from_slice(&chunk[..n]);
+        progress.advance(n);
+        if let Some(out) = tee.as_deref_mut() {
+            match out.write_all(&chunk[..n]).and_then(|()| out.flush()) {
+                Ok(()) => {}
+                Err(e) if e.kind() == io::ErrorKind::BrokenPipe => tee = None,
+                Err(e) => return Err(e),
+            }
+        }
     }
 }
 
+/// Read text input (see `read_input_bytes`)
+fn read_input(input: &InputArgs, limit: Option<InputLimit>, progress: bool) -> Result<String> {
+    let bytes = read_input_bytes(
+        input,
+        limit,
+        progress,
+        "Usage: echo 'text' | bc, or bc FILE...",
+    )?;
+    String::from_utf8(bytes).context("Input is not valid UTF-8")
+}
+
 fn contains_binary_data(text: &str) -> bool {
     text.contains('\0')
         || text
@@ -196,45 +2533,214 @@ fn contains_binary_data(text: &str) -> bool {
             .any(|c| c.is_control() && !ALLOWED_CONTROL_CHARS.contains(&c))
 }
 
-fn show_preview(content: &str) {
+fn show_preview(settings: &Settings, content: &str) {
     if content.is_empty() {
-        eprintln!("Copied: <empty> (0 bytes)");
+        message!("Copied: <empty> (0 bytes)");
         return;
     }
 
-    let total = content.len();
     let total_chars = content.chars().count();
+    let lines = content.lines().count();
+    let sizes = format!(
+        "{} bytes, {} chars, {} {}, {}",
+        content.len(),
+        total_chars,
+        lines,
+        if lines == 1 { "line" } else { "lines" },
+        bc::detect::detect(content)
+    );
+    let color = io::stderr().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+
+    // Binary content (copied with --force) reads better as bytes
+    if contains_binary_data(content) {
+        let shown = content.len().min(preview::HEXDUMP_BYTES);
+        message!(
+            "Copied ({}), first {} bytes:\n{}",
+            sizes,
+            shown,
+            preview::hexdump(content.as_bytes(), preview::HEXDUMP_BYTES).trim_end()
+        );
+        return;
+    }
+
+    if let Some(count) = settings.preview_lines {
+        let mut preview = format!("Copied ({}):", sizes);
+        for line in content.lines().take(count) {
+            preview.push_str("\n  ");
+            preview.push_str(&escape_control_chars(line.chars(), color));
+        }
+        if lines > count {
+            preview.push_str(&format!("\n  ... ({} more)", lines - count));
+        }
+        message!("{}", preview);
+        return;
+    }
 
-    let preview = escape_control_chars(content.chars().take(PREVIEW_LENGTH));
+    let preview = escape_control_chars(content.chars().take(PREVIEW_LENGTH), color);
     let preview = if total_chars > PREVIEW_LENGTH {
         format!("{}...", preview)
     } else {
         preview
     };
 
-    eprintln!(
-        "Copied: \"{}\" ({} bytes, {} chars)",
-        preview, total, total_chars
-    );
+    message!("Copied: \"{}\" ({})", preview, sizes);
 }
 
-/// Escape control characters for display
-fn escape_control_chars(chars: impl Iterator<Item = char>) -> String {
-    chars
-        .map(|c| match c {
-            '\n' => "\\n".to_string(),
-            '\r' => "\\r".to_string(),
-            '\t' => "\\t".to_string(),
-            '\x0c' => "\\f".to_string(),
-            c if c.is_control() => format!("\\x{:02x}", c as u32),
-            c => c.to_string(),
-        })
-        .collect()
+/// Single-line escaped preview for listings
+fn preview_line(content: &str) -> String {
+    let mut preview = escape_control_chars(content.chars().take(LISTING_PREVIEW_LENGTH), false);
+    if content.chars().count() > LISTING_PREVIEW_LENGTH {
+        preview.push_str("...");
+    }
+    preview
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use bc::backends::BackendError;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_write_atomic_replaces_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.txt");
+        fs::write(&path, "old content").unwrap();
+
+        write_atomic(&path, FileMode::Keep, |file| file.write_all(b"new")).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+
+        append_file(&path, |file| file.write_all(b" more")).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new more");
+    }
+
+    #[test]
+    fn test_write_repeated() {
+        let repeated = |repeat, separator: Option<&str>| {
+            let args = PasteArgs {
+                repeat,
+                separator: separator.map(str::to_string),
+                ..PasteArgs::default()
+            };
+            let mut out = Vec::new();
+            write_repeated(&mut out, b"row", &args).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        assert_eq!(repeated(None, None), "row");
+        assert_eq!(repeated(Some(1), Some(",")), "row");
+        assert_eq!(repeated(Some(3), None), "row\nrow\nrow");
+        assert_eq!(repeated(Some(3), Some(", ")), "row, row, row");
+    }
+
+    #[test]
+    fn test_append_to() {
+        assert_eq!(append_to("one".to_string(), "two", "\n"), "one\ntwo");
+        assert_eq!(append_to("one\n".to_string(), "two", ""), "one\ntwo");
+        assert_eq!(append_to(String::new(), "two", "\n"), "two");
+    }
+
+    #[test]
+    fn test_read_into_forwards_to_tee() {
+        let mut buffer = b"a".to_vec();
+        let mut tee = Vec::new();
+        let mut progress = Progress::hidden();
+        read_into(
+            &mut &b"bc"[..],
+            &mut buffer,
+            Some(&mut tee),
+            usize::MAX,
+            &mut progress,
+        )
+        .unwrap();
+        assert_eq!(buffer, b"abc");
+        assert_eq!(tee, b"bc");
+
+        read_into(
+            &mut &b"d"[..],
+            &mut buffer,
+            None::<&mut Vec<u8>>,
+            usize::MAX,
+            &mut progress,
+        )
+        .unwrap();
+        assert_eq!(buffer, b"abcd");
+    }
+
+    #[test]
+    fn test_read_into_stops_past_limit() {
+        let data = vec![b'x'; 100_000];
+        let mut buffer = Vec::new();
+        read_into(
+            &mut &data[..],
+            &mut buffer,
+            None::<&mut Vec<u8>>,
+            10,
+            &mut Progress::hidden(),
+        )
+        .unwrap();
+        assert!(
+            buffer.len() > 10 && buffer.len() <= 8192,
+            "{}",
+            buffer.len()
+        );
+    }
+
+    #[test]
+    fn test_read_input_joins_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("a.txt");
+        let b = dir.path().join("b.txt");
+        fs::write(&a, "one").unwrap();
+        fs::write(&b, "two").unwrap();
+
+        let mut input = InputArgs {
+            files: vec![a.clone(), b],
+            separator: None,
+            tee: false,
+            ..InputArgs::default()
+        };
+        assert_eq!(read_input(&input, None, false).unwrap(), "onetwo");
+        input.separator = Some("\n---\n".to_string());
+        assert_eq!(read_input(&input, None, false).unwrap(), "one\n---\ntwo");
+
+        input.max_size = Some(8);
+        let limit = InputLimit::for_copy(&input, &Config::default(), true);
+        let err = read_input(&input, limit, false).unwrap_err();
+        assert!(err.is::<InputTooLarge>());
+        let (code, fields) = error_code(&err, ErrorCode::General);
+        assert_eq!(code, ErrorCode::InputTooLarge);
+        assert_eq!(code.exit_code(), BcExitCode::InvalidInput);
+        assert_eq!(fields["max"], 8);
+        input.max_size = Some(11);
+        let limit = InputLimit::for_copy(&input, &Config::default(), true);
+        assert!(read_input(&input, limit, false).is_ok());
+
+        input.max_size = None;
+        input.files = vec![a, PathBuf::from("/nonexistent/file")];
+        let err = read_input(&input, None, false).unwrap_err();
+        assert!(err.to_string().contains("/nonexistent/file"));
+    }
+
+    #[test]
+    fn test_input_limit_precedence() {
+        let mut input = InputArgs::default();
+        let mut config = Config::default();
+        assert_eq!(InputLimit::for_copy(&input, &config, false), None);
+        let osc52 = InputLimit::for_copy(&input, &config, true).unwrap();
+        assert_eq!(osc52.bytes, osc52::OSC52_MAX_INPUT);
+
+        config.limits.max_size = Some(100);
+        let limit = InputLimit::for_copy(&input, &config, true).unwrap();
+        assert_eq!(limit.bytes, 100);
+        input.max_size = Some(50);
+        let limit = InputLimit::for_copy(&input, &config, false).unwrap();
+        assert_eq!((limit.bytes, limit.origin), (50, "--max-size"));
+        assert_eq!(
+            InputTooLarge(limit).to_string(),
+            "Input exceeds 50 bytes (--max-size)"
+        );
+    }
 
     #[test]
     fn test_trim_logic() {
@@ -285,11 +2791,34 @@ mod tests {
     }
 
     #[test]
-    fn test_preview_formatting() {
-        assert!(escape_control_chars("hello\n".chars()).contains("\\n"));
-        assert!(escape_control_chars("hello\r".chars()).contains("\\r"));
-        assert!(escape_control_chars("hello\t".chars()).contains("\\t"));
-        assert!(escape_control_chars("hello\x01".chars()).contains("\\x01"));
+    fn test_exit_code_for_missing_program() {
+        let missing = anyhow::Error::new(BackendError::NotInstalled("wl-copy".into()))
+            .context("Failed to copy");
+        let (code, fields) = error_code(&missing, ErrorCode::General);
+        assert_eq!(code.exit_code(), BcExitCode::ClipboardUnavailable);
+        assert_eq!(fields["program"], "wl-copy");
+
+        let other = anyhow::anyhow!("boom");
+        assert_eq!(
+            error_code(&other, ErrorCode::General).0.exit_code(),
+            BcExitCode::GeneralError
+        );
+    }
+
+    #[test]
+    fn test_watch_event_formatting() {
+        assert_eq!(format_watch_event(WatchFormat::Raw, "a\nb"), "a\nb");
+
+        let event: serde_json::Value =
+            serde_json::from_str(&format_watch_event(WatchFormat::Json, "a\nb")).unwrap();
+        assert_eq!(event["content"], "a\nb");
+        assert!(event["timestamp"].is_u64());
+        assert_eq!(event["type"], "text");
+
+        let event: serde_json::Value =
+            serde_json::from_str(&format_watch_event(WatchFormat::Json, "https://x.io")).unwrap();
+        assert_eq!(event["type"], "url");
+        assert_eq!(event["language"], serde_json::Value::Null);
     }
 
     #[test]
@@ -0,0 +1,85 @@
+//! Text recognition for clipboard images (`paste --ocr`).
+//!
+//! The image goes to the `tesseract` program as PNG on stdin, and the
+//! recognized text comes back on stdout.
+
+use anyhow::{Context, Result};
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::thread;
+
+use crate::debug;
+
+/// Recognize the text in a PNG image, in `language` (a tesseract language
+/// code like `eng` or `deu+eng`; tesseract's default when `None`)
+pub fn recognize(png: &[u8], language: Option<&str>) -> Result<String> {
+    let mut args = vec!["stdin", "stdout"];
+    if let Some(language) = language {
+        args.extend(["-l", language]);
+    }
+    let text = run("tesseract", &args, png)?;
+    let text = String::from_utf8(text).context("tesseract produced invalid UTF-8")?;
+    Ok(clean(&text))
+}
+
+/// Tesseract ends pages with a form feed and pads with blank lines
+fn clean(text: &str) -> String {
+    let text = text.replace('\x0c', "");
+    let lines: Vec<_> = text.lines().map(str::trim_end).collect();
+    lines.join("\n").trim_matches('\n').to_string()
+}
+
+/// Run `program` with `input` on stdin and return its stdout
+fn run(program: &str, args: &[&str], input: &[u8]) -> Result<Vec<u8>> {
+    debug!("Running '{} {}'", program, args.join(" "));
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => {
+                anyhow::anyhow!("{} is not installed (needed for --ocr)", program)
+            }
+            _ => anyhow::anyhow!("Failed to run {}: {}", program, e),
+        })?;
+
+    // Feed stdin from another thread so a large output can't fill the pipe
+    // while we are still writing
+    let mut stdin = child
+        .stdin
+        .take()
+        .with_context(|| format!("Failed to open {}'s stdin", program))?;
+    let output = thread::scope(|scope| {
+        scope.spawn(move || {
+            // An early exit closes stdin; the status explains why
+            let _ = stdin.write_all(input);
+        });
+        child.wait_with_output()
+    })
+    .with_context(|| format!("Failed to run {}", program))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!("{} failed: {}", program, stderr.trim());
+    }
+    Ok(output.stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clean() {
+        assert_eq!(clean("\nHello  \nworld\n\n\x0c"), "Hello\nworld");
+        assert_eq!(clean("a\n\nb\n"), "a\n\nb");
+    }
+
+    #[test]
+    fn test_missing_program() {
+        let err = run("bc-no-such-ocr", &[], b"").unwrap_err();
+        assert!(err.to_string().contains("needed for --ocr"), "{}", err);
+    }
+}
@@ -1,32 +1,208 @@
 use anyhow::{Context, Result};
+use base64::write::EncoderWriter;
 use is_terminal::IsTerminal;
+use std::env;
+use std::fmt;
+use std::fs::{File, OpenOptions};
 use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
 
-/// OSC 52 escape sequence prefix: \x1b]52;c;
-const OSC52_PREFIX: &str = "\x1b]52;c;";
+/// OSC 52 escape sequence introducer, followed by the selection and `;`
+const OSC52_INTRO: &str = "\x1b]52;";
+/// Selection parameter for the regular clipboard
+const OSC52_CLIPBOARD: &str = "c";
 /// OSC 52 escape sequence terminator: \x07
 const OSC52_TERMINATOR: char = '\x07';
 /// String terminator (ST) alternative to BEL
 const OSC52_ST: &str = "\x1b\\";
 /// Maximum size for OSC 52 clipboard content (10MB)
 pub const OSC52_MAX_SIZE: usize = 10 * 1024 * 1024;
+/// Largest content whose base64 fits in `OSC52_MAX_SIZE`
+pub const OSC52_MAX_INPUT: usize = OSC52_MAX_SIZE / 4 * 3;
+/// Base64 bytes per sequence in chunked writes (a multiple of 4, so every
+/// chunk decodes on its own)
+pub const OSC52_CHUNK_SIZE: usize = 4096;
+/// Data that resets the pending clipboard in terminals with append semantics
+const OSC52_CHUNK_RESET: &str = "!";
+/// Payload bytes per screen DCS string; screen truncates DCS strings at
+/// 768 bytes, including the 4-byte envelope
+pub const SCREEN_CHUNK_SIZE: usize = 760;
 
 /// Build OSC 52 escape sequence with pre-encoded base64 data.
 /// Format: \x1b]52;c;{base64}\x07
 pub fn build_sequence_raw(encoded: &str) -> String {
-    format!("{}{}{}", OSC52_PREFIX, encoded, OSC52_TERMINATOR)
+    build_sequence(OSC52_CLIPBOARD, encoded)
 }
 
-/// Write OSC 52 sequence to terminal.
-/// Uses stdout if it's a TTY, otherwise falls back to stderr.
-/// Disables auto-wrap during the sequence to prevent corruption in legacy terminals.
-pub fn write_sequence(osc52: &str) -> Result<()> {
-    let mut stream: Box<dyn Write> = if io::stdout().is_terminal() {
-        Box::new(io::stdout())
-    } else {
-        Box::new(io::stderr())
-    };
+/// Build OSC 52 escape sequence for a selection target (`c`, `p`, `pc`, ...).
+/// Format: \x1b]52;{target};{base64}\x07
+pub fn build_sequence(target: &str, encoded: &str) -> String {
+    format!("{}{};{}{}", OSC52_INTRO, target, encoded, OSC52_TERMINATOR)
+}
+
+/// Build the sequences for a chunked write: a reset followed by one
+/// sequence per `OSC52_CHUNK_SIZE` piece of the payload, which terminals
+/// with append semantics (kitty, mosh) concatenate into one clipboard value.
+pub fn build_chunked_sequences(target: &str, encoded: &str) -> Vec<String> {
+    std::iter::once(OSC52_CHUNK_RESET)
+        .chain(chunk(encoded, OSC52_CHUNK_SIZE))
+        .map(|piece| build_sequence(target, piece))
+        .collect()
+}
+
+/// Whether the terminal is known to append successive OSC 52 writes, which
+/// makes chunked writes safe
+pub fn supports_chunked_writes() -> bool {
+    is_kitty() || is_mosh()
+}
+
+fn is_kitty() -> bool {
+    env::var_os("KITTY_WINDOW_ID").is_some()
+        || env::var("TERM").is_ok_and(|term| term == "xterm-kitty")
+}
+
+/// Detect mosh by looking for `mosh-server` among our ancestor processes
+fn is_mosh() -> bool {
+    crate::clipboard::ancestor_processes()
+        .iter()
+        .any(|name| name == "mosh-server")
+}
+
+/// Wrap a sequence in tmux's DCS passthrough envelope so it reaches the
+/// outer terminal. ESC bytes inside the envelope must be doubled.
+/// Format: \x1bPtmux;{sequence with ESC doubled}\x1b\\
+pub fn wrap_tmux(sequence: &str) -> String {
+    format!("\x1bPtmux;{}\x1b\\", sequence.replace('\x1b', "\x1b\x1b"))
+}
 
+/// Wrap a sequence in GNU screen's DCS passthrough, split into
+/// `SCREEN_CHUNK_SIZE` pieces that screen forwards one by one.
+/// Format: \x1bP{chunk}\x1b\\ repeated
+pub fn wrap_screen(sequence: &str) -> String {
+    chunk(sequence, SCREEN_CHUNK_SIZE)
+        .into_iter()
+        .map(|piece| format!("\x1bP{}\x1b\\", piece))
+        .collect()
+}
+
+/// Split a string into pieces of at most `max_len` bytes, never splitting
+/// a character
+pub fn chunk(s: &str, max_len: usize) -> Vec<&str> {
+    assert!(max_len >= 4, "chunks must fit any UTF-8 character");
+    let mut pieces = Vec::new();
+    let mut rest = s;
+    while !rest.is_empty() {
+        let mut end = rest.len().min(max_len);
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        let (piece, tail) = rest.split_at(end);
+        pieces.push(piece);
+        rest = tail;
+    }
+    pieces
+}
+
+/// Terminal multiplexer envelope an OSC 52 write needs to reach the terminal
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Passthrough {
+    /// Write sequences as they are
+    None,
+    /// tmux DCS passthrough (see `wrap_tmux`)
+    Tmux,
+    /// GNU screen chunked DCS passthrough (see `wrap_screen`)
+    Screen,
+}
+
+impl Passthrough {
+    /// Detect the envelope for the current session. Inside tmux, wrapping is
+    /// only needed when tmux doesn't handle OSC 52 itself (`set-clipboard on`);
+    /// screen (`$STY`) always needs it.
+    pub fn detect() -> Self {
+        if env::var_os("TMUX").is_some() {
+            if tmux_set_clipboard_enabled() {
+                Passthrough::None
+            } else {
+                Passthrough::Tmux
+            }
+        } else if env::var_os("STY").is_some() {
+            Passthrough::Screen
+        } else {
+            Passthrough::None
+        }
+    }
+
+    /// Apply the envelope to a sequence
+    pub fn wrap(self, sequence: &str) -> String {
+        match self {
+            Passthrough::None => sequence.to_string(),
+            Passthrough::Tmux => wrap_tmux(sequence),
+            Passthrough::Screen => wrap_screen(sequence),
+        }
+    }
+}
+
+/// Whether tmux accepts OSC 52 from applications and forwards it itself
+fn tmux_set_clipboard_enabled() -> bool {
+    tmux_option("set-clipboard").is_some_and(|value| value == "on")
+}
+
+/// Value of a global tmux option, if tmux is reachable and knows it
+pub(crate) fn tmux_option(name: &str) -> Option<String> {
+    let output = Command::new("tmux")
+        .args(["show-options", "-gv", name])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// The controlling terminal, used when stdout and stderr are both redirected
+#[cfg(windows)]
+pub const CONTROLLING_TTY: &str = "CONOUT$";
+#[cfg(not(windows))]
+pub const CONTROLLING_TTY: &str = "/dev/tty";
+
+/// Where OSC 52 sequences are written
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Output {
+    /// stdout or stderr, whichever is a TTY; otherwise the controlling
+    /// terminal, and stderr if there is none
+    #[default]
+    Auto,
+    /// A specific terminal device (`--tty`)
+    Tty(PathBuf),
+}
+
+impl Output {
+    /// Open the writer for this output
+    pub fn open(&self) -> Result<Box<dyn Write>> {
+        match self {
+            Output::Auto if io::stdout().is_terminal() => Ok(Box::new(io::stdout())),
+            Output::Auto if io::stderr().is_terminal() => Ok(Box::new(io::stderr())),
+            Output::Auto => Ok(open_tty(Path::new(CONTROLLING_TTY))
+                .map(|tty| Box::new(tty) as Box<dyn Write>)
+                .unwrap_or_else(|_| Box::new(io::stderr()))),
+            Output::Tty(path) => Ok(Box::new(open_tty(path)?)),
+        }
+    }
+}
+
+fn open_tty(path: &Path) -> Result<File> {
+    OpenOptions::new()
+        .write(true)
+        .open(path)
+        .with_context(|| format!("Failed to open terminal {}", path.display()))
+}
+
+/// Write OSC 52 sequence to a terminal writer (see `Output::open`).
+/// Disables auto-wrap during the sequence to prevent corruption in legacy terminals.
+pub fn write_sequence(stream: &mut dyn Write, osc52: &str) -> Result<()> {
     // Disable auto-wrap, write OSC 52, then re-enable (\x1b[?7l ... \x1b[?7h)
     // Prevents newline insertion in legacy terminals (e.g., conhost.exe)
     write!(stream, "\x1b[?7l{}\x1b[?7h", osc52).context("Failed to write OSC 52 sequence")?;
@@ -34,14 +210,121 @@ pub fn write_sequence(osc52: &str) -> Result<()> {
     Ok(())
 }
 
+/// Bytes `write_streamed` buffers on their way to the terminal
+const WRITE_BUFFER: usize = 64 * 1024;
+
+/// Write the OSC 52 sequence for `data` like `write_sequence` with
+/// `passthrough.wrap(&build_sequence(target, &encoded))`, but base64-encode
+/// straight into the output through `EncoderWriter`, so no encoded copy of
+/// the input is ever held.
+pub fn write_streamed(
+    stream: &mut dyn Write,
+    target: &str,
+    data: &[u8],
+    passthrough: Passthrough,
+) -> Result<()> {
+    let mut write = || -> io::Result<()> {
+        let mut out = io::BufWriter::with_capacity(WRITE_BUFFER, &mut *stream);
+        out.write_all(b"\x1b[?7l")?;
+        let mut wrapped = Enveloped::new(&mut out, passthrough)?;
+        write!(wrapped, "{}{};", OSC52_INTRO, target)?;
+        {
+            let mut encoder =
+                EncoderWriter::new(&mut wrapped, &base64::engine::general_purpose::STANDARD);
+            encoder.write_all(data)?;
+            encoder.finish()?;
+        }
+        write!(wrapped, "{}", OSC52_TERMINATOR)?;
+        wrapped.finish()?;
+        out.write_all(b"\x1b[?7h")?;
+        out.flush()
+    };
+    write().context("Failed to write OSC 52 sequence")
+}
+
+/// A writer that applies a `Passthrough` envelope to what goes through it,
+/// for sequences too large to wrap in memory. The output matches
+/// `Passthrough::wrap` once `finish` is called.
+struct Enveloped<'a> {
+    out: &'a mut dyn Write,
+    passthrough: Passthrough,
+    /// The current screen piece, up to `SCREEN_CHUNK_SIZE` bytes
+    pending: Vec<u8>,
+}
+
+impl<'a> Enveloped<'a> {
+    fn new(out: &'a mut dyn Write, passthrough: Passthrough) -> io::Result<Self> {
+        if passthrough == Passthrough::Tmux {
+            out.write_all(b"\x1bPtmux;")?;
+        }
+        Ok(Self {
+            out,
+            passthrough,
+            pending: Vec::new(),
+        })
+    }
+
+    fn write_screen_piece(&mut self) -> io::Result<()> {
+        self.out.write_all(b"\x1bP")?;
+        self.out.write_all(&self.pending)?;
+        self.out.write_all(b"\x1b\\")?;
+        self.pending.clear();
+        Ok(())
+    }
+
+    /// Close the envelope
+    fn finish(mut self) -> io::Result<()> {
+        match self.passthrough {
+            Passthrough::None => Ok(()),
+            Passthrough::Tmux => self.out.write_all(b"\x1b\\"),
+            Passthrough::Screen if self.pending.is_empty() => Ok(()),
+            Passthrough::Screen => self.write_screen_piece(),
+        }
+    }
+}
+
+impl Write for Enveloped<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.passthrough {
+            Passthrough::None => self.out.write_all(buf)?,
+            Passthrough::Tmux => {
+                for piece in buf.split_inclusive(|&byte| byte == 0x1b) {
+                    self.out.write_all(piece)?;
+                    if piece.ends_with(b"\x1b") {
+                        self.out.write_all(b"\x1b")?;
+                    }
+                }
+            }
+            // OSC 52 sequences are ASCII, so byte pieces are the character
+            // pieces `wrap_screen` makes
+            Passthrough::Screen => {
+                let mut rest = buf;
+                while !rest.is_empty() {
+                    let take = (SCREEN_CHUNK_SIZE - self.pending.len()).min(rest.len());
+                    self.pending.extend_from_slice(&rest[..take]);
+                    rest = &rest[take..];
+                    if self.pending.len() == SCREEN_CHUNK_SIZE {
+                        self.write_screen_piece()?;
+                    }
+                }
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
 /// Build OSC 52 query sequence to request clipboard contents from terminal.
 /// Format: \x1b]52;c;?\x07
 pub fn build_query_sequence() -> String {
-    format!("{}?{}", OSC52_PREFIX, OSC52_TERMINATOR)
+    build_sequence(OSC52_CLIPBOARD, "?")
 }
 
 /// Parse OSC 52 response to extract base64-encoded clipboard content.
-/// Response format: \x1b]52;c;<base64_data>\x07
+/// Response format: \x1b]52;<selection>;<base64_data>\x07
 ///
 /// Handles both BEL (\x07) and ST (\x1b\\) terminators.
 /// Finds the LAST occurrence of the prefix to handle junk before the response.
@@ -50,11 +333,17 @@ pub fn build_query_sequence() -> String {
 pub fn parse_response(input: &str) -> Result<String> {
     // Find the LAST occurrence of the prefix (in case of junk before response)
     let start_idx = input
-        .rfind(OSC52_PREFIX)
-        .ok_or_else(|| anyhow::anyhow!("Invalid OSC 52 response: missing prefix '\\x1b]52;c;'"))?;
+        .rfind(OSC52_INTRO)
+        .ok_or_else(|| anyhow::anyhow!("Invalid OSC 52 response: missing prefix '\\x1b]52;'"))?;
+
+    // Skip the selection parameter (e.g. "c;" or "p;")
+    let after_intro = &input[start_idx + OSC52_INTRO.len()..];
+    let data_start = after_intro
+        .find(';')
+        .ok_or_else(|| anyhow::anyhow!("Invalid OSC 52 response: missing selection"))?;
 
     // Find the end: either BEL or ST terminator
-    let response_part = &input[start_idx + OSC52_PREFIX.len()..];
+    let response_part = &after_intro[data_start + 1..];
     let end_idx = response_part
         .find(OSC52_TERMINATOR)
         .or_else(|| response_part.find(OSC52_ST))
@@ -68,17 +357,29 @@ pub fn parse_response(input: &str) -> Result<String> {
     Ok(base64_data.to_string())
 }
 
+/// The terminal didn't answer an OSC 52 query in time (it may not support
+/// queries, or the link is slow; worth retrying)
+#[derive(Debug)]
+pub struct NoResponse;
+
+impl fmt::Display for NoResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Terminal doesn't support OSC 52 query (no response)")
+    }
+}
+
+impl std::error::Error for NoResponse {}
+
 /// Query clipboard via OSC 52 and return base64-encoded content.
 ///
-/// Returns an empty string if:
-/// - Terminal doesn't respond within timeout
-/// - Clipboard is empty
+/// Returns an empty string if the clipboard is empty.
 ///
 /// Returns an error if:
+/// - Terminal doesn't respond within timeout ([`NoResponse`])
 /// - Terminal operations fail
 /// - Response is malformed
 /// - Response exceeds size limit
-pub fn query_clipboard(timeout_ms: u64) -> Result<String> {
+pub fn query_clipboard(target: &str, timeout_ms: u64) -> Result<String> {
     use crate::terminal;
 
     if !terminal::is_stdin_tty() {
@@ -87,13 +388,15 @@ pub fn query_clipboard(timeout_ms: u64) -> Result<String> {
 
     #[allow(clippy::let_unit_value)]
     let _guard = terminal::set_raw_mode().context("Failed to set terminal to raw mode")?;
-    write_sequence(&build_query_sequence()).context("Failed to write OSC 52 query sequence")?;
+    // The reply arrives on stdin, so the query goes to the same terminal
+    write_sequence(&mut *Output::Auto.open()?, &build_sequence(target, "?"))
+        .context("Failed to write OSC 52 query sequence")?;
 
     let response =
         terminal::read_with_timeout(timeout_ms).context("Failed to read OSC 52 response")?;
 
     if response.is_empty() {
-        anyhow::bail!("Terminal doesn't support OSC 52 query (no response)");
+        return Err(NoResponse.into());
     }
 
     parse_response(&response)
@@ -115,6 +418,100 @@ mod tests {
         assert_eq!(build_sequence_raw("SGVsbG8="), "\x1b]52;c;SGVsbG8=\x07");
     }
 
+    #[test]
+    fn test_build_sequence_targets() {
+        assert_eq!(build_sequence("p", "SGVsbG8="), "\x1b]52;p;SGVsbG8=\x07");
+        assert_eq!(build_sequence("pc", ""), "\x1b]52;pc;\x07");
+    }
+
+    #[test]
+    fn test_build_chunked_sequences() {
+        let encoded = "QUFB".repeat(OSC52_CHUNK_SIZE / 4 + 1);
+        let sequences = build_chunked_sequences("c", &encoded);
+        assert_eq!(sequences.len(), 3);
+        assert_eq!(sequences[0], "\x1b]52;c;!\x07");
+        assert_eq!(sequences[2], "\x1b]52;c;QUFB\x07");
+        assert!(sequences[1..]
+            .iter()
+            .all(|s| s.len() <= OSC52_CHUNK_SIZE + "\x1b]52;c;\x07".len()));
+    }
+
+    #[test]
+    fn test_chunk_size_keeps_base64_aligned() {
+        assert_eq!(OSC52_CHUNK_SIZE % 4, 0);
+    }
+
+    #[test]
+    fn test_wrap_tmux_doubles_escapes() {
+        assert_eq!(
+            wrap_tmux("\x1b]52;c;SGVsbG8=\x07"),
+            "\x1bPtmux;\x1b\x1b]52;c;SGVsbG8=\x07\x1b\\"
+        );
+        assert_eq!(
+            wrap_tmux("\x1b]52;c;\x1b\\"),
+            "\x1bPtmux;\x1b\x1b]52;c;\x1b\x1b\\\x1b\\"
+        );
+    }
+
+    #[test]
+    fn test_chunk_boundaries() {
+        assert_eq!(chunk("abcdefg", 4), ["abcd", "efg"]);
+        assert_eq!(chunk("abcdefgh", 4), ["abcd", "efgh"]);
+        assert_eq!(chunk("abc", 4), ["abc"]);
+        assert!(chunk("", 4).is_empty());
+    }
+
+    #[test]
+    fn test_chunk_keeps_characters_whole() {
+        // "é" is 2 bytes; a 5-byte limit would split the third one
+        assert_eq!(chunk("ééé", 5), ["éé", "é"]);
+        assert_eq!(chunk("ééé", 5).concat(), "ééé");
+    }
+
+    #[test]
+    fn test_wrap_screen_small_sequence() {
+        assert_eq!(
+            wrap_screen("\x1b]52;c;SGVsbG8=\x07"),
+            "\x1bP\x1b]52;c;SGVsbG8=\x07\x1b\\"
+        );
+    }
+
+    #[test]
+    fn test_wrap_screen_chunks_under_limit() {
+        let sequence = build_sequence_raw(&"A".repeat(2000));
+        let wrapped = wrap_screen(&sequence);
+        let pieces: Vec<_> = wrapped.split_inclusive("\x1b\\").collect();
+        assert_eq!(pieces.len(), sequence.len().div_ceil(SCREEN_CHUNK_SIZE));
+        assert!(pieces.iter().all(|piece| piece.len() < 768));
+        let unwrapped: String = pieces
+            .iter()
+            .map(|piece| &piece[2..piece.len() - 2])
+            .collect();
+        assert_eq!(unwrapped, sequence);
+    }
+
+    #[test]
+    fn test_write_streamed_matches_wrap() {
+        // Several encoding steps, and a screen piece boundary mid-step
+        let data: Vec<u8> = (0..2 * WRITE_BUFFER + 1000).map(|i| i as u8).collect();
+        for data in [&data[..], b"", b"Hello"] {
+            let sequence = build_sequence("p", &general_purpose::STANDARD.encode(data));
+            for passthrough in [Passthrough::None, Passthrough::Tmux, Passthrough::Screen] {
+                let mut out = Vec::new();
+                write_streamed(&mut out, "p", data, passthrough).unwrap();
+                let expected = format!("\x1b[?7l{}\x1b[?7h", passthrough.wrap(&sequence));
+                assert!(out == expected.as_bytes(), "{:?}", passthrough);
+            }
+        }
+    }
+
+    #[test]
+    fn test_passthrough_none_is_identity() {
+        let sequence = build_sequence_raw("SGVsbG8=");
+        assert_eq!(Passthrough::None.wrap(&sequence), sequence);
+        assert_eq!(Passthrough::Tmux.wrap(&sequence), wrap_tmux(&sequence));
+    }
+
     #[test]
     fn test_osc52_size_limit() {
         let small_text = "Hello World";
@@ -125,6 +522,23 @@ mod tests {
         let large_text = "x".repeat(8 * 1024 * 1024);
         let encoded = general_purpose::STANDARD.encode(&large_text);
         assert!(encoded.len() > OSC52_MAX_SIZE);
+        // The input limit is exactly what fits
+        let encoded = general_purpose::STANDARD.encode("x".repeat(OSC52_MAX_INPUT));
+        assert_eq!(encoded.len(), OSC52_MAX_SIZE);
+    }
+
+    #[test]
+    fn test_write_sequence_disables_autowrap() {
+        let mut out = Vec::new();
+        write_sequence(&mut out, "\x1b]52;c;\x07").unwrap();
+        assert_eq!(out, b"\x1b[?7l\x1b]52;c;\x07\x1b[?7h");
+    }
+
+    #[test]
+    fn test_output_tty_missing_device() {
+        let output = Output::Tty(PathBuf::from("/nonexistent/tty"));
+        let err = output.open().err().unwrap();
+        assert!(err.to_string().contains("/nonexistent/tty"));
     }
 
     #[test]
@@ -167,6 +581,12 @@ mod tests {
         assert_eq!(parsed, "SGVsbG8=");
     }
 
+    #[test]
+    fn test_parse_primary_response() {
+        let response = "\x1b]52;p;SGVsbG8=\x07";
+        assert_eq!(parse_response(response).unwrap(), "SGVsbG8=");
+    }
+
     #[test]
     fn test_parse_malformed_response_missing_prefix() {
         let response = "SGVsbG8=\x07";
@@ -0,0 +1,192 @@
+//! Per-user storage locations.
+
+use anyhow::Result;
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Directory for persistent data (history, registers).
+///
+/// `$XDG_DATA_HOME/bc` or `~/.local/share/bc` on Unix, `%APPDATA%\bc` on Windows.
+pub fn data_dir() -> Result<PathBuf> {
+    if let Some(dir) = env::var_os("XDG_DATA_HOME").filter(|d| !d.is_empty()) {
+        return Ok(PathBuf::from(dir).join("bc"));
+    }
+    if cfg!(windows) {
+        if let Some(dir) = env::var_os("APPDATA") {
+            return Ok(PathBuf::from(dir).join("bc"));
+        }
+    }
+    home_dir()
+        .map(|home| home.join(".local").join("share").join("bc"))
+        .ok_or_else(|| anyhow::anyhow!("Cannot determine data directory (HOME is not set)"))
+}
+
+/// Directory for user configuration (`config.toml`).
+///
+/// `$XDG_CONFIG_HOME/bc` or `~/.config/bc` on Unix, `%APPDATA%\bc` on Windows.
+pub fn config_dir() -> Result<PathBuf> {
+    if let Some(dir) = env::var_os("XDG_CONFIG_HOME").filter(|d| !d.is_empty()) {
+        return Ok(PathBuf::from(dir).join("bc"));
+    }
+    if cfg!(windows) {
+        if let Some(dir) = env::var_os("APPDATA") {
+            return Ok(PathBuf::from(dir).join("bc"));
+        }
+    }
+    home_dir()
+        .map(|home| home.join(".config").join("bc"))
+        .ok_or_else(|| anyhow::anyhow!("Cannot determine config directory (HOME is not set)"))
+}
+
+/// How `write_atomic` sets the permissions of the file it writes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileMode {
+    /// Readable only by the current user, for bc's own files (history,
+    /// registers, ...); their directory is created if needed
+    Private,
+    /// Those of the file being replaced, else the defaults for a new file,
+    /// for files the user names (`paste -o`, `shot -o`)
+    Keep,
+}
+
+/// Temp files written by this process so far, to keep their names unique
+static TEMP_FILES: AtomicU64 = AtomicU64::new(0);
+
+/// Replace `path` with what `write` writes, so readers never see partial
+/// content: the data goes to a temp file beside it, unique to this write so
+/// concurrent writers can't mix theirs up, which is synced and renamed over
+/// `path`. The temp file is removed if anything fails.
+pub fn write_atomic(
+    path: &Path,
+    mode: FileMode,
+    write: impl FnOnce(&mut dyn Write) -> io::Result<()>,
+) -> io::Result<()> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not a file path"))?;
+    let tmp = path.with_file_name(format!(
+        ".{}.bc-{}-{}.tmp",
+        name.to_string_lossy(),
+        process::id(),
+        TEMP_FILES.fetch_add(1, Ordering::Relaxed)
+    ));
+    if mode == FileMode::Private {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+    }
+
+    let result = (|| {
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        if mode == FileMode::Private {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = io::BufWriter::new(options.open(&tmp)?);
+        write(&mut file)?;
+        let file = file.into_inner().map_err(|e| e.into_error())?;
+        if mode == FileMode::Keep {
+            if let Ok(metadata) = fs::metadata(path) {
+                fs::set_permissions(&tmp, metadata.permissions())?;
+            }
+        }
+        file.sync_all()?;
+        drop(file);
+        fs::rename(&tmp, path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
+
+fn home_dir() -> Option<PathBuf> {
+    env::var_os("HOME")
+        .or_else(|| env::var_os("USERPROFILE"))
+        .filter(|h| !h.is_empty())
+        .map(PathBuf::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(path: &Path, mode: FileMode, data: &str) -> io::Result<()> {
+        write_atomic(path, mode, |out| out.write_all(data.as_bytes()))
+    }
+
+    #[test]
+    fn test_write_atomic() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested").join("state.json");
+        write(&path, FileMode::Private, "one").unwrap();
+        write(&path, FileMode::Private, "two").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "two");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+            assert_eq!(mode(&path), 0o600);
+
+            // Keep carries the replaced file's permissions over
+            let shared = dir.path().join("shared.txt");
+            fs::write(&shared, "old").unwrap();
+            fs::set_permissions(&shared, fs::Permissions::from_mode(0o640)).unwrap();
+            write(&shared, FileMode::Keep, "new").unwrap();
+            assert_eq!(mode(&shared), 0o640);
+            assert_eq!(fs::read_to_string(&shared).unwrap(), "new");
+        }
+
+        // A failed rename (onto a directory) leaves no temp file behind
+        let target = dir.path().join("taken");
+        fs::create_dir(&target).unwrap();
+        assert!(write(&target, FileMode::Keep, "x").is_err());
+        let names: Vec<_> = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(names.len(), if cfg!(unix) { 3 } else { 2 }, "{:?}", names);
+    }
+
+    #[test]
+    fn test_concurrent_writers() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("register");
+        write(&path, FileMode::Private, &"-".repeat(65536)).unwrap();
+        let done = std::sync::atomic::AtomicBool::new(false);
+        std::thread::scope(|scope| {
+            // Every read sees one writer's content, whole
+            scope.spawn(|| {
+                while !done.load(Ordering::Relaxed) {
+                    let text = fs::read_to_string(&path).unwrap();
+                    assert_eq!(text.len(), 65536);
+                    assert!(text.bytes().all(|b| b == text.as_bytes()[0]));
+                }
+            });
+            let writers: Vec<_> = ["a", "b", "c", "d"]
+                .into_iter()
+                .map(|c| {
+                    let path = &path;
+                    scope.spawn(move || {
+                        let data = c.repeat(65536);
+                        for _ in 0..50 {
+                            write(path, FileMode::Private, &data).unwrap();
+                        }
+                    })
+                })
+                .collect();
+            for writer in writers {
+                writer.join().unwrap();
+            }
+            done.store(true, Ordering::Relaxed);
+        });
+        // No temp files left over
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+}
@@ -0,0 +1,271 @@
+//! Finding other bc instances on the local network.
+//!
+//! `bc serve --advertise` announces itself over mDNS as a `_bc._tcp`
+//! service named after the machine. `bc peers` lists the instances that
+//! answer, and `bc push`/`bc pull --peer NAME` move clipboard contents to or
+//! from one through its HTTP API (see [`crate::server`]), so no addresses
+//! need configuring.
+
+use anyhow::{Context, Result};
+use mdns_sd::{ResolvedService, ServiceDaemon, ServiceEvent, ServiceInfo};
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant};
+
+use crate::crypto::Key;
+use crate::{debug, verbose};
+
+/// mDNS service type bc instances advertise
+pub const SERVICE_TYPE: &str = "_bc._tcp.local.";
+
+/// How long to wait for peers to answer
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How long push/pull wait for the peer's HTTP server
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A bc instance reachable over the network
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Peer {
+    /// Advertised name, the machine's hostname unless `--name` was given
+    pub name: String,
+    pub addr: SocketAddr,
+    /// Whether the peer serves HTTPS
+    pub tls: bool,
+}
+
+impl Peer {
+    /// The peer's clipboard endpoint
+    pub fn url(&self) -> String {
+        let scheme = if self.tls { "https" } else { "http" };
+        format!("{}://{}/clip", scheme, self.addr)
+    }
+
+    fn from_service(service: &ResolvedService) -> Option<Self> {
+        // Prefer IPv4: link-local IPv6 addresses need a scope id to be usable
+        let mut addresses: Vec<IpAddr> = service
+            .get_addresses()
+            .iter()
+            .map(|ip| ip.to_ip_addr())
+            .collect();
+        addresses.sort_by_key(|ip| (ip.is_ipv6(), *ip));
+        Some(Self {
+            name: instance_name(&service.fullname).to_string(),
+            addr: SocketAddr::new(*addresses.first()?, service.port),
+            tls: service.get_property_val_str("tls") == Some("1"),
+        })
+    }
+}
+
+/// Keeps this instance advertised until dropped
+pub struct Advertisement {
+    daemon: ServiceDaemon,
+}
+
+impl Drop for Advertisement {
+    fn drop(&mut self) {
+        let _ = self.daemon.shutdown();
+    }
+}
+
+/// Announce a bc server listening on `port` as `name`
+pub fn advertise(name: &str, port: u16, tls: bool) -> Result<Advertisement> {
+    let daemon = ServiceDaemon::new().context("Failed to start mDNS")?;
+    let host = format!("{}.local.", name);
+    let properties = [("tls", if tls { "1" } else { "0" })];
+    let service = ServiceInfo::new(SERVICE_TYPE, name, &host, "", port, &properties[..])
+        .context("Invalid peer name")?
+        .enable_addr_auto();
+    daemon
+        .register(service)
+        .context("Failed to advertise over mDNS")?;
+    verbose!("Advertising as '{}' on port {}", name, port);
+    Ok(Advertisement { daemon })
+}
+
+/// This machine's hostname, the default advertised name
+pub fn default_name() -> String {
+    #[cfg(unix)]
+    let name = rustix::system::uname()
+        .nodename()
+        .to_string_lossy()
+        .into_owned();
+    #[cfg(not(unix))]
+    let name = std::env::var("COMPUTERNAME").unwrap_or_default();
+    // Drop any domain: the name is advertised under .local
+    match name.split('.').next() {
+        Some(short) if !short.is_empty() => short.to_string(),
+        _ => "bc".to_string(),
+    }
+}
+
+/// Every peer that answers within `timeout`, sorted by name
+pub fn discover(timeout: Duration) -> Result<Vec<Peer>> {
+    browse(timeout, |_| false)
+}
+
+/// Find a peer by name. `HOST:PORT` skips discovery and is used as given.
+pub fn resolve(peer: &str, timeout: Duration) -> Result<Peer> {
+    if let Ok(addr) = peer.parse::<SocketAddr>() {
+        return Ok(Peer {
+            name: peer.to_string(),
+            addr,
+            tls: false,
+        });
+    }
+    let peers = browse(timeout, |found| found.name.eq_ignore_ascii_case(peer))?;
+    pick(peers, peer)
+}
+
+fn browse(timeout: Duration, mut stop: impl FnMut(&Peer) -> bool) -> Result<Vec<Peer>> {
+    let daemon = ServiceDaemon::new().context("Failed to start mDNS")?;
+    let events = daemon
+        .browse(SERVICE_TYPE)
+        .context("Failed to browse for peers")?;
+    let deadline = Instant::now() + timeout;
+    let mut peers: Vec<Peer> = Vec::new();
+    while let Ok(event) = events.recv_deadline(deadline) {
+        let ServiceEvent::ServiceResolved(service) = event else {
+            continue;
+        };
+        let Some(peer) = Peer::from_service(&service) else {
+            continue;
+        };
+        debug!("Found peer '{}' at {}", peer.name, peer.addr);
+        let done = stop(&peer);
+        if !peers.iter().any(|known| known.name == peer.name) {
+            peers.push(peer);
+        }
+        if done {
+            break;
+        }
+    }
+    let _ = daemon.shutdown();
+    peers.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(peers)
+}
+
+/// The peer called `name` (case-insensitively)
+fn pick(peers: Vec<Peer>, name: &str) -> Result<Peer> {
+    let known: Vec<String> = peers.iter().map(|peer| peer.name.clone()).collect();
+    peers
+        .into_iter()
+        .find(|peer| peer.name.eq_ignore_ascii_case(name))
+        .ok_or_else(|| match known.as_slice() {
+            [] => anyhow::anyhow!(
+                "No peer named '{}' found (no peers answered; is `bc serve --advertise` running?)",
+                name
+            ),
+            _ => anyhow::anyhow!(
+                "No peer named '{}' found (found: {})",
+                name,
+                known.join(", ")
+            ),
+        })
+}
+
+/// `laptop._bc._tcp.local.` -> `laptop`
+fn instance_name(fullname: &str) -> &str {
+    fullname
+        .strip_suffix(SERVICE_TYPE)
+        .and_then(|name| name.strip_suffix('.'))
+        .unwrap_or(fullname)
+}
+
+/// Replace the peer's clipboard with `text`, sealed with `key` if given
+pub fn push(peer: &Peer, token: Option<&str>, key: Option<&Key>, text: &str) -> Result<()> {
+    let body = match key {
+        Some(key) => key.seal_text(text)?,
+        None => text.to_string(),
+    };
+    request(peer, token, "POST")
+        .send_string(&body)
+        .map_err(|e| request_error(peer, e))?;
+    Ok(())
+}
+
+/// Read the peer's clipboard, opening it with `key` if given
+pub fn pull(peer: &Peer, token: Option<&str>, key: Option<&Key>) -> Result<String> {
+    let body = request(peer, token, "GET")
+        .call()
+        .map_err(|e| request_error(peer, e))?
+        .into_string()
+        .with_context(|| format!("Failed to read the clipboard from '{}'", peer.name))?;
+    match key {
+        Some(key) => key.open_text(&body).map_err(|e| {
+            anyhow::anyhow!(
+                "Failed to decrypt the clipboard from '{}': {}",
+                peer.name,
+                e
+            )
+        }),
+        None => Ok(body),
+    }
+}
+
+fn request(peer: &Peer, token: Option<&str>, method: &str) -> ureq::Request {
+    let request = ureq::request(method, &peer.url()).timeout(REQUEST_TIMEOUT);
+    match token {
+        Some(token) => request.set("Authorization", &format!("Bearer {}", token)),
+        None => request,
+    }
+}
+
+fn request_error(peer: &Peer, error: ureq::Error) -> anyhow::Error {
+    match error {
+        ureq::Error::Status(401, _) => anyhow::anyhow!(
+            "'{}' rejected the token (pass the one its `bc serve` printed with --token or BC_SERVE_TOKEN)",
+            peer.name
+        ),
+        ureq::Error::Status(status, response) => anyhow::anyhow!(
+            "'{}' answered {}: {}",
+            peer.name,
+            status,
+            response.into_string().unwrap_or_default().trim_end()
+        ),
+        ureq::Error::Transport(e) => {
+            anyhow::anyhow!("Failed to reach '{}' at {}: {}", peer.name, peer.addr, e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn peer(name: &str) -> Peer {
+        Peer {
+            name: name.to_string(),
+            addr: "192.168.1.2:9111".parse().unwrap(),
+            tls: false,
+        }
+    }
+
+    #[test]
+    fn test_instance_name() {
+        assert_eq!(instance_name("laptop._bc._tcp.local."), "laptop");
+        assert_eq!(instance_name("odd"), "odd");
+    }
+
+    #[test]
+    fn test_pick_ignores_case() {
+        let found = pick(vec![peer("desktop"), peer("Laptop")], "laptop").unwrap();
+        assert_eq!(found.name, "Laptop");
+        let err = pick(vec![peer("desktop")], "laptop").unwrap_err();
+        assert!(err.to_string().contains("found: desktop"));
+        let err = pick(Vec::new(), "laptop").unwrap_err();
+        assert!(err.to_string().contains("no peers answered"));
+    }
+
+    #[test]
+    fn test_resolve_address_skips_discovery() {
+        let found = resolve("10.0.0.5:9111", Duration::ZERO).unwrap();
+        assert_eq!(found.url(), "http://10.0.0.5:9111/clip");
+    }
+
+    #[test]
+    fn test_default_name_is_short() {
+        let name = default_name();
+        assert!(!name.is_empty());
+        assert!(!name.contains('.'));
+    }
+}
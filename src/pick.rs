@@ -0,0 +1,319 @@
+//! Fuzzy selection of a history entry on the terminal (`bc pick`), matched
+//! with nucleo.
+
+use anyhow::{Context, Result};
+use nucleo_matcher::pattern::{CaseMatching, Normalization, Pattern};
+use nucleo_matcher::{Config, Matcher, Utf32Str};
+use std::cmp::Reverse;
+use std::io::{self, Write};
+
+use crate::terminal;
+
+/// Candidate rows shown below the prompt
+pub const HEIGHT: usize = 10;
+/// Width to draw to when the terminal's is unknown
+const DEFAULT_WIDTH: usize = 80;
+
+/// A key press the picker reacts to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    Char(char),
+    Backspace,
+    /// Ctrl-U
+    ClearQuery,
+    Up,
+    Down,
+    Enter,
+    /// Esc, Ctrl-C, or Ctrl-G
+    Cancel,
+}
+
+/// The keys in a chunk of raw terminal input; unknown escape sequences and
+/// control characters are dropped
+pub fn parse_keys(input: &str) -> Vec<Key> {
+    if input == "\x1b" {
+        return vec![Key::Cancel];
+    }
+    let mut keys = Vec::new();
+    let mut chars = input.chars();
+    while let Some(c) = chars.next() {
+        keys.push(match c {
+            '\r' | '\n' => Key::Enter,
+            '\x7f' | '\x08' => Key::Backspace,
+            '\x15' => Key::ClearQuery,
+            '\x10' => Key::Up,
+            '\x0e' => Key::Down,
+            '\x03' | '\x07' => Key::Cancel,
+            '\x1b' => match (chars.next(), chars.next()) {
+                (Some('[' | 'O'), Some('A')) => Key::Up,
+                (Some('[' | 'O'), Some('B')) => Key::Down,
+                _ => continue,
+            },
+            c if c.is_control() => continue,
+            c => Key::Char(c),
+        });
+    }
+    keys
+}
+
+/// How a pick ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// The index of the chosen candidate
+    Picked(usize),
+    Cancelled,
+}
+
+/// Filtering state: the query, the candidates matching it (best first),
+/// and the highlighted one
+pub struct Picker {
+    /// Candidates flattened to one line, in their original order
+    lines: Vec<String>,
+    query: String,
+    /// Indices into `lines`
+    matches: Vec<usize>,
+    /// Position in `matches`
+    selected: usize,
+    matcher: Matcher,
+}
+
+impl Picker {
+    /// Pick from `candidates`, listed in the given order until a query
+    /// ranks them
+    pub fn new<S: AsRef<str>>(candidates: &[S]) -> Self {
+        let lines: Vec<String> = candidates
+            .iter()
+            .map(|candidate| {
+                candidate
+                    .as_ref()
+                    .split_whitespace()
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect();
+        Self {
+            matches: (0..lines.len()).collect(),
+            lines,
+            query: String::new(),
+            selected: 0,
+            matcher: Matcher::new(Config::DEFAULT),
+        }
+    }
+
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    /// Indices of the matching candidates, best first
+    pub fn matches(&self) -> &[usize] {
+        &self.matches
+    }
+
+    /// Index of the highlighted candidate, if any match
+    pub fn selected(&self) -> Option<usize> {
+        self.matches.get(self.selected).copied()
+    }
+
+    /// React to `key`; `Some` once the pick is over
+    pub fn handle(&mut self, key: Key) -> Option<Outcome> {
+        match key {
+            Key::Char(c) => {
+                self.query.push(c);
+                self.refilter();
+            }
+            Key::Backspace => {
+                if self.query.pop().is_some() {
+                    self.refilter();
+                }
+            }
+            Key::ClearQuery => {
+                self.query.clear();
+                self.refilter();
+            }
+            Key::Up => self.selected = self.selected.saturating_sub(1),
+            Key::Down => {
+                if self.selected + 1 < self.matches.len() {
+                    self.selected += 1;
+                }
+            }
+            Key::Enter => return self.selected().map(Outcome::Picked),
+            Key::Cancel => return Some(Outcome::Cancelled),
+        }
+        None
+    }
+
+    fn refilter(&mut self) {
+        self.selected = 0;
+        if self.query.is_empty() {
+            self.matches = (0..self.lines.len()).collect();
+            return;
+        }
+        let pattern = Pattern::parse(&self.query, CaseMatching::Smart, Normalization::Smart);
+        let mut buf = Vec::new();
+        let mut scored: Vec<(usize, u32)> = self
+            .lines
+            .iter()
+            .enumerate()
+            .filter_map(|(i, line)| {
+                pattern
+                    .score(Utf32Str::new(line, &mut buf), &mut self.matcher)
+                    .map(|score| (i, score))
+            })
+            .collect();
+        // Stable, so equal scores keep the original order
+        scored.sort_by_key(|&(_, score)| Reverse(score));
+        self.matches = scored.into_iter().map(|(i, _)| i).collect();
+    }
+
+    /// The prompt line and up to `HEIGHT` candidates, each cut to `width`
+    /// columns, with the highlighted one in reverse video. Ends with the
+    /// cursor back after the query.
+    pub fn render(&self, width: usize) -> String {
+        let offset = self.selected.saturating_sub(HEIGHT - 1);
+        let rows: Vec<usize> = self
+            .matches
+            .iter()
+            .skip(offset)
+            .take(HEIGHT)
+            .copied()
+            .collect();
+        let count = format!("  {}/{}", self.matches.len(), self.lines.len());
+        let prompt = format!("> {}", self.query);
+        let mut frame = format!("\r\x1b[J{}", truncate(&(prompt.clone() + &count), width));
+        for (row, &i) in rows.iter().enumerate() {
+            let line = truncate(&self.lines[i], width.saturating_sub(2));
+            if offset + row == self.selected {
+                frame.push_str(&format!("\r\n\x1b[7m> {}\x1b[0m", line));
+            } else {
+                frame.push_str(&format!("\r\n  {}", line));
+            }
+        }
+        if !rows.is_empty() {
+            frame.push_str(&format!("\x1b[{}A", rows.len()));
+        }
+        frame.push('\r');
+        let column = prompt.chars().count().min(width.saturating_sub(1));
+        if column > 0 {
+            frame.push_str(&format!("\x1b[{}C", column));
+        }
+        frame
+    }
+}
+
+/// The first `width` characters of `line`
+fn truncate(line: &str, width: usize) -> String {
+    line.chars().take(width).collect()
+}
+
+/// Let the user pick one of `candidates` on the terminal (stdin and
+/// stderr). `None` if they cancel.
+pub fn run<S: AsRef<str>>(candidates: &[S]) -> Result<Option<usize>> {
+    if !terminal::is_stdin_tty() {
+        anyhow::bail!("bc pick needs a terminal on stdin");
+    }
+    let mut picker = Picker::new(candidates);
+    let _guard = terminal::set_raw_mode()?;
+    let mut stderr = io::stderr().lock();
+    let width = terminal::width().unwrap_or(DEFAULT_WIDTH);
+    let outcome = loop {
+        stderr
+            .write_all(picker.render(width).as_bytes())
+            .and_then(|_| stderr.flush())
+            .context("Failed to draw the picker")?;
+        let input = terminal::read_keys()?;
+        if input.is_empty() {
+            break Outcome::Cancelled;
+        }
+        if let Some(outcome) = parse_keys(&input)
+            .into_iter()
+            .find_map(|key| picker.handle(key))
+        {
+            break outcome;
+        }
+    };
+    // Leave the screen as it was
+    let _ = stderr.write_all(b"\r\x1b[J").and_then(|_| stderr.flush());
+    Ok(match outcome {
+        Outcome::Picked(index) => Some(index),
+        Outcome::Cancelled => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn type_query(picker: &mut Picker, query: &str) {
+        for c in query.chars() {
+            assert_eq!(picker.handle(Key::Char(c)), None);
+        }
+    }
+
+    #[test]
+    fn test_parse_keys() {
+        assert_eq!(
+            parse_keys("ab\x7f\r"),
+            [Key::Char('a'), Key::Char('b'), Key::Backspace, Key::Enter]
+        );
+        assert_eq!(
+            parse_keys("\x1b[A\x1bOB\x10\x0e"),
+            [Key::Up, Key::Down, Key::Up, Key::Down]
+        );
+        assert_eq!(parse_keys("\x1b"), [Key::Cancel]);
+        assert_eq!(parse_keys("\x03"), [Key::Cancel]);
+        // Unknown sequences and controls are dropped
+        assert_eq!(parse_keys("\x1b[Cx\x01"), [Key::Char('x')]);
+    }
+
+    #[test]
+    fn test_filter_and_pick() {
+        let mut picker = Picker::new(&["git status", "cargo test", "git\n  commit -m wip"]);
+        assert_eq!(picker.matches(), [0, 1, 2]);
+
+        type_query(&mut picker, "gcm");
+        assert_eq!(picker.matches(), [2]);
+        assert_eq!(picker.handle(Key::Enter), Some(Outcome::Picked(2)));
+
+        assert_eq!(picker.handle(Key::ClearQuery), None);
+        assert_eq!(picker.query(), "");
+        assert_eq!(picker.matches(), [0, 1, 2]);
+
+        type_query(&mut picker, "zzz");
+        assert!(picker.matches().is_empty());
+        assert_eq!(picker.handle(Key::Enter), None);
+        assert_eq!(picker.handle(Key::Cancel), Some(Outcome::Cancelled));
+    }
+
+    #[test]
+    fn test_selection_moves_within_matches() {
+        let mut picker = Picker::new(&["a1", "a2", "b"]);
+        picker.handle(Key::Up);
+        assert_eq!(picker.selected(), Some(0));
+        picker.handle(Key::Down);
+        picker.handle(Key::Down);
+        picker.handle(Key::Down);
+        assert_eq!(picker.selected(), Some(2));
+
+        // A new query starts from the best match again
+        type_query(&mut picker, "a");
+        assert_eq!(picker.selected(), Some(0));
+        picker.handle(Key::Down);
+        picker.handle(Key::Down);
+        assert_eq!(picker.selected(), Some(1));
+    }
+
+    #[test]
+    fn test_render() {
+        let mut picker = Picker::new(&["first entry", "second"]);
+        let frame = picker.render(10);
+        assert!(frame.starts_with("\r\x1b[J>   2/2"), "{:?}", frame);
+        assert!(frame.contains("\x1b[7m> first en\x1b[0m"), "{:?}", frame);
+        assert!(frame.contains("\r\n  second"), "{:?}", frame);
+        assert!(frame.ends_with("\x1b[2A\r\x1b[2C"), "{:?}", frame);
+
+        type_query(&mut picker, "sec");
+        let frame = picker.render(10);
+        assert!(frame.starts_with("\r\x1b[J> sec  1/2"), "{:?}", frame);
+        assert!(frame.ends_with("\x1b[1A\r\x1b[5C"), "{:?}", frame);
+    }
+}
@@ -0,0 +1,94 @@
+//! Display helpers for copy previews (`--preview`) and listings.
+
+/// How much of forced binary content the preview dumps
+pub const HEXDUMP_BYTES: usize = 256;
+
+/// Escape control characters for display, in color (so they stand out
+/// from the text) if `color`
+pub fn escape_control_chars(chars: impl Iterator<Item = char>, color: bool) -> String {
+    chars
+        .map(|c| {
+            let escaped = match c {
+                '\n' => "\\n".to_string(),
+                '\r' => "\\r".to_string(),
+                '\t' => "\\t".to_string(),
+                '\x0c' => "\\f".to_string(),
+                c if c.is_control() => format!("\\x{:02x}", c as u32),
+                c => return c.to_string(),
+            };
+            if color {
+                format!("\x1b[36m{}\x1b[0m", escaped)
+            } else {
+                escaped
+            }
+        })
+        .collect()
+}
+
+/// `hexdump -C` style lines for the first `limit` bytes: offset, sixteen
+/// hex bytes in two groups, and the printable ASCII
+pub fn hexdump(bytes: &[u8], limit: usize) -> String {
+    let shown = &bytes[..bytes.len().min(limit)];
+    let mut out = String::new();
+    for (row, chunk) in shown.chunks(16).enumerate() {
+        let mut hex = String::with_capacity(49);
+        for (i, byte) in chunk.iter().enumerate() {
+            if i == 8 {
+                hex.push(' ');
+            }
+            hex.push_str(&format!("{:02x} ", byte));
+        }
+        let ascii: String = chunk
+            .iter()
+            .map(|&byte| {
+                if byte.is_ascii_graphic() || byte == b' ' {
+                    char::from(byte)
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        out.push_str(&format!("{:08x}  {:<49} |{}|\n", row * 16, hex, ascii));
+    }
+    if bytes.len() > shown.len() {
+        out.push_str(&format!("... ({} more bytes)\n", bytes.len() - shown.len()));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape_control_chars() {
+        assert!(escape_control_chars("hello\n".chars(), false).contains("\\n"));
+        assert!(escape_control_chars("hello\r".chars(), false).contains("\\r"));
+        assert!(escape_control_chars("hello\t".chars(), false).contains("\\t"));
+        assert!(escape_control_chars("hello\x01".chars(), false).contains("\\x01"));
+        assert_eq!(
+            escape_control_chars("a\tb".chars(), true),
+            "a\x1b[36m\\t\x1b[0mb"
+        );
+    }
+
+    #[test]
+    fn test_hexdump_layout() {
+        let bytes: Vec<u8> = b"Hello, world!\n\x00\x01\x02\xff tail".to_vec();
+        assert_eq!(
+            hexdump(&bytes, HEXDUMP_BYTES),
+            "00000000  48 65 6c 6c 6f 2c 20 77  6f 72 6c 64 21 0a 00 01  |Hello, world!...|\n\
+             00000010  02 ff 20 74 61 69 6c                              |.. tail|\n"
+        );
+    }
+
+    #[test]
+    fn test_hexdump_limit() {
+        let bytes = vec![0u8; 300];
+        let dump = hexdump(&bytes, HEXDUMP_BYTES);
+        assert_eq!(dump.lines().count(), 17);
+        assert!(dump.contains("000000f0  00"));
+        assert!(dump.ends_with("... (44 more bytes)\n"));
+        assert_eq!(hexdump(&[], HEXDUMP_BYTES), "");
+    }
+}
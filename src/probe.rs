@@ -0,0 +1,221 @@
+//! Terminal capability probe (`bc --probe`).
+//!
+//! Each query is followed by a DA1 request, which every terminal answers,
+//! so an unsupported query shows up as a DA1 reply with nothing before it
+//! instead of a long timeout.
+
+use anyhow::{Context, Result};
+use base64::Engine as _;
+use std::env;
+use std::fmt;
+
+use crate::backends::{ClipboardBackend, Osc52Backend};
+use crate::osc52;
+use crate::terminal;
+
+/// Primary device attributes request (DA1)
+const DA1_QUERY: &str = "\x1b[c";
+/// Terminal name and version request (XTVERSION)
+const XTVERSION_QUERY: &str = "\x1b[>0q";
+/// How long to wait for each reply
+const REPLY_TIMEOUT_MS: u64 = 1000;
+
+/// What the current terminal supports
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Report {
+    /// DA1 reply parameters (e.g. `62;22`)
+    pub device_attributes: Option<String>,
+    /// XTVERSION reply (e.g. `kitty(0.31.0)`)
+    pub version: Option<String>,
+    /// Terminal multiplexer in the way, if any
+    pub multiplexer: Option<&'static str>,
+    /// Whether tmux forwards DCS passthrough (`allow-passthrough`); None
+    /// outside tmux or when tmux can't be asked
+    pub tmux_passthrough: Option<bool>,
+    /// Whether an OSC 52 write was read back; None when reads don't work,
+    /// so the write can't be verified
+    pub osc52_write: Option<bool>,
+    /// Whether the terminal answers OSC 52 queries
+    pub osc52_read: bool,
+}
+
+/// Probe the terminal on stdin/stdout. The OSC 52 round trip overwrites the
+/// clipboard; its previous content is restored when it could be read.
+pub fn run(passthrough: bool) -> Result<Report> {
+    if !terminal::is_stdin_tty() {
+        anyhow::bail!("Probing requires a terminal (stdin is not a TTY)");
+    }
+
+    #[allow(clippy::let_unit_value)]
+    let _guard = terminal::set_raw_mode().context("Failed to set terminal to raw mode")?;
+
+    let version = query(XTVERSION_QUERY)?;
+    let (version, device_attributes) =
+        (parse_xtversion(&version.before), version.device_attributes);
+
+    let backend = Osc52Backend::new().with_passthrough(passthrough);
+    let original = read_clipboard()?;
+    let osc52_read = original.is_some();
+    let osc52_write = if osc52_read {
+        let token = format!("bc-probe-{}", std::process::id());
+        backend.copy(&token)?;
+        let roundtrip = read_clipboard()?;
+        if let Some(original) = &original {
+            backend.copy(original)?;
+        }
+        Some(roundtrip.as_deref() == Some(token.as_str()))
+    } else {
+        None
+    };
+
+    let multiplexer = if env::var_os("TMUX").is_some() {
+        Some("tmux")
+    } else if env::var_os("STY").is_some() {
+        Some("screen")
+    } else {
+        None
+    };
+    let tmux_passthrough = match multiplexer {
+        Some("tmux") => osc52::tmux_option("allow-passthrough").map(|value| value != "off"),
+        _ => None,
+    };
+
+    Ok(Report {
+        device_attributes,
+        version,
+        multiplexer,
+        tmux_passthrough,
+        osc52_write,
+        osc52_read,
+    })
+}
+
+/// Reply to a query, split at the DA1 sentinel
+struct Reply {
+    /// Everything that arrived before the DA1 reply
+    before: String,
+    /// DA1 parameters, if the terminal answered at all
+    device_attributes: Option<String>,
+}
+
+/// Send a query followed by DA1 and read until the DA1 reply
+fn query(request: &str) -> Result<Reply> {
+    osc52::write_sequence(
+        &mut *osc52::Output::Auto.open()?,
+        &format!("{}{}", request, DA1_QUERY),
+    )?;
+    let response = terminal::read_until(REPLY_TIMEOUT_MS, |r| find_da1(r).is_some())?;
+    Ok(match find_da1(&response) {
+        Some((start, params)) => Reply {
+            before: response[..start].to_string(),
+            device_attributes: Some(params.to_string()),
+        },
+        None => Reply {
+            before: response,
+            device_attributes: None,
+        },
+    })
+}
+
+/// Read the clipboard via OSC 52; None if the terminal doesn't answer
+fn read_clipboard() -> Result<Option<String>> {
+    let reply = query(&osc52::build_query_sequence())?;
+    let Ok(encoded) = osc52::parse_response(&reply.before) else {
+        return Ok(None);
+    };
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(encoded)
+        .context("Failed to decode base64 clipboard content")?;
+    Ok(Some(String::from_utf8_lossy(&bytes).into_owned()))
+}
+
+/// Find a DA1 reply (`\x1b[?{params}c`): its start and parameters
+fn find_da1(response: &str) -> Option<(usize, &str)> {
+    let start = response.rfind("\x1b[?")?;
+    let rest = &response[start + 3..];
+    let end = rest.find(|c: char| !(c.is_ascii_digit() || c == ';'))?;
+    (rest.as_bytes()[end] == b'c').then(|| (start, &rest[..end]))
+}
+
+/// Extract the name from an XTVERSION reply (`\x1bP>|{name}\x1b\\`)
+fn parse_xtversion(response: &str) -> Option<String> {
+    let start = response.find("\x1bP>|")? + 4;
+    let len = response[start..].find("\x1b\\")?;
+    Some(response[start..start + len].to_string())
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let yes_no = |supported: bool| if supported { "yes" } else { "no" };
+        writeln!(
+            f,
+            "Terminal:          {}",
+            self.version
+                .as_deref()
+                .unwrap_or("unknown (no XTVERSION reply)")
+        )?;
+        writeln!(
+            f,
+            "Device attributes: {}",
+            self.device_attributes.as_deref().unwrap_or("no reply")
+        )?;
+        match (self.multiplexer, self.tmux_passthrough) {
+            (None, _) => writeln!(f, "Multiplexer:       none")?,
+            (Some(name), None) => writeln!(f, "Multiplexer:       {}", name)?,
+            (Some(name), Some(allowed)) => writeln!(
+                f,
+                "Multiplexer:       {} (passthrough {})",
+                name,
+                if allowed { "allowed" } else { "disabled" }
+            )?,
+        }
+        writeln!(
+            f,
+            "OSC 52 write:      {}",
+            match self.osc52_write {
+                Some(verified) => yes_no(verified),
+                None => "unknown (can't read back)",
+            }
+        )?;
+        write!(f, "OSC 52 read:       {}", yes_no(self.osc52_read))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_da1() {
+        assert_eq!(find_da1("\x1b[?62;22c"), Some((0, "62;22")));
+        assert_eq!(find_da1("junk\x1b[?1;2c"), Some((4, "1;2")));
+        assert_eq!(find_da1("\x1b[?62;22"), None);
+        assert_eq!(find_da1("\x1b]52;c;?\x07"), None);
+    }
+
+    #[test]
+    fn test_parse_xtversion() {
+        assert_eq!(
+            parse_xtversion("\x1bP>|kitty(0.31.0)\x1b\\"),
+            Some("kitty(0.31.0)".to_string())
+        );
+        assert_eq!(parse_xtversion(""), None);
+    }
+
+    #[test]
+    fn test_report_display() {
+        let report = Report {
+            device_attributes: Some("62;22".to_string()),
+            version: None,
+            multiplexer: Some("tmux"),
+            tmux_passthrough: Some(false),
+            osc52_write: None,
+            osc52_read: false,
+        };
+        let text = report.to_string();
+        assert!(text.contains("unknown (no XTVERSION reply)"));
+        assert!(text.contains("tmux (passthrough disabled)"));
+        assert!(text.contains("OSC 52 write:      unknown"));
+        assert!(text.ends_with("OSC 52 read:       no"));
+    }
+}
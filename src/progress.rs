@@ -0,0 +1,99 @@
+//! Progress bars on stderr for large reads and OSC 52 writes (indicatif),
+//! shown unless `--no-progress` is given.
+
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use is_terminal::IsTerminal;
+use std::io;
+
+use crate::log::{self, Level};
+
+/// Smaller transfers finish too quickly to need progress
+pub const MIN_BYTES: u64 = 1024 * 1024;
+
+/// Bar for a known total
+const BAR_TEMPLATE: &str = "{msg}: [{bar:30}] {bytes}/{total_bytes} ({bytes_per_sec})";
+/// Counter for an unknown total, such as a pipe
+const COUNTER_TEMPLATE: &str = "{msg}: {bytes} ({bytes_per_sec})";
+
+/// A progress bar for bytes read or written. Draws nothing unless enabled,
+/// stderr is a terminal, and `-q` wasn't given, nor before `MIN_BYTES`
+/// have gone by; cleared when finished or dropped.
+#[derive(Debug)]
+pub struct Progress {
+    bar: ProgressBar,
+    /// Whether the bar may appear once `MIN_BYTES` have gone by
+    enabled: bool,
+}
+
+impl Progress {
+    /// Progress towards `total` bytes, if known
+    pub fn new(label: &'static str, total: Option<u64>, enabled: bool) -> Self {
+        let bar = match total {
+            Some(total) => ProgressBar::with_draw_target(Some(total), ProgressDrawTarget::hidden())
+                .with_style(style(BAR_TEMPLATE).progress_chars("=> ")),
+            None => ProgressBar::with_draw_target(None, ProgressDrawTarget::hidden())
+                .with_style(style(COUNTER_TEMPLATE)),
+        };
+        Self {
+            bar: bar.with_message(label),
+            enabled: enabled && log::enabled(Level::Normal) && io::stderr().is_terminal(),
+        }
+    }
+
+    /// Progress that is never shown
+    pub fn hidden() -> Self {
+        Self::new("", None, false)
+    }
+
+    /// Count `n` more bytes
+    pub fn advance(&mut self, n: usize) {
+        self.bar.inc(n as u64);
+        if self.enabled && self.bar.is_hidden() && self.bar.position() >= MIN_BYTES {
+            self.bar.set_draw_target(ProgressDrawTarget::stderr());
+        }
+    }
+
+    /// Remove the bar
+    pub fn finish(&mut self) {
+        self.bar.finish_and_clear();
+    }
+
+    /// Bytes counted so far
+    pub fn position(&self) -> u64 {
+        self.bar.position()
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+fn style(template: &str) -> ProgressStyle {
+    ProgressStyle::with_template(template).expect("progress template is valid")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_templates() {
+        style(BAR_TEMPLATE);
+        style(COUNTER_TEMPLATE);
+    }
+
+    #[test]
+    fn test_disabled_never_draws() {
+        let mut progress = Progress::new("Reading", Some(4 * MIN_BYTES), false);
+        progress.advance(2 * MIN_BYTES as usize);
+        assert_eq!(progress.position(), 2 * MIN_BYTES);
+        assert!(progress.bar.is_hidden());
+
+        let mut progress = Progress::hidden();
+        progress.advance(1);
+        progress.finish();
+        assert!(progress.bar.is_hidden());
+    }
+}
@@ -0,0 +1,56 @@
+//! QR codes drawn with Unicode half blocks (`--qr`), for moving short text
+//! to a phone without a network.
+
+use anyhow::{anyhow, bail, Result};
+use qrcode::render::unicode::Dense1x2;
+use qrcode::{EcLevel, QrCode};
+
+/// Largest content `render` accepts. Longer text makes a code too wide for
+/// most terminals (this is about 105 columns) and too dense to scan.
+pub const MAX_BYTES: usize = 512;
+
+/// `text` as a QR code, two modules per character cell, with a quiet zone.
+/// Light modules are the drawn ones, which suits the usual dark terminal
+/// background.
+pub fn render(text: &str) -> Result<String> {
+    if text.len() > MAX_BYTES {
+        bail!(
+            "Content is too large for a QR code ({} bytes; at most {})",
+            text.len(),
+            MAX_BYTES
+        );
+    }
+    let code = QrCode::with_error_correction_level(text, EcLevel::M)
+        .map_err(|e| anyhow!("Failed to make a QR code: {}", e))?;
+    let mut out = code
+        .render::<Dense1x2>()
+        .dark_color(Dense1x2::Light)
+        .light_color(Dense1x2::Dark)
+        .build();
+    out.push('\n');
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_size() {
+        let qr = render("hello").unwrap();
+        let lines: Vec<&str> = qr.lines().collect();
+        // Version 1 is 21 modules, plus a 4-module quiet zone on each side
+        assert_eq!(lines[0].chars().count(), 29);
+        assert_eq!(lines.len(), 15);
+        // The quiet zone is drawn (light) all the way round
+        assert!(lines[0].chars().all(|c| c == '█'));
+        assert!(qr.ends_with('\n'));
+    }
+
+    #[test]
+    fn test_too_large() {
+        assert!(render(&"x".repeat(MAX_BYTES)).is_ok());
+        let err = render(&"x".repeat(MAX_BYTES + 1)).unwrap_err();
+        assert!(err.to_string().contains("too large"), "{}", err);
+    }
+}
@@ -0,0 +1,170 @@
+//! Named registers stored on disk, independent of the system clipboard.
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::paths::{self, FileMode};
+
+/// Registers directory name inside the data directory
+const REGISTERS_DIR: &str = "registers";
+/// Maximum register name length
+const MAX_NAME_LENGTH: usize = 64;
+
+/// A directory of named registers, one file per register
+#[derive(Debug, Clone)]
+pub struct Registers {
+    dir: PathBuf,
+}
+
+impl Registers {
+    /// Registers at the default location (`~/.local/share/bc/registers`)
+    pub fn open_default() -> Result<Self> {
+        Ok(Self::at(paths::data_dir()?.join(REGISTERS_DIR)))
+    }
+
+    /// Registers stored in an explicit directory
+    pub fn at(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Read a register, or None if it doesn't exist
+    pub fn get(&self, name: &str) -> Result<Option<String>> {
+        match fs::read_to_string(self.path(name)?) {
+            Ok(content) => Ok(Some(content)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).with_context(|| format!("Failed to read register '{}'", name)),
+        }
+    }
+
+    /// Write a register, replacing any previous content
+    pub fn set(&self, name: &str, content: &str) -> Result<()> {
+        let path = self.path(name)?;
+        paths::write_atomic(&path, FileMode::Private, |out| {
+            out.write_all(content.as_bytes())
+        })
+        .with_context(|| format!("Failed to write register '{}'", name))
+    }
+
+    /// Delete a register. Returns Ok(false) if it didn't exist.
+    pub fn delete(&self, name: &str) -> Result<bool> {
+        match fs::remove_file(self.path(name)?) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e).with_context(|| format!("Failed to delete register '{}'", name)),
+        }
+    }
+
+    /// Names of all registers, sorted
+    pub fn list(&self) -> Result<Vec<String>> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to list {}", self.dir.display()))
+            }
+        };
+
+        let mut names: Vec<String> = entries
+            .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+            .filter(|name| validate_name(name).is_ok())
+            .collect();
+        names.sort();
+        Ok(names)
+    }
+
+    fn path(&self, name: &str) -> Result<PathBuf> {
+        validate_name(name)?;
+        Ok(self.dir.join(name))
+    }
+}
+
+/// Register names are limited to ASCII letters, digits, `-` and `_` so they
+/// map safely to file names on every platform
+pub fn validate_name(name: &str) -> Result<()> {
+    if name.is_empty() || name.len() > MAX_NAME_LENGTH {
+        anyhow::bail!(
+            "Invalid register name '{}' (must be 1-{} characters)",
+            name,
+            MAX_NAME_LENGTH
+        );
+    }
+    if !name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        anyhow::bail!(
+            "Invalid register name '{}' (use letters, digits, '-' and '_')",
+            name
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_registers() -> (tempfile::TempDir, Registers) {
+        let dir = tempfile::tempdir().unwrap();
+        let registers = Registers::at(dir.path().join(REGISTERS_DIR));
+        (dir, registers)
+    }
+
+    #[test]
+    fn test_set_get_roundtrip() {
+        let (_dir, registers) = temp_registers();
+        assert_eq!(registers.get("work").unwrap(), None);
+
+        registers.set("work", "hello\n").unwrap();
+        assert_eq!(registers.get("work").unwrap().as_deref(), Some("hello\n"));
+
+        registers.set("work", "replaced").unwrap();
+        assert_eq!(registers.get("work").unwrap().as_deref(), Some("replaced"));
+    }
+
+    #[test]
+    fn test_list_sorted_and_skips_temp_files() {
+        let (_dir, registers) = temp_registers();
+        assert!(registers.list().unwrap().is_empty());
+
+        registers.set("b", "2").unwrap();
+        registers.set("a", "1").unwrap();
+        fs::write(registers.dir().join(".c.tmp"), "partial").unwrap();
+
+        assert_eq!(registers.list().unwrap(), ["a", "b"]);
+    }
+
+    #[test]
+    fn test_delete() {
+        let (_dir, registers) = temp_registers();
+        registers.set("gone", "x").unwrap();
+
+        assert!(registers.delete("gone").unwrap());
+        assert!(!registers.delete("gone").unwrap());
+        assert_eq!(registers.get("gone").unwrap(), None);
+    }
+
+    #[test]
+    fn test_validate_name() {
+        assert!(validate_name("work").is_ok());
+        assert!(validate_name("my-reg_2").is_ok());
+        assert!(validate_name("").is_err());
+        assert!(validate_name("../etc").is_err());
+        assert!(validate_name("a/b").is_err());
+        assert!(validate_name("with space").is_err());
+        assert!(validate_name(&"x".repeat(MAX_NAME_LENGTH + 1)).is_err());
+    }
+
+    #[test]
+    fn test_invalid_name_rejected_by_operations() {
+        let (_dir, registers) = temp_registers();
+        assert!(registers.set("../escape", "x").is_err());
+        assert!(registers.get("../escape").is_err());
+        assert!(registers.delete("../escape").is_err());
+    }
+}
@@ -0,0 +1,78 @@
+//! Rich-text renderings of plain input for HTML copies: syntax-highlighted
+//! code via syntect, and Markdown via pulldown-cmark.
+
+use anyhow::{Context, Result};
+use pulldown_cmark::{html, Options, Parser};
+use syntect::highlighting::ThemeSet;
+use syntect::html::highlighted_html_for_string;
+use syntect::parsing::SyntaxSet;
+
+/// Theme used for highlighting; light, so it reads well in documents
+const THEME: &str = "InspiredGitHub";
+
+/// Render `code` as HTML with inline syntax colors. `language` is a name or
+/// file extension such as `rust`, `rs`, or `py`.
+pub fn highlight_html(code: &str, language: &str) -> Result<String> {
+    let syntaxes = SyntaxSet::load_defaults_newlines();
+    let syntax = syntaxes
+        .find_syntax_by_token(language)
+        .with_context(|| format!("Unknown language '{}' for --highlight", language))?;
+    let themes = ThemeSet::load_defaults();
+    let theme = themes
+        .themes
+        .get(THEME)
+        .context("Highlighting theme is missing")?;
+    highlighted_html_for_string(code, &syntaxes, syntax, theme).context("Failed to highlight code")
+}
+
+/// Render Markdown as HTML, with the GitHub extensions (tables,
+/// strikethrough, task lists) and footnotes
+pub fn markdown_html(markdown: &str) -> String {
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_FOOTNOTES;
+    let mut out = String::with_capacity(markdown.len() * 3 / 2);
+    html::push_html(&mut out, Parser::new_ext(markdown, options));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_highlight_html() {
+        let html = highlight_html("fn main() {}\n", "rust").unwrap();
+        assert!(html.starts_with("<pre"));
+        assert!(html.contains("<span style="));
+        assert!(html.contains("main"));
+        assert_eq!(crate::html::to_text(&html), "fn main() {}");
+    }
+
+    #[test]
+    fn test_language_by_extension() {
+        assert!(highlight_html("x = 1\n", "py").is_ok());
+    }
+
+    #[test]
+    fn test_markdown_html() {
+        let html = markdown_html(
+            "# Title\n\nSome **bold** and [a link](https://x.io).\n\n- one\n- ~~two~~\n",
+        );
+        assert!(html.contains("<h1>Title</h1>"));
+        assert!(html.contains("<strong>bold</strong>"));
+        assert!(html.contains("<a href=\"https://x.io\">a link</a>"));
+        assert!(html.contains("<li><del>two</del></li>"));
+
+        let table = markdown_html("| a | b |\n|---|---|\n| 1 | 2 |\n");
+        assert!(table.contains("<table>"));
+        assert!(table.contains("<td>2</td>"));
+    }
+
+    #[test]
+    fn test_unknown_language() {
+        let err = highlight_html("x", "no-such-language").unwrap_err();
+        assert!(err.to_string().contains("no-such-language"));
+    }
+}
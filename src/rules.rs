@@ -0,0 +1,223 @@
+//! Rewrite rules for `bc watch --apply-rules`.
+//!
+//! Each `[rules.NAME]` table in the config applies `--filter` transforms to
+//! clipboard content matching its regex. `bc rules enable|disable NAME`
+//! records an override in `rules.json` in the data directory instead of
+//! editing the config file, and a running watch picks it up at the next
+//! change.
+
+use anyhow::{Context, Result};
+use regex::Regex;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::{Config, RuleConfig};
+use crate::error::{CodedError, ErrorCode};
+use crate::lock::{self, FileLock};
+use crate::paths::{self, FileMode};
+use crate::transform::{Pipeline, Transform};
+
+/// Override file name inside the data directory
+const STATE_FILE: &str = "rules.json";
+
+/// A configured rule, ready to apply
+#[derive(Debug)]
+pub struct Rule {
+    pub name: String,
+    pub pattern: Regex,
+    /// The transforms as configured, for listing
+    pub transforms: Vec<String>,
+    pub enabled: bool,
+    pipeline: Pipeline,
+}
+
+/// Every configured rule, in name order
+#[derive(Debug, Default)]
+pub struct Rules {
+    rules: Vec<Rule>,
+}
+
+impl Rules {
+    /// The rules in the config file, with the default overrides applied
+    pub fn load() -> Result<Self> {
+        let config = Config::load()?;
+        Self::new(&config.rules, &State::open_default()?.overrides()?)
+    }
+
+    /// Build `configs`, letting `overrides` turn rules on or off. Fails with
+    /// [`ErrorCode::InvalidInput`] on the first rule with a bad regex or
+    /// transform.
+    pub fn new(
+        configs: &BTreeMap<String, RuleConfig>,
+        overrides: &BTreeMap<String, bool>,
+    ) -> Result<Self> {
+        let rules = configs
+            .iter()
+            .map(|(name, config)| {
+                let invalid = |message: String| {
+                    CodedError::new(ErrorCode::InvalidInput, message).with("rule", name.as_str())
+                };
+                let pattern = Regex::new(&config.pattern)
+                    .map_err(|e| invalid(format!("Invalid match in rule '{}': {}", name, e)))?;
+                let pipeline = config
+                    .transforms
+                    .iter()
+                    .map(|spec| spec.parse::<Transform>())
+                    .collect::<Result<Pipeline, _>>()
+                    .map_err(|e| invalid(format!("Invalid rule '{}': {}", name, e)))?;
+                Ok(Rule {
+                    name: name.clone(),
+                    pattern,
+                    transforms: config.transforms.clone(),
+                    enabled: overrides.get(name).copied().unwrap_or(config.enabled),
+                    pipeline,
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { rules })
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Rule> {
+        self.rules.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Run each enabled rule that matches, in name order, on the previous
+    /// one's output. Returns the result and the names of the rules that ran.
+    pub fn apply(&self, text: &str) -> Result<(String, Vec<&str>)> {
+        let mut text = text.to_string();
+        let mut applied = Vec::new();
+        for rule in self.rules.iter().filter(|rule| rule.enabled) {
+            if !rule.pattern.is_match(&text) {
+                continue;
+            }
+            text = rule
+                .pipeline
+                .apply(text)
+                .with_context(|| format!("Rule '{}' failed", rule.name))?;
+            applied.push(rule.name.as_str());
+        }
+        Ok((text, applied))
+    }
+}
+
+/// The overrides `bc rules enable|disable` keeps, by rule name
+#[derive(Debug, Clone)]
+pub struct State {
+    path: PathBuf,
+}
+
+impl State {
+    /// Overrides at the default location (`~/.local/share/bc/rules.json`)
+    pub fn open_default() -> Result<Self> {
+        Ok(Self::at(paths::data_dir()?.join(STATE_FILE)))
+    }
+
+    /// Overrides stored at an explicit path
+    pub fn at(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether each overridden rule is on; a missing file overrides nothing
+    pub fn overrides(&self) -> Result<BTreeMap<String, bool>> {
+        match fs::read_to_string(&self.path) {
+            Ok(text) => serde_json::from_str(&text)
+                .with_context(|| format!("Invalid rule state {}", self.path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+            Err(e) => Err(e).with_context(|| format!("Failed to read {}", self.path.display())),
+        }
+    }
+
+    /// Turn rule `name` on or off
+    pub fn set(&self, name: &str, enabled: bool) -> Result<()> {
+        let _lock = FileLock::acquire(&self.path, lock::DEFAULT_TIMEOUT)?;
+        let mut overrides = self.overrides()?;
+        overrides.insert(name.to_string(), enabled);
+        let data = format!("{}\n", serde_json::to_string_pretty(&overrides)?);
+        paths::write_atomic(&self.path, FileMode::Private, |out| {
+            out.write_all(data.as_bytes())
+        })
+        .with_context(|| format!("Failed to write {}", self.path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(pattern: &str, transforms: &[&str], enabled: bool) -> RuleConfig {
+        RuleConfig {
+            pattern: pattern.to_string(),
+            transforms: transforms.iter().map(|t| t.to_string()).collect(),
+            enabled,
+        }
+    }
+
+    fn configs() -> BTreeMap<String, RuleConfig> {
+        BTreeMap::from([
+            ("a-trim".to_string(), config("^\\s", &["trim"], true)),
+            ("b-shout".to_string(), config("^hello", &["upper"], true)),
+            ("c-off".to_string(), config(".", &["base64"], false)),
+        ])
+    }
+
+    #[test]
+    fn test_apply_chains_matching_rules() {
+        let rules = Rules::new(&configs(), &BTreeMap::new()).unwrap();
+        // Trimming makes the second rule match
+        assert_eq!(
+            rules.apply("  hello\n").unwrap(),
+            ("HELLO".to_string(), vec!["a-trim", "b-shout"])
+        );
+        assert_eq!(rules.apply("other").unwrap(), ("other".to_string(), vec![]));
+    }
+
+    #[test]
+    fn test_overrides() {
+        let overrides =
+            BTreeMap::from([("b-shout".to_string(), false), ("c-off".to_string(), true)]);
+        let rules = Rules::new(&configs(), &overrides).unwrap();
+        let enabled: Vec<_> = rules
+            .iter()
+            .filter(|rule| rule.enabled)
+            .map(|rule| rule.name.as_str())
+            .collect();
+        assert_eq!(enabled, ["a-trim", "c-off"]);
+        assert_eq!(rules.apply("hi").unwrap().0, "aGk=");
+    }
+
+    #[test]
+    fn test_invalid_rules() {
+        let bad = BTreeMap::from([("x".to_string(), config("(", &[], true))]);
+        let err = Rules::new(&bad, &BTreeMap::new()).unwrap_err();
+        assert!(err.to_string().contains("rule 'x'"), "{}", err);
+        let (code, fields) = crate::error::classify(&err, ErrorCode::General);
+        assert_eq!(code, ErrorCode::InvalidInput);
+        assert_eq!(fields["rule"], "x");
+        let bad = BTreeMap::from([("y".to_string(), config(".", &["nope"], true))]);
+        let err = Rules::new(&bad, &BTreeMap::new()).unwrap_err();
+        assert!(err.to_string().contains("unknown filter 'nope'"), "{}", err);
+    }
+
+    #[test]
+    fn test_state_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = State::at(dir.path().join("nested").join(STATE_FILE));
+        assert!(state.overrides().unwrap().is_empty());
+        state.set("a", false).unwrap();
+        state.set("b", true).unwrap();
+        state.set("a", true).unwrap();
+        assert_eq!(
+            state.overrides().unwrap(),
+            BTreeMap::from([("a".to_string(), true), ("b".to_string(), true)])
+        );
+    }
+}
@@ -0,0 +1,193 @@
+//! Running commands around the clipboard: `bc run` captures a command's
+//! output, `bc pipe` feeds the clipboard to a command's stdin.
+//!
+//! For `bc run`, stdout and stderr share one pipe, so the capture
+//! interleaves them the way a terminal would show them. Everything read is
+//! passed through as it arrives.
+
+use anyhow::{anyhow, Context, Result};
+use std::io::{self, Read, Write};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::thread;
+
+use crate::debug;
+
+/// A finished command
+#[derive(Debug)]
+pub struct Captured {
+    /// What the command printed: stdout and stderr combined for `capture`,
+    /// the captured stdout for `feed`
+    pub output: String,
+    pub status: ExitStatus,
+}
+
+/// Run `command` (program and arguments), copying its combined output to
+/// `passthrough` as it is produced
+pub fn capture(command: &[String], passthrough: &mut dyn Write) -> Result<Captured> {
+    let (program, args) = command.split_first().context("No command given")?;
+    debug!("Running '{}'", command_line(command));
+    let (mut reader, writer) = io::pipe().context("Failed to create a pipe")?;
+    let mut child = {
+        let mut command = Command::new(program);
+        command
+            .args(args)
+            .stdout(writer.try_clone().context("Failed to create a pipe")?)
+            .stderr(writer);
+        // Dropping `command` closes our copies of the write end, so the read
+        // below ends when the child (and anything it started) exits
+        spawn(&mut command, program)?
+    };
+
+    let mut output = Vec::new();
+    let mut chunk = [0u8; 8192];
+    loop {
+        let n = match reader.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e).context("Failed to read the command's output"),
+        };
+        output.extend_from_slice(&chunk[..n]);
+        // A closed stdout (`bc run ... | head`) shouldn't stop the capture
+        let _ = passthrough
+            .write_all(&chunk[..n])
+            .and_then(|()| passthrough.flush());
+    }
+    let status = child.wait().context("Failed to wait for the command")?;
+    Ok(Captured {
+        output: String::from_utf8_lossy(&output).into_owned(),
+        status,
+    })
+}
+
+/// Run `command` with `input` on its stdin. Its stdout is captured when
+/// `capture` is set and goes to ours otherwise; stderr always goes to ours.
+pub fn feed(command: &[String], input: &str, capture: bool) -> Result<Captured> {
+    let (program, args) = command.split_first().context("No command given")?;
+    debug!("Running '{}'", command_line(command));
+    let mut child = spawn(
+        Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(if capture {
+                Stdio::piped()
+            } else {
+                Stdio::inherit()
+            }),
+        program,
+    )?;
+
+    // Write from another thread so a large output can't fill its pipe while
+    // we are still writing
+    let mut stdin = child
+        .stdin
+        .take()
+        .context("Failed to open the command's stdin")?;
+    let output = thread::scope(|scope| {
+        scope.spawn(move || {
+            // A command that stops reading early (`head -1`) is fine
+            let _ = stdin.write_all(input.as_bytes());
+        });
+        child.wait_with_output()
+    })
+    .context("Failed to wait for the command")?;
+    Ok(Captured {
+        output: String::from_utf8(output.stdout)
+            .map_err(|_| anyhow!("The command's output is not valid UTF-8 text"))?,
+        status: output.status,
+    })
+}
+
+fn spawn(command: &mut Command, program: &str) -> Result<Child> {
+    command.spawn().map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => anyhow!("Command not found: {}", program),
+        _ => anyhow!("Failed to run {}: {}", program, e),
+    })
+}
+
+/// The text `bc run` copies: a `$ command  # exit status` header, then the
+/// output
+pub fn transcript(command: &[String], captured: &Captured) -> String {
+    let mut text = format!("$ {}  # {}\n", command_line(command), captured.status);
+    text.push_str(&captured.output);
+    if !text.ends_with('\n') {
+        text.push('\n');
+    }
+    text
+}
+
+/// `command` as a shell would need it typed, quoting arguments that need it
+fn command_line(command: &[String]) -> String {
+    command
+        .iter()
+        .map(|arg| {
+            let safe = !arg.is_empty()
+                && arg
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c));
+            if safe {
+                arg.clone()
+            } else {
+                format!("'{}'", arg.replace('\'', r"'\''"))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(command: &[&str]) -> Vec<String> {
+        command.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_command_line_quoting() {
+        assert_eq!(
+            command_line(&args(&["cargo", "test", "--", "--nocapture"])),
+            "cargo test -- --nocapture"
+        );
+        assert_eq!(
+            command_line(&args(&["sh", "-c", "echo 'hi' >&2", ""])),
+            r"sh -c 'echo '\''hi'\'' >&2' ''"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_capture_combines_output_and_status() {
+        let command = args(&["sh", "-c", "echo out; echo err >&2; exit 3"]);
+        let mut passthrough = Vec::new();
+        let captured = capture(&command, &mut passthrough).unwrap();
+        assert_eq!(captured.output, "out\nerr\n");
+        assert_eq!(passthrough, b"out\nerr\n");
+        assert_eq!(captured.status.code(), Some(3));
+        assert_eq!(
+            transcript(&command, &captured),
+            "$ sh -c 'echo out; echo err >&2; exit 3'  # exit status: 3\nout\nerr\n"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_feed_stdin() {
+        let captured = feed(&args(&["tr", "a-z", "A-Z"]), "hello\n", true).unwrap();
+        assert_eq!(captured.output, "HELLO\n");
+        assert!(captured.status.success());
+
+        let captured = feed(&args(&["head", "-c", "1"]), &"x".repeat(1 << 20), true).unwrap();
+        assert_eq!(captured.output, "x");
+
+        let captured = feed(&args(&["sh", "-c", "cat >/dev/null; exit 4"]), "", false).unwrap();
+        assert_eq!(captured.output, "");
+        assert_eq!(captured.status.code(), Some(4));
+    }
+
+    #[test]
+    fn test_missing_command() {
+        let err = capture(&args(&["bc-no-such-command"]), &mut io::sink()).unwrap_err();
+        assert!(err.to_string().contains("not found"), "{}", err);
+    }
+}
@@ -0,0 +1,106 @@
+//! Clipboard selection targets (X11/Wayland primary vs. regular clipboard).
+
+use std::fmt;
+use std::str::FromStr;
+
+/// Which selection an operation targets
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Selection {
+    /// The regular clipboard (Ctrl-C / Ctrl-V)
+    #[default]
+    Clipboard,
+    /// The primary selection (middle-click paste on X11/Wayland)
+    Primary,
+    /// Both clipboard and primary; reads use the clipboard
+    Both,
+}
+
+impl Selection {
+    /// Every selection name, as accepted by `FromStr`
+    pub const NAMES: [&'static str; 3] = ["clipboard", "primary", "both"];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Selection::Clipboard => "clipboard",
+            Selection::Primary => "primary",
+            Selection::Both => "both",
+        }
+    }
+
+    /// OSC 52 selection parameter for writes (`c`, `p`, or both)
+    pub fn osc52_target(self) -> &'static str {
+        match self {
+            Selection::Clipboard => "c",
+            Selection::Primary => "p",
+            Selection::Both => "pc",
+        }
+    }
+
+    /// OSC 52 selection parameter for queries (a single selection)
+    pub fn osc52_query_target(self) -> &'static str {
+        match self {
+            Selection::Primary => "p",
+            Selection::Clipboard | Selection::Both => "c",
+        }
+    }
+
+    pub fn includes_clipboard(self) -> bool {
+        self != Selection::Primary
+    }
+
+    pub fn includes_primary(self) -> bool {
+        self != Selection::Clipboard
+    }
+}
+
+impl FromStr for Selection {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "clipboard" => Ok(Selection::Clipboard),
+            "primary" => Ok(Selection::Primary),
+            "both" => Ok(Selection::Both),
+            _ => Err(format!(
+                "unknown selection '{}' (expected {})",
+                s,
+                Self::NAMES.join(", ")
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Selection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_names_roundtrip() {
+        for name in Selection::NAMES {
+            assert_eq!(name.parse::<Selection>().unwrap().name(), name);
+        }
+        assert!("secondary".parse::<Selection>().is_err());
+    }
+
+    #[test]
+    fn test_osc52_targets() {
+        assert_eq!(Selection::Clipboard.osc52_target(), "c");
+        assert_eq!(Selection::Primary.osc52_target(), "p");
+        assert_eq!(Selection::Both.osc52_target(), "pc");
+        assert_eq!(Selection::Both.osc52_query_target(), "c");
+        assert_eq!(Selection::Primary.osc52_query_target(), "p");
+    }
+
+    #[test]
+    fn test_includes() {
+        assert!(Selection::Both.includes_clipboard() && Selection::Both.includes_primary());
+        assert!(!Selection::Clipboard.includes_primary());
+        assert!(!Selection::Primary.includes_clipboard());
+    }
+}
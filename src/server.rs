@@ -0,0 +1,357 @@
+//! HTTP access to the clipboard (`bc serve`): `GET /clip` reads it and
+//! `POST /clip` (or `PUT`) replaces it. Every request must carry the token,
+//! as `Authorization: Bearer <token>` or a `?token=` query parameter for
+//! browsers.
+//!
+//! With a key, bodies in both directions are sealed with [`crate::crypto`]
+//! (the `bc1:` text form), and plain uploads are refused.
+
+use anyhow::{Context, Result};
+use std::io::Read;
+use std::path::PathBuf;
+use tiny_http::{Header, Method, Response, Server, SslConfig};
+
+use crate::backends::ClipboardBackend;
+use crate::crypto::Key;
+use crate::peers;
+use crate::{debug, message, verbose};
+
+/// Default limit on uploaded content
+pub const DEFAULT_MAX_SIZE: usize = 1024 * 1024;
+
+/// How `bc serve` listens
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Address to bind, e.g. `127.0.0.1:9111`
+    pub listen: String,
+    /// Secret every request must present
+    pub token: String,
+    /// Largest accepted POST body, in bytes
+    pub max_size: usize,
+    /// PEM certificate and private key files, to serve HTTPS
+    pub tls: Option<(PathBuf, PathBuf)>,
+    /// Name to announce over mDNS for `bc peers`, if any
+    pub advertise: Option<String>,
+    /// Key that request and response bodies are sealed with
+    pub key: Option<Key>,
+}
+
+/// A random token for when none is configured
+pub fn generate_token() -> Result<String> {
+    let mut bytes = [0u8; 16];
+    getrandom::getrandom(&mut bytes)
+        .map_err(|e| anyhow::anyhow!("Failed to generate a token: {}", e))?;
+    Ok(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Serve requests until the process is stopped
+pub fn run(config: &Config, backend: &dyn ClipboardBackend) -> Result<()> {
+    let server = match &config.tls {
+        Some((cert, key)) => {
+            let ssl = SslConfig {
+                certificate: std::fs::read(cert)
+                    .with_context(|| format!("Failed to read {}", cert.display()))?,
+                private_key: std::fs::read(key)
+                    .with_context(|| format!("Failed to read {}", key.display()))?,
+            };
+            Server::https(&config.listen, ssl)
+        }
+        None => Server::http(&config.listen),
+    }
+    .map_err(|e| anyhow::anyhow!("Failed to listen on {}: {}", config.listen, e))?;
+
+    let scheme = if config.tls.is_some() {
+        "https"
+    } else {
+        "http"
+    };
+    message!(
+        "Serving the clipboard at {}://{}/clip",
+        scheme,
+        config.listen
+    );
+
+    let _advertisement = match (&config.advertise, server.server_addr().to_ip()) {
+        (Some(name), Some(addr)) => {
+            if addr.ip().is_loopback() {
+                message!(
+                    "Warning: listening on {} only; other machines cannot reach it (use --listen 0.0.0.0:{})",
+                    addr.ip(),
+                    addr.port()
+                );
+            }
+            Some(peers::advertise(name, addr.port(), config.tls.is_some())?)
+        }
+        _ => None,
+    };
+
+    for mut request in server.incoming_requests() {
+        let authorization = request
+            .headers()
+            .iter()
+            .find(|h| h.field.equiv("Authorization"))
+            .map(|h| h.value.as_str().to_string());
+        let method = request.method().clone();
+        let url = request.url().to_string();
+        let reply = handle(
+            config,
+            backend,
+            &method,
+            &url,
+            authorization.as_deref(),
+            request.as_reader(),
+        );
+        verbose!(
+            "{} {} from {} -> {}",
+            method,
+            url.split('?').next().unwrap_or(""),
+            request
+                .remote_addr()
+                .map_or("unknown".to_string(), |a| a.to_string()),
+            reply.status
+        );
+        let content_type = Header::from_bytes("Content-Type", "text/plain; charset=utf-8")
+            .expect("static header is valid");
+        let response = Response::from_string(reply.body)
+            .with_status_code(reply.status)
+            .with_header(content_type);
+        if let Err(e) = request.respond(response) {
+            debug!("Failed to send a response: {}", e);
+        }
+    }
+    Ok(())
+}
+
+/// Status and body for one request
+#[derive(Debug, PartialEq, Eq)]
+struct Reply {
+    status: u16,
+    body: String,
+}
+
+impl Reply {
+    fn new(status: u16, body: impl Into<String>) -> Self {
+        Self {
+            status,
+            body: body.into(),
+        }
+    }
+}
+
+fn handle(
+    config: &Config,
+    backend: &dyn ClipboardBackend,
+    method: &Method,
+    url: &str,
+    authorization: Option<&str>,
+    body: &mut dyn Read,
+) -> Reply {
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+    if path != "/clip" {
+        return Reply::new(404, "Not found\n");
+    }
+
+    let presented = authorization
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .or_else(|| {
+            query
+                .split('&')
+                .find_map(|pair| pair.strip_prefix("token="))
+        });
+    if !presented.is_some_and(|token| tokens_match(token, &config.token)) {
+        return Reply::new(401, "Missing or wrong token\n");
+    }
+
+    match method {
+        Method::Get => match backend.paste() {
+            Ok(text) => match &config.key {
+                Some(key) => match key.seal_text(&text) {
+                    Ok(sealed) => Reply::new(200, sealed),
+                    Err(e) => Reply::new(500, format!("{}\n", e)),
+                },
+                None => Reply::new(200, text),
+            },
+            Err(e) => Reply::new(503, format!("{}\n", e)),
+        },
+        Method::Post | Method::Put => {
+            let mut data = Vec::new();
+            // Read one byte past the limit to tell "exactly at" from "over"
+            let limit = config.max_size as u64 + 1;
+            if let Err(e) = body.take(limit).read_to_end(&mut data) {
+                return Reply::new(400, format!("Failed to read the request: {}\n", e));
+            }
+            if data.len() > config.max_size {
+                return Reply::new(413, format!("Content exceeds {} bytes\n", config.max_size));
+            }
+            let Ok(text) = String::from_utf8(data) else {
+                return Reply::new(400, "Content is not valid UTF-8\n");
+            };
+            let text = match &config.key {
+                Some(key) => match key.open_text(&text) {
+                    Ok(text) => text,
+                    Err(e) => return Reply::new(400, format!("{}\n", e)),
+                },
+                None => text,
+            };
+            match backend.copy(&text) {
+                Ok(()) => Reply::new(200, format!("Copied {} bytes\n", text.len())),
+                Err(e) => Reply::new(503, format!("{}\n", e)),
+            }
+        }
+        _ => Reply::new(405, "Use GET or POST\n"),
+    }
+}
+
+/// Compare without returning early, so timing doesn't reveal the token. An
+/// empty token never matches, even an empty expected one.
+fn tokens_match(presented: &str, expected: &str) -> bool {
+    !presented.is_empty()
+        && presented.len() == expected.len()
+        && presented
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backends::Memory;
+
+    fn config() -> Config {
+        Config {
+            listen: "127.0.0.1:0".to_string(),
+            token: "secret".to_string(),
+            max_size: 8,
+            tls: None,
+            advertise: None,
+            key: None,
+        }
+    }
+
+    fn request(
+        backend: &Memory,
+        method: Method,
+        url: &str,
+        auth: Option<&str>,
+        body: &str,
+    ) -> Reply {
+        handle(&config(), backend, &method, url, auth, &mut body.as_bytes())
+    }
+
+    #[test]
+    fn test_post_then_get() {
+        let backend = Memory::default();
+        let auth = Some("Bearer secret");
+        assert_eq!(
+            request(&backend, Method::Post, "/clip", auth, "hello").status,
+            200
+        );
+        assert_eq!(
+            request(&backend, Method::Get, "/clip", auth, ""),
+            Reply::new(200, "hello")
+        );
+        assert_eq!(
+            request(&backend, Method::Get, "/clip?token=secret", None, "").body,
+            "hello"
+        );
+    }
+
+    #[test]
+    fn test_requires_token() {
+        let backend = Memory::default();
+        for auth in [None, Some("Bearer wrong"), Some("secret")] {
+            assert_eq!(
+                request(&backend, Method::Get, "/clip", auth, "").status,
+                401
+            );
+        }
+        assert_eq!(
+            request(&backend, Method::Get, "/clip?token=secre", None, "").status,
+            401
+        );
+
+        // An empty token lets nobody in
+        let config = Config {
+            token: String::new(),
+            ..config()
+        };
+        for (url, auth) in [("/clip?token=", None), ("/clip", Some("Bearer "))] {
+            let reply = handle(
+                &config,
+                &backend,
+                &Method::Get,
+                url,
+                auth,
+                &mut "".as_bytes(),
+            );
+            assert_eq!(reply.status, 401);
+        }
+    }
+
+    #[test]
+    fn test_limits_and_errors() {
+        let backend = Memory::default();
+        let auth = Some("Bearer secret");
+        assert_eq!(
+            request(&backend, Method::Post, "/clip", auth, "12345678").status,
+            200
+        );
+        assert_eq!(
+            request(&backend, Method::Post, "/clip", auth, "123456789").status,
+            413
+        );
+        assert_eq!(backend.content(), "12345678");
+        assert_eq!(
+            request(&backend, Method::Delete, "/clip", auth, "").status,
+            405
+        );
+        assert_eq!(
+            request(&backend, Method::Get, "/other", auth, "").status,
+            404
+        );
+    }
+
+    #[test]
+    fn test_encrypted_bodies() {
+        let backend = Memory::default();
+        let key = Key::generate().unwrap();
+        let config = Config {
+            key: Some(key.clone()),
+            max_size: 1024,
+            ..config()
+        };
+        let auth = Some("Bearer secret");
+        let post = |body: &str| {
+            handle(
+                &config,
+                &backend,
+                &Method::Post,
+                "/clip",
+                auth,
+                &mut body.as_bytes(),
+            )
+        };
+        assert_eq!(post("plain").status, 400);
+        assert_eq!(post(&key.seal_text("hidden").unwrap()).status, 200);
+        assert_eq!(backend.content(), "hidden");
+
+        let reply = handle(
+            &config,
+            &backend,
+            &Method::Get,
+            "/clip",
+            auth,
+            &mut "".as_bytes(),
+        );
+        assert_eq!(key.open_text(&reply.body).unwrap(), "hidden");
+    }
+
+    #[test]
+    fn test_generate_token() {
+        let token = generate_token().unwrap();
+        assert_eq!(token.len(), 32);
+        assert_ne!(token, generate_token().unwrap());
+    }
+}
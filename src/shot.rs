@@ -0,0 +1,157 @@
+//! Screenshots for `bc shot`.
+//!
+//! The platform's capture tool writes a PNG into a private temp file:
+//! `screencapture` on macOS, grim (with slurp for regions) on Wayland, maim
+//! (with xdotool for the active window) on X11, and PowerShell's
+//! System.Drawing on Windows.
+
+use anyhow::{anyhow, bail, Context, Result};
+use std::fs;
+use std::io;
+use std::process::{Command, Stdio};
+
+use crate::debug;
+use crate::edit::TempFile;
+
+/// What to capture
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Area {
+    /// Every screen
+    #[default]
+    Screen,
+    /// A rectangle selected with the mouse
+    Region,
+    /// One window: the active one, or the one clicked on macOS
+    Window,
+}
+
+/// Take a screenshot of `area` and return it as PNG data. Fails if the
+/// capture tool is missing or the selection was cancelled.
+pub fn capture(area: Area) -> Result<Vec<u8>> {
+    let file = TempFile::create("shot", "png", b"")?;
+    let path = file.path.to_string_lossy().into_owned();
+    platform::capture(area, &path)?;
+    let png = fs::read(&file.path)
+        .with_context(|| format!("Failed to read the screenshot from {}", path))?;
+    // Tools that exit cleanly on Escape leave the file empty
+    if png.is_empty() {
+        bail!("Screenshot cancelled");
+    }
+    Ok(png)
+}
+
+/// Run a capture tool, returning its stdout
+fn run(command: &[&str]) -> Result<String> {
+    let (program, args) = command.split_first().context("No command given")?;
+    debug!("Running '{}'", command.join(" "));
+    let output = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => anyhow!("'{}' is not installed", program),
+            _ => anyhow!("Failed to run {}: {}", program, e),
+        })?;
+    if !output.status.success() {
+        bail!("{} failed or was cancelled ({})", program, output.status);
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::{run, Area};
+    use anyhow::Result;
+
+    pub fn capture(area: Area, path: &str) -> Result<()> {
+        let mode: &[&str] = match area {
+            Area::Screen => &[],
+            Area::Region => &["-i", "-s"],
+            Area::Window => &["-i", "-w"],
+        };
+        let mut command = vec!["screencapture", "-x"];
+        command.extend_from_slice(mode);
+        command.push(path);
+        run(&command).map(drop)
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::{run, Area};
+    use anyhow::{bail, Result};
+
+    /// Saves the whole virtual screen to `PATH`
+    const SCRIPT: &str = "Add-Type -AssemblyName System.Windows.Forms, System.Drawing; \
+        $b = [System.Windows.Forms.SystemInformation]::VirtualScreen; \
+        $bmp = New-Object System.Drawing.Bitmap $b.Width, $b.Height; \
+        [System.Drawing.Graphics]::FromImage($bmp).CopyFromScreen($b.Location, [System.Drawing.Point]::Empty, $b.Size); \
+        $bmp.Save(PATH, [System.Drawing.Imaging.ImageFormat]::Png)";
+
+    pub fn capture(area: Area, path: &str) -> Result<()> {
+        if area != Area::Screen {
+            bail!("Only --screen is supported on Windows (use Win+Shift+S for a region)");
+        }
+        let script = SCRIPT.replace("PATH", &format!("'{}'", path.replace('\'', "''")));
+        run(&[
+            "powershell.exe",
+            "-NoProfile",
+            "-NonInteractive",
+            "-Command",
+            &script,
+        ])
+        .map(drop)
+    }
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+mod platform {
+    use super::{run, Area};
+    use anyhow::{bail, Result};
+
+    use crate::clipboard;
+
+    pub fn capture(area: Area, path: &str) -> Result<()> {
+        if clipboard::is_wayland_session() {
+            match area {
+                Area::Screen => run(&["grim", path]),
+                Area::Region => {
+                    let geometry = run(&["slurp"])?;
+                    run(&["grim", "-g", &geometry, path])
+                }
+                Area::Window => {
+                    bail!("--window isn't supported on Wayland (no common way to find the window); use --region")
+                }
+            }
+        } else {
+            match area {
+                Area::Screen => run(&["maim", path]),
+                Area::Region => run(&["maim", "-s", path]),
+                Area::Window => {
+                    let window = run(&["xdotool", "getactivewindow"])?;
+                    run(&["maim", "-i", &window, path])
+                }
+            }
+        }
+        .map(drop)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_reports_missing_and_failing_tools() {
+        let err = run(&["bc-no-such-capture-tool"]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "'bc-no-such-capture-tool' is not installed"
+        );
+        let err = run(&["false"]).unwrap_err();
+        assert!(err.to_string().contains("cancelled"), "{}", err);
+        assert_eq!(run(&["echo", " 10,20 30x40 "]).unwrap(), "10,20 30x40");
+    }
+}
@@ -0,0 +1,252 @@
+//! Reusable text templates (`bc snippet`), one file per snippet in
+//! `~/.config/bc/snippets`, with `{date}`-style placeholders filled in when
+//! one is copied.
+
+use anyhow::{Context as _, Result};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::paths::{self, FileMode};
+use crate::template;
+
+/// Snippets directory name inside the config directory
+const SNIPPETS_DIR: &str = "snippets";
+/// Maximum snippet name length
+const MAX_NAME_LENGTH: usize = 64;
+
+/// A directory of snippets, one file per snippet
+#[derive(Debug, Clone)]
+pub struct Snippets {
+    dir: PathBuf,
+}
+
+impl Snippets {
+    /// Snippets at the default location (`~/.config/bc/snippets`)
+    pub fn open_default() -> Result<Self> {
+        Ok(Self::at(paths::config_dir()?.join(SNIPPETS_DIR)))
+    }
+
+    /// Snippets stored in an explicit directory
+    pub fn at(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// A snippet's template, or None if it doesn't exist
+    pub fn get(&self, name: &str) -> Result<Option<String>> {
+        match fs::read_to_string(self.path(name)?) {
+            Ok(template) => Ok(Some(template)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).with_context(|| format!("Failed to read snippet '{}'", name)),
+        }
+    }
+
+    /// Save a snippet, replacing any with the same name. Returns whether
+    /// one was replaced.
+    pub fn add(&self, name: &str, template: &str) -> Result<bool> {
+        let path = self.path(name)?;
+        let replaced = path.exists();
+        paths::write_atomic(&path, FileMode::Private, |out| {
+            out.write_all(template.as_bytes())
+        })
+        .with_context(|| format!("Failed to write snippet '{}'", name))?;
+        Ok(replaced)
+    }
+
+    /// Names of all snippets, sorted
+    pub fn list(&self) -> Result<Vec<String>> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to list {}", self.dir.display()))
+            }
+        };
+
+        let mut names: Vec<String> = entries
+            .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+            .filter(|name| validate_name(name).is_ok())
+            .collect();
+        names.sort();
+        Ok(names)
+    }
+
+    fn path(&self, name: &str) -> Result<PathBuf> {
+        validate_name(name)?;
+        Ok(self.dir.join(name))
+    }
+}
+
+/// Snippet names follow the register rules (ASCII letters, digits, `-` and
+/// `_`) so they map safely to file names on every platform
+pub fn validate_name(name: &str) -> Result<()> {
+    if name.is_empty() || name.len() > MAX_NAME_LENGTH {
+        anyhow::bail!(
+            "Invalid snippet name '{}' (must be 1-{} characters)",
+            name,
+            MAX_NAME_LENGTH
+        );
+    }
+    if !name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        anyhow::bail!(
+            "Invalid snippet name '{}' (use letters, digits, '-' and '_')",
+            name
+        );
+    }
+    Ok(())
+}
+
+/// Values for a template's placeholders
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Context {
+    /// `{date}`: local date as YYYY-MM-DD
+    pub date: String,
+    /// `{time}`: local time as HH:MM:SS
+    pub time: String,
+    /// `{hostname}`
+    pub hostname: String,
+    /// `{user}`
+    pub user: String,
+    /// `{1}`, `{2}`, ...: the extra command-line arguments
+    pub args: Vec<String>,
+}
+
+impl Context {
+    /// The current date, time, host, and user, with `args` as `{1}`...
+    pub fn current(args: Vec<String>) -> Self {
+        let now = chrono::Local::now();
+        Self {
+            date: now.format("%Y-%m-%d").to_string(),
+            time: now.format("%H:%M:%S").to_string(),
+            hostname: template::hostname(),
+            user: env::var("USER")
+                .or_else(|_| env::var("USERNAME"))
+                .unwrap_or_default(),
+            args,
+        }
+    }
+
+    fn value(&self, name: &str) -> Result<Option<&str>> {
+        if let Ok(position) = name.parse::<usize>() {
+            if position == 0 {
+                return Ok(None);
+            }
+            return match self.args.get(position - 1) {
+                Some(arg) => Ok(Some(arg)),
+                None => anyhow::bail!(
+                    "The snippet uses {{{}}}, but only {} argument(s) were given",
+                    position,
+                    self.args.len()
+                ),
+            };
+        }
+        Ok(match name {
+            "date" => Some(&self.date),
+            "time" => Some(&self.time),
+            "hostname" => Some(&self.hostname),
+            "user" => Some(&self.user),
+            _ => None,
+        })
+    }
+}
+
+/// Fill in `template`'s placeholders: `{date}`, `{time}`, `{hostname}`,
+/// `{user}`, and `{1}`... for arguments. `{{` and `}}` are literal braces;
+/// other braced text is left as it is.
+pub fn expand(template: &str, context: &Context) -> Result<String> {
+    let mut expanded = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find(['{', '}']) {
+        expanded.push_str(&rest[..start]);
+        let tail = &rest[start..];
+        if tail.starts_with("{{") || tail.starts_with("}}") {
+            expanded.push_str(&tail[..1]);
+            rest = &tail[2..];
+            continue;
+        }
+        let name = tail[1..]
+            .split_once('}')
+            .map(|(name, _)| name)
+            .filter(|name| {
+                tail.starts_with('{')
+                    && !name.is_empty()
+                    && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+            });
+        match name.map(|name| (name, context.value(name))) {
+            Some((name, value)) => {
+                match value? {
+                    Some(value) => expanded.push_str(value),
+                    None => {
+                        expanded.push('{');
+                        expanded.push_str(name);
+                        expanded.push('}');
+                    }
+                }
+                rest = &tail[name.len() + 2..];
+            }
+            None => {
+                expanded.push_str(&tail[..1]);
+                rest = &tail[1..];
+            }
+        }
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context(args: &[&str]) -> Context {
+        Context {
+            date: "2024-05-01".to_string(),
+            time: "09:30:00".to_string(),
+            hostname: "devbox".to_string(),
+            user: "pat".to_string(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_add_get_list() {
+        let dir = tempfile::tempdir().unwrap();
+        let snippets = Snippets::at(dir.path().join("snippets"));
+        assert!(snippets.list().unwrap().is_empty());
+        assert_eq!(snippets.get("sig").unwrap(), None);
+
+        assert!(!snippets.add("sig", "-- \n{user}").unwrap());
+        assert!(snippets.add("sig", "Regards,\n{user}").unwrap());
+        snippets.add("addr", "1 Main St").unwrap();
+        assert_eq!(snippets.get("sig").unwrap().unwrap(), "Regards,\n{user}");
+        assert_eq!(snippets.list().unwrap(), ["addr", "sig"]);
+        assert!(snippets.add("../escape", "x").is_err());
+    }
+
+    #[test]
+    fn test_expand_placeholders() {
+        let context = context(&["alice", "42"]);
+        assert_eq!(
+            expand("{user}@{hostname} on {date} {time}", &context).unwrap(),
+            "pat@devbox on 2024-05-01 09:30:00"
+        );
+        assert_eq!(
+            expand("Hi {1}, ticket #{2} ({1})", &context).unwrap(),
+            "Hi alice, ticket #42 (alice)"
+        );
+        // Escapes, unknown names, and non-placeholder braces stay literal
+        assert_eq!(
+            expand("{{date}} {unknown} {\"a\": 1} {} {0} }", &context).unwrap(),
+            "{date} {unknown} {\"a\": 1} {} {0} }"
+        );
+        let err = expand("{3}", &context).unwrap_err();
+        assert!(err.to_string().contains("{3}"), "{}", err);
+    }
+}
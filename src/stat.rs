@@ -0,0 +1,139 @@
+//! Size and shape of clipboard text for `bc stat`, reported without the
+//! content itself.
+
+use serde::Serialize;
+use std::fmt;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{detect, history};
+
+/// Counts and guesses about a piece of text
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Stats {
+    pub bytes: usize,
+    pub chars: usize,
+    /// User-perceived characters (an emoji with modifiers counts once)
+    pub graphemes: usize,
+    pub words: usize,
+    pub lines: usize,
+    /// `ASCII`, `UTF-8`, or `UTF-8 with BOM`
+    pub encoding: &'static str,
+    /// `LF`, `CRLF`, `CR`, `mixed`, or `none`
+    pub line_endings: &'static str,
+    /// A [`detect::Kind`] name: `json`, `url`, `code`, `text`, ...
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    /// The language, for code
+    pub language: Option<&'static str>,
+    /// When this text was copied (seconds since the Unix epoch), if the
+    /// history has it
+    pub copied_at: Option<u64>,
+}
+
+impl Stats {
+    pub fn of(text: &str) -> Self {
+        let kind = detect::detect(text);
+        Self {
+            bytes: text.len(),
+            chars: text.chars().count(),
+            graphemes: text.graphemes(true).count(),
+            words: text.split_whitespace().count(),
+            lines: text.lines().count(),
+            encoding: if text.starts_with('\u{feff}') {
+                "UTF-8 with BOM"
+            } else if text.is_ascii() {
+                "ASCII"
+            } else {
+                "UTF-8"
+            },
+            line_endings: line_endings(text),
+            kind: kind.name(),
+            language: kind.language(),
+            copied_at: None,
+        }
+    }
+
+    pub fn with_copied_at(mut self, copied_at: Option<u64>) -> Self {
+        self.copied_at = copied_at;
+        self
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "bytes:        {}", self.bytes)?;
+        writeln!(f, "chars:        {}", self.chars)?;
+        writeln!(f, "graphemes:    {}", self.graphemes)?;
+        writeln!(f, "words:        {}", self.words)?;
+        writeln!(f, "lines:        {}", self.lines)?;
+        writeln!(f, "encoding:     {}", self.encoding)?;
+        writeln!(f, "line endings: {}", self.line_endings)?;
+        match self.language {
+            Some(language) => writeln!(f, "type:         {} ({})", self.kind, language)?,
+            None => writeln!(f, "type:         {}", self.kind)?,
+        }
+        if let Some(copied_at) = self.copied_at {
+            writeln!(f, "copied:       {}", history::format_age(copied_at))?;
+        }
+        Ok(())
+    }
+}
+
+fn line_endings(text: &str) -> &'static str {
+    let crlf = text.matches("\r\n").count();
+    let lf = text.matches('\n').count() - crlf;
+    let cr = text.matches('\r').count() - crlf;
+    match (lf, crlf, cr) {
+        (0, 0, 0) => "none",
+        (_, 0, 0) => "LF",
+        (0, _, 0) => "CRLF",
+        (0, 0, _) => "CR",
+        _ => "mixed",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counts() {
+        let stats = Stats::of("héllo wörld\n👍🏽 ok\n");
+        assert_eq!(stats.bytes, 26);
+        assert_eq!(stats.chars, 18);
+        assert_eq!(stats.graphemes, 17);
+        assert_eq!(stats.words, 4);
+        assert_eq!(stats.lines, 2);
+        assert_eq!(stats.encoding, "UTF-8");
+        assert_eq!(stats.line_endings, "LF");
+    }
+
+    #[test]
+    fn test_encoding_and_line_endings() {
+        assert_eq!(Stats::of("plain").encoding, "ASCII");
+        assert_eq!(Stats::of("\u{feff}x").encoding, "UTF-8 with BOM");
+        assert_eq!(Stats::of("a\r\nb\r\n").line_endings, "CRLF");
+        assert_eq!(Stats::of("a\r\nb\n").line_endings, "mixed");
+        assert_eq!(Stats::of("a").line_endings, "none");
+        assert_eq!(Stats::of("a\rb").line_endings, "CR");
+    }
+
+    #[test]
+    fn test_display_and_json() {
+        let stats = Stats::of("ab\n");
+        assert!(stats.to_string().contains("graphemes:    3\n"));
+        let json = serde_json::to_value(&stats).unwrap();
+        assert_eq!(json["type"], "text");
+        assert_eq!(json["language"], serde_json::Value::Null);
+        assert_eq!(json["line_endings"], "LF");
+
+        let stats = Stats::of("fn main() {\n    println!(\"hi\");\n}\n");
+        assert!(stats.to_string().contains("type:         code (rust)\n"));
+        assert!(!stats.to_string().contains("copied:"));
+
+        let stats = Stats::of("ab").with_copied_at(Some(history::now() - 120));
+        assert!(stats.to_string().ends_with("copied:       2m ago\n"));
+        let json = serde_json::to_value(&stats).unwrap();
+        assert!(json["copied_at"].is_u64());
+    }
+}
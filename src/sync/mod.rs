@@ -0,0 +1,309 @@
+//! Two-way clipboard sync between this machine and a remote host (`bc sync`).
+//!
+//! The local side starts `bc sync --serve` on the remote host over SSH and
+//! talks to it through the session's stdin/stdout. Each peer polls its own
+//! clipboard, sends changes as `protocol::Message::Update`, and applies the
+//! updates it receives. Content applied from the peer is remembered so the
+//! watcher does not send it straight back.
+//!
+//! The session's own stdin/stdout stand in for an SSH-forwarded Unix
+//! socket. Forwarding one needs `AllowStreamLocalForwarding` on the server
+//! and leaves socket files behind to clean up (or `StreamLocalBindUnlink`),
+//! while the session's pipes work with any sshd, carry the same encrypted
+//! channel, and close when either side exits.
+//!
+//! With a shared key (`--key`), update contents are sealed with
+//! [`crate::crypto`] before they leave the process.
+
+pub mod protocol;
+
+use anyhow::{Context, Result};
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use crate::backends::ClipboardBackend;
+use crate::crypto::Key;
+use crate::watch::{Watcher, DEFAULT_INTERVAL};
+use crate::{debug, verbose};
+use protocol::{read_message, write_message, Message, VERSION};
+
+/// Creates a backend for each sync thread
+pub type BackendFactory<'a> = &'a (dyn Fn() -> Box<dyn ClipboardBackend> + Sync);
+
+/// The content both peers last agreed on
+#[derive(Debug, Default)]
+struct State {
+    last: Mutex<Option<String>>,
+}
+
+impl State {
+    /// Record `content` as synced. Returns false if it already was, i.e. the
+    /// change came from the peer and must not be echoed back.
+    fn update(&self, content: &str) -> bool {
+        let mut last = self.last.lock().unwrap_or_else(|e| e.into_inner());
+        if last.as_deref() == Some(content) {
+            return false;
+        }
+        *last = Some(content.to_string());
+        true
+    }
+}
+
+/// Start `bc sync --serve` on `host` via ssh. `program` is bc's name on the
+/// remote PATH (often not `bc`, which is usually the calculator there);
+/// `key` is the path of the remote end's key file, if encrypting.
+pub fn spawn_remote(host: &str, program: &str, key: Option<&str>) -> Result<Child> {
+    verbose!("Starting '{} sync --serve' on {}", program, host);
+    Command::new("ssh")
+        .args(ssh_args(host, program, key))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .context("Failed to run ssh")
+}
+
+/// ssh's arguments for running the server on `host`. ssh joins everything
+/// after the host into one line for the remote shell, so each word is quoted
+/// to reach bc as it is, spaces and quotes included.
+fn ssh_args(host: &str, program: &str, key: Option<&str>) -> Vec<String> {
+    let mut remote = vec![program, "sync", "--serve"];
+    if let Some(key) = key {
+        remote.extend(["--key", key]);
+    }
+    let command: Vec<String> = remote.into_iter().map(quote).collect();
+    ["-T", "-o", "BatchMode=yes", host]
+        .into_iter()
+        .map(String::from)
+        .chain([command.join(" ")])
+        .collect()
+}
+
+/// Quote `word` for the remote POSIX shell, whatever the local platform
+fn quote(word: &str) -> String {
+    format!("'{}'", word.replace('\'', r"'\''"))
+}
+
+/// Exchange hellos, then sync until the peer hangs up
+pub fn run(
+    backends: BackendFactory,
+    key: Option<&Key>,
+    mut reader: impl BufRead,
+    mut writer: impl Write + Send,
+) -> Result<()> {
+    let encrypted = key.is_some();
+    write_message(
+        &mut writer,
+        &Message::Hello {
+            version: VERSION,
+            encrypted,
+        },
+    )?;
+    match read_message(&mut reader)? {
+        Some(Message::Hello {
+            version,
+            encrypted: theirs,
+        }) if version == VERSION => {
+            if theirs != encrypted {
+                anyhow::bail!(
+                    "Only one end of the sync has a --key; give both ends the same key file"
+                );
+            }
+        }
+        Some(Message::Hello { version, .. }) => anyhow::bail!(
+            "The sync peer speaks protocol version {} (this bc speaks {}); upgrade the older one",
+            version,
+            VERSION
+        ),
+        _ => anyhow::bail!("The sync peer did not answer (is bc installed on the remote host?)"),
+    }
+    verbose!("Sync connected");
+
+    let state = State::default();
+    let done = AtomicBool::new(false);
+    thread::scope(|scope| {
+        let sender = scope.spawn(|| {
+            let backend = backends();
+            send_local_changes(
+                backend.as_ref(),
+                key,
+                &state,
+                &mut writer,
+                &done,
+                DEFAULT_INTERVAL,
+            )
+        });
+        let received = apply_peer_updates(backends().as_ref(), key, &state, &mut reader);
+        done.store(true, Ordering::Relaxed);
+        let sent = sender.join().unwrap_or_else(|_| Ok(()));
+        received.and(sent)
+    })
+}
+
+/// Poll the local clipboard and send each change the peer doesn't have yet
+fn send_local_changes(
+    backend: &dyn ClipboardBackend,
+    key: Option<&Key>,
+    state: &State,
+    writer: &mut dyn Write,
+    done: &AtomicBool,
+    interval: Duration,
+) -> Result<()> {
+    let mut watcher = Watcher::new(backend);
+    while !done.load(Ordering::Relaxed) {
+        match watcher.poll() {
+            Some(content) if state.update(&content) => {
+                debug!("Sending {} bytes to the sync peer", content.len());
+                let content = match key {
+                    Some(key) => key.seal_text(&content)?,
+                    None => content,
+                };
+                write_message(writer, &Message::Update { content })?;
+            }
+            _ => thread::sleep(interval),
+        }
+    }
+    Ok(())
+}
+
+/// Copy each update from the peer to the local clipboard until it hangs up
+fn apply_peer_updates(
+    backend: &dyn ClipboardBackend,
+    key: Option<&Key>,
+    state: &State,
+    reader: &mut dyn BufRead,
+) -> Result<()> {
+    while let Some(message) = read_message(reader)? {
+        if let Message::Update { content } = message {
+            let content = match key {
+                Some(key) => key.open_text(&content).map_err(|e| {
+                    anyhow::anyhow!("Failed to decrypt an update from the sync peer: {}", e)
+                })?,
+                None => content,
+            };
+            debug!("Received {} bytes from the sync peer", content.len());
+            if state.update(&content) {
+                backend.copy(&content)?;
+            }
+        }
+    }
+    verbose!("Sync peer disconnected");
+    Ok(())
+}
+
+/// Use stdin/stdout as the connection (the remote end of `bc sync`)
+pub fn serve(backends: BackendFactory, key: Option<&Key>) -> Result<()> {
+    let stdin = std::io::stdin();
+    run(
+        backends,
+        key,
+        BufReader::new(stdin.lock()),
+        std::io::stdout(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backends::Memory;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_ssh_args_quote_remote_words() {
+        assert_eq!(
+            ssh_args("dev", "bc", None),
+            ["-T", "-o", "BatchMode=yes", "dev", "'bc' 'sync' '--serve'"]
+        );
+        let args = ssh_args("dev", "bc-clip", Some("/home/me/my keys/it's.key"));
+        assert_eq!(
+            args.last().unwrap(),
+            r"'bc-clip' 'sync' '--serve' '--key' '/home/me/my keys/it'\''s.key'"
+        );
+    }
+
+    fn lines(messages: &[Message]) -> Cursor<Vec<u8>> {
+        let mut buffer = Vec::new();
+        for message in messages {
+            write_message(&mut buffer, message).unwrap();
+        }
+        Cursor::new(buffer)
+    }
+
+    fn hello(encrypted: bool) -> Message {
+        Message::Hello {
+            version: VERSION,
+            encrypted,
+        }
+    }
+
+    fn update(content: &str) -> Message {
+        Message::Update {
+            content: content.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_state_skips_echoes() {
+        let state = State::default();
+        assert!(state.update("a"));
+        assert!(!state.update("a"));
+        assert!(state.update("b"));
+    }
+
+    #[test]
+    fn test_apply_peer_updates() {
+        let backend = Memory::default();
+        let state = State::default();
+        let mut reader = lines(&[update("one"), update("one"), update("two")]);
+        apply_peer_updates(&backend, None, &state, &mut reader).unwrap();
+        assert_eq!(backend.copies(), ["one", "two"]);
+        // The local watcher will see "two" next; it must not go back out
+        assert!(!state.update("two"));
+    }
+
+    #[test]
+    fn test_run_rejects_other_versions() {
+        let factory = || Box::new(Memory::default()) as Box<dyn ClipboardBackend>;
+        let reader = lines(&[Message::Hello {
+            version: VERSION + 1,
+            encrypted: false,
+        }]);
+        let err = run(&factory, None, reader, Vec::new()).unwrap_err();
+        assert!(err.to_string().contains("protocol version"));
+    }
+
+    #[test]
+    fn test_run_requires_matching_keys() {
+        let factory = || Box::new(Memory::default()) as Box<dyn ClipboardBackend>;
+        let key = Key::generate().unwrap();
+        let reader = lines(&[hello(false)]);
+        let err = run(&factory, Some(&key), reader, Vec::new()).unwrap_err();
+        assert!(err.to_string().contains("--key"));
+    }
+
+    #[test]
+    fn test_encrypted_updates() {
+        let key = Key::generate().unwrap();
+        let backend = Memory::default();
+        let state = State::default();
+        let mut reader = lines(&[update(&key.seal_text("secret").unwrap())]);
+        apply_peer_updates(&backend, Some(&key), &state, &mut reader).unwrap();
+        assert_eq!(backend.copies(), ["secret"]);
+
+        let mut reader = lines(&[update("secret")]);
+        assert!(apply_peer_updates(&backend, Some(&key), &state, &mut reader).is_err());
+    }
+
+    #[test]
+    fn test_run_until_peer_hangs_up() {
+        let factory = || Box::new(Memory::default()) as Box<dyn ClipboardBackend>;
+        let reader = lines(&[hello(false), update("hi")]);
+        let mut writer = Vec::new();
+        run(&factory, None, reader, &mut writer).unwrap();
+        let sent = read_message(&mut Cursor::new(writer)).unwrap();
+        assert_eq!(sent, Some(hello(false)));
+    }
+}
@@ -0,0 +1,95 @@
+//! Messages exchanged by sync peers, one JSON object per line.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, Write};
+
+/// Bumped when peers can no longer understand each other
+pub const VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Message {
+    /// First message from each side
+    Hello {
+        version: u32,
+        /// Whether updates are sealed with a shared `--key`
+        #[serde(default)]
+        encrypted: bool,
+    },
+    /// The sender's clipboard changed to `content` (sealed when encrypted)
+    Update { content: String },
+}
+
+/// Send one message and flush it
+pub fn write_message(writer: &mut dyn Write, message: &Message) -> Result<()> {
+    let mut line = serde_json::to_string(message)?;
+    line.push('\n');
+    writer
+        .write_all(line.as_bytes())
+        .and_then(|_| writer.flush())
+        .context("Lost the connection to the sync peer")
+}
+
+/// Read the next message; Ok(None) once the peer hangs up
+pub fn read_message(reader: &mut dyn BufRead) -> Result<Option<Message>> {
+    let mut line = String::new();
+    if reader
+        .read_line(&mut line)
+        .context("Lost the connection to the sync peer")?
+        == 0
+    {
+        return Ok(None);
+    }
+    serde_json::from_str(&line)
+        .map(Some)
+        .with_context(|| format!("Unexpected message from the sync peer: {}", line.trim_end()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_roundtrip() {
+        let messages = [
+            Message::Hello {
+                version: VERSION,
+                encrypted: true,
+            },
+            Message::Update {
+                content: "two\nlines".to_string(),
+            },
+        ];
+        let mut buffer = Vec::new();
+        for message in &messages {
+            write_message(&mut buffer, message).unwrap();
+        }
+        assert_eq!(buffer.iter().filter(|&&b| b == b'\n').count(), 2);
+
+        let mut reader = Cursor::new(buffer);
+        for message in &messages {
+            assert_eq!(read_message(&mut reader).unwrap().as_ref(), Some(message));
+        }
+        assert_eq!(read_message(&mut reader).unwrap(), None);
+    }
+
+    #[test]
+    fn test_hello_without_encrypted_field() {
+        let message = read_message(&mut Cursor::new("{\"type\":\"hello\",\"version\":1}\n"));
+        assert_eq!(
+            message.unwrap(),
+            Some(Message::Hello {
+                version: 1,
+                encrypted: false
+            })
+        );
+    }
+
+    #[test]
+    fn test_rejects_garbage() {
+        let err = read_message(&mut Cursor::new("(standard_in) 1: syntax error\n")).unwrap_err();
+        assert!(err.to_string().contains("syntax error"));
+    }
+}
@@ -0,0 +1,147 @@
+//! `--template`: `{{name}}` placeholders expanded in the text before it is
+//! copied, for canned responses and commit message scaffolds.
+
+use anyhow::{anyhow, bail, Result};
+use chrono::format::{Item, StrftimeItems};
+use std::env;
+
+/// Format `{{date}}` uses without one
+const DEFAULT_DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// Replace each `{{env:VAR}}`, `{{date}}` / `{{date:FORMAT}}` (strftime),
+/// `{{uuid}}` (a random v4 UUID), and `{{hostname}}` in `text`. Braces that
+/// don't form a `{{name}}` or `{{name:arg}}` are left as they are; a
+/// placeholder bc doesn't know is an error.
+pub fn expand(text: &str) -> Result<String> {
+    let mut expanded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        expanded.push_str(&rest[..start]);
+        let tail = &rest[start + 2..];
+        match placeholder(tail) {
+            Some((name, arg, len)) => {
+                expanded.push_str(&value(name, arg)?);
+                rest = &tail[len..];
+            }
+            None => {
+                expanded.push_str("{{");
+                rest = tail;
+            }
+        }
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+/// The name and argument of the placeholder `tail` starts with (after its
+/// `{{`), and its length through the closing `}}`
+fn placeholder(tail: &str) -> Option<(&str, Option<&str>, usize)> {
+    let end = tail.find("}}")?;
+    let inner = &tail[..end];
+    let (name, arg) = match inner.split_once(':') {
+        Some((name, arg)) => (name, Some(arg)),
+        None => (inner, None),
+    };
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_lowercase()) {
+        return None;
+    }
+    Some((name, arg, end + 2))
+}
+
+fn value(name: &str, arg: Option<&str>) -> Result<String> {
+    match (name, arg) {
+        ("env", Some(var)) => {
+            env::var(var).map_err(|_| anyhow!("{{{{env:{}}}}}: {} is not set", var, var))
+        }
+        ("date", format) => date(format.unwrap_or(DEFAULT_DATE_FORMAT)),
+        ("uuid", None) => uuid(),
+        ("hostname", None) => Ok(hostname()),
+        ("env", None) => bail!("{{{{env}}}} needs a variable name, as in {{{{env:USER}}}}"),
+        (_, Some(_)) if matches!(name, "uuid" | "hostname") => {
+            bail!("{{{{{}}}}} takes no argument", name)
+        }
+        _ => bail!(
+            "Unknown template placeholder {{{{{}}}}} (use env:VAR, date, date:FORMAT, uuid, hostname)",
+            name
+        ),
+    }
+}
+
+/// The local date and time in strftime `format`
+fn date(format: &str) -> Result<String> {
+    let items: Vec<Item> = StrftimeItems::new(format).collect();
+    if items.iter().any(|item| matches!(item, Item::Error)) {
+        bail!("Invalid date format '{}' in {{{{date:...}}}}", format);
+    }
+    Ok(chrono::Local::now()
+        .format_with_items(items.into_iter())
+        .to_string())
+}
+
+/// A random (version 4) UUID in its hyphenated lowercase form
+fn uuid() -> Result<String> {
+    let mut bytes = [0u8; 16];
+    getrandom::getrandom(&mut bytes).map_err(|e| anyhow!("Failed to generate a UUID: {}", e))?;
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+    Ok(format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    ))
+}
+
+/// This machine's host name
+pub fn hostname() -> String {
+    #[cfg(unix)]
+    return rustix::system::uname()
+        .nodename()
+        .to_string_lossy()
+        .into_owned();
+    #[cfg(not(unix))]
+    return env::var("COMPUTERNAME").unwrap_or_default();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand() {
+        env::set_var("BC_TEMPLATE_TEST", "value");
+        let year = chrono::Local::now().format("%Y").to_string();
+        assert_eq!(
+            expand("{{env:BC_TEMPLATE_TEST}} {{date:%Y}} on {{hostname}}").unwrap(),
+            format!("value {} on {}", year, hostname())
+        );
+        assert_eq!(expand("{{date}}").unwrap().len(), "2024-01-31".len());
+        // Braces that aren't placeholders pass through
+        assert_eq!(
+            expand("{{ x }} {{Name}} {{open {a}").unwrap(),
+            "{{ x }} {{Name}} {{open {a}"
+        );
+    }
+
+    #[test]
+    fn test_uuid() {
+        let first = expand("{{uuid}}").unwrap();
+        assert_eq!(first.len(), 36);
+        assert_eq!(&first[14..15], "4");
+        assert!("89ab".contains(&first[19..20]), "{}", first);
+        assert_ne!(first, expand("{{uuid}}").unwrap());
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(expand("{{env:BC_TEMPLATE_UNSET}}").is_err());
+        assert!(expand("{{env}}").is_err());
+        assert!(expand("{{uuid:4}}").is_err());
+        assert!(expand("{{date:%Q}}").is_err());
+        let err = expand("{{nope}}").unwrap_err();
+        assert!(err.to_string().contains("{{nope}}"), "{}", err);
+    }
+}
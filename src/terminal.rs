@@ -1,5 +1,7 @@
 //! Terminal raw mode handling for OSC 52 clipboard queries (Unix-only).
 
+#[cfg(unix)]
+use crate::debug;
 #[cfg(unix)]
 use crate::osc52;
 #[cfg(unix)]
@@ -9,7 +11,9 @@ use is_terminal::IsTerminal;
 #[cfg(unix)]
 use rustix::termios::{self, LocalModes, OptionalActions, Termios};
 #[cfg(unix)]
-use std::os::fd::AsRawFd;
+use std::os::fd::{AsFd, AsRawFd};
+#[cfg(unix)]
+use std::time::{Duration, Instant};
 
 /// RAII guard that restores terminal mode on drop.
 #[cfg(unix)]
@@ -47,50 +51,148 @@ pub fn set_raw_mode() -> Result<TerminalGuard> {
     })
 }
 
-/// Read from stdin with a timeout. Returns empty string if no data available.
+/// How long a terminal reply may take as a whole: a large clipboard over a
+/// slow link keeps arriving long after the first bytes
 #[cfg(unix)]
-pub fn read_with_timeout(timeout_ms: u64) -> Result<String> {
-    use rustix::event::{poll, PollFd, PollFlags};
-    use std::io::Read;
+const REPLY_DEADLINE: Duration = Duration::from_secs(30);
 
-    let stdin_fd = std::io::stdin().as_raw_fd();
-    // SAFETY: stdin_fd is valid from std::io::stdin().as_raw_fd()
-    // It remains valid for this function call since stdin is global
-    let borrowed = unsafe { rustix::fd::BorrowedFd::borrow_raw(stdin_fd) };
-    let mut poll_fds = [PollFd::new(&borrowed, PollFlags::IN)];
+/// Limits on waiting for a reply
+#[cfg(unix)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReadLimits {
+    /// Longest wait for the next bytes, the first ones included
+    pub idle: Duration,
+    /// Longest wait overall
+    pub total: Duration,
+}
 
-    if poll(&mut poll_fds, timeout_ms as i32).context("Failed to poll stdin")? == 0 {
-        return Ok(String::new());
+#[cfg(unix)]
+impl ReadLimits {
+    /// `timeout_ms` of silence ends the read, up to `REPLY_DEADLINE` overall
+    fn from_timeout(timeout_ms: u64) -> Self {
+        let idle = Duration::from_millis(timeout_ms);
+        Self {
+            idle,
+            total: idle.max(REPLY_DEADLINE),
+        }
     }
+}
+
+/// Read an OSC 52 reply from stdin, until its BEL or ST terminator. Returns
+/// an empty string if nothing arrives within the timeout, and what arrived
+/// so far if the terminal goes quiet or the deadline passes first.
+#[cfg(unix)]
+pub fn read_with_timeout(timeout_ms: u64) -> Result<String> {
+    read_reply(
+        std::io::stdin(),
+        ReadLimits::from_timeout(timeout_ms),
+        has_terminator,
+    )
+}
+
+/// Read from stdin until `complete` accepts what has arrived, or until no
+/// more data arrives within the timeout. Returns what was read so far.
+#[cfg(unix)]
+pub fn read_until(timeout_ms: u64, complete: impl Fn(&str) -> bool) -> Result<String> {
+    read_reply(
+        std::io::stdin(),
+        ReadLimits::from_timeout(timeout_ms),
+        |buffer, _| complete(&String::from_utf8_lossy(buffer)),
+    )
+}
+
+/// Whether the bytes from `new` on (or the one before, for a split ST)
+/// end an OSC string
+#[cfg(unix)]
+fn has_terminator(buffer: &[u8], new: usize) -> bool {
+    let tail = &buffer[new.saturating_sub(1)..];
+    tail.contains(&0x07) || tail.windows(2).any(|pair| pair == b"\x1b\\")
+}
 
+/// Read `source` in chunks as they arrive, until `complete(buffer, new)`
+/// (where `new` is where the latest chunk starts) accepts them, the source
+/// is quiet for `limits.idle`, or `limits.total` has passed. Reads the file
+/// descriptor directly, so no bytes sit in a buffer that `poll` can't see.
+#[cfg(unix)]
+pub fn read_reply(
+    source: impl AsFd,
+    limits: ReadLimits,
+    mut complete: impl FnMut(&[u8], usize) -> bool,
+) -> Result<String> {
+    use rustix::event::{poll, PollFd, PollFlags};
+    use rustix::io::Errno;
+
+    let deadline = Instant::now() + limits.total;
     let mut buffer = Vec::new();
-    let mut handle = std::io::stdin().lock();
+    let mut chunk = [0u8; 4096];
 
     loop {
-        let mut chunk = [0u8; 4096];
-        match handle.read(&mut chunk) {
+        let wait = limits
+            .idle
+            .min(deadline.saturating_duration_since(Instant::now()));
+        if wait.is_zero() {
+            debug!(
+                "Terminal reply deadline passed after {} bytes",
+                buffer.len()
+            );
+            break;
+        }
+        // Round up, so a sub-millisecond wait doesn't become "don't wait"
+        let wait_ms = i32::try_from(wait.as_micros().div_ceil(1000)).unwrap_or(i32::MAX);
+        let mut poll_fds = [PollFd::new(&source, PollFlags::IN)];
+        match poll(&mut poll_fds, wait_ms) {
             Ok(0) => break,
-            Ok(n) => {
-                if buffer.len() + n > osc52::OSC52_MAX_SIZE {
-                    anyhow::bail!(
-                        "Response exceeds maximum size ({} bytes)",
-                        osc52::OSC52_MAX_SIZE
-                    );
-                }
-                buffer.extend_from_slice(&chunk[..n]);
-                let response = String::from_utf8_lossy(&buffer);
-                if response.contains('\x07') || response.contains("\x1b\\") {
-                    break;
-                }
-            }
-            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => break,
-            Err(e) => return Err(e).context("Failed to read from stdin"),
+            Ok(_) => {}
+            Err(Errno::INTR) => continue,
+            Err(e) => return Err(e).context("Failed to poll the terminal"),
+        }
+        let n = match rustix::io::read(&source, &mut chunk) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(Errno::INTR) => continue,
+            Err(Errno::AGAIN) => break,
+            Err(e) => return Err(e).context("Failed to read from the terminal"),
+        };
+        if buffer.len() + n > osc52::OSC52_MAX_SIZE {
+            anyhow::bail!(
+                "Response exceeds maximum size ({} bytes)",
+                osc52::OSC52_MAX_SIZE
+            );
+        }
+        let new = buffer.len();
+        buffer.extend_from_slice(&chunk[..n]);
+        if complete(&buffer, new) {
+            break;
         }
     }
 
     String::from_utf8(buffer).context("Response is not valid UTF-8")
 }
 
+/// Wait for the next key press (or a pasted run of keys) on stdin, which
+/// should be in raw mode
+#[cfg(unix)]
+pub fn read_keys() -> Result<String> {
+    let forever = Duration::from_secs(24 * 60 * 60);
+    read_reply(
+        std::io::stdin(),
+        ReadLimits {
+            idle: forever,
+            total: forever,
+        },
+        |_, _| true,
+    )
+}
+
+/// Columns of the terminal on stderr, if it is one
+#[cfg(unix)]
+pub fn width() -> Option<usize> {
+    termios::tcgetwinsize(std::io::stderr())
+        .ok()
+        .map(|size| usize::from(size.ws_col))
+        .filter(|&cols| cols > 0)
+}
+
 /// Check if stdin is a terminal (TTY).
 #[cfg(unix)]
 pub fn is_stdin_tty() -> bool {
@@ -108,6 +210,25 @@ pub fn read_with_timeout(_timeout_ms: u64) -> anyhow::Result<String> {
     Err(anyhow::anyhow!("OSC 52 query is not supported on Windows"))
 }
 
+#[cfg(not(unix))]
+pub fn read_until(_timeout_ms: u64, _complete: impl Fn(&str) -> bool) -> anyhow::Result<String> {
+    Err(anyhow::anyhow!(
+        "Terminal queries are not supported on Windows"
+    ))
+}
+
+#[cfg(not(unix))]
+pub fn read_keys() -> anyhow::Result<String> {
+    Err(anyhow::anyhow!(
+        "Reading key presses is not supported on Windows"
+    ))
+}
+
+#[cfg(not(unix))]
+pub fn width() -> Option<usize> {
+    None
+}
+
 #[cfg(not(unix))]
 pub fn is_stdin_tty() -> bool {
     false
@@ -126,4 +247,89 @@ mod tests {
     fn test_read_with_timeout() {
         let _ = super::read_with_timeout(0);
     }
+
+    #[cfg(unix)]
+    mod read_reply {
+        use super::super::*;
+        use std::io::Write;
+        use std::thread;
+
+        fn limits(idle_ms: u64, total_ms: u64) -> ReadLimits {
+            ReadLimits {
+                idle: Duration::from_millis(idle_ms),
+                total: Duration::from_millis(total_ms),
+            }
+        }
+
+        /// A pipe whose write end gets `chunks`, `gap_ms` apart, and then
+        /// stays open for `hold_ms` more
+        fn trickle(chunks: Vec<&'static [u8]>, gap_ms: u64, hold_ms: u64) -> std::io::PipeReader {
+            let (reader, mut writer) = std::io::pipe().unwrap();
+            thread::spawn(move || {
+                for chunk in chunks {
+                    if writer.write_all(chunk).is_err() {
+                        return;
+                    }
+                    thread::sleep(Duration::from_millis(gap_ms));
+                }
+                thread::sleep(Duration::from_millis(hold_ms));
+            });
+            reader
+        }
+
+        #[test]
+        fn test_slow_reply_is_read_whole() {
+            let reader = trickle(
+                vec![b"\x1b]52;c;SGVs", b"bG8s", b"IHdvcmxk", b"\x1b\\"],
+                50,
+                5000,
+            );
+            let started = Instant::now();
+            let reply = read_reply(&reader, limits(500, 5000), has_terminator).unwrap();
+            assert_eq!(reply, "\x1b]52;c;SGVsbG8sIHdvcmxk\x1b\\");
+            // Returned at the terminator, not when the writer closed
+            assert!(started.elapsed() < Duration::from_secs(2));
+        }
+
+        #[test]
+        fn test_terminator_split_across_reads() {
+            let reader = trickle(vec![b"\x1b]52;c;eA==\x1b", b"\\"], 50, 5000);
+            let reply = read_reply(&reader, limits(500, 5000), has_terminator).unwrap();
+            assert!(reply.ends_with("\x1b\\"));
+        }
+
+        #[test]
+        fn test_idle_timeout_returns_partial() {
+            let reader = trickle(vec![b"\x1b]52;c;SGVs"], 0, 5000);
+            let started = Instant::now();
+            let reply = read_reply(&reader, limits(100, 5000), has_terminator).unwrap();
+            assert_eq!(reply, "\x1b]52;c;SGVs");
+            assert!(started.elapsed() < Duration::from_secs(2));
+        }
+
+        #[test]
+        fn test_deadline_stops_a_steady_trickle() {
+            let chunks = vec![&b"A"[..]; 200];
+            let reader = trickle(chunks, 10, 0);
+            let started = Instant::now();
+            let reply = read_reply(&reader, limits(100, 200), has_terminator).unwrap();
+            assert!(!reply.is_empty() && reply.len() < 200, "{}", reply.len());
+            assert!(started.elapsed() < Duration::from_secs(1));
+        }
+
+        #[test]
+        fn test_no_reply_and_eof() {
+            let reader = trickle(vec![], 0, 5000);
+            assert_eq!(
+                read_reply(&reader, limits(50, 5000), has_terminator).unwrap(),
+                ""
+            );
+            // A closed writer ends the read with what arrived
+            let reader = trickle(vec![b"partial"], 0, 0);
+            assert_eq!(
+                read_reply(&reader, limits(2000, 5000), has_terminator).unwrap(),
+                "partial"
+            );
+        }
+    }
 }
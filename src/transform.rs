@@ -0,0 +1,849 @@
+//! Rewriting text on its way into or out of the clipboard.
+//!
+//! Each transform flag (`--redact`, ...) adds a [`Transform`] to a
+//! [`Pipeline`], which applies them in order. `--filter` picks transforms by
+//! name from [`REGISTRY`], or runs `ext:NAME` from [`filters_dir`].
+//! Decoding transforms fail on malformed input.
+
+use anyhow::{bail, Result};
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::ansi;
+use crate::clean_url::Cleaner;
+use crate::config::Config;
+use crate::encoding;
+use crate::error::{CodedError, ErrorCode};
+use crate::fields::Fields;
+use crate::paths;
+use crate::redact::Redactor;
+use crate::run;
+use crate::template;
+use crate::unicode::{self, Form};
+
+/// One rewriting step
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Transform {
+    /// Remove terminal escape sequences (colors, cursor movement, OSC)
+    StripAnsi,
+    /// Remove zero-width spaces, BOMs, soft hyphens, and bidi controls
+    StripInvisible,
+    /// Convert to a Unicode normalization form
+    Normalize(Form),
+    /// Replace curly quotes, dashes, and ellipses with ASCII
+    AsciiPunctuation,
+    /// Remove leading and trailing whitespace
+    Trim,
+    /// Remove the leading whitespace all non-blank lines share
+    Dedent,
+    /// Prefix each non-blank line with this many spaces
+    Indent(usize),
+    /// Keep some fields of each line (`--fields`)
+    Fields(Fields),
+    /// Sort lines by byte value
+    Sort,
+    /// Sort lines by the number they start with
+    SortNumeric,
+    /// Reverse the order of lines
+    Reverse,
+    /// Drop lines seen before
+    Unique,
+    /// Expand tabs in indentation to tab stops this far apart
+    TabsToSpaces(usize),
+    /// Remove spaces and tabs at the end of each line
+    StripTrailingWs,
+    /// Collapse runs of blank lines into one
+    SqueezeBlank,
+    /// Convert CRLF line endings to LF
+    Lf,
+    /// Convert LF line endings to CRLF
+    Crlf,
+    /// Mask sensitive substrings
+    Redact(Redactor),
+    /// Drop tracking parameters and unwrap redirector links
+    CleanUrl(Cleaner),
+    Upper,
+    Lower,
+    UrlEncode,
+    UrlDecode,
+    Base64,
+    Base64Decode,
+    Hex,
+    HexDecode,
+    /// Reformat JSON with two-space indentation
+    JsonPretty,
+    /// Reformat JSON without whitespace
+    JsonMinify,
+    /// Expand `{{name}}` placeholders (see [`template::expand`])
+    Template,
+    /// Pipe through this executable and use its stdout (`ext:NAME`)
+    External(PathBuf),
+}
+
+/// A transform `--filter` knows by name, written `name` or `name=ARG`
+pub struct Entry {
+    pub name: &'static str,
+    pub summary: &'static str,
+    build: fn(Option<&str>) -> Result<Transform, String>,
+}
+
+/// Every transform available to `--filter`
+pub const REGISTRY: &[Entry] = &[
+    Entry {
+        name: "strip-ansi",
+        summary: "remove terminal colors and other escape sequences",
+        build: |arg| plain(arg, Transform::StripAnsi),
+    },
+    Entry {
+        name: "strip-invisible",
+        summary: "remove zero-width spaces, BOMs, soft hyphens, and bidi controls",
+        build: |arg| plain(arg, Transform::StripInvisible),
+    },
+    Entry {
+        name: "normalize",
+        summary: "convert to a Unicode normalization form (normalize=nfc|nfkc, default nfc)",
+        build: |arg| Ok(Transform::Normalize(arg.map_or(Ok(Form::Nfc), str::parse)?)),
+    },
+    Entry {
+        name: "ascii-punctuation",
+        summary: "replace curly quotes, dashes, and ellipses with ASCII",
+        build: |arg| plain(arg, Transform::AsciiPunctuation),
+    },
+    Entry {
+        name: "trim",
+        summary: "remove leading and trailing whitespace",
+        build: |arg| plain(arg, Transform::Trim),
+    },
+    Entry {
+        name: "dedent",
+        summary: "remove common leading indentation",
+        build: |arg| plain(arg, Transform::Dedent),
+    },
+    Entry {
+        name: "indent",
+        summary: "prefix each line with N spaces (indent=N, default 4)",
+        build: |arg| Ok(Transform::Indent(width(arg, DEFAULT_INDENT)?)),
+    },
+    Entry {
+        name: "fields",
+        summary:
+            "keep whitespace-separated fields of each line (fields=N or fields=N-M, default 1)",
+        build: |arg| {
+            let list = arg.unwrap_or("1").parse()?;
+            Ok(Transform::Fields(Fields::new(list, None, false)))
+        },
+    },
+    Entry {
+        name: "sort",
+        summary: "sort lines by byte value",
+        build: |arg| plain(arg, Transform::Sort),
+    },
+    Entry {
+        name: "sort-numeric",
+        summary: "sort lines by their leading number",
+        build: |arg| plain(arg, Transform::SortNumeric),
+    },
+    Entry {
+        name: "reverse",
+        summary: "reverse the order of lines",
+        build: |arg| plain(arg, Transform::Reverse),
+    },
+    Entry {
+        name: "unique",
+        summary: "drop repeated lines, keeping the first",
+        build: |arg| plain(arg, Transform::Unique),
+    },
+    Entry {
+        name: "tabs-to-spaces",
+        summary: "expand indentation tabs to N spaces (tabs-to-spaces=N, default 4)",
+        build: |arg| Ok(Transform::TabsToSpaces(width(arg, DEFAULT_TAB_WIDTH)?)),
+    },
+    Entry {
+        name: "strip-trailing-ws",
+        summary: "remove whitespace at the end of each line",
+        build: |arg| plain(arg, Transform::StripTrailingWs),
+    },
+    Entry {
+        name: "squeeze-blank",
+        summary: "collapse runs of blank lines into one",
+        build: |arg| plain(arg, Transform::SqueezeBlank),
+    },
+    Entry {
+        name: "lf",
+        summary: "convert line endings to LF",
+        build: |arg| plain(arg, Transform::Lf),
+    },
+    Entry {
+        name: "crlf",
+        summary: "convert line endings to CRLF",
+        build: |arg| plain(arg, Transform::Crlf),
+    },
+    Entry {
+        name: "redact",
+        summary: "mask emails, IPs, keys and tokens (all --redact rules)",
+        build: |arg| plain(arg, Transform::Redact(Redactor::default())),
+    },
+    Entry {
+        name: "clean-url",
+        summary: "drop tracking parameters from links and unwrap redirectors",
+        build: |arg| {
+            no_arg(arg)?;
+            let config = Config::load().map_err(|e| e.to_string())?;
+            Ok(Transform::CleanUrl(Cleaner::new(&config.clean_url)))
+        },
+    },
+    Entry {
+        name: "upper",
+        summary: "convert to uppercase",
+        build: |arg| plain(arg, Transform::Upper),
+    },
+    Entry {
+        name: "lower",
+        summary: "convert to lowercase",
+        build: |arg| plain(arg, Transform::Lower),
+    },
+    Entry {
+        name: "url-encode",
+        summary: "percent-encode all but unreserved characters",
+        build: |arg| plain(arg, Transform::UrlEncode),
+    },
+    Entry {
+        name: "url-decode",
+        summary: "decode %XX escapes",
+        build: |arg| plain(arg, Transform::UrlDecode),
+    },
+    Entry {
+        name: "base64",
+        summary: "encode as base64",
+        build: |arg| plain(arg, Transform::Base64),
+    },
+    Entry {
+        name: "base64-decode",
+        summary: "decode base64 (whitespace ignored)",
+        build: |arg| plain(arg, Transform::Base64Decode),
+    },
+    Entry {
+        name: "hex",
+        summary: "encode as lowercase hex",
+        build: |arg| plain(arg, Transform::Hex),
+    },
+    Entry {
+        name: "hex-decode",
+        summary: "decode hex (whitespace ignored)",
+        build: |arg| plain(arg, Transform::HexDecode),
+    },
+    Entry {
+        name: "json-pretty",
+        summary: "reformat JSON with indentation",
+        build: |arg| plain(arg, Transform::JsonPretty),
+    },
+    Entry {
+        name: "json-minify",
+        summary: "reformat JSON without whitespace",
+        build: |arg| plain(arg, Transform::JsonMinify),
+    },
+    Entry {
+        name: "template",
+        summary: "expand {{env:VAR}}, {{date:FORMAT}}, {{uuid}}, and {{hostname}}",
+        build: |arg| plain(arg, Transform::Template),
+    },
+];
+
+/// Spaces `indent` adds when no width is given
+pub const DEFAULT_INDENT: usize = 4;
+/// Tab stop distance for `tabs-to-spaces` when no width is given
+pub const DEFAULT_TAB_WIDTH: usize = 4;
+/// Largest width `indent` and `tabs-to-spaces` accept
+pub const MAX_WIDTH: usize = 1000;
+
+/// A transform that takes no argument
+fn plain(arg: Option<&str>, transform: Transform) -> Result<Transform, String> {
+    no_arg(arg).map(|_| transform)
+}
+
+fn no_arg(arg: Option<&str>) -> Result<(), String> {
+    match arg {
+        None => Ok(()),
+        Some(_) => Err("takes no argument".to_string()),
+    }
+}
+
+/// A width argument up to [`MAX_WIDTH`], `default` when absent
+fn width(arg: Option<&str>, default: usize) -> Result<usize, String> {
+    arg.map_or(Ok(default), |arg| match arg.parse() {
+        Ok(width) if width <= MAX_WIDTH => Ok(width),
+        Ok(_) => Err(format!("expects a width up to {}, not {}", MAX_WIDTH, arg)),
+        Err(_) => Err(format!("expects a number, not '{}'", arg)),
+    })
+}
+
+impl Transform {
+    pub fn apply(&self, text: &str) -> Result<String> {
+        Ok(match self {
+            Transform::StripAnsi => ansi::strip(text),
+            Transform::StripInvisible => unicode::strip_invisible(text),
+            Transform::Normalize(form) => unicode::normalize(text, *form),
+            Transform::AsciiPunctuation => unicode::ascii_punctuation(text),
+            Transform::Trim => text.trim().to_string(),
+            Transform::Dedent => dedent(text),
+            Transform::Indent(width) => indent(text, *width),
+            Transform::Fields(fields) => fields.apply(text),
+            Transform::Sort => reorder_lines(text, |lines| lines.sort_unstable()),
+            Transform::SortNumeric => {
+                reorder_lines(text, |lines| lines.sort_by(|a, b| compare_numeric(a, b)))
+            }
+            Transform::Reverse => reorder_lines(text, |lines| lines.reverse()),
+            Transform::Unique => reorder_lines(text, |lines| {
+                let mut seen = HashSet::new();
+                lines.retain(|line| seen.insert(*line))
+            }),
+            Transform::TabsToSpaces(width) => tabs_to_spaces(text, *width),
+            Transform::StripTrailingWs => strip_trailing_ws(text),
+            Transform::SqueezeBlank => squeeze_blank(text),
+            Transform::Lf => text.replace("\r\n", "\n"),
+            Transform::Crlf => text.replace("\r\n", "\n").replace('\n', "\r\n"),
+            Transform::Redact(redactor) => redactor.apply(text),
+            Transform::CleanUrl(cleaner) => cleaner.apply(text),
+            Transform::Upper => text.to_uppercase(),
+            Transform::Lower => text.to_lowercase(),
+            Transform::UrlEncode => encoding::url_encode(text),
+            Transform::UrlDecode => invalid_input(encoding::url_decode(text))?,
+            Transform::Base64 => encoding::base64_encode(text),
+            Transform::Base64Decode => invalid_input(encoding::base64_decode(text))?,
+            Transform::Hex => encoding::hex_encode(text),
+            Transform::HexDecode => invalid_input(encoding::hex_decode(text))?,
+            Transform::JsonPretty => reformat_json(text, true)?,
+            Transform::JsonMinify => reformat_json(text, false)?,
+            Transform::Template => template::expand(text)?,
+            Transform::External(path) => external(path, text)?,
+        })
+    }
+}
+
+impl FromStr for Transform {
+    type Err = String;
+
+    /// Look up a `--filter` name (with its `=ARG`, if any) in [`REGISTRY`],
+    /// or an `ext:NAME` executable in [`filters_dir`]
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        if let Some(name) = spec.trim().strip_prefix("ext:") {
+            return external_filter(name).map(Transform::External);
+        }
+        let (name, arg) = match spec.trim().split_once('=') {
+            Some((name, arg)) => (name, Some(arg)),
+            None => (spec.trim(), None),
+        };
+        let entry = REGISTRY
+            .iter()
+            .find(|entry| entry.name == name)
+            .ok_or_else(|| {
+                let names: Vec<_> = REGISTRY.iter().map(|entry| entry.name).collect();
+                format!("unknown filter '{}' (use {})", name, names.join(", "))
+            })?;
+        (entry.build)(arg).map_err(|e| format!("filter '{}' {}", name, e))
+    }
+}
+
+/// Directory `ext:NAME` filters are looked up in (`~/.config/bc/filters`)
+pub fn filters_dir() -> Result<PathBuf> {
+    Ok(paths::config_dir()?.join("filters"))
+}
+
+/// The executable for `ext:NAME`. Names can't leave the filters directory.
+fn external_filter(name: &str) -> Result<PathBuf, String> {
+    if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+        return Err(format!("invalid external filter name '{}'", name));
+    }
+    let dir = filters_dir().map_err(|e| e.to_string())?;
+    let path = dir.join(name);
+    if !path.is_file() {
+        return Err(format!(
+            "no external filter '{}' in {}",
+            name,
+            dir.display()
+        ));
+    }
+    Ok(path)
+}
+
+/// Run an external filter on `text`; a failure aborts the pipeline
+fn external(path: &Path, text: &str) -> Result<String> {
+    let captured = run::feed(&[path.to_string_lossy().into_owned()], text, true)?;
+    if !captured.status.success() {
+        bail!(
+            "External filter {} failed ({})",
+            path.display(),
+            captured.status
+        );
+    }
+    Ok(captured.output)
+}
+
+/// Strip the whitespace prefix shared by all non-blank lines; lines holding
+/// only whitespace become empty
+fn dedent(text: &str) -> String {
+    let indent = text
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| &line[..line.len() - line.trim_start().len()])
+        .reduce(common_prefix)
+        .unwrap_or("");
+    text.split_inclusive('\n')
+        .map(|line| {
+            if line.trim().is_empty() {
+                &line[line.trim_end_matches(['\r', '\n']).len()..]
+            } else {
+                &line[indent.len()..]
+            }
+        })
+        .collect()
+}
+
+/// Prefix every non-blank line with `width` spaces
+fn indent(text: &str, width: usize) -> String {
+    let prefix = " ".repeat(width);
+    text.split_inclusive('\n')
+        .map(|line| {
+            if line.trim().is_empty() {
+                line.to_string()
+            } else {
+                format!("{}{}", prefix, line)
+            }
+        })
+        .collect()
+}
+
+/// Expand tabs in each line's leading whitespace to the next multiple of
+/// `width` columns. Tabs after the first visible character (inside string
+/// literals, between TSV columns) are content and stay.
+fn tabs_to_spaces(text: &str, width: usize) -> String {
+    text.split_inclusive('\n')
+        .map(|line| {
+            let rest = line.trim_start_matches([' ', '\t']);
+            let mut column = 0;
+            let mut out = String::with_capacity(line.len());
+            for c in line[..line.len() - rest.len()].chars() {
+                let advance = match c {
+                    '\t' if width > 0 => width - column % width,
+                    '\t' => 0,
+                    _ => 1,
+                };
+                out.push_str(&" ".repeat(advance));
+                column += advance;
+            }
+            out + rest
+        })
+        .collect()
+}
+
+/// Drop spaces and tabs before each line ending (which is kept as is)
+fn strip_trailing_ws(text: &str) -> String {
+    text.split_inclusive('\n')
+        .map(|line| {
+            let content = line.trim_end_matches(['\r', '\n']);
+            let ending = &line[content.len()..];
+            format!("{}{}", content.trim_end_matches([' ', '\t']), ending)
+        })
+        .collect()
+}
+
+/// Keep only the first of consecutive blank (or whitespace-only) lines
+fn squeeze_blank(text: &str) -> String {
+    let mut previous_blank = false;
+    text.split_inclusive('\n')
+        .filter(|line| {
+            let blank = line.trim().is_empty();
+            let keep = !(blank && previous_blank);
+            previous_blank = blank;
+            keep
+        })
+        .collect()
+}
+
+/// Rearrange the lines of `text` with `reorder`. Lines are rejoined with
+/// CRLF if `text` used it, and the result ends with a newline if `text` did,
+/// so the last line can move without running into the next.
+fn reorder_lines(text: &str, reorder: impl FnOnce(&mut Vec<&str>)) -> String {
+    let mut lines: Vec<&str> = text.lines().collect();
+    reorder(&mut lines);
+    let ending = if text.contains("\r\n") { "\r\n" } else { "\n" };
+    let mut out = lines.join(ending);
+    if text.ends_with('\n') && !lines.is_empty() {
+        out.push_str(ending);
+    }
+    out
+}
+
+/// Order lines like `sort -n`: by the number they start with (lines without
+/// one count as 0), then by byte value
+fn compare_numeric(a: &str, b: &str) -> Ordering {
+    leading_number(a)
+        .total_cmp(&leading_number(b))
+        .then_with(|| a.cmp(b))
+}
+
+/// The decimal number at the start of `line` after any blanks, or 0
+fn leading_number(line: &str) -> f64 {
+    let line = line.trim_start();
+    let sign = usize::from(line.starts_with('-'));
+    let mut end = sign;
+    let mut point = false;
+    for c in line[sign..].chars() {
+        match c {
+            '0'..='9' => {}
+            '.' if !point => point = true,
+            _ => break,
+        }
+        end += 1;
+    }
+    line[..end].parse().unwrap_or(0.0)
+}
+
+/// Parse `text` as JSON and write it back out, keeping key order and
+/// whether it ended with a newline
+fn reformat_json(text: &str, pretty: bool) -> Result<String> {
+    let value: serde_json::Value = serde_json::from_str(text).map_err(|e| {
+        CodedError::new(
+            ErrorCode::InvalidInput,
+            format!("Input is not valid JSON: {}", e),
+        )
+    })?;
+    let mut out = if pretty {
+        serde_json::to_string_pretty(&value)?
+    } else {
+        serde_json::to_string(&value)?
+    };
+    if text.ends_with('\n') {
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+/// Report a decoding failure as input that couldn't be decoded (exit 4),
+/// not a general error
+fn invalid_input(result: Result<String>) -> Result<String> {
+    result.map_err(|e| CodedError::new(ErrorCode::InvalidInput, format!("{:#}", e)).into())
+}
+
+/// Longest common prefix of two whitespace runs
+fn common_prefix<'a>(a: &'a str, b: &str) -> &'a str {
+    let len = a
+        .char_indices()
+        .zip(b.chars())
+        .find(|((_, ca), cb)| ca != cb)
+        .map_or(a.len().min(b.len()), |((i, _), _)| i);
+    &a[..len]
+}
+
+/// Transforms applied in order
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Pipeline {
+    steps: Vec<Transform>,
+}
+
+impl Pipeline {
+    pub fn push(&mut self, step: Transform) {
+        self.steps.push(step);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    /// Run every step, stopping at the first that fails
+    pub fn apply(&self, text: String) -> Result<String> {
+        self.steps
+            .iter()
+            .try_fold(text, |text, step| step.apply(&text))
+    }
+}
+
+impl Extend<Transform> for Pipeline {
+    fn extend<I: IntoIterator<Item = Transform>>(&mut self, steps: I) {
+        self.steps.extend(steps);
+    }
+}
+
+impl FromIterator<Transform> for Pipeline {
+    fn from_iter<I: IntoIterator<Item = Transform>>(steps: I) -> Self {
+        Self {
+            steps: steps.into_iter().collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(name: &str, text: &str) -> String {
+        name.parse::<Transform>().unwrap().apply(text).unwrap()
+    }
+
+    #[test]
+    fn test_fields_filter() {
+        assert_eq!(filter("fields", "a b\nc d\n"), "a\nc\n");
+        assert_eq!(filter("fields=2-3", "a b c d"), "b c");
+        assert!("fields=x".parse::<Transform>().is_err());
+    }
+
+    #[test]
+    fn test_sort_and_unique() {
+        let text = "pear\napple\npear\nBanana";
+        assert_eq!(filter("sort", text), "Banana\napple\npear\npear");
+        assert_eq!(filter("unique", text), "pear\napple\nBanana");
+        assert_eq!(filter("reverse", "a\r\nb\r\nc\r\n"), "c\r\nb\r\na\r\n");
+        assert_eq!(filter("sort", ""), "");
+        assert_eq!(filter("unique", "\n\n"), "\n");
+
+        // The classic sort -u, and sort -r
+        let pipeline: Pipeline = "sort,unique,reverse"
+            .split(',')
+            .map(|name| name.parse().unwrap())
+            .collect();
+        assert_eq!(
+            pipeline.apply(format!("{}\n", text)).unwrap(),
+            "pear\napple\nBanana\n"
+        );
+    }
+
+    #[test]
+    fn test_sort_numeric() {
+        assert_eq!(
+            filter("sort-numeric", "10 x\n9\n-2.5\n  3.1\nnone\n1e3\n"),
+            "-2.5\nnone\n1e3\n  3.1\n9\n10 x\n"
+        );
+        // Equal numbers fall back to byte order
+        assert_eq!(filter("sort-numeric", "2b\n2a\n02"), "02\n2a\n2b");
+        assert_eq!(leading_number("1.2.3"), 1.2);
+        assert_eq!(leading_number("-"), 0.0);
+    }
+
+    #[test]
+    fn test_registry_names_parse() {
+        for entry in REGISTRY {
+            assert!(entry.name.parse::<Transform>().is_ok(), "{}", entry.name);
+        }
+        let err = "bogus".parse::<Transform>().unwrap_err();
+        assert!(err.contains("trim"));
+    }
+
+    #[test]
+    fn test_strip_ansi() {
+        assert_eq!(
+            filter("strip-ansi", "\x1b[1;31merror\x1b[0m: x"),
+            "error: x"
+        );
+    }
+
+    #[test]
+    fn test_unicode_cleanup() {
+        assert_eq!(filter("strip-invisible", "\u{feff}a\u{200b}b"), "ab");
+        assert_eq!(filter("normalize", "e\u{301}"), "é");
+        assert_eq!(filter("normalize=nfkc", "２ﬁ"), "2fi");
+        let err = "normalize=nfd".parse::<Transform>().unwrap_err();
+        assert!(err.contains("unknown form"), "{}", err);
+        assert_eq!(
+            filter("ascii-punctuation", "\u{2018}a\u{2019} \u{2014}"),
+            "'a' --"
+        );
+    }
+
+    #[test]
+    fn test_trim() {
+        assert_eq!(filter("trim", "\n  hello world \n\n"), "hello world");
+        assert_eq!(filter("trim", " \t\n"), "");
+    }
+
+    #[test]
+    fn test_dedent() {
+        assert_eq!(
+            filter("dedent", "    fn a() {\n        b();\n    }\n"),
+            "fn a() {\n    b();\n}\n"
+        );
+        // Blank lines don't count towards the common indent
+        assert_eq!(filter("dedent", "  a\n\n      \n  b"), "a\n\n\nb");
+        // Mixed tabs and spaces only share their common prefix
+        assert_eq!(filter("dedent", "\t  a\r\n\t b\r\n"), " a\r\nb\r\n");
+        assert_eq!(filter("dedent", "a\n  b\n"), "a\n  b\n");
+    }
+
+    #[test]
+    fn test_indent() {
+        assert_eq!(filter("indent=2", "a\n\n  b\n"), "  a\n\n    b\n");
+        assert_eq!(filter("indent", "x"), "    x");
+        assert_eq!(filter("indent=0", "x\n"), "x\n");
+        assert!("indent=two".parse::<Transform>().is_err());
+        let err = "indent=99999999999".parse::<Transform>().unwrap_err();
+        assert!(err.contains("up to 1000"), "{}", err);
+        assert!("trim=1".parse::<Transform>().is_err());
+    }
+
+    #[test]
+    fn test_tabs_to_spaces() {
+        assert_eq!(
+            filter("tabs-to-spaces", "\tif x:\n\t\ty()\n"),
+            "    if x:\n        y()\n"
+        );
+        // Tab stops, not a fixed number of spaces per tab
+        assert_eq!(filter("tabs-to-spaces=4", "  \tx"), "    x");
+        assert_eq!(filter("tabs-to-spaces=2", " \t \tx"), "    x");
+        // Tabs inside the line are content
+        assert_eq!(
+            filter("tabs-to-spaces", "\tprint(\"a\\tb\", \"\t\")\n"),
+            "    print(\"a\\tb\", \"\t\")\n"
+        );
+        assert_eq!(filter("tabs-to-spaces", "a\tb\tc"), "a\tb\tc");
+        assert_eq!(filter("tabs-to-spaces=0", "\t x"), " x");
+        assert!("tabs-to-spaces=99999999999".parse::<Transform>().is_err());
+    }
+
+    #[test]
+    fn test_strip_trailing_ws() {
+        assert_eq!(
+            filter("strip-trailing-ws", "a  \nb\t\r\n \n c"),
+            "a\nb\r\n\n c"
+        );
+        assert_eq!(filter("strip-trailing-ws", "   "), "");
+    }
+
+    #[test]
+    fn test_squeeze_blank() {
+        assert_eq!(
+            filter("squeeze-blank", "a\n\n\n  \nb\n\nc\n"),
+            "a\n\nb\n\nc\n"
+        );
+        assert_eq!(filter("squeeze-blank", "\n\n\na"), "\na");
+        assert_eq!(filter("squeeze-blank", "a\r\n\r\n\r\nb"), "a\r\n\r\nb");
+    }
+
+    #[test]
+    fn test_line_endings() {
+        assert_eq!(filter("lf", "a\r\nb\r\n"), "a\nb\n");
+        assert_eq!(filter("crlf", "a\nb\r\nc"), "a\r\nb\r\nc");
+        assert_eq!(filter("lf", "no newline"), "no newline");
+    }
+
+    #[test]
+    fn test_redact() {
+        assert_eq!(filter("redact", "mail bob@corp.io"), "mail ***");
+    }
+
+    #[test]
+    fn test_clean_url() {
+        let transform = Transform::CleanUrl(Cleaner::default());
+        assert_eq!(
+            transform.apply("see https://a.io/?utm_source=x").unwrap(),
+            "see https://a.io/"
+        );
+    }
+
+    #[test]
+    fn test_case() {
+        assert_eq!(filter("upper", "straße ok"), "STRASSE OK");
+        assert_eq!(filter("lower", "ÀB C"), "àb c");
+    }
+
+    #[test]
+    fn test_encodings() {
+        assert_eq!(filter("url-encode", "a b"), "a%20b");
+        assert_eq!(filter("url-decode", "a%20b"), "a b");
+        assert_eq!(filter("base64", "hi"), "aGk=");
+        assert_eq!(filter("base64-decode", "aGk="), "hi");
+        assert_eq!(filter("hex", "hi"), "6869");
+        assert_eq!(filter("hex-decode", "6869"), "hi");
+        assert!(Transform::HexDecode.apply("xyz").is_err());
+    }
+
+    #[test]
+    fn test_json() {
+        let text = "{\"b\": [1, 2], \"a\": {\"ok\": true}}\n";
+        assert_eq!(
+            filter("json-pretty", text),
+            "{\n  \"b\": [\n    1,\n    2\n  ],\n  \"a\": {\n    \"ok\": true\n  }\n}\n"
+        );
+        assert_eq!(
+            filter("json-minify", text),
+            "{\"b\":[1,2],\"a\":{\"ok\":true}}\n"
+        );
+        assert_eq!(filter("json-minify", " \"x\" "), "\"x\"");
+        let err = Transform::JsonPretty.apply("{\"a\": 1,}").unwrap_err();
+        assert!(err.to_string().contains("not valid JSON"), "{}", err);
+        assert!(err.to_string().contains("line 1"), "{}", err);
+    }
+
+    #[test]
+    fn test_decode_errors_are_invalid_input() {
+        for (transform, text) in [
+            (Transform::JsonPretty, "{x"),
+            (Transform::JsonMinify, "[1,"),
+            (Transform::Base64Decode, "@@@@"),
+            (Transform::HexDecode, "abc"),
+            (Transform::UrlDecode, "100%"),
+        ] {
+            let err = transform.apply(text).unwrap_err();
+            let (code, _) = crate::error::classify(&err, ErrorCode::General);
+            assert_eq!(code, ErrorCode::InvalidInput, "{:?}: {}", transform, err);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_external_filter() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let script = dir.path().join("shout");
+        std::fs::write(&script, "#!/bin/sh\ntr a-z A-Z\n").unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+        assert_eq!(
+            Transform::External(script.clone()).apply("hi\n").unwrap(),
+            "HI\n"
+        );
+
+        std::fs::write(&script, "#!/bin/sh\nexit 3\n").unwrap();
+        let err = Transform::External(script).apply("hi").unwrap_err();
+        assert!(err.to_string().contains("failed"), "{}", err);
+
+        for name in ["ext:", "ext:../bin/sh", "ext:.hidden"] {
+            let err = name.parse::<Transform>().unwrap_err();
+            assert!(err.contains("invalid external filter"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_empty_pipeline_is_identity() {
+        let pipeline = Pipeline::default();
+        assert!(pipeline.is_empty());
+        assert_eq!(pipeline.apply("a@b.io\n".to_string()).unwrap(), "a@b.io\n");
+    }
+
+    #[test]
+    fn test_steps_apply_in_order() {
+        let mut pipeline = Pipeline::default();
+        pipeline.push(Transform::Redact("ipv4".parse().unwrap()));
+        pipeline.push(Transform::Redact("email".parse().unwrap()));
+        assert_eq!(
+            pipeline
+                .apply("root@10.0.0.1.example.com".to_string())
+                .unwrap(),
+            "root@***.example.com"
+        );
+
+        let pipeline: Pipeline = [Transform::Dedent, Transform::Trim, Transform::Crlf]
+            .into_iter()
+            .collect();
+        assert_eq!(
+            pipeline.apply("\n  a\n    b\n".to_string()).unwrap(),
+            "a\r\n  b"
+        );
+
+        let pipeline: Pipeline = [Transform::Base64Decode, Transform::Upper]
+            .into_iter()
+            .collect();
+        assert_eq!(pipeline.apply("aGk=".to_string()).unwrap(), "HI");
+        assert!(pipeline.apply("!!".to_string()).is_err());
+    }
+}
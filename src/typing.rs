@@ -0,0 +1,77 @@
+//! Typing text into the focused window with synthetic key events
+//! (`bc paste --type`), for fields and consoles that block pasting.
+
+use anyhow::{anyhow, Result};
+use enigo::{Direction, Enigo, Key, Keyboard, Settings};
+use std::thread;
+use std::time::Duration;
+
+/// Pause between keystrokes when `--delay-ms` isn't given
+pub const DEFAULT_DELAY: Duration = Duration::from_millis(12);
+
+/// One key press
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stroke {
+    Char(char),
+    Enter,
+    Tab,
+}
+
+/// Type `text` into whatever window has focus, pausing `delay` after each
+/// key. Newlines (LF, CRLF, or CR) are typed as Enter and tabs as Tab. On
+/// Wayland only XWayland windows receive the keys.
+pub fn type_text(text: &str, delay: Duration) -> Result<()> {
+    let mut enigo =
+        Enigo::new(&Settings::default()).map_err(|e| anyhow!("Failed to start typing: {}", e))?;
+    for stroke in strokes(text) {
+        let result = match stroke {
+            Stroke::Char(c) => enigo.text(c.encode_utf8(&mut [0; 4])),
+            Stroke::Enter => enigo.key(Key::Return, Direction::Click),
+            Stroke::Tab => enigo.key(Key::Tab, Direction::Click),
+        };
+        result.map_err(|e| anyhow!("Failed to type {:?}: {}", stroke, e))?;
+        thread::sleep(delay);
+    }
+    Ok(())
+}
+
+fn strokes(text: &str) -> Vec<Stroke> {
+    let mut strokes = Vec::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        strokes.push(match c {
+            '\r' => {
+                chars.next_if_eq(&'\n');
+                Stroke::Enter
+            }
+            '\n' => Stroke::Enter,
+            '\t' => Stroke::Tab,
+            c => Stroke::Char(c),
+        });
+    }
+    strokes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strokes() {
+        assert_eq!(
+            strokes("a\tb\r\nc\rd\né"),
+            [
+                Stroke::Char('a'),
+                Stroke::Tab,
+                Stroke::Char('b'),
+                Stroke::Enter,
+                Stroke::Char('c'),
+                Stroke::Enter,
+                Stroke::Char('d'),
+                Stroke::Enter,
+                Stroke::Char('é'),
+            ]
+        );
+        assert!(strokes("").is_empty());
+    }
+}
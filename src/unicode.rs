@@ -0,0 +1,145 @@
+//! Unicode cleanup for text copied from web pages and documents:
+//! normalization (`--normalize`), invisible-character removal
+//! (`--strip-invisible`), and typographic punctuation (`--ascii-punctuation`).
+
+use std::fmt;
+use std::str::FromStr;
+
+use unicode_normalization::UnicodeNormalization;
+
+/// Normalization form for `--normalize`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Form {
+    /// Canonical composition: `e` + combining acute becomes `é`
+    #[default]
+    Nfc,
+    /// Compatibility composition: also folds ligatures, fullwidth forms,
+    /// superscripts, and non-breaking spaces
+    Nfkc,
+}
+
+impl FromStr for Form {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "nfc" => Ok(Form::Nfc),
+            "nfkc" => Ok(Form::Nfkc),
+            _ => Err(format!("unknown form '{}' (use nfc, nfkc)", value)),
+        }
+    }
+}
+
+impl fmt::Display for Form {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Form::Nfc => "nfc",
+            Form::Nfkc => "nfkc",
+        })
+    }
+}
+
+/// `text` in normalization form `form`
+pub fn normalize(text: &str, form: Form) -> String {
+    match form {
+        Form::Nfc => text.nfc().collect(),
+        Form::Nfkc => text.nfkc().collect(),
+    }
+}
+
+/// Characters that render as nothing but change what the text means to a
+/// compiler or shell. Zero-width joiners stay: emoji sequences and some
+/// scripts need them.
+fn is_invisible(c: char) -> bool {
+    matches!(
+        c,
+        // Soft hyphen, Mongolian vowel separator
+        '\u{ad}' | '\u{180e}'
+        // Zero-width space, word joiner, invisible math operators
+        | '\u{200b}' | '\u{2060}'..='\u{2064}'
+        // Byte order mark / zero-width no-break space
+        | '\u{feff}'
+        // Bidi marks, embeddings, overrides, and isolates
+        | '\u{61c}' | '\u{200e}' | '\u{200f}' | '\u{202a}'..='\u{202e}' | '\u{2066}'..='\u{2069}'
+    )
+}
+
+/// `text` without zero-width spaces, BOMs, soft hyphens, and bidi controls
+pub fn strip_invisible(text: &str) -> String {
+    text.chars().filter(|&c| !is_invisible(c)).collect()
+}
+
+/// The ASCII stand-in for a typographic character, if it has one
+fn ascii_equivalent(c: char) -> Option<&'static str> {
+    Some(match c {
+        // Curly and low single quotes, prime
+        '\u{2018}' | '\u{2019}' | '\u{201a}' | '\u{201b}' | '\u{2032}' => "'",
+        // Curly and low double quotes, double prime
+        '\u{201c}' | '\u{201d}' | '\u{201e}' | '\u{201f}' | '\u{2033}' => "\"",
+        // Hyphen, non-breaking hyphen, figure dash, en dash, minus sign
+        '\u{2010}' | '\u{2011}' | '\u{2012}' | '\u{2013}' | '\u{2212}' => "-",
+        // Em dash, horizontal bar
+        '\u{2014}' | '\u{2015}' => "--",
+        '\u{2026}' => "...",
+        // No-break, narrow no-break, thin, and other fixed-width spaces
+        '\u{a0}' | '\u{202f}' | '\u{2000}'..='\u{200a}' => " ",
+        _ => return None,
+    })
+}
+
+/// `text` with curly quotes, dashes, ellipses, and typographic spaces
+/// replaced by their ASCII equivalents
+pub fn ascii_punctuation(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match ascii_equivalent(c) {
+            Some(ascii) => out.push_str(ascii),
+            None => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        let decomposed = "cafe\u{301}";
+        assert_eq!(normalize(decomposed, Form::Nfc), "café");
+        assert_eq!(normalize("ﬁle ２\u{a0}x²", Form::Nfc), "ﬁle ２\u{a0}x²");
+        assert_eq!(normalize("ﬁle ２\u{a0}x²", Form::Nfkc), "file 2 x2");
+    }
+
+    #[test]
+    fn test_form_names() {
+        assert_eq!("nfc".parse::<Form>().unwrap(), Form::Nfc);
+        assert_eq!("NFKC".parse::<Form>().unwrap(), Form::Nfkc);
+        assert!("nfd".parse::<Form>().unwrap_err().contains("nfkc"));
+        assert_eq!(Form::Nfkc.to_string(), "nfkc");
+    }
+
+    #[test]
+    fn test_strip_invisible() {
+        assert_eq!(
+            strip_invisible("\u{feff}let\u{200b} x = \u{202e}1\u{202c};\n"),
+            "let x = 1;\n"
+        );
+        assert_eq!(strip_invisible("hyphen\u{ad}ation"), "hyphenation");
+        // Joiners in emoji sequences are kept
+        let family = "👩\u{200d}👧";
+        assert_eq!(strip_invisible(family), family);
+    }
+
+    #[test]
+    fn test_ascii_punctuation() {
+        assert_eq!(
+            ascii_punctuation("name: \u{201c}it\u{2019}s\u{201d} \u{2014} 1\u{2013}2\u{2026}"),
+            "name: \"it's\" -- 1-2..."
+        );
+        assert_eq!(ascii_punctuation("10\u{a0}kB \u{2212}3"), "10 kB -3");
+        // Other non-ASCII text is left alone
+        assert_eq!(ascii_punctuation("«ünïcødé» ✓"), "«ünïcødé» ✓");
+    }
+}
@@ -0,0 +1,252 @@
+//! Polling clipboard watcher, the `--match`/`--ignore` filter on its
+//! changes, and the commands `bc watch --exec` runs on each.
+
+use anyhow::{anyhow, bail, Context, Result};
+use regex::Regex;
+use std::io::Write;
+use std::process::Stdio;
+use std::thread;
+use std::time::Duration;
+
+use crate::backends::ClipboardBackend;
+use crate::debug;
+use crate::edit::TempFile;
+use crate::hooks;
+
+/// Default interval between clipboard polls
+pub const DEFAULT_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Polls a backend and reports content changes
+pub struct Watcher<'a> {
+    backend: &'a dyn ClipboardBackend,
+    interval: Duration,
+    last: Option<String>,
+}
+
+impl<'a> Watcher<'a> {
+    /// Start watching. The current content becomes the baseline and is not
+    /// reported as a change.
+    pub fn new(backend: &'a dyn ClipboardBackend) -> Self {
+        Self {
+            backend,
+            interval: DEFAULT_INTERVAL,
+            last: read(backend),
+        }
+    }
+
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Poll once. Returns the new content if it changed since the last poll.
+    /// Empty or unreadable (e.g. image-only) clipboards are not reported, but
+    /// reset the baseline so copying the same text again counts as a change.
+    pub fn poll(&mut self) -> Option<String> {
+        let current = read(self.backend);
+        if current == self.last {
+            return None;
+        }
+        self.last = current.clone();
+        current
+    }
+
+    /// Take `text`, just copied by us, as the new baseline so it isn't
+    /// reported as a change
+    pub fn expect(&mut self, text: &str) {
+        self.last = Some(text.to_string()).filter(|text| !text.is_empty());
+    }
+
+    /// Block until the clipboard content changes
+    pub fn next_change(&mut self) -> String {
+        loop {
+            if let Some(text) = self.poll() {
+                return text;
+            }
+            thread::sleep(self.interval);
+        }
+    }
+}
+
+fn read(backend: &dyn ClipboardBackend) -> Option<String> {
+    backend.paste().ok().filter(|text| !text.is_empty())
+}
+
+/// Which changes `bc watch` acts on: those matching any `--match` pattern
+/// (or all, without one) and none of the `--ignore` patterns
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    matches: Vec<Regex>,
+    ignores: Vec<Regex>,
+}
+
+impl Filter {
+    pub fn new(matches: &[String], ignores: &[String]) -> Result<Self> {
+        Ok(Self {
+            matches: compile(matches, "--match")?,
+            ignores: compile(ignores, "--ignore")?,
+        })
+    }
+
+    pub fn allows(&self, content: &str) -> bool {
+        (self.matches.is_empty() || self.matches.iter().any(|re| re.is_match(content)))
+            && !self.ignores.iter().any(|re| re.is_match(content))
+    }
+}
+
+fn compile(patterns: &[String], flag: &str) -> Result<Vec<Regex>> {
+    patterns
+        .iter()
+        .map(|pattern| {
+            Regex::new(pattern)
+                .map_err(|e| anyhow!("Invalid {} pattern '{}': {}", flag, pattern, e))
+        })
+        .collect()
+}
+
+/// Run `command` through the shell for a change to `content`, waiting for
+/// it to finish. Each `{}` becomes the path of a private temp file holding
+/// the content, removed once the command exits; with `stdin` the content is
+/// piped to the command instead.
+pub fn exec(command: &str, content: &str, stdin: bool) -> Result<()> {
+    if stdin {
+        return run(command, Some(content));
+    }
+    let file = TempFile::create("watch", "txt", content.as_bytes())?;
+    let path = quote(&file.path.to_string_lossy());
+    run(&command.replace("{}", &path), None)
+}
+
+fn run(command: &str, input: Option<&str>) -> Result<()> {
+    debug!("Running '{}'", command);
+    let mut shell = hooks::shell(command);
+    shell.stdin(if input.is_some() {
+        Stdio::piped()
+    } else {
+        Stdio::null()
+    });
+    let mut child = shell.spawn().context("Failed to run the --exec command")?;
+    if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+        // Its output goes to our stdout, so nothing can fill up while we
+        // write; a command that doesn't read its input is fine
+        let _ = stdin.write_all(input.as_bytes());
+    }
+    let status = child
+        .wait()
+        .context("Failed to wait for the --exec command")?;
+    if !status.success() {
+        bail!("The --exec command failed ({})", status);
+    }
+    Ok(())
+}
+
+/// Quote a path for the shell `command` runs in
+#[cfg(unix)]
+fn quote(path: &str) -> String {
+    format!("'{}'", path.replace('\'', r"'\''"))
+}
+
+#[cfg(not(unix))]
+fn quote(path: &str) -> String {
+    format!("\"{}\"", path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backends::Memory;
+
+    #[test]
+    fn test_baseline_not_reported() {
+        let backend = Memory::scripted(&[Some("initial"), Some("initial")]);
+        let mut watcher = Watcher::new(&backend);
+        assert_eq!(watcher.poll(), None);
+    }
+
+    #[test]
+    fn test_reports_each_change_once() {
+        let backend = Memory::scripted(&[Some("a"), Some("b"), Some("b"), Some("c")]);
+        let mut watcher = Watcher::new(&backend);
+        assert_eq!(watcher.poll().as_deref(), Some("b"));
+        assert_eq!(watcher.poll(), None);
+        assert_eq!(watcher.poll().as_deref(), Some("c"));
+    }
+
+    #[test]
+    fn test_unreadable_clipboard_resets_baseline() {
+        let backend = Memory::scripted(&[Some("a"), None, Some("a"), Some("")]);
+        let mut watcher = Watcher::new(&backend);
+        assert_eq!(watcher.poll(), None);
+        assert_eq!(watcher.poll().as_deref(), Some("a"));
+        assert_eq!(watcher.poll(), None);
+    }
+
+    #[test]
+    fn test_next_change_blocks_until_change() {
+        let backend = Memory::scripted(&[Some("a"), Some("a"), Some("a"), Some("new")]);
+        let mut watcher = Watcher::new(&backend).with_interval(Duration::ZERO);
+        assert_eq!(watcher.next_change(), "new");
+    }
+
+    #[test]
+    fn test_expected_copy_not_reported() {
+        let backend = Memory::scripted(&[Some("a"), Some("A"), Some("b")]);
+        let mut watcher = Watcher::new(&backend);
+        watcher.expect("A");
+        assert_eq!(watcher.poll(), None);
+        assert_eq!(watcher.poll().as_deref(), Some("b"));
+    }
+
+    #[test]
+    fn test_filter() {
+        let patterns = |list: &[&str]| list.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+        assert!(Filter::default().allows("anything"));
+
+        let filter = Filter::new(&patterns(&["^magnet:", "^https?://"]), &[]).unwrap();
+        assert!(filter.allows("magnet:?xt=urn:btih:abc"));
+        assert!(filter.allows("https://example.com"));
+        assert!(!filter.allows("plain text"));
+
+        let filter =
+            Filter::new(&patterns(&["^https?://"]), &patterns(&["example\\.org"])).unwrap();
+        assert!(filter.allows("https://example.com"));
+        assert!(!filter.allows("https://example.org/page"));
+
+        let filter = Filter::new(&[], &patterns(&["(?i)password"])).unwrap();
+        assert!(!filter.allows("my Password"));
+        assert!(filter.allows("hello"));
+
+        let err = Filter::new(&patterns(&["("]), &[]).unwrap_err();
+        assert!(
+            err.to_string().starts_with("Invalid --match pattern '('"),
+            "{}",
+            err
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_exec_passes_content() {
+        // The temp file holds the content and is gone afterwards
+        let dir = tempfile::tempdir().unwrap();
+        let seen = dir.path().join("seen");
+        let command = format!(
+            "test \"$(cat {{}})\" = \"it's new\" && echo {{}} > '{}'",
+            seen.display()
+        );
+        exec(&command, "it's new", false).unwrap();
+        let path = std::fs::read_to_string(&seen).unwrap();
+        assert!(!std::path::Path::new(path.trim()).exists());
+
+        exec("test \"$(cat)\" = \"it's new\"", "it's new", true).unwrap();
+        let err = exec("cat >/dev/null; exit 3", "x", true).unwrap_err();
+        assert!(err.to_string().contains("--exec command failed"), "{}", err);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote("/tmp/a b"), "'/tmp/a b'");
+        assert_eq!(quote("it's"), r"'it'\''s'");
+    }
+}